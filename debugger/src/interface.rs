@@ -387,12 +387,12 @@ fn update_registers(
     let d_flag: gtk::CheckButton = builder.get_object("d_flag").unwrap();
     let i_flag: gtk::CheckButton = builder.get_object("i_flag").unwrap();
 
-    c_flag.set_active(app.machine.cpu.regs.flags.carry);
-    z_flag.set_active(app.machine.cpu.regs.flags.zero);
-    s_flag.set_active(app.machine.cpu.regs.flags.sign);
-    o_flag.set_active(app.machine.cpu.regs.flags.overflow);
-    a_flag.set_active(app.machine.cpu.regs.flags.adjust);
-    p_flag.set_active(app.machine.cpu.regs.flags.parity);
+    c_flag.set_active(app.machine.cpu.regs.flags.carry());
+    z_flag.set_active(app.machine.cpu.regs.flags.zero());
+    s_flag.set_active(app.machine.cpu.regs.flags.sign());
+    o_flag.set_active(app.machine.cpu.regs.flags.overflow());
+    a_flag.set_active(app.machine.cpu.regs.flags.adjust());
+    p_flag.set_active(app.machine.cpu.regs.flags.parity());
     d_flag.set_active(app.machine.cpu.regs.flags.direction);
     i_flag.set_active(app.machine.cpu.regs.flags.interrupt);
 
@@ -40,7 +40,9 @@ fn main() {
 fn flat_disassembly(filename: &str) {
     let mut machine = Machine::deterministic();
     match tools::read_binary(filename) {
-        Ok(data) => machine.load_executable(&data, 0x085F),
+        Ok(data) => if let Err(err) = machine.load_executable(&data, 0x085F) {
+            panic!("failed to load {}: {}", filename, err);
+        },
         Err(err) => panic!("failed to read {}: {}", filename, err),
     }
 
@@ -65,7 +67,9 @@ fn flat_disassembly(filename: &str) {
 fn trace_disassembly(filename: &str) {
     let mut machine = Machine::deterministic();
     match tools::read_binary(filename) {
-        Ok(data) => machine.load_executable(&data, 0x085F),
+        Ok(data) => if let Err(err) = machine.load_executable(&data, 0x085F) {
+            panic!("failed to load {}: {}", filename, err);
+        },
         Err(err) => panic!("failed to read {}: {}", filename, err),
     }
     let mut tracer = ProgramTracer::default();
@@ -1,6 +1,8 @@
 // https://wiki.osdev.org/BIOS
 // dosbox-x: src/hardware/bios.cpp
 
+use crate::cpu::CpuModel;
+use crate::machine::MachineConfig;
 use crate::memory::{MMU, MemoryAddress};
 
 #[derive(Clone)]
@@ -10,7 +12,9 @@ pub struct BIOS {
 impl BIOS {
     pub const DATA_SEG: u16           = 0x0040; // bios data segment, 256 byte at 000400 to 0004FF
 
+    pub const DATA_EQUIPMENT: u16     = 0x0010; // equipment list word, returned by INT 11h
     pub const DATA_INITIAL_MODE: u16  = 0x0010;
+    pub const DATA_MEMORY_SIZE: u16   = 0x0013; // conventional memory in KiB, returned by INT 12h
     pub const DATA_CURRENT_MODE: u16  = 0x0049;
     pub const DATA_NB_COLS: u16       = 0x004A;
     pub const DATA_PAGE_SIZE: u16     = 0x004C;
@@ -30,17 +34,23 @@ impl BIOS {
     pub const DATA_CRTCPU_PAGE: u16   = 0x008A;
     pub const DATA_VS_POINTER: u16    = 0x00A8;
 
-    const ROM_SEG: u16                = 0xF000; // bios rom segment, 64k at F_0000 to F_FFFF
-    const ROM_EQUIPMENT_WORD: u16     = 0x0410;
+    pub const ROM_SEG: u16                = 0xF000; // bios rom segment, 64k at F_0000 to F_FFFF
+    const ROM_POST: u16                   = 0xE05B; // power-on self test entry point
+    const ROM_CONFIGURATION_TABLE: u16    = 0xE6F5;
+    const ROM_RESET_VECTOR: u16           = 0xFFF0;
+    const ROM_DATE: u16                   = 0xFFF5; // release date, "MM/DD/YY"
+    const ROM_MODEL: u16                  = 0xFFFE;
 
     pub fn default() -> Self {
         BIOS {
         }
     }
 
-    pub fn init(&mut self, mut mmu: &mut MMU) {
+    pub fn init(&mut self, mut mmu: &mut MMU, config: &MachineConfig) {
         self.init_ivt(&mut mmu);
-        self.write_configuration_data_table(&mut mmu);
+        self.write_configuration_data_table(&mut mmu, config);
+        self.write_rom_image(&mut mmu, config);
+        mmu.map_rom(u32::from(BIOS::ROM_SEG) << 4, 0x1_0000);
     }
 
     fn init_ivt(&mut self, mmu: &mut MMU) {
@@ -58,11 +68,40 @@ impl BIOS {
         mmu.write_u16(_seg, _offset + 2, seg);
     }
 
+    /// returns the machine model byte, as found at F000:FFFE
+    fn model(config: &MachineConfig) -> u8 {
+        if config.graphic_card.is_pc_jr() {
+            0xFD // PCjr
+        } else {
+            match config.cpu_model {
+                CpuModel::I8086 | CpuModel::I80186 => 0xFE, // XT
+                _ => 0xFC, // AT
+            }
+        }
+    }
+
+    /// writes the fixed locations of the BIOS ROM read by programs identifying the machine
+    fn write_rom_image(&self, mmu: &mut MMU, config: &MachineConfig) {
+        // XXX there is no POST, so a reset halts the machine
+        let mut addr = MemoryAddress::RealSegmentOffset(BIOS::ROM_SEG, BIOS::ROM_POST);
+        mmu.write_u8_inc(&mut addr, 0xFA);        // cli
+        mmu.write_u8_inc(&mut addr, 0xF4);        // hlt
+        mmu.write_u16_inc(&mut addr, 0xFDEB);     // jmp short hlt
+
+        let mut addr = MemoryAddress::RealSegmentOffset(BIOS::ROM_SEG, BIOS::ROM_RESET_VECTOR);
+        mmu.write_u8_inc(&mut addr, 0xEA);        // jmp far
+        mmu.write_u16_inc(&mut addr, BIOS::ROM_POST);
+        mmu.write_u16_inc(&mut addr, BIOS::ROM_SEG);
+
+        mmu.write(BIOS::ROM_SEG, BIOS::ROM_DATE, b"01/01/92");
+        mmu.write_u8(BIOS::ROM_SEG, BIOS::ROM_MODEL, BIOS::model(config));
+    }
+
     /// initializes the Configuration Data Table
-    fn write_configuration_data_table(&self, mmu: &mut MMU) {
-        let mut addr = MemoryAddress::RealSegmentOffset(BIOS::ROM_SEG, 0xE6F5);
+    fn write_configuration_data_table(&self, mmu: &mut MMU, config: &MachineConfig) {
+        let mut addr = MemoryAddress::RealSegmentOffset(BIOS::ROM_SEG, BIOS::ROM_CONFIGURATION_TABLE);
         mmu.write_u16_inc(&mut addr, 8);          // table size
-        mmu.write_u8_inc(&mut addr, 0xFC);        // model: AT
+        mmu.write_u8_inc(&mut addr, BIOS::model(config)); // model
         mmu.write_u8_inc(&mut addr, 0);           // submodel
         mmu.write_u8_inc(&mut addr, 0);           // BIOS revision
         mmu.write_u8_inc(&mut addr, 0b0000_0000); // feature byte 1
@@ -70,6 +109,20 @@ impl BIOS {
         mmu.write_u8_inc(&mut addr, 0b0000_0000); // feature byte 3
         mmu.write_u8_inc(&mut addr, 0b0000_0000); // feature byte 4
         mmu.write_u8_inc(&mut addr, 0b0000_0000); // feature byte 5
-        mmu.write_u16(BIOS::ROM_SEG, BIOS::ROM_EQUIPMENT_WORD, 0x0021);
+
+        // bit 0: floppy drives installed, bit 1: math coprocessor, bit 2: pointing device,
+        // bits 5-4: initial video mode (00 = EGA/VGA, 10 = 80x25 color)
+        let mut equipment = 0b0000_0000_0000_0001;
+        if config.has_fpu() {
+            equipment |= 0b0000_0000_0000_0010;
+        }
+        if config.mouse {
+            equipment |= 0b0000_0000_0000_0100;
+        }
+        if !config.graphic_card.is_ega_vga() {
+            equipment |= 0b0000_0000_0010_0000;
+        }
+        mmu.write_u16(BIOS::DATA_SEG, BIOS::DATA_EQUIPMENT, equipment);
+        mmu.write_u16(BIOS::DATA_SEG, BIOS::DATA_MEMORY_SIZE, config.conventional_memory_kb());
     }
 }
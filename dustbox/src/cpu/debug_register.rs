@@ -0,0 +1,136 @@
+use crate::cpu::register::R;
+use crate::memory::DataBreakpoint;
+
+#[cfg(test)]
+#[path = "./debug_register_test.rs"]
+mod debug_register_test;
+
+/// DR6 status bits. B0-B3 are set for the breakpoints that triggered a debug exception
+pub const DR6_B0: u32 = 0x0001;
+/// set for a debug exception raised by MOV DRn while DR7.GD is set
+pub const DR6_BD: u32 = 0x2000;
+/// set for a single step trap (TF)
+pub const DR6_BS: u32 = 0x4000;
+
+/// DR7 general detect bit, making accesses to the debug registers raise a debug exception
+pub const DR7_GD: u32 = 0x2000;
+
+/// the access that triggers a breakpoint, from the R/W bits of DR7
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BreakpointCondition {
+    Execute,
+    Write,
+    /// I/O port accesses, as with CR4.DE set on later models
+    Io,
+    ReadWrite,
+}
+
+/// the 386 debug registers: DR0-DR3 holding breakpoint addresses, DR6 reporting the
+/// cause of a debug exception and DR7 enabling the breakpoints
+#[derive(Clone)]
+pub struct DebugRegisters {
+    pub dr: [u32; 4],
+    pub dr6: u32,
+    pub dr7: u32,
+
+    /// DR6 bits of the I/O breakpoints hit by the current instruction
+    io_hits: u32,
+}
+
+impl Default for DebugRegisters {
+    fn default() -> Self {
+        DebugRegisters {
+            dr: [0; 4],
+            // reserved bits reads as 1
+            dr6: 0xFFFF_0FF0,
+            dr7: 0x0000_0400,
+            io_hits: 0,
+        }
+    }
+}
+
+impl DebugRegisters {
+    pub fn get(&self, r: R) -> u32 {
+        match r {
+            R::DR0 | R::DR1 | R::DR2 | R::DR3 => self.dr[r.index()],
+            R::DR6 => self.dr6,
+            R::DR7 => self.dr7,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn set(&mut self, r: R, val: u32) {
+        match r {
+            R::DR0 | R::DR1 | R::DR2 | R::DR3 => self.dr[r.index()] = val,
+            R::DR6 => self.dr6 = val,
+            R::DR7 => self.dr7 = val,
+            _ => unreachable!(),
+        }
+    }
+
+    /// returns the condition and length in bytes of breakpoint `n`, if it is enabled in DR7
+    pub fn breakpoint(&self, n: usize) -> Option<(BreakpointCondition, u32)> {
+        if (self.dr7 >> (n * 2)) & 3 == 0 {
+            return None;
+        }
+        let control = self.dr7 >> (16 + n * 4);
+        let condition = match control & 3 {
+            0 => BreakpointCondition::Execute,
+            1 => BreakpointCondition::Write,
+            2 => BreakpointCondition::Io,
+            _ => BreakpointCondition::ReadWrite,
+        };
+        let len = match (control >> 2) & 3 {
+            0 => 1,
+            1 => 2,
+            2 => 8,
+            _ => 4,
+        };
+        Some((condition, len))
+    }
+
+    /// returns the DR6 bits of the breakpoints with `condition` covering `len` bytes at `addr`
+    fn hits(&self, condition: BreakpointCondition, addr: u32, len: u32) -> u32 {
+        (0..4).fold(0, |hits, n| match self.breakpoint(n) {
+            Some((c, bp_len)) if c == condition => {
+                let start = self.dr[n] & !(bp_len - 1);
+                if addr <= start.wrapping_add(bp_len - 1) && start <= addr.wrapping_add(len - 1) {
+                    hits | (DR6_B0 << n)
+                } else {
+                    hits
+                }
+            }
+            _ => hits,
+        })
+    }
+
+    /// returns the DR6 bits of the execute breakpoints at linear address `addr`
+    pub fn execute_hits(&self, addr: u32) -> u32 {
+        self.hits(BreakpointCondition::Execute, addr, 1)
+    }
+
+    /// records the I/O breakpoints covering `len` ports at `port`, reported by take_io_hits
+    pub fn check_io(&mut self, port: u16, len: u16) {
+        if self.dr7 & 0xFF != 0 {
+            self.io_hits |= self.hits(BreakpointCondition::Io, u32::from(port), u32::from(len));
+        }
+    }
+
+    /// returns the DR6 bits of the I/O breakpoints hit since the last call
+    pub fn take_io_hits(&mut self) -> u32 {
+        std::mem::replace(&mut self.io_hits, 0)
+    }
+
+    /// returns the enabled write and read/write breakpoints, watched by the MMU
+    pub fn data_breakpoints(&self) -> [Option<DataBreakpoint>; 4] {
+        let mut res = [None; 4];
+        for (n, bp) in res.iter_mut().enumerate() {
+            *bp = match self.breakpoint(n) {
+                Some((BreakpointCondition::Write, len)) => Some(DataBreakpoint { address: self.dr[n] & !(len - 1), len, write_only: true }),
+                Some((BreakpointCondition::ReadWrite, len)) => Some(DataBreakpoint { address: self.dr[n] & !(len - 1), len, write_only: false }),
+                _ => None,
+            };
+        }
+        res
+    }
+}
@@ -0,0 +1,40 @@
+use crate::cpu::{DebugRegisters, BreakpointCondition, DR6_B0, R};
+use crate::memory::DataBreakpoint;
+
+#[test]
+fn can_decode_breakpoints_from_dr7() {
+    let mut debug = DebugRegisters::default();
+    assert_eq!(None, debug.breakpoint(0));
+
+    // DR0: global, execute. DR1: local, write 2 bytes. DR2: local, I/O 4 bytes. DR3: local, read/write 8 bytes
+    debug.set(R::DR7, 0xBE50_0056);
+    assert_eq!(Some((BreakpointCondition::Execute, 1)), debug.breakpoint(0));
+    assert_eq!(Some((BreakpointCondition::Write, 2)), debug.breakpoint(1));
+    assert_eq!(Some((BreakpointCondition::Io, 4)), debug.breakpoint(2));
+    assert_eq!(Some((BreakpointCondition::ReadWrite, 8)), debug.breakpoint(3));
+}
+
+#[test]
+fn can_match_execute_and_io_breakpoints() {
+    let mut debug = DebugRegisters::default();
+    debug.set(R::DR0, 0x1234);
+    debug.set(R::DR2, 0x0061);
+    debug.set(R::DR7, 0x0200_0011); // DR0: execute, DR2: I/O 1 byte
+
+    assert_eq!(DR6_B0, debug.execute_hits(0x1234));
+    assert_eq!(0, debug.execute_hits(0x1235));
+
+    debug.check_io(0x60, 1);
+    assert_eq!(0, debug.take_io_hits());
+    debug.check_io(0x60, 2);
+    assert_eq!(DR6_B0 << 2, debug.take_io_hits());
+    assert_eq!(0, debug.take_io_hits());
+}
+
+#[test]
+fn can_align_data_breakpoints() {
+    let mut debug = DebugRegisters::default();
+    debug.set(R::DR1, 0x2003);
+    debug.set(R::DR7, 0x00D0_0004); // DR1: write 4 bytes
+    assert_eq!([None, Some(DataBreakpoint { address: 0x2000, len: 4, write_only: true }), None, None], debug.data_breakpoints());
+}
@@ -1,9 +1,10 @@
 use std::num::Wrapping;
 
 use crate::cpu::instruction::{Instruction, InstructionInfo, ModRegRm, RepeatMode};
+use crate::cpu::model::CpuModel;
 use crate::cpu::parameter::{Parameter, ParameterSet};
 use crate::cpu::op::{Op, Invalid};
-use crate::cpu::register::{R, r8, r16, r32, sr, fpr};
+use crate::cpu::register::{R, AMode, r8, r16, r32, sr, fpr, cr, dr};
 use crate::cpu::segment::Segment;
 use crate::memory::{MMU, MemoryAddress};
 
@@ -28,15 +29,35 @@ pub enum AddressSize {
     _16bit, _32bit,
 }
 
+/// the memory operand of a ModRM byte, before the operand size is applied
+enum RmAddress {
+    Direct(u16),
+    Amode(AMode),
+    AmodeS8(AMode, i8),
+    AmodeS16(AMode, i16),
+    AmodeS32(AMode, i32),
+}
+
 #[derive(Clone, Default)]
 pub struct Decoder {
-    current_seg: u16,
+    /// linear base address of the code segment
+    current_base: u32,
 
     /// starting instruction decoding offset
-    current_offset: u16,
+    current_offset: u32,
+
+    /// decoding a 32-bit code segment, with 32-bit default operand and address sizes
+    code32: bool,
+
+    /// opcodes not implemented by this model decodes as invalid
+    model: CpuModel,
 }
 
 impl Decoder {
+    pub fn set_model(&mut self, model: CpuModel) {
+        self.model = model;
+    }
+
     /// decodes given seg::offset into Vec with `n` InstructionInfo's
     pub fn decode_to_block(&mut self, mut mmu: &mut MMU, seg: u16, offset: u16, n: usize) -> Vec<InstructionInfo> {
         let mut ops: Vec<InstructionInfo> = Vec::new();
@@ -68,11 +89,22 @@ impl Decoder {
         }
     }
 
-    /// decodes op at seg:offset into a Instruction
-    pub fn get_instruction(&mut self, mut mmu: &mut MMU, segment: u16, offset: u16) -> Instruction {
-        self.current_seg = segment;
+    /// decodes op at real mode seg:offset into a Instruction
+    pub fn get_instruction(&mut self, mmu: &mut MMU, segment: u16, offset: u16) -> Instruction {
+        self.get_instruction_at(mmu, u32::from(segment) << 4, u32::from(offset), false)
+    }
+
+    /// decodes op at `offset` in the code segment starting at linear address `base`.
+    /// `code32` selects 32-bit default operand and address sizes (the D bit of CS)
+    pub fn get_instruction_at(&mut self, mut mmu: &mut MMU, base: u32, offset: u32, code32: bool) -> Instruction {
+        self.current_base = base;
         self.current_offset = offset;
+        self.code32 = code32;
         let mut op = Instruction::new(Op::Uninitialized);
+        if code32 {
+            op.op_size = OperandSize::_32bit;
+            op.address_size = AddressSize::_32bit;
+        }
         self.decode(&mut mmu, &mut op);
         op
     }
@@ -85,12 +117,23 @@ impl Decoder {
         if DEBUG_DECODER {
             // println!("decode op {:04X}: {}", start_offset, op);
         }
+        let b = if self.model == CpuModel::I8086 {
+            alias_8086_opcode(b)
+        } else {
+            b
+        };
 
         match b {
+            0x0F if self.model == CpuModel::I8086 => {
+                // pop cs (8086 only)
+                op.command = Op::Pop16;
+                op.params.dst = Parameter::SReg16(R::CS);
+            }
+            _ if self.model < CpuModel::first_with_opcode(b) => op.command = Op::Invalid(vec!(b), Invalid::Op),
             0x00 => {
                 // add r/m8, r8
                 op.command = Op::Add8;
-                op.params = self.rm8_r8(&mut mmu, op.segment_prefix);
+                op.params = self.rm8_r8(&mut mmu, op);
             }
             0x01 => {
                 // add r/m16, r16
@@ -100,7 +143,7 @@ impl Decoder {
             0x02 => {
                 // add r8, r/m8
                 op.command = Op::Add8;
-                op.params = self.r8_rm8(&mut mmu, op.segment_prefix);
+                op.params = self.r8_rm8(&mut mmu, op);
             }
             0x03 => {
                 // add r16, r/m16
@@ -142,7 +185,7 @@ impl Decoder {
             0x08 => {
                 // or r/m8, r8
                 op.command = Op::Or8;
-                op.params = self.rm8_r8(&mut mmu, op.segment_prefix);
+                op.params = self.rm8_r8(&mut mmu, op);
             }
             0x09 => {
                 // or r/m16, r16
@@ -152,12 +195,12 @@ impl Decoder {
             0x0A => {
                 // or r8, r/m8
                 op.command = Op::Or8;
-                op.params = self.r8_rm8(&mut mmu, op.segment_prefix);
+                op.params = self.r8_rm8(&mut mmu, op);
             }
             0x0B => {
                 // or r16, r/m16
-                op.command = Op::Or16;
-                op.params = self.r16_rm16(&mut mmu, op);
+                // or r32, r/m32
+                self.prefixed_16_32_r_rm(&mut mmu, &mut op, Op::Or16, Op::Or32)
             }
             0x0C => {
                 // or AL, imm8
@@ -189,11 +232,31 @@ impl Decoder {
             0x0F => {
                 let b2 = self.read_u8(mmu);
                 match b2 {
+                    _ if self.model < CpuModel::first_with_opcode_0f(b2) => op.command = Op::Invalid(vec!(b, b2), Invalid::Op),
                     0x00 => {
                         let x = self.read_mod_reg_rm(mmu);
                         op.params.dst = self.rm16(&mut mmu, op, x.rm, x.md);
                         op.command = match x.reg {
                             0 => Op::Sldt, // sldt r/m16
+                            1 => Op::Str,  // str r/m16
+                            2 => Op::Lldt, // lldt r/m16
+                            3 => Op::Ltr,  // ltr r/m16
+                            4 => Op::Verr, // verr r/m16
+                            5 => Op::Verw, // verw r/m16
+                            _ => Op::Invalid(vec!(b, b2), Invalid::Reg(x.reg)),
+                        };
+                    }
+                    0x01 => {
+                        let x = self.read_mod_reg_rm(mmu);
+                        op.params.dst = self.rm16(&mut mmu, op, x.rm, x.md);
+                        op.command = match x.reg {
+                            // sgdt m, sidt m, lgdt m, lidt m
+                            0 if x.md != 3 => Op::Sgdt,
+                            1 if x.md != 3 => Op::Sidt,
+                            2 if x.md != 3 => Op::Lgdt,
+                            3 if x.md != 3 => Op::Lidt,
+                            4 => Op::Smsw, // smsw r/m16
+                            6 => Op::Lmsw, // lmsw r/m16
                             _ => Op::Invalid(vec!(b, b2), Invalid::Reg(x.reg)),
                         };
                     }
@@ -202,78 +265,227 @@ impl Decoder {
                         op.command = Op::Lar16;
                         op.params = self.r16_rm16(&mut mmu, op);
                     }
+                    0x03 => {
+                        // lsl r16, r16/m16
+                        op.command = Op::Lsl16;
+                        op.params = self.r16_rm16(&mut mmu, op);
+                    }
+                    0x06 => op.command = Op::Clts,
+                    0x08 => op.command = Op::Invd,
+                    0x09 => op.command = Op::Wbinvd,
+                    0x20 => {
+                        // mov r32, cr0-3
+                        let x = self.read_mod_reg_rm(mmu);
+                        match cr(x.reg) {
+                            Some(r) => {
+                                op.command = Op::Mov32;
+                                op.params.dst = Parameter::Reg32(r32(x.rm));
+                                op.params.src = Parameter::CReg32(r);
+                            }
+                            None => op.command = Op::Invalid(vec!(b, b2), Invalid::Reg(x.reg)),
+                        }
+                    }
+                    0x21 => {
+                        // mov r32, dr0-7
+                        let x = self.read_mod_reg_rm(mmu);
+                        op.command = Op::Mov32;
+                        op.params.dst = Parameter::Reg32(r32(x.rm));
+                        op.params.src = Parameter::DReg32(dr(x.reg));
+                    }
+                    0x22 => {
+                        // mov cr0-3, r32
+                        let x = self.read_mod_reg_rm(mmu);
+                        match cr(x.reg) {
+                            Some(r) => {
+                                op.command = Op::Mov32;
+                                op.params.dst = Parameter::CReg32(r);
+                                op.params.src = Parameter::Reg32(r32(x.rm));
+                            }
+                            None => op.command = Op::Invalid(vec!(b, b2), Invalid::Reg(x.reg)),
+                        }
+                    }
+                    0x23 => {
+                        // mov dr0-7, r32
+                        let x = self.read_mod_reg_rm(mmu);
+                        op.command = Op::Mov32;
+                        op.params.dst = Parameter::DReg32(dr(x.reg));
+                        op.params.src = Parameter::Reg32(r32(x.rm));
+                    }
+                    0x80 => {
+                        // jo rel16
+                        op.command = Op::Jo;
+                        op.params.dst = self.read_rel(mmu, op);
+                    }
+                    0x81 => {
+                        // jno rel16
+                        op.command = Op::Jno;
+                        op.params.dst = self.read_rel(mmu, op);
+                    }
                     0x82 => {
                         // jc rel16
                         op.command = Op::Jc;
-                        op.params.dst = Parameter::Imm16(self.read_rel16(mmu));
+                        op.params.dst = self.read_rel(mmu, op);
                     }
                     0x83 => {
                         // jnc rel16
                         op.command = Op::Jnc;
-                        op.params.dst = Parameter::Imm16(self.read_rel16(mmu));
+                        op.params.dst = self.read_rel(mmu, op);
                     }
                     0x84 => {
                         // jz rel16
                         op.command = Op::Jz;
-                        op.params.dst = Parameter::Imm16(self.read_rel16(mmu));
+                        op.params.dst = self.read_rel(mmu, op);
                     }
                     0x85 => {
                         // jnz rel16
                         op.command = Op::Jnz;
-                        op.params.dst = Parameter::Imm16(self.read_rel16(mmu));
+                        op.params.dst = self.read_rel(mmu, op);
                     }
                     0x86 => {
                         // jna rel16
                         op.command = Op::Jna;
-                        op.params.dst = Parameter::Imm16(self.read_rel16(mmu));
+                        op.params.dst = self.read_rel(mmu, op);
                     }
                     0x87 => {
                         // ja rel16
                         op.command = Op::Ja;
-                        op.params.dst = Parameter::Imm16(self.read_rel16(mmu));
+                        op.params.dst = self.read_rel(mmu, op);
+                    }
+                    0x88 => {
+                        // js rel16
+                        op.command = Op::Js;
+                        op.params.dst = self.read_rel(mmu, op);
                     }
                     0x89 => {
                         // jns rel16
                         op.command = Op::Jns;
-                        op.params.dst = Parameter::Imm16(self.read_rel16(mmu));
+                        op.params.dst = self.read_rel(mmu, op);
+                    }
+                    0x8A => {
+                        // jpe rel16
+                        op.command = Op::Jpe;
+                        op.params.dst = self.read_rel(mmu, op);
+                    }
+                    0x8B => {
+                        // jpo rel16
+                        op.command = Op::Jpo;
+                        op.params.dst = self.read_rel(mmu, op);
                     }
                     0x8C => {
                         // jl rel16
                         op.command = Op::Jl;
-                        op.params.dst = Parameter::Imm16(self.read_rel16(mmu));
+                        op.params.dst = self.read_rel(mmu, op);
                     }
                     0x8D => {
                         // jnl rel16
                         op.command = Op::Jnl;
-                        op.params.dst = Parameter::Imm16(self.read_rel16(mmu));
+                        op.params.dst = self.read_rel(mmu, op);
                     }
                     0x8E => {
                         // jng rel16
                         op.command = Op::Jng;
-                        op.params.dst = Parameter::Imm16(self.read_rel16(mmu));
+                        op.params.dst = self.read_rel(mmu, op);
                     }
                     0x8F => {
                         // jg rel16
                         op.command = Op::Jg;
-                        op.params.dst = Parameter::Imm16(self.read_rel16(mmu));
+                        op.params.dst = self.read_rel(mmu, op);
+                    }
+                    0x90 => {
+                        // seto r/m8
+                        let x = self.read_mod_reg_rm(mmu);
+                        op.command = Op::Seto;
+                        op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
+                    }
+                    0x91 => {
+                        // setno r/m8
+                        let x = self.read_mod_reg_rm(mmu);
+                        op.command = Op::Setno;
+                        op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
                     }
                     0x92 => {
                         // setc r/m8
                         let x = self.read_mod_reg_rm(mmu);
                         op.command = Op::Setc;
-                        op.params.dst = self.rm8(&mut mmu, op.segment_prefix, x.rm, x.md);
+                        op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
+                    }
+                    0x93 => {
+                        // setnc r/m8
+                        let x = self.read_mod_reg_rm(mmu);
+                        op.command = Op::Setnc;
+                        op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
+                    }
+                    0x94 => {
+                        // setz r/m8
+                        let x = self.read_mod_reg_rm(mmu);
+                        op.command = Op::Setz;
+                        op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
                     }
                     0x95 => {
                         // setnz r/m8
                         let x = self.read_mod_reg_rm(mmu);
                         op.command = Op::Setnz;
-                        op.params.dst = self.rm8(&mut mmu, op.segment_prefix, x.rm, x.md);
+                        op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
+                    }
+                    0x96 => {
+                        // setna r/m8
+                        let x = self.read_mod_reg_rm(mmu);
+                        op.command = Op::Setna;
+                        op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
+                    }
+                    0x97 => {
+                        // seta r/m8
+                        let x = self.read_mod_reg_rm(mmu);
+                        op.command = Op::Seta;
+                        op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
+                    }
+                    0x98 => {
+                        // sets r/m8
+                        let x = self.read_mod_reg_rm(mmu);
+                        op.command = Op::Sets;
+                        op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
+                    }
+                    0x99 => {
+                        // setns r/m8
+                        let x = self.read_mod_reg_rm(mmu);
+                        op.command = Op::Setns;
+                        op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
+                    }
+                    0x9A => {
+                        // setpe r/m8
+                        let x = self.read_mod_reg_rm(mmu);
+                        op.command = Op::Setpe;
+                        op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
+                    }
+                    0x9B => {
+                        // setpo r/m8
+                        let x = self.read_mod_reg_rm(mmu);
+                        op.command = Op::Setpo;
+                        op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
+                    }
+                    0x9C => {
+                        // setl r/m8
+                        let x = self.read_mod_reg_rm(mmu);
+                        op.command = Op::Setl;
+                        op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
+                    }
+                    0x9D => {
+                        // setnl r/m8
+                        let x = self.read_mod_reg_rm(mmu);
+                        op.command = Op::Setnl;
+                        op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
+                    }
+                    0x9E => {
+                        // setng r/m8
+                        let x = self.read_mod_reg_rm(mmu);
+                        op.command = Op::Setng;
+                        op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
                     }
                     0x9F => {
                         // setg r/m8
                         let x = self.read_mod_reg_rm(mmu);
                         op.command = Op::Setg;
-                        op.params.dst = self.rm8(&mut mmu, op.segment_prefix, x.rm, x.md);
+                        op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
                     }
                     0xA0 => {
                         // push fs
@@ -285,17 +497,24 @@ impl Decoder {
                         op.command = Op::Pop16;
                         op.params.dst = Parameter::SReg16(R::FS);
                     }
+                    0xA2 => op.command = Op::Cpuid,
                     0xA3 => {
                         // bt r/m16, r16
-                        op.command = Op::Bt;
-                        op.params = self.rm16_r16(&mut mmu, op);
+                        // bt r/m32, r32
+                        self.prefixed_16_32_rm_r(&mut mmu, &mut op, Op::Bt, Op::Bt)
                     }
                     0xA4 =>{
                         // shld r/m16, r16, imm8
-                        op.command = Op::Shld;
-                        op.params = self.rm16_r16(&mut mmu, op);
+                        // shld r/m32, r32, imm8
+                        self.prefixed_16_32_rm_r(&mut mmu, &mut op, Op::Shld, Op::Shld);
                         op.params.src2 = Parameter::Imm8(self.read_u8(mmu));
                     }
+                    0xA5 =>{
+                        // shld r/m16, r16, cl
+                        // shld r/m32, r32, cl
+                        self.prefixed_16_32_rm_r(&mut mmu, &mut op, Op::Shld, Op::Shld);
+                        op.params.src2 = Parameter::Reg8(R::CL);
+                    }
                     0xA8 => {
                         // push gs
                         op.command = Op::Push16;
@@ -306,28 +525,69 @@ impl Decoder {
                         op.command = Op::Pop16;
                         op.params.dst = Parameter::SReg16(R::GS);
                     }
+                    0xAB => {
+                        // bts r/m16, r16
+                        // bts r/m32, r32
+                        self.prefixed_16_32_rm_r(&mut mmu, &mut op, Op::Bts, Op::Bts)
+                    }
                     0xAC => {
                         // shrd r/m16, r16, imm8
-                        op.command = Op::Shrd;
-                        op.params = self.rm16_r16(&mut mmu, op);
+                        // shrd r/m32, r32, imm8
+                        self.prefixed_16_32_rm_r(&mut mmu, &mut op, Op::Shrd, Op::Shrd);
                         op.params.src2 = Parameter::Imm8(self.read_u8(mmu));
                     }
+                    0xAD => {
+                        // shrd r/m16, r16, cl
+                        // shrd r/m32, r32, cl
+                        self.prefixed_16_32_rm_r(&mut mmu, &mut op, Op::Shrd, Op::Shrd);
+                        op.params.src2 = Parameter::Reg8(R::CL);
+                    }
                     0xAF => {
                         // imul r16, r/m16
                         // imul r32, r/m32
                         self.prefixed_16_32_r_rm(&mut mmu, &mut op, Op::Imul16, Op::Imul32)
                     }
+                    0xB0 => {
+                        // cmpxchg r/m8, r8
+                        op.command = Op::Cmpxchg8;
+                        op.params = self.rm8_r8(&mut mmu, op);
+                    }
+                    0xB1 => {
+                        // cmpxchg r/m16, r16
+                        // cmpxchg r/m32, r32
+                        self.prefixed_16_32_rm_r(&mut mmu, &mut op, Op::Cmpxchg16, Op::Cmpxchg32)
+                    }
+                    0xB2 => {
+                        // lss r16, m16
+                        op.command = Op::Lss;
+                        op.params = self.r16_m16(&mut mmu, op);
+                    }
+                    0xB3 => {
+                        // btr r/m16, r16
+                        // btr r/m32, r32
+                        self.prefixed_16_32_rm_r(&mut mmu, &mut op, Op::Btr, Op::Btr)
+                    }
+                    0xB4 => {
+                        // lfs r16, m16
+                        op.command = Op::Lfs;
+                        op.params = self.r16_m16(&mut mmu, op);
+                    }
+                    0xB5 => {
+                        // lgs r16, m16
+                        op.command = Op::Lgs;
+                        op.params = self.r16_m16(&mut mmu, op);
+                    }
                     0xB6 => {
                         match op.op_size {
                             OperandSize::_16bit => {
                                 // movzx r16, r/m8
                                 op.command = Op::Movzx16;
-                                op.params = self.r16_rm8(&mut mmu, op.segment_prefix);
+                                op.params = self.r16_rm8(&mut mmu, op);
                             }
                             OperandSize::_32bit => {
                                 // movzx r32, r/m8
                                 op.command = Op::Movzx32;
-                                op.params = self.r32_rm8(&mut mmu, op.segment_prefix);
+                                op.params = self.r32_rm8(&mut mmu, op);
                             }
                         }
                     }
@@ -342,28 +602,48 @@ impl Decoder {
                         }
                     }
                     0xBA => {
-                        // bts r/m16, imm8
+                        // bt/bts/btr/btc r/m16, imm8
+                        // bt/bts/btr/btc r/m32, imm8
                         let x = self.read_mod_reg_rm(mmu);
-                        op.command = Op::Bts;
-                        op.params.dst = self.rm16(&mut mmu, op, x.rm, x.md);
+                        op.params.dst = match op.op_size {
+                            OperandSize::_16bit => self.rm16(&mut mmu, op, x.rm, x.md),
+                            OperandSize::_32bit => self.rm32(&mut mmu, op, x.rm, x.md),
+                        };
                         op.params.src = Parameter::Imm8(self.read_u8(mmu));
+                        op.command = match x.reg {
+                            4 => Op::Bt,
+                            5 => Op::Bts,
+                            6 => Op::Btr,
+                            7 => Op::Btc,
+                            _ => Op::Invalid(vec!(b, b2), Invalid::Reg(x.reg)),
+                        };
+                    }
+                    0xBB => {
+                        // btc r/m16, r16
+                        // btc r/m32, r32
+                        self.prefixed_16_32_rm_r(&mut mmu, &mut op, Op::Btc, Op::Btc)
                     }
                     0xBC => {
                         // bsf r16, r/m16
-                        op.command = Op::Bsf;
-                        op.params = self.r16_rm16(&mut mmu, op);
+                        // bsf r32, r/m32
+                        self.prefixed_16_32_r_rm(&mut mmu, &mut op, Op::Bsf, Op::Bsf)
+                    }
+                    0xBD => {
+                        // bsr r16, r/m16
+                        // bsr r32, r/m32
+                        self.prefixed_16_32_r_rm(&mut mmu, &mut op, Op::Bsr, Op::Bsr)
                     }
                     0xBE => {
                         match op.op_size {
                             OperandSize::_16bit => {
                                 // movsx r16, r/m8
                                 op.command = Op::Movsx16;
-                                op.params = self.r16_rm8(&mut mmu, op.segment_prefix);
+                                op.params = self.r16_rm8(&mut mmu, op);
                             }
                             OperandSize::_32bit => {
                                 // movsx r32, r/m8
                                 op.command = Op::Movsx32;
-                                op.params = self.r32_rm8(&mut mmu, op.segment_prefix);
+                                op.params = self.r32_rm8(&mut mmu, op);
                             }
                         }
                     }
@@ -377,13 +657,28 @@ impl Decoder {
                             }
                         }
                     }
+                    0xC0 => {
+                        // xadd r/m8, r8
+                        op.command = Op::Xadd8;
+                        op.params = self.rm8_r8(&mut mmu, op);
+                    }
+                    0xC1 => {
+                        // xadd r/m16, r16
+                        // xadd r/m32, r32
+                        self.prefixed_16_32_rm_r(&mut mmu, &mut op, Op::Xadd16, Op::Xadd32)
+                    }
+                    0xC8..=0xCF => {
+                        // bswap r32
+                        op.command = Op::Bswap;
+                        op.params.dst = Parameter::Reg32(r32(b2 & 7));
+                    }
                     _ => op.command = Op::Invalid(vec!(b, b2), Invalid::Op),
                 }
             }
             0x10 => {
                 // adc r/m8, r8
                 op.command = Op::Adc8;
-                op.params = self.rm8_r8(&mut mmu, op.segment_prefix);
+                op.params = self.rm8_r8(&mut mmu, op);
             }
             0x11 => {
                 // adc r/m16, r16
@@ -393,12 +688,12 @@ impl Decoder {
             0x12 => {
                 // adc r8, r/m8
                 op.command = Op::Adc8;
-                op.params = self.r8_rm8(&mut mmu, op.segment_prefix);
+                op.params = self.r8_rm8(&mut mmu, op);
             }
             0x13 => {
                 // adc r16, r/m16
-                op.command = Op::Adc16;
-                op.params = self.r16_rm16(&mut mmu, op);
+                // adc r32, r/m32
+                self.prefixed_16_32_r_rm(&mut mmu, &mut op, Op::Adc16, Op::Adc32)
             }
             0x14 => {
                 // adc al, imm8
@@ -435,7 +730,7 @@ impl Decoder {
             0x18 => {
                 // sbb r/m8, r8
                 op.command = Op::Sbb8;
-                op.params = self.rm8_r8(&mut mmu, op.segment_prefix);
+                op.params = self.rm8_r8(&mut mmu, op);
             }
             0x19 => {
                 // sbb r/m16, r16
@@ -445,12 +740,12 @@ impl Decoder {
             0x1A => {
                 // sbb r8, r/m8
                 op.command = Op::Sbb8;
-                op.params = self.r8_rm8(&mut mmu, op.segment_prefix);
+                op.params = self.r8_rm8(&mut mmu, op);
             }
             0x1B => {
                 // sbb r16, r/m16
-                op.command = Op::Sbb16;
-                op.params = self.r16_rm16(&mut mmu, op);
+                // sbb r32, r/m32
+                self.prefixed_16_32_r_rm(&mut mmu, &mut op, Op::Sbb16, Op::Sbb32)
             }
             0x1C => {
                 // sbb al, imm8
@@ -487,7 +782,7 @@ impl Decoder {
             0x20 => {
                 // and r/m8, r8
                 op.command = Op::And8;
-                op.params = self.rm8_r8(&mut mmu, op.segment_prefix);
+                op.params = self.rm8_r8(&mut mmu, op);
             }
             0x21 => {
                 // and r/m16, r16
@@ -497,12 +792,12 @@ impl Decoder {
             0x22 => {
                 // and r8, r/m8
                 op.command = Op::And8;
-                op.params = self.r8_rm8(&mut mmu, op.segment_prefix);
+                op.params = self.r8_rm8(&mut mmu, op);
             }
             0x23 => {
                 // and r16, r/m16
-                op.command = Op::And16;
-                op.params = self.r16_rm16(&mut mmu, op);
+                // and r32, r/m32
+                self.prefixed_16_32_r_rm(&mut mmu, &mut op, Op::And16, Op::And32)
             }
             0x24 => {
                 // and AL, imm8
@@ -537,7 +832,7 @@ impl Decoder {
             0x28 => {
                 // sub r/m8, r8
                 op.command = Op::Sub8;
-                op.params = self.rm8_r8(&mut mmu, op.segment_prefix);
+                op.params = self.rm8_r8(&mut mmu, op);
             }
             0x29 => {
                 // sub r/m16, r16
@@ -547,7 +842,7 @@ impl Decoder {
             0x2A => {
                 // sub r8, r/m8
                 op.command = Op::Sub8;
-                op.params = self.r8_rm8(&mut mmu, op.segment_prefix);
+                op.params = self.r8_rm8(&mut mmu, op);
             }
             0x2B => {
                 // sub r16, r/m16
@@ -587,7 +882,7 @@ impl Decoder {
             0x30 => {
                 // xor r/m8, r8
                 op.command = Op::Xor8;
-                op.params = self.rm8_r8(&mut mmu, op.segment_prefix);
+                op.params = self.rm8_r8(&mut mmu, op);
             }
             0x31 => {
                 // xor r/m16, r16
@@ -597,7 +892,7 @@ impl Decoder {
             0x32 => {
                 // xor r8, r/m8
                 op.command = Op::Xor8;
-                op.params = self.r8_rm8(&mut mmu, op.segment_prefix);
+                op.params = self.r8_rm8(&mut mmu, op);
             }
             0x33 => {
                 // xor r16, r/m16
@@ -637,7 +932,7 @@ impl Decoder {
             0x38 => {
                 // cmp r/m8, r8
                 op.command = Op::Cmp8;
-                op.params = self.rm8_r8(&mut mmu, op.segment_prefix);
+                op.params = self.rm8_r8(&mut mmu, op);
             }
             0x39 => {
                 // cmp r/m16, r16
@@ -647,7 +942,7 @@ impl Decoder {
             0x3A => {
                 // cmp r8, r/m8
                 op.command = Op::Cmp8;
-                op.params = self.r8_rm8(&mut mmu, op.segment_prefix);
+                op.params = self.r8_rm8(&mut mmu, op);
             }
             0x3B => {
                 // cmp r16, r/m16
@@ -767,14 +1062,22 @@ impl Decoder {
             }
             0x66 => {
                 // 80386+ Operand-size override prefix
-                op.op_size = OperandSize::_32bit;
+                op.op_size = if self.code32 {
+                    OperandSize::_16bit
+                } else {
+                    OperandSize::_32bit
+                };
                 self.decode(&mut mmu, &mut op);
                 op.length += 1;
                 return;
             }
             0x67 => {
                 // 80386+ Address-size override prefix
-                op.address_size = AddressSize::_32bit;
+                op.address_size = if self.code32 {
+                    AddressSize::_16bit
+                } else {
+                    AddressSize::_32bit
+                };
                 self.decode(&mut mmu, &mut op);
                 op.length += 1;
                 return;
@@ -818,94 +1121,100 @@ impl Decoder {
                 }
             },
             0x6C => op.command = Op::Insb,
-            0x6D => op.command = Op::Insw,
+            0x6D => op.command = match op.op_size {
+                OperandSize::_16bit => Op::Insw,
+                OperandSize::_32bit => Op::Insd,
+            },
             0x6E => op.command = Op::Outsb,
-            0x6F => op.command = Op::Outsw,
+            0x6F => op.command = match op.op_size {
+                OperandSize::_16bit => Op::Outsw,
+                OperandSize::_32bit => Op::Outsd,
+            },
             0x70 => {
                 // jo rel8
                 op.command = Op::Jo;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0x71 => {
                 // jno rel8
                 op.command = Op::Jno;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0x72 => {
                 // jc rel8
                 op.command = Op::Jc;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0x73 => {
                 // jnc rel8
                 op.command = Op::Jnc;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0x74 => {
                 // jz rel8
                 op.command = Op::Jz;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0x75 => {
                 // jnz rel8
                 op.command = Op::Jnz;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0x76 => {
                 // jna rel8
                 op.command = Op::Jna;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0x77 => {
                 // ja rel8
                 op.command = Op::Ja;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0x78 => {
                 // js rel8
                 op.command = Op::Js;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0x79 => {
                 // jns rel8
                 op.command = Op::Jns;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
 	        0x7A => {
                 // jpe rel8
 		        op.command = Op::Jpe; // alias: jp
-		        op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+		        op.params.dst = self.read_rel8(mmu, op);
             }
             0x7B => {
                 // jpo rel8
                 op.command = Op::Jpo; // alias: jnp
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0x7C => {
                 // jl rel8
                 op.command = Op::Jl;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0x7D => {
                 // jnl rel8
                 op.command = Op::Jnl;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0x7E => {
                 // jng rel8
                 op.command = Op::Jng;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0x7F => {
                 // jg rel8
                 op.command = Op::Jg;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0x80 | 0x82 => {
                 // <arithmetic> r/m8, imm8
                 // 0x82 is unrecognized by objdump & ndisasm, but alias to 0x80 on pre Pentium 4:s according to ref.x86asm.net
                 let x = self.read_mod_reg_rm(mmu);
-                op.params.dst = self.rm8(&mut mmu, op.segment_prefix, x.rm, x.md);
+                op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
                 op.params.src = Parameter::Imm8(self.read_u8(mmu));
                 op.command = match x.reg {
                     0 => Op::Add8,
@@ -997,7 +1306,7 @@ impl Decoder {
             0x84 => {
                 // test r/m8, r8
                 op.command = Op::Test8;
-                op.params = self.rm8_r8(&mut mmu, op.segment_prefix);
+                op.params = self.rm8_r8(&mut mmu, op);
             }
             0x85 => {
                 // test r/m16, r16
@@ -1008,17 +1317,17 @@ impl Decoder {
             0x86 => {
                 // xchg r/m8, r8
                 op.command = Op::Xchg8;
-                op.params = self.rm8_r8(&mut mmu, op.segment_prefix);
+                op.params = self.rm8_r8(&mut mmu, op);
             }
             0x87 => {
                 // xchg r/m16, r16
-                op.command = Op::Xchg16;
-                op.params = self.rm16_r16(&mut mmu, op);
+                // xchg r/m32, r32
+                self.prefixed_16_32_rm_r(&mut mmu, &mut op, Op::Xchg16, Op::Xchg32)
             }
             0x88 => {
                 // mov r/m8, r8
                 op.command = Op::Mov8;
-                op.params = self.rm8_r8(&mut mmu, op.segment_prefix);
+                op.params = self.rm8_r8(&mut mmu, op);
             }
             0x89 => {
                 // mov r/m16, r16
@@ -1028,7 +1337,7 @@ impl Decoder {
             0x8A => {
                 // mov r8, r/m8
                 op.command = Op::Mov8;
-                op.params = self.r8_rm8(&mut mmu, op.segment_prefix);
+                op.params = self.r8_rm8(&mut mmu, op);
             }
             0x8B => {
                 // mov r16, r/m16
@@ -1040,11 +1349,18 @@ impl Decoder {
                 op.command = Op::Mov16;
                 op.params = self.rm16_sreg(&mut mmu, op);
             }
-            0x8D => {
-                // lea r16, m
-                op.command = Op::Lea16;
-                op.params = self.r16_m16(&mut mmu, op);
-            }
+            0x8D => match op.op_size {
+                OperandSize::_16bit => {
+                    // lea r16, m
+                    op.command = Op::Lea16;
+                    op.params = self.r16_m16(&mut mmu, op);
+                }
+                OperandSize::_32bit => {
+                    // lea r32, m
+                    op.command = Op::Lea32;
+                    op.params = self.r32_rm32(&mut mmu, op);
+                }
+            },
             0x8E => {
                 // mov sreg, r/m16
                 op.command = Op::Mov16;
@@ -1079,56 +1395,64 @@ impl Decoder {
                     OperandSize::_32bit => Op::Cwde32,
                 };
             }
-            0x99 => op.command = Op::Cwd16,
+            0x99 => op.command = match op.op_size {
+                OperandSize::_16bit => Op::Cwd16,
+                OperandSize::_32bit => Op::Cdq32,
+            },
             0x9A => {
                 // call ptr16:16
+                // call ptr16:32
                 op.command = Op::CallFar;
-                let imm = self.read_u16(mmu);
-                let seg = self.read_u16(mmu);
-                op.params.dst = Parameter::Ptr16Imm(seg, imm);
+                op.params.dst = self.read_far_pointer(mmu, op);
             }
             0x9B => op.command = Op::Fwait,
-            0x9C => op.command = Op::Pushf,
-            0x9D => op.command = Op::Popf,
+            0x9C => op.command = match op.op_size {
+                OperandSize::_16bit => Op::Pushf,
+                OperandSize::_32bit => Op::Pushfd,
+            },
+            0x9D => op.command = match op.op_size {
+                OperandSize::_16bit => Op::Popf,
+                OperandSize::_32bit => Op::Popfd,
+            },
             0x9E => op.command = Op::Sahf,
             0x9F => op.command = Op::Lahf,
             0xA0 => {
                 // mov AL, [moffs8]
                 op.command = Op::Mov8;
                 op.params.dst = Parameter::Reg8(R::AL);
-                op.params.src = Parameter::Ptr8(op.segment_prefix, self.read_u16(mmu));
+                op.params.src = self.rm8(&mut mmu, op, direct_rm(op), 0);
             }
             0xA1 => match op.op_size {
                 OperandSize::_16bit => {
                     // mov AX, [moffs16]
                     op.command = Op::Mov16;
                     op.params.dst = Parameter::Reg16(R::AX);
-                    op.params.src = Parameter::Ptr16(op.segment_prefix, self.read_u16(mmu));
+                    op.params.src = self.rm16(&mut mmu, op, direct_rm(op), 0);
                 }
                 OperandSize::_32bit => {
                     // mov EAX, [moffs32]
                     op.command = Op::Mov32;
                     op.params.dst = Parameter::Reg32(R::EAX);
-                    op.params.src = Parameter::Ptr32(op.segment_prefix, self.read_u16(mmu));
+                    op.params.src = self.rm32(&mut mmu, op, direct_rm(op), 0);
                 }
             },
             0xA2 => {
                 // mov [moffs8], AL
                 op.command = Op::Mov8;
-                op.params.dst = Parameter::Ptr8(op.segment_prefix, self.read_u16(mmu));
+                op.params.dst = self.rm8(&mut mmu, op, direct_rm(op), 0);
                 op.params.src = Parameter::Reg8(R::AL);
             }
             0xA3 => match op.op_size {
                 OperandSize::_16bit => {
                     // mov [moffs16], AX
                     op.command = Op::Mov16;
-                    op.params.dst = Parameter::Ptr16(op.segment_prefix, self.read_u16(mmu));
+                    op.params.dst = self.rm16(&mut mmu, op, direct_rm(op), 0);
                     op.params.src = Parameter::Reg16(R::AX);
                 }
                 OperandSize::_32bit => {
                     // mov [moffs32], EAX
                     op.command = Op::Mov32;
-                    op.params.dst = Parameter::Ptr32(op.segment_prefix, self.read_u16(mmu));
+                    op.params.dst = self.rm32(&mut mmu, op, direct_rm(op), 0);
                     op.params.src = Parameter::Reg32(R::EAX);
                 }
             },
@@ -1138,7 +1462,10 @@ impl Decoder {
                 OperandSize::_32bit => Op::Movsd,
             },
             0xA6 => op.command = Op::Cmpsb,
-            0xA7 => op.command = Op::Cmpsw,
+            0xA7 => op.command = match op.op_size {
+                OperandSize::_16bit => Op::Cmpsw,
+                OperandSize::_32bit => Op::Cmpsd,
+            },
             0xA8 => {
                 // test AL, imm8
                 op.command = Op::Test8;
@@ -1172,7 +1499,10 @@ impl Decoder {
                 OperandSize::_32bit => Op::Lodsd,
             },
             0xAE => op.command = Op::Scasb,
-            0xAF => op.command = Op::Scasw,
+            0xAF => op.command = match op.op_size {
+                OperandSize::_16bit => Op::Scasw,
+                OperandSize::_32bit => Op::Scasd,
+            },
             0xB0..=0xB7 => {
                 // mov r8, u8
                 op.command = Op::Mov8;
@@ -1206,7 +1536,7 @@ impl Decoder {
                     7 => Op::Sar8,
                     _ => Op::Invalid(vec!(b), Invalid::Reg(x.reg)),
                 };
-                op.params.dst = self.rm8(&mut mmu, op.segment_prefix, x.rm, x.md);
+                op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
                 op.params.src = Parameter::Imm8(self.read_u8(mmu));
             }
             0xC1 => {
@@ -1262,7 +1592,7 @@ impl Decoder {
             }
             0xC6 => {
                 let x = self.read_mod_reg_rm(mmu);
-                op.params.dst = self.rm8(&mut mmu, op.segment_prefix, x.rm, x.md);
+                op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
                 op.params.src = Parameter::Imm8(self.read_u8(mmu));
                 op.command = match x.reg {
                     0 => Op::Mov8, // mov r/m8, imm8
@@ -1313,7 +1643,10 @@ impl Decoder {
                 op.params.dst = Parameter::Imm8(self.read_u8(mmu));
             }
             0xCE => op.command = Op::Into,
-	        0xCF => op.command = Op::Iret,
+            0xCF => op.command = match op.op_size {
+                OperandSize::_16bit => Op::Iret,
+                OperandSize::_32bit => Op::Iretd,
+            },
             0xD0 => {
                 // bit shift byte by 1
                 let x = self.read_mod_reg_rm(mmu);
@@ -1327,23 +1660,41 @@ impl Decoder {
                     7 => Op::Sar8,
                     _ => Op::Invalid(vec!(b, x.u8()), Invalid::Reg(x.reg)),
                 };
-                op.params.dst = self.rm8(&mut mmu, op.segment_prefix, x.rm, x.md);
+                op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
                 op.params.src = Parameter::Imm8(1);
             }
             0xD1 => {
-                // bit shift word by 1
                 let x = self.read_mod_reg_rm(mmu);
-                op.command = match x.reg {
-                    0 => Op::Rol16,
-                    1 => Op::Ror16,
-                    2 => Op::Rcl16,
-                    3 => Op::Rcr16,
-                    4 => Op::Shl16,
-                    5 => Op::Shr16,
-                    7 => Op::Sar16,
-                    _ => Op::Invalid(vec!(b), Invalid::Reg(x.reg)),
-                };
-                op.params.dst = self.rm16(&mut mmu, op, x.rm, x.md);
+                match op.op_size {
+                    OperandSize::_16bit => {
+                        // bit shift word by 1
+                        op.command = match x.reg {
+                            0 => Op::Rol16,
+                            1 => Op::Ror16,
+                            2 => Op::Rcl16,
+                            3 => Op::Rcr16,
+                            4 => Op::Shl16,
+                            5 => Op::Shr16,
+                            7 => Op::Sar16,
+                            _ => Op::Invalid(vec!(b), Invalid::Reg(x.reg)),
+                        };
+                        op.params.dst = self.rm16(&mut mmu, op, x.rm, x.md);
+                    }
+                    OperandSize::_32bit => {
+                        // bit shift dword by 1
+                        op.command = match x.reg {
+                            0 => Op::Rol32,
+                            1 => Op::Ror32,
+                            2 => Op::Rcl32,
+                            3 => Op::Rcr32,
+                            4 => Op::Shl32,
+                            5 => Op::Shr32,
+                            7 => Op::Sar32,
+                            _ => Op::Invalid(vec!(b), Invalid::Reg(x.reg)),
+                        };
+                        op.params.dst = self.rm32(&mut mmu, op, x.rm, x.md);
+                    }
+                }
                 op.params.src = Parameter::Imm16(1);
             }
             0xD2 => {
@@ -1359,23 +1710,41 @@ impl Decoder {
                     7 => Op::Sar8,
                     _ => Op::Invalid(vec!(b), Invalid::Reg(x.reg)),
                 };
-                op.params.dst = self.rm8(&mut mmu, op.segment_prefix, x.rm, x.md);
+                op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
                 op.params.src = Parameter::Reg8(R::CL);
             }
             0xD3 => {
-                // bit shift word by CL
                 let x = self.read_mod_reg_rm(mmu);
-                op.command = match x.reg {
-                    0 => Op::Rol16,
-                    1 => Op::Ror16,
-                    2 => Op::Rcl16,
-                    3 => Op::Rcr16,
-                    4 => Op::Shl16,
-                    5 => Op::Shr16,
-                    7 => Op::Sar16,
-                    _ => Op::Invalid(vec!(b), Invalid::Reg(x.reg)),
-                };
-                op.params.dst = self.rm16(&mut mmu, op, x.rm, x.md);
+                match op.op_size {
+                    OperandSize::_16bit => {
+                        // bit shift word by CL
+                        op.command = match x.reg {
+                            0 => Op::Rol16,
+                            1 => Op::Ror16,
+                            2 => Op::Rcl16,
+                            3 => Op::Rcr16,
+                            4 => Op::Shl16,
+                            5 => Op::Shr16,
+                            7 => Op::Sar16,
+                            _ => Op::Invalid(vec!(b), Invalid::Reg(x.reg)),
+                        };
+                        op.params.dst = self.rm16(&mut mmu, op, x.rm, x.md);
+                    }
+                    OperandSize::_32bit => {
+                        // bit shift dword by CL
+                        op.command = match x.reg {
+                            0 => Op::Rol32,
+                            1 => Op::Ror32,
+                            2 => Op::Rcl32,
+                            3 => Op::Rcr32,
+                            4 => Op::Shl32,
+                            5 => Op::Shr32,
+                            7 => Op::Sar32,
+                            _ => Op::Invalid(vec!(b), Invalid::Reg(x.reg)),
+                        };
+                        op.params.dst = self.rm32(&mut mmu, op, x.rm, x.md);
+                    }
+                }
                 op.params.src = Parameter::Reg8(R::CL);
             }
             0xD4 => {
@@ -1392,40 +1761,26 @@ impl Decoder {
             0xD8 => {
                 // fpu
                 let x = self.read_mod_reg_rm(mmu);
-                match x.reg {
-                    0 => { // D8 /0 FADD m32fp
-                        op.command = Op::Fadd;
-                        op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
-                    }
-                    1 => { // D8 /1 FMUL m32fp
-                        op.command = Op::Fmul;
-                        op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
-                    }
-                    3 => { // D8 /3 FCOMP m32fp
-                        op.command = Op::Fcomp;
-                        op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
-                    }
-                    4 => { // D8 /4 FSUB m32fp
-                        op.command = Op::Fsub;
-                        op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
-                    }
-                    5 => { // D8 /5 FSUBR m32fp
-                        op.command = Op::Fsubr;
-                        op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
-                    }
-                    6 => { // D8 /6 FDIV m32fp
-                        op.command = Op::Fdiv;
-                        op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
-                    }
-                    7 => { // D8 /7 FDIVR m32fp
-                        op.command = Op::Fdivr;
-                        op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
-                    }
-                    _ => {
-                        println!("XXX unhandled D8 reg {:?}", x);
-                        op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
-                    }
-                }
+                // D8 /0 FADD m32fp         D8 C0+i FADD ST(0), ST(i)
+                // D8 /1 FMUL m32fp         D8 C8+i FMUL ST(0), ST(i)
+                // D8 /2 FCOM m32fp         D8 D0+i FCOM ST(i)
+                // D8 /3 FCOMP m32fp        D8 D8+i FCOMP ST(i)
+                // D8 /4 FSUB m32fp         D8 E0+i FSUB ST(0), ST(i)
+                // D8 /5 FSUBR m32fp        D8 E8+i FSUBR ST(0), ST(i)
+                // D8 /6 FDIV m32fp         D8 F0+i FDIV ST(0), ST(i)
+                // D8 /7 FDIVR m32fp        D8 F8+i FDIVR ST(0), ST(i)
+                op.command = match x.reg {
+                    0 => Op::Fadd,
+                    1 => Op::Fmul,
+                    2 => Op::Fcom,
+                    3 => Op::Fcomp,
+                    4 => Op::Fsub,
+                    5 => Op::Fsubr,
+                    6 => Op::Fdiv,
+                    7 => Op::Fdivr,
+                    _ => unreachable!(),
+                };
+                op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
             }
             0xD9 => {
                 // fpu
@@ -1449,12 +1804,22 @@ impl Decoder {
                             op.command = Op::Fstp;
                             op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
                         }
+                        4 => {
+                            // D9 /4 FLDENV m14/28byte
+                            op.command = Op::Fldenv;
+                            op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
+                        }
                         5 => {
                             // D9 /5 FLDCW m2byte
                             // D928              fldcw [bx+si] { md: 0, reg: 5, rm: 0 }
                             op.command = Op::Fldcw;
                             op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
                         }
+                        6 => {
+                            // D9 /6 FNSTENV m14/28byte
+                            op.command = Op::Fnstenv;
+                            op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
+                        }
                         7 => {
                             // D9 /7 FNSTCW m2byte
                             op.command = Op::Fnstcw;
@@ -1477,10 +1842,18 @@ impl Decoder {
                             op.command = Op::Fxch;
                             op.params.dst = Parameter::FPR80(fpr(x.rm));
                         }
+                        2 => match x.rm {
+                            0 => op.command = Op::Fnop,     // { md: 3, reg: 2, rm: 0 }
+                            _ => {
+                                println!("XXX unhandled D9 md3 reg2 rm {:?}", x);
+                                op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
+                            }
+                        }
                         4 => match x.rm {
                             0 => op.command = Op::Fchs, // { md: 3, reg: 4, rm: 0 }
                             1 => op.command = Op::Fabs, // { md: 3, reg: 4, rm: 1 }
                             4 => op.command = Op::Ftst, // { md: 3, reg: 4, rm: 4 }
+                            5 => op.command = Op::Fxam, // { md: 3, reg: 4, rm: 5 }
                             _ => {
                                 println!("XXX unhandled D9 md3 reg4 rm {:?}", x);
                                 op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
@@ -1491,240 +1864,397 @@ impl Decoder {
                             1 => op.command = Op::Fldl2t,   // { md: 3, reg: 5, rm: 1 }
                             2 => op.command = Op::Fldl2e,   // { md: 3, reg: 5, rm: 2 }
                             3 => op.command = Op::Fldpi,    // { md: 3, reg: 5, rm: 3 }
+                            4 => op.command = Op::Fldlg2,   // { md: 3, reg: 5, rm: 4 }
+                            5 => op.command = Op::Fldln2,   // { md: 3, reg: 5, rm: 5 }
                             6 => op.command = Op::Fldz,     // { md: 3, reg: 5, rm: 6 }
                             _ => {
                                 println!("XXX unhandled D9 md3 reg5 rm {:?}", x);
                                 op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
                             }
                         }
-                        6 => match x.rm {
-                            3 => op.command = Op::Fpatan, // { md: 3, reg: 6, rm: 3 }
-                            _ => {
-                                println!("XXX unhandled D9 md3 reg5 rm {:?}", x);
-                                op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
-                            }
-                        }
-                        7 => match x.rm {
-                            2 => op.command = Op::Fsqrt,    // { md: 3, reg: 7, rm: 2 }
-                            3 => op.command = Op::Fsincos,  // { md: 3, reg: 7, rm: 3 }
-                            4 => op.command = Op::Frndint,  // { md: 3, reg: 7, rm: 4 }
-                            6 => op.command = Op::Fsin,     // { md: 3, reg: 7, rm: 6 }
-                            7 => op.command = Op::Fcos,     // { md: 3, reg: 7, rm: 7 }
-                            _ => {
-                                println!("XXX unhandled D9 md3 reg7 rm {:?}", x);
-                                op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
-                            }
-                        }
+                        6 => op.command = match x.rm {
+                            0 => Op::F2xm1,     // { md: 3, reg: 6, rm: 0 }
+                            1 => Op::Fyl2x,     // { md: 3, reg: 6, rm: 1 }
+                            2 => Op::Fptan,     // { md: 3, reg: 6, rm: 2 }
+                            3 => Op::Fpatan,    // { md: 3, reg: 6, rm: 3 }
+                            4 => Op::Fxtract,   // { md: 3, reg: 6, rm: 4 }
+                            5 => Op::Fprem1,    // { md: 3, reg: 6, rm: 5 }
+                            6 => Op::Fdecstp,   // { md: 3, reg: 6, rm: 6 }
+                            7 => Op::Fincstp,   // { md: 3, reg: 6, rm: 7 }
+                            _ => unreachable!(),
+                        },
+                        7 => op.command = match x.rm {
+                            0 => Op::Fprem,     // { md: 3, reg: 7, rm: 0 }
+                            1 => Op::Fyl2xp1,   // { md: 3, reg: 7, rm: 1 }
+                            2 => Op::Fsqrt,     // { md: 3, reg: 7, rm: 2 }
+                            3 => Op::Fsincos,   // { md: 3, reg: 7, rm: 3 }
+                            4 => Op::Frndint,   // { md: 3, reg: 7, rm: 4 }
+                            5 => Op::Fscale,    // { md: 3, reg: 7, rm: 5 }
+                            6 => Op::Fsin,      // { md: 3, reg: 7, rm: 6 }
+                            7 => Op::Fcos,      // { md: 3, reg: 7, rm: 7 }
+                            _ => unreachable!(),
+                        },
                         _ => {
                             println!("XXX unhandled D9 md3 reg {:?}", x);
                             op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
                         }
                     }
-                    _ => {
-                        println!("XXX unhandled D9 md {:?}", x);
-                        op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
-                    }
+                    _ => unreachable!(),
                 }
             }
             0xDA => {
+                // fpu
                 let x = self.read_mod_reg_rm(mmu);
-                match x.reg {
-                    3 => {
-                        // DA /3 FICOMP m32int
-                        op.command = Op::Ficomp;
-                        op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
-                    }
-                    _ => {
-                        println!("XXX unhandled DA md {:?}", x);
+                if x.md == 3 {
+                    if x.reg == 5 && x.rm == 1 {
+                        // DA E9 FUCOMPP
+                        op.command = Op::Fucompp;
+                    } else {
+                        // DA C0+i FCMOVcc are P6 instructions
+                        println!("XXX unhandled DA md3 {:?}", x);
                         op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
                     }
+                } else {
+                    // DA /0 FIADD m32int
+                    // DA /1 FIMUL m32int
+                    // DA /2 FICOM m32int
+                    // DA /3 FICOMP m32int
+                    // DA /4 FISUB m32int
+                    // DA /5 FISUBR m32int
+                    // DA /6 FIDIV m32int
+                    // DA /7 FIDIVR m32int
+                    op.command = match x.reg {
+                        0 => Op::Fiadd,
+                        1 => Op::Fimul,
+                        2 => Op::Ficom,
+                        3 => Op::Ficomp,
+                        4 => Op::Fisub,
+                        5 => Op::Fisubr,
+                        6 => Op::Fidiv,
+                        7 => Op::Fidivr,
+                        _ => unreachable!(),
+                    };
+                    op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
                 }
             }
             0xDB => {
+                // fpu
                 let x = self.read_mod_reg_rm(mmu);
-                match x.reg {
-                    0 => {
-                        // DB /0 FILD m32int
-                        // DB05              fild dword [di]
-                        op.command = Op::Fild;
-                        op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
-                    }
-                    1 => {
-                        // DB /1 FISTTP m32int
-                        op.command = Op::Fisttp;
-                        op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
-                    }
-                    3 => {
-                        // DB /3 FISTP m32int
-                        op.command = Op::Fistp;
-                        op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
-                    }
-                    4 => {
-                        if x.md == 3 && x.rm == 3 { // DB E3 FINIT
-                            op.command = Op::Finit;
+                if x.md == 3 {
+                    match x.reg {
+                        4 => match x.rm {
+                            0 | 1 | 4 => {
+                                // DB E0 FENI, DB E1 FDISI (8087 only), DB E4 FSETPM (80287 only)
+                                op.command = Op::Fnop;
+                            }
+                            2 => op.command = Op::Fclex,    // DB E2 FNCLEX
+                            3 => op.command = Op::Finit,    // DB E3 FNINIT
+                            _ => {
+                                println!("XXX unhandled DB md3 reg4 {:?}", x);
+                                op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
+                            }
+                        }
+                        _ => {
+                            // DB C0+i FCMOVcc are P6 instructions
+                            println!("XXX unhandled DB md3 {:?}", x);
+                            op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
                         }
                     }
-                    _ => {
-                        println!("XXX unhandled DB reg {:?}", x);
-                        op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
+                } else {
+                    match x.reg {
+                        0 => {
+                            // DB /0 FILD m32int
+                            // DB05              fild dword [di]
+                            op.command = Op::Fild;
+                            op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
+                        }
+                        1 => {
+                            // DB /1 FISTTP m32int
+                            op.command = Op::Fisttp;
+                            op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
+                        }
+                        2 => {
+                            // DB /2 FIST m32int
+                            op.command = Op::Fist;
+                            op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
+                        }
+                        3 => {
+                            // DB /3 FISTP m32int
+                            op.command = Op::Fistp;
+                            op.params.dst = self.rmf32(mmu, op, x.rm, x.md);
+                        }
+                        5 => {
+                            // DB /5 FLD m80fp
+                            op.command = Op::Fld;
+                            op.params.dst = self.rmf80(mmu, op, x.rm, x.md);
+                        }
+                        7 => {
+                            // DB /7 FSTP m80fp
+                            op.command = Op::Fstp;
+                            op.params.dst = self.rmf80(mmu, op, x.rm, x.md);
+                        }
+                        _ => {
+                            println!("XXX unhandled DB reg {:?}", x);
+                            op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
+                        }
                     }
                 }
             }
             0xDC => {
-                // DC /0    FADD m64fp
-                // DC C0+i  FADD ST(i), ST(0)
-
-                // DC /1    FMUL m64fp
-                // DC C8+i  FMUL ST(i), ST(0)
-
-                // DC /4    FSUB m64fp
-                // DC E8+i  FSUB ST(i), ST(0)
-
-                // DC /5       FSUBR m64fp
-                // DC E0+i     FSUBR ST(i), ST(0)
-
-                // DCC1              fadd to st1        dos-software-decoding/demo-fpu/kruzhok/kruzhok.com
-                // DCCB              fmul to st3        dos-software-decoding/demo-fpu/chekerz/chekerz.com
-                // DCE9              fsub to st1        dos-software-decoding/demo-fpu/glass512/glass512.com
-                // DCE5              fsubr to st5       dos-software-decoding/demo-fpu/zud/zud_final.com
-
+                // fpu
                 let x = self.read_mod_reg_rm(mmu);
-                println!("XXX DC {:?}", x);
+                if x.md == 3 {
+                    // DC C0+i  FADD ST(i), ST(0)
+                    // DC C8+i  FMUL ST(i), ST(0)
+                    // DC E0+i  FSUBR ST(i), ST(0)
+                    // DC E8+i  FSUB ST(i), ST(0)
+                    // DC F0+i  FDIVR ST(i), ST(0)
+                    // DC F8+i  FDIV ST(i), ST(0)
 
-                op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
+                    // DCC1              fadd to st1        dos-software-decoding/demo-fpu/kruzhok/kruzhok.com
+                    // DCCB              fmul to st3        dos-software-decoding/demo-fpu/chekerz/chekerz.com
+                    // DCE9              fsub to st1        dos-software-decoding/demo-fpu/glass512/glass512.com
+                    // DCE5              fsubr to st5       dos-software-decoding/demo-fpu/zud/zud_final.com
+                    op.command = match x.reg {
+                        0 => Op::Fadd,
+                        1 => Op::Fmul,
+                        4 => Op::Fsubr,
+                        5 => Op::Fsub,
+                        6 => Op::Fdivr,
+                        7 => Op::Fdiv,
+                        _ => {
+                            println!("XXX unhandled DC md3 reg {:?}", x);
+                            Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp)
+                        }
+                    };
+                    if op.command.is_valid() {
+                        op.params.dst = Parameter::FPR80(fpr(x.rm));
+                        op.params.src = Parameter::FPR80(R::ST0);
+                    }
+                } else {
+                    // DC /0 FADD m64fp
+                    // DC /1 FMUL m64fp
+                    // DC /2 FCOM m64fp
+                    // DC /3 FCOMP m64fp
+                    // DC /4 FSUB m64fp
+                    // DC /5 FSUBR m64fp
+                    // DC /6 FDIV m64fp
+                    // DC /7 FDIVR m64fp
+                    op.command = match x.reg {
+                        0 => Op::Fadd,
+                        1 => Op::Fmul,
+                        2 => Op::Fcom,
+                        3 => Op::Fcomp,
+                        4 => Op::Fsub,
+                        5 => Op::Fsubr,
+                        6 => Op::Fdiv,
+                        7 => Op::Fdivr,
+                        _ => unreachable!(),
+                    };
+                    op.params.dst = self.rmf64(mmu, op, x.rm, x.md);
+                }
             }
             0xDD => {
                 // fpu
                 let x = self.read_mod_reg_rm(mmu);
-                match x.reg {
-                    0 => {
-                        // DD C0+i FFREE ST(i)
-                        op.command = Op::Ffree;
-                        op.params.dst = Parameter::FPR80(fpr(x.rm));
-                    }
-                    2 => {
-                        // DD /2 FST m64fp
-                        op.command = Op::Fst;
-                        op.params.dst = self.rmf32(mmu, op, x.rm, x.md); // XXX m64fp
-                    }
-                    3 => {
-                        // DD /3 FSTP m64fp
-                        op.command = Op::Fstp;
-                        op.params.dst = self.rmf32(mmu, op, x.rm, x.md); // XXX m64fp
+                if x.md == 3 {
+                    match x.reg {
+                        0 => {
+                            // DD C0+i FFREE ST(i)
+                            op.command = Op::Ffree;
+                            op.params.dst = Parameter::FPR80(fpr(x.rm));
+                        }
+                        2 => {
+                            // DD D0+i FST ST(i)
+                            op.command = Op::Fst;
+                            op.params.dst = Parameter::FPR80(fpr(x.rm));
+                        }
+                        3 => {
+                            // DD D8+i FSTP ST(i)
+                            op.command = Op::Fstp;
+                            op.params.dst = Parameter::FPR80(fpr(x.rm));
+                        }
+                        4 => {
+                            // DD E0+i FUCOM ST(i)
+                            op.command = Op::Fucom;
+                            op.params.dst = Parameter::FPR80(fpr(x.rm));
+                        }
+                        5 => {
+                            // DD E8+i FUCOMP ST(i)
+                            op.command = Op::Fucomp;
+                            op.params.dst = Parameter::FPR80(fpr(x.rm));
+                        }
+                        _ => {
+                            println!("XXX unhandled DD md3 reg {:?}", x);
+                            op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
+                        }
                     }
-                    _ => {
-                        println!("XXX unhandled DD reg {:?}", x);
-                        op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
+                } else {
+                    match x.reg {
+                        0 => {
+                            // DD /0 FLD m64fp
+                            op.command = Op::Fld;
+                            op.params.dst = self.rmf64(mmu, op, x.rm, x.md);
+                        }
+                        1 => {
+                            // DD /1 FISTTP m64int
+                            op.command = Op::Fisttp;
+                            op.params.dst = self.rmf64(mmu, op, x.rm, x.md);
+                        }
+                        2 => {
+                            // DD /2 FST m64fp
+                            op.command = Op::Fst;
+                            op.params.dst = self.rmf64(mmu, op, x.rm, x.md);
+                        }
+                        3 => {
+                            // DD /3 FSTP m64fp
+                            op.command = Op::Fstp;
+                            op.params.dst = self.rmf64(mmu, op, x.rm, x.md);
+                        }
+                        4 => {
+                            // DD /4 FRSTOR m94/108byte
+                            op.command = Op::Frstor;
+                            op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
+                        }
+                        6 => {
+                            // DD /6 FNSAVE m94/108byte
+                            op.command = Op::Fnsave;
+                            op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
+                        }
+                        7 => {
+                            // DD /7 FNSTSW m2byte
+                            op.command = Op::Fstsw;
+                            op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
+                        }
+                        _ => {
+                            println!("XXX unhandled DD reg {:?}", x);
+                            op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
+                        }
                     }
                 }
             }
             0xDE => {
                 // fpu
                 let x = self.read_mod_reg_rm(mmu);
-                match x.reg {
-                    0 => {
-                        // DEC1              faddp st1 { md: 3, reg: 0, rm: 1 }
-                        op.command = Op::Faddp;
-                        op.params.dst = Parameter::FPR80(fpr(x.rm));
-                    }
-                    1 => {
-                        op.command = Op::Fimul;
-                        op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
-                    }
-                    2 => {
-                        // DE /2 FICOM m16int
-                        op.command = Op::Ficom;
-                        op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
-                    }
-                    3 => {
-                        // DE /3 FICOMP m16int
-                        op.command = Op::Ficomp;
-                        op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
-                    }
-                    4 => {
-                        // DE E0+i FSUBRP ST(i), ST(0)
-                        // DEE2              fsubrp st2 { md: 3, reg: 4, rm: 2 }
-                        op.command = Op::Fsubrp;
-                        op.params.dst = Parameter::FPR80(fpr(x.rm));
-                    }
-                    5 => {
-                        // DE E8+i FSUBP ST(i), ST(0)
-                        // DEEA              fsubp st2 { md: 3, reg: 5, rm: 2 }
-                        op.command = Op::Fsubp;
+                if x.md == 3 {
+                    // DE C0+i FADDP ST(i), ST(0)
+                    // DE C8+i FMULP ST(i), ST(0)
+                    // DE D9   FCOMPP
+                    // DE E0+i FSUBRP ST(i), ST(0)
+                    // DE E8+i FSUBP ST(i), ST(0)
+                    // DE F0+i FDIVRP ST(i), ST(0)
+                    // DE F8+i FDIVP ST(i), ST(0)
+
+                    // DEC1              faddp st1 { md: 3, reg: 0, rm: 1 }
+                    // DEE2              fsubrp st2 { md: 3, reg: 4, rm: 2 }
+                    // DEEA              fsubp st2 { md: 3, reg: 5, rm: 2 }
+                    op.command = match x.reg {
+                        0 => Op::Faddp,
+                        1 => Op::Fmulp,
+                        3 if x.rm == 1 => Op::Fcompp,
+                        4 => Op::Fsubrp,
+                        5 => Op::Fsubp,
+                        6 => Op::Fdivrp,
+                        7 => Op::Fdivp,
+                        _ => {
+                            println!("XXX unhandled DE md3 reg {:?}", x);
+                            Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp)
+                        }
+                    };
+                    if op.command.is_valid() && op.command != Op::Fcompp {
                         op.params.dst = Parameter::FPR80(fpr(x.rm));
                     }
-                    6 => {
-                        op.command = Op::Fidiv;
-                        op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
-                    }
-                    7 => {
-                        op.command = Op::Fdivp;
-                        op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
-                    }
-                    _ => {
-                        println!("XXX unhandled DE reg {:?}", x);
-                        op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
-                    }
+                } else {
+                    // DE /0 FIADD m16int
+                    // DE /1 FIMUL m16int
+                    // DE /2 FICOM m16int
+                    // DE /3 FICOMP m16int
+                    // DE /4 FISUB m16int
+                    // DE /5 FISUBR m16int
+                    // DE /6 FIDIV m16int
+                    // DE /7 FIDIVR m16int
+                    op.command = match x.reg {
+                        0 => Op::Fiadd,
+                        1 => Op::Fimul,
+                        2 => Op::Ficom,
+                        3 => Op::Ficomp,
+                        4 => Op::Fisub,
+                        5 => Op::Fisubr,
+                        6 => Op::Fidiv,
+                        7 => Op::Fidivr,
+                        _ => unreachable!(),
+                    };
+                    op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
                 }
             }
             0xDF => {
                 // fpu
                 let x = self.read_mod_reg_rm(mmu);
-                match x.reg {
-                    0 => {  // FILD m16int
-                        op.command = Op::Fild;
-                        op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
-                    }
-                    2 => { // FIST m16int
-                        op.command = Op::Fist;
-                        op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
-                    }
-                    3 => {  // FISTP m16int
-                        op.command = Op::Fistp;
-                        op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
+                if x.md == 3 {
+                    if x.reg == 4 && x.rm == 0 {
+                        // DF E0 FNSTSW AX
+                        op.command = Op::Fstsw;
+                        op.params.dst = Parameter::Reg16(R::AX);
+                    } else {
+                        println!("XXX unhandled DF md3 {:?}", x);
+                        op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
                     }
-                    4 => {
-                        if x.md == 3 && x.rm == 0 {
-                            // DF E0 FNSTSW AX
-                            op.command = Op::Fstsw;
-                            op.params.dst = Parameter::Reg16(R::AX);
-                        } else {
-                            println!("XXX unhandled DF reg4 {:?}", x);
-                            op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
+                } else {
+                    match x.reg {
+                        0 => {  // DF /0 FILD m16int
+                            op.command = Op::Fild;
+                            op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
                         }
-                    }
-                    5 => { // DF /5 FILD m64int
-                        // DF28              fild qword [bx+si]
-                        op.command = Op::Fild;
-                        op.params.dst = self.rmf32(mmu, op, x.rm, x.md); // XXX 64-bit
-                    }
-                    7 => { // DF /7 FISTP m64int
-                        // DF3D              fistp qword [di]
-                        op.command = Op::Fistp;
-                        op.params.dst = self.rmf32(mmu, op, x.rm, x.md); // XXX 64-bit
-                    }
-                    _ => {
-                        println!("XXX unhandled DF reg {:?}", x);
-                        op.command = Op::Invalid(vec!(b, x.u8()), Invalid::FPUOp);
+                        1 => { // DF /1 FISTTP m16int
+                            op.command = Op::Fisttp;
+                            op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
+                        }
+                        2 => { // DF /2 FIST m16int
+                            op.command = Op::Fist;
+                            op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
+                        }
+                        3 => {  // DF /3 FISTP m16int
+                            op.command = Op::Fistp;
+                            op.params.dst = self.rmf16(mmu, op, x.rm, x.md);
+                        }
+                        4 => { // DF /4 FBLD m80bcd
+                            op.command = Op::Fbld;
+                            op.params.dst = self.rmf80(mmu, op, x.rm, x.md);
+                        }
+                        5 => { // DF /5 FILD m64int
+                            // DF28              fild qword [bx+si]
+                            op.command = Op::Fild;
+                            op.params.dst = self.rmf64(mmu, op, x.rm, x.md);
+                        }
+                        6 => { // DF /6 FBSTP m80bcd
+                            op.command = Op::Fbstp;
+                            op.params.dst = self.rmf80(mmu, op, x.rm, x.md);
+                        }
+                        7 => { // DF /7 FISTP m64int
+                            // DF3D              fistp qword [di]
+                            op.command = Op::Fistp;
+                            op.params.dst = self.rmf64(mmu, op, x.rm, x.md);
+                        }
+                        _ => unreachable!(),
                     }
                 }
             }
             0xE0 => {
                 op.command = Op::Loopne;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0xE1 => {
                 op.command = Op::Loope;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0xE2 => {
                 op.command = Op::Loop;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0xE3 => {
                 // jcxz rel8
                 op.command = Op::Jcxz;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0xE4 => {
                 // in AL, imm8
@@ -1753,24 +2283,23 @@ impl Decoder {
             0xE8 => {
                 // call near s16
                 op.command = Op::CallNear;
-                op.params.dst = Parameter::Imm16(self.read_rel16(mmu));
+                op.params.dst = self.read_rel(mmu, op);
             }
             0xE9 => {
                 // jmp near rel16
                 op.command = Op::JmpNear;
-                op.params.dst = Parameter::Imm16(self.read_rel16(mmu));
+                op.params.dst = self.read_rel(mmu, op);
             }
             0xEA => {
                 // jmp far ptr16:16
+                // jmp far ptr16:32
                 op.command = Op::JmpFar;
-                let imm = self.read_u16(mmu);
-                let seg = self.read_u16(mmu);
-                op.params.dst = Parameter::Ptr16Imm(seg, imm);
+                op.params.dst = self.read_far_pointer(mmu, op);
             }
             0xEB => {
                 // jmp short rel8
                 op.command = Op::JmpShort;
-                op.params.dst = Parameter::Imm16(self.read_rel8(mmu));
+                op.params.dst = self.read_rel8(mmu, op);
             }
             0xEC => {
                 // in AL, DX
@@ -1839,7 +2368,7 @@ impl Decoder {
             0xF6 => {
                 // <math> r/m8
                 let x = self.read_mod_reg_rm(mmu);
-                op.params.dst = self.rm8(&mut mmu, op.segment_prefix, x.rm, x.md);
+                op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
                 match x.reg {
                     0 | 1 => {
                         // test r/m8, imm8
@@ -1904,7 +2433,7 @@ impl Decoder {
             0xFE => {
                 // r/m8
                 let x = self.read_mod_reg_rm(mmu);
-                op.params.dst = self.rm8(&mut mmu, op.segment_prefix, x.rm, x.md);
+                op.params.dst = self.rm8(&mut mmu, op, x.rm, x.md);
                 op.command = match x.reg {
                     // NOTE: 2 is a deprecated but valid encoding, example:
                     // https://www.pouet.net/prod.php?which=65203
@@ -1936,18 +2465,19 @@ impl Decoder {
                         op.command = match x.reg {
                             0 => Op::Inc32,
                             1 => Op::Dec32,
+                            2 => Op::CallNear,
+                            3 => Op::CallFar,
+                            4 => Op::JmpNear,
+                            5 => Op::JmpFar,
                             6 => Op::Push32,
-                            _ => {
-                                println!("XXX FF 32bit {:?}", x);
-                                Op::Invalid(vec!(b, x.u8()), Invalid::Reg(x.reg))
-                            }
+                            _ => Op::Invalid(vec!(b, x.u8()), Invalid::Reg(x.reg)),
                         };
                     }
                 }
             }
         }
         // calculate instruction length
-        op.length = (Wrapping(u16::from(op.length)) + Wrapping(self.current_offset) - Wrapping(start_offset)).0 as u8;
+        op.length = (Wrapping(u32::from(op.length)) + Wrapping(self.current_offset) - Wrapping(start_offset)).0 as u8;
         if DEBUG_DECODER {
             println!("{:04X}: decoded {}", start_offset, op);
         }
@@ -1979,132 +2509,188 @@ impl Decoder {
         }
     }
 
-    /// decode rm8
-    fn rm8(&mut self, mmu: &mut MMU, seg: Segment, rm: u8, md: u8) -> Parameter {
-        let adr_size = AddressSize::_16bit;
+    /// decodes the memory operand of a ModRM byte with mod 0-2
+    fn read_rm_address(&mut self, mmu: &mut MMU, op: &Instruction, rm: u8, md: u8) -> RmAddress {
+        if op.address_size == AddressSize::_32bit {
+            return self.read_rm_address32(mmu, rm, md);
+        }
         match md {
-            0 => {
-                if rm == 6 {
-                    // [u16]
-                    Parameter::Ptr8(seg, self.read_u16(mmu))
-                } else {
-                    // [amode]
-                    Parameter::Ptr8Amode(seg, adr_size.amode_from(rm))
-                }
-            }
+            // [u16]
+            0 if rm == 6 => RmAddress::Direct(self.read_u16(mmu)),
+            // [amode]
+            0 => RmAddress::Amode(op.address_size.amode_from(rm)),
             // [amode+s8]
-            1 => Parameter::Ptr8AmodeS8(seg, adr_size.amode_from(rm), self.read_s8(mmu)),
+            1 => RmAddress::AmodeS8(op.address_size.amode_from(rm), self.read_s8(mmu)),
             // [amode+s16]
-            2 => Parameter::Ptr8AmodeS16(seg, adr_size.amode_from(rm), self.read_s16(mmu)),
+            2 => RmAddress::AmodeS16(op.address_size.amode_from(rm), self.read_s16(mmu)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// decodes the memory operand of a ModRM byte with a 32-bit address size, where
+    /// rm 4 is followed by a SIB byte and mod 0 with rm 5 is a 32-bit displacement
+    fn read_rm_address32(&mut self, mmu: &mut MMU, rm: u8, md: u8) -> RmAddress {
+        let amode = if rm == 4 {
+            self.read_sib(mmu, md)
+        } else if md == 0 && rm == 5 {
+            AMode::Disp32
+        } else {
+            AddressSize::_32bit.amode_from(rm)
+        };
+        match amode {
+            // [s32], [index*scale+s32]
+            AMode::Disp32 | AMode::SIBIndex(..) => RmAddress::AmodeS32(amode, self.read_s32(mmu)),
+            // [amode]
+            _ if md == 0 => RmAddress::Amode(amode),
+            // [amode+s8]
+            _ if md == 1 => RmAddress::AmodeS8(amode, self.read_s8(mmu)),
+            // [amode+s32]
+            _ => RmAddress::AmodeS32(amode, self.read_s32(mmu)),
+        }
+    }
+
+    /// decodes a SIB (scale, index, base) byte
+    fn read_sib(&mut self, mmu: &mut MMU, md: u8) -> AMode {
+        let b = self.read_u8(mmu);
+        let scale = 1 << (b >> 6);
+        let index = (b >> 3) & 7;
+        let base = b & 7;
+        // base 5 with mod 0 means no base register, index 4 means no index register
+        match (base == 5 && md == 0, index == 4) {
+            (true, true) => AMode::Disp32,
+            (true, false) => AMode::SIBIndex(r32(index), scale),
+            (false, true) => AddressSize::_32bit.amode_from(base),
+            (false, false) => AMode::SIB(r32(base), r32(index), scale),
+        }
+    }
+
+    /// decode rm8
+    fn rm8(&mut self, mmu: &mut MMU, op: &Instruction, rm: u8, md: u8) -> Parameter {
+        let seg = op.segment_prefix;
+        match md {
             // reg
             3 => Parameter::Reg8(r8(rm)),
-            _ => unreachable!(),
+            _ => match self.read_rm_address(mmu, op, rm, md) {
+                RmAddress::Direct(imm) => Parameter::Ptr8(seg, imm),
+                RmAddress::Amode(amode) => Parameter::Ptr8Amode(seg, amode),
+                RmAddress::AmodeS8(amode, imm) => Parameter::Ptr8AmodeS8(seg, amode, imm),
+                RmAddress::AmodeS16(amode, imm) => Parameter::Ptr8AmodeS16(seg, amode, imm),
+                RmAddress::AmodeS32(amode, imm) => Parameter::Ptr8AmodeS32(seg, amode, imm),
+            }
         }
     }
 
     /// decode rm16
     fn rm16(&mut self, mmu: &mut MMU, op: &Instruction, rm: u8, md: u8) -> Parameter {
+        let seg = op.segment_prefix;
         match md {
-            0 => {
-                if rm == 6 {
-                    // [u16]
-                    Parameter::Ptr16(op.segment_prefix, self.read_u16(mmu))
-                } else {
-                    // [amode]
-                    Parameter::Ptr16Amode(op.segment_prefix, op.address_size.amode_from(rm))
-                }
-            }
-            // [amode+s8]
-            1 => Parameter::Ptr16AmodeS8(op.segment_prefix, op.address_size.amode_from(rm), self.read_s8(mmu)),
-            // [amode+s16]
-            2 => Parameter::Ptr16AmodeS16(op.segment_prefix, op.address_size.amode_from(rm), self.read_s16(mmu)),
             // [reg]
             3 => Parameter::Reg16(r16(rm)),
-            _ => unreachable!(),
+            _ => match self.read_rm_address(mmu, op, rm, md) {
+                RmAddress::Direct(imm) => Parameter::Ptr16(seg, imm),
+                RmAddress::Amode(amode) => Parameter::Ptr16Amode(seg, amode),
+                RmAddress::AmodeS8(amode, imm) => Parameter::Ptr16AmodeS8(seg, amode, imm),
+                RmAddress::AmodeS16(amode, imm) => Parameter::Ptr16AmodeS16(seg, amode, imm),
+                RmAddress::AmodeS32(amode, imm) => Parameter::Ptr16AmodeS32(seg, amode, imm),
+            }
         }
     }
 
     /// decode rm32
     fn rm32(&mut self, mmu: &mut MMU, op: &Instruction, rm: u8, md: u8) -> Parameter {
+        let seg = op.segment_prefix;
         match md {
-            0 => {
-                if rm == 6 {
-                    // [u16]
-                    Parameter::Ptr32(op.segment_prefix, self.read_u16(mmu))
-                } else {
-                    // [amode]
-                    Parameter::Ptr32Amode(op.segment_prefix, op.address_size.amode_from(rm))
-                }
-            }
-            // [amode+s8]
-            1 => Parameter::Ptr32AmodeS8(op.segment_prefix, op.address_size.amode_from(rm), self.read_s8(mmu)),
-            // [amode+s16]
-            2 => Parameter::Ptr32AmodeS16(op.segment_prefix, op.address_size.amode_from(rm), self.read_s16(mmu)),
             // [reg]
             3 => Parameter::Reg32(r32(rm)),
-            _ => unreachable!(),
+            _ => match self.read_rm_address(mmu, op, rm, md) {
+                RmAddress::Direct(imm) => Parameter::Ptr32(seg, imm),
+                RmAddress::Amode(amode) => Parameter::Ptr32Amode(seg, amode),
+                RmAddress::AmodeS8(amode, imm) => Parameter::Ptr32AmodeS8(seg, amode, imm),
+                RmAddress::AmodeS16(amode, imm) => Parameter::Ptr32AmodeS16(seg, amode, imm),
+                RmAddress::AmodeS32(amode, imm) => Parameter::Ptr32AmodeS32(seg, amode, imm),
+            }
         }
     }
 
     /// decode rm as 16-bit fpu op argument
     fn rmf16(&mut self, mmu: &mut MMU, op: &Instruction, rm: u8, md: u8) -> Parameter {
+        let seg = op.segment_prefix;
         match md {
-            0 => {
-                if rm == 6 {
-                    // [u16]
-                    Parameter::Ptr16(op.segment_prefix, self.read_u16(mmu))
-                } else {
-                    // [amode]
-                    Parameter::Ptr16Amode(op.segment_prefix, op.address_size.amode_from(rm))
-                }
-            }
-            // [amode+s8]
-            1 => Parameter::Ptr16AmodeS8(op.segment_prefix, op.address_size.amode_from(rm), self.read_s8(mmu)),
-            // [amode+s16]
-            2 => Parameter::Ptr16AmodeS16(op.segment_prefix, op.address_size.amode_from(rm), self.read_s16(mmu)),
             // [reg]
             3 => Parameter::FPR80(fpr(rm)),
-            _ => unreachable!(),
+            _ => match self.read_rm_address(mmu, op, rm, md) {
+                RmAddress::Direct(imm) => Parameter::Ptr16(seg, imm),
+                RmAddress::Amode(amode) => Parameter::Ptr16Amode(seg, amode),
+                RmAddress::AmodeS8(amode, imm) => Parameter::Ptr16AmodeS8(seg, amode, imm),
+                RmAddress::AmodeS16(amode, imm) => Parameter::Ptr16AmodeS16(seg, amode, imm),
+                RmAddress::AmodeS32(amode, imm) => Parameter::Ptr16AmodeS32(seg, amode, imm),
+            }
         }
     }
 
     /// decode rm as 32-bit fpu op argument
     fn rmf32(&mut self, mmu: &mut MMU, op: &Instruction, rm: u8, md: u8) -> Parameter {
+        let seg = op.segment_prefix;
         match md {
-            0 => {
-                if rm == 6 {
-                    // [u16]
-                    Parameter::Ptr32(op.segment_prefix, self.read_u16(mmu))
-                } else {
-                    // [amode]
-                    Parameter::Ptr32Amode(op.segment_prefix, op.address_size.amode_from(rm))
-                }
+            // [reg]
+            3 => Parameter::FPR80(fpr(rm)),
+            _ => match self.read_rm_address(mmu, op, rm, md) {
+                RmAddress::Direct(imm) => Parameter::Ptr32(seg, imm),
+                RmAddress::Amode(amode) => Parameter::Ptr32Amode(seg, amode),
+                RmAddress::AmodeS8(amode, imm) => Parameter::Ptr32AmodeS8(seg, amode, imm),
+                RmAddress::AmodeS16(amode, imm) => Parameter::Ptr32AmodeS16(seg, amode, imm),
+                RmAddress::AmodeS32(amode, imm) => Parameter::Ptr32AmodeS32(seg, amode, imm),
             }
-            // [amode+s8]
-            1 => Parameter::Ptr32AmodeS8(op.segment_prefix, op.address_size.amode_from(rm), self.read_s8(mmu)),
-            // [amode+s16]
-            2 => Parameter::Ptr32AmodeS16(op.segment_prefix, op.address_size.amode_from(rm), self.read_s16(mmu)),
+        }
+    }
+
+    /// decode rm as 64-bit fpu op argument
+    fn rmf64(&mut self, mmu: &mut MMU, op: &Instruction, rm: u8, md: u8) -> Parameter {
+        let seg = op.segment_prefix;
+        match md {
             // [reg]
             3 => Parameter::FPR80(fpr(rm)),
-            _ => unreachable!(),
+            _ => match self.read_rm_address(mmu, op, rm, md) {
+                RmAddress::Direct(imm) => Parameter::Ptr64(seg, imm),
+                RmAddress::Amode(amode) => Parameter::Ptr64Amode(seg, amode),
+                RmAddress::AmodeS8(amode, imm) => Parameter::Ptr64AmodeS8(seg, amode, imm),
+                RmAddress::AmodeS16(amode, imm) => Parameter::Ptr64AmodeS16(seg, amode, imm),
+                RmAddress::AmodeS32(amode, imm) => Parameter::Ptr64AmodeS32(seg, amode, imm),
+            }
+        }
+    }
+
+    /// decode rm as 80-bit fpu op argument
+    fn rmf80(&mut self, mmu: &mut MMU, op: &Instruction, rm: u8, md: u8) -> Parameter {
+        let seg = op.segment_prefix;
+        match md {
+            // [reg]
+            3 => Parameter::FPR80(fpr(rm)),
+            _ => match self.read_rm_address(mmu, op, rm, md) {
+                RmAddress::Direct(imm) => Parameter::Ptr80(seg, imm),
+                RmAddress::Amode(amode) => Parameter::Ptr80Amode(seg, amode),
+                RmAddress::AmodeS8(amode, imm) => Parameter::Ptr80AmodeS8(seg, amode, imm),
+                RmAddress::AmodeS16(amode, imm) => Parameter::Ptr80AmodeS16(seg, amode, imm),
+                RmAddress::AmodeS32(amode, imm) => Parameter::Ptr80AmodeS32(seg, amode, imm),
+            }
         }
     }
 
     /// decode r8, r/m8
-    fn r8_rm8(&mut self, mut mmu: &mut MMU, seg: Segment) -> ParameterSet {
+    fn r8_rm8(&mut self, mut mmu: &mut MMU, op: &Instruction) -> ParameterSet {
         let x = self.read_mod_reg_rm(mmu);
         ParameterSet {
             dst: Parameter::Reg8(r8(x.reg)),
-            src: self.rm8(&mut mmu, seg, x.rm, x.md),
+            src: self.rm8(&mut mmu, op, x.rm, x.md),
             src2: Parameter::None,
         }
     }
 
     /// decode r/m8, r8
-    fn rm8_r8(&mut self, mut mmu: &mut MMU, seg: Segment) -> ParameterSet {
+    fn rm8_r8(&mut self, mut mmu: &mut MMU, op: &Instruction) -> ParameterSet {
         let x = self.read_mod_reg_rm(mmu);
         ParameterSet {
-            dst: self.rm8(&mut mmu, seg, x.rm, x.md),
+            dst: self.rm8(&mut mmu, op, x.rm, x.md),
             src: Parameter::Reg8(r8(x.reg)),
             src2: Parameter::None,
         }
@@ -2131,21 +2717,21 @@ impl Decoder {
     }
 
     /// decode r16, r/m8 (movzx)
-    fn r16_rm8(&mut self, mut mmu: &mut MMU, seg: Segment) -> ParameterSet {
+    fn r16_rm8(&mut self, mut mmu: &mut MMU, op: &Instruction) -> ParameterSet {
         let x = self.read_mod_reg_rm(mmu);
         ParameterSet {
             dst: Parameter::Reg16(r16(x.reg)),
-            src: self.rm8(&mut mmu, seg, x.rm, x.md),
+            src: self.rm8(&mut mmu, op, x.rm, x.md),
             src2: Parameter::None,
         }
     }
 
     /// decode r32, r/m8 (movzx)
-    fn r32_rm8(&mut self, mut mmu: &mut MMU, seg: Segment) -> ParameterSet {
+    fn r32_rm8(&mut self, mut mmu: &mut MMU, op: &Instruction) -> ParameterSet {
         let x = self.read_mod_reg_rm(mmu);
         ParameterSet {
             dst: Parameter::Reg32(r32(x.reg)),
-            src: self.rm8(&mut mmu, seg, x.rm, x.md),
+            src: self.rm8(&mut mmu, op, x.rm, x.md),
             src2: Parameter::None,
         }
     }
@@ -2214,8 +2800,7 @@ impl Decoder {
     }
 
     fn read_mod_reg_rm(&mut self, mmu: &MMU) -> ModRegRm {
-        let b = mmu.read_u8(self.current_seg, self.current_offset);
-        self.current_offset = self.current_offset.wrapping_add(1);
+        let b = self.read_u8(mmu);
         let res = ModRegRm {
             md: b >> 6, // high 2 bits
             reg: (b >> 3) & 7, // mid 3 bits
@@ -2227,19 +2812,52 @@ impl Decoder {
         res
     }
 
-    fn read_rel8(&mut self, mmu: &MMU) -> u16 {
+    /// returns the branch target `disp` bytes from the end of the instruction,
+    /// as a Imm32 for 32-bit operand size, or a Imm16 wrapping at 64k
+    fn rel_target(&self, op: &Instruction, disp: i32) -> Parameter {
+        let target = self.current_offset.wrapping_add(disp as u32);
+        match op.op_size {
+            OperandSize::_16bit => Parameter::Imm16(target as u16),
+            OperandSize::_32bit => Parameter::Imm32(target),
+        }
+    }
+
+    fn read_rel8(&mut self, mmu: &MMU, op: &Instruction) -> Parameter {
         let val = self.read_s8(mmu);
-        (self.current_offset as isize + val as isize) as u16
+        self.rel_target(op, i32::from(val))
     }
 
-    fn read_rel16(&mut self, mmu: &MMU) -> u16 {
-        let val = self.read_s16(mmu);
-        (self.current_offset as isize + val as isize) as u16
+    /// reads a rel16, or a rel32 for 32-bit operand size
+    fn read_rel(&mut self, mmu: &MMU, op: &Instruction) -> Parameter {
+        let val = match op.op_size {
+            OperandSize::_16bit => i32::from(self.read_s16(mmu)),
+            OperandSize::_32bit => self.read_s32(mmu),
+        };
+        self.rel_target(op, val)
+    }
+
+    /// reads a ptr16:16, or a ptr16:32 for 32-bit operand size
+    fn read_far_pointer(&mut self, mmu: &MMU, op: &Instruction) -> Parameter {
+        match op.op_size {
+            OperandSize::_16bit => {
+                let imm = self.read_u16(mmu);
+                let seg = self.read_u16(mmu);
+                Parameter::Ptr16Imm(seg, imm)
+            }
+            OperandSize::_32bit => {
+                let imm = self.read_u32(mmu);
+                let seg = self.read_u16(mmu);
+                Parameter::Ptr16Imm32(seg, imm)
+            }
+        }
     }
 
     fn read_u8(&mut self, mmu: &MMU) -> u8 {
-        let b = mmu.read_u8(self.current_seg, self.current_offset);
-        self.current_offset = (Wrapping(self.current_offset) + Wrapping(1)).0;
+        let b = mmu.read_at(self.current_base.wrapping_add(self.current_offset), 1) as u8;
+        self.current_offset = self.current_offset.wrapping_add(1);
+        if !self.code32 {
+            self.current_offset &= 0xFFFF;
+        }
         b
     }
 
@@ -2263,9 +2881,13 @@ impl Decoder {
         self.read_u16(mmu) as i16
     }
 
+    fn read_s32(&mut self, mmu: &MMU) -> i32 {
+        self.read_u32(mmu) as i32
+    }
+
     /// returns the flat starting offset of the instruction being decoded
     fn current_flat(&self) -> u32 {
-        MemoryAddress::RealSegmentOffset(self.current_seg, self.current_offset).value()
+        self.current_base.wrapping_add(self.current_offset)
     }
 }
 
@@ -2284,3 +2906,23 @@ pub fn instructions_to_str(ops: &[Instruction]) -> String {
     }
     lines.join("\n")
 }
+
+/// returns the rm value of a direct memory operand for the address size of `op`
+fn direct_rm(op: &Instruction) -> u8 {
+    match op.address_size {
+        AddressSize::_16bit => 6,
+        AddressSize::_32bit => 5,
+    }
+}
+
+/// the 8086 does not implement the opcodes added by the 80186, some of them are aliases of other opcodes
+fn alias_8086_opcode(b: u8) -> u8 {
+    match b {
+        0x60..=0x6F => b + 0x10, // jcc rel8
+        0xC0 => 0xC2,            // ret imm16
+        0xC1 => 0xC3,            // ret
+        0xC8 => 0xCA,            // retf imm16
+        0xC9 => 0xCB,            // retf
+        _ => b,
+    }
+}
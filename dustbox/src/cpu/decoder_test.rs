@@ -1,5 +1,6 @@
 use pretty_assertions::assert_eq;
 
+use crate::cpu::CpuModel;
 use crate::machine::Machine;
 
 #[test]
@@ -13,7 +14,7 @@ fn can_disassemble_basic() {
         0xE8, 0xFB, 0xFF, // call l_0x108   ; call an earlier offset
         0xFF, 0x18,       // call far [bx+si]
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 6);
     assert_eq!("[085F:0100] E80500           CallNear 0x0108
@@ -31,7 +32,7 @@ fn can_disassemble_lea() {
     let code: Vec<u8> = vec![
         0x8D, 0x47, 0x80, // lea ax,[bx-0x80]
  ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 1);
     assert_eq!("[085F:0100] 8D4780           Lea16    ax, word [ds:bx-0x80]",
@@ -45,7 +46,7 @@ fn can_disassemble_segment_prefixed() {
         0x26, 0x88, 0x25, // mov [es:di],ah
         0x26, 0x8A, 0x25, // mov ah,[es:di]
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 2);
     assert_eq!("[085F:0100] 268825           Mov8     byte [es:di], ah
@@ -62,7 +63,7 @@ fn can_disassemble_values() {
         0x83, 0xC7, 0x3A,             // add di,byte +0x3a
         0x83, 0xC7, 0xC6,             // add di,byte -0x3a
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 4);
     assert_eq!("[085F:0100] 803E311000       Cmp8     byte [ds:0x1031], 0x00
@@ -81,7 +82,7 @@ fn can_disassemble_relative_short_jumps() {
         0x74, 0x00, // jz 0x106
         0x74, 0xFA, // jz 0x102
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 4);
     assert_eq!("[085F:0100] 7404             Jz       0x0106
@@ -99,7 +100,7 @@ fn can_disassemble_xor16() {
         0x81, 0xF3, 0x55, 0x44,     // xor bx,0x4455
         0x35, 0x22, 0x11,           // xor ax,0x1122
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 3);
     assert_eq!("[085F:0100] 31CB             Xor16    bx, cx
@@ -115,7 +116,7 @@ fn can_disassemble_xor32() {
         0x66, 0x81, 0xF3, 0x11, 0x22, 0x55, 0x44,   // xor ebx,0x44552211
         0x66, 0x35, 0xAA, 0xDD, 0xEE, 0xFF,         // xor eax,0xffeeddaa
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 3);
     assert_eq!("[085F:0100] 6631CB           Xor32    ebx, ecx
@@ -131,7 +132,7 @@ fn can_disassemble_or32() {
         0x66, 0x81, 0xCB, 0x11, 0x22, 0x55, 0x44,   // or ebx,0x44552211
         0x66, 0x0D, 0xAA, 0xDD, 0xEE, 0xFF,         // or eax,0xffeeddaa
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 3);
     assert_eq!("[085F:0100] 6609CB           Or32     ebx, ecx
@@ -147,7 +148,7 @@ fn can_disassemble_adc32() {
         0x66, 0x81, 0xD3, 0x11, 0x22, 0x55, 0x44,   // adc ebx,0x44552211
         0x66, 0x15, 0xAA, 0xDD, 0xEE, 0xFF,         // adc eax,0xffeeddaa
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 3);
     assert_eq!("[085F:0100] 6611CB           Adc32    ebx, ecx
@@ -164,7 +165,7 @@ fn can_disassemble_add32() {
         0x66, 0x05, 0xAA, 0xDD, 0xEE, 0xFF,         // add eax,0xffeeddaa
     ];
 
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 3);
     assert_eq!("[085F:0100] 6601CB           Add32    ebx, ecx
@@ -180,7 +181,7 @@ fn can_disassemble_sub32() {
         0x66, 0x81, 0xEB, 0x11, 0x22, 0x55, 0x44,   // sub ebx,0x44552211
         0x66, 0x2D, 0xAA, 0xDD, 0xEE, 0xFF,         // sub eax,0xffeeddaa
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 3);
     assert_eq!("[085F:0100] 6629CB           Sub32    ebx, ecx
@@ -196,7 +197,7 @@ fn can_disassemble_sbb32() {
         0x66, 0x81, 0xDB, 0x11, 0x22, 0x55, 0x44,   // sbb ebx,0x44552211
         0x66, 0x1D, 0xAA, 0xDD, 0xEE, 0xFF,         // sbb eax,0xffeeddaa
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 3);
     assert_eq!("[085F:0100] 6619CB           Sbb32    ebx, ecx
@@ -212,7 +213,7 @@ fn can_disassemble_and32() {
         0x66, 0x81, 0xE3, 0x11, 0x22, 0x55, 0x44,   // and ebx,0x44552211
         0x66, 0x25, 0xAA, 0xDD, 0xEE, 0xFF,         // and eax,0xffeeddaa
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 3);
     assert_eq!("[085F:0100] 6621CB           And32    ebx, ecx
@@ -228,7 +229,7 @@ fn can_disassemble_cmp32() {
         0x66, 0x81, 0xFB, 0x11, 0x22, 0x55, 0x44,   // cmp ebx,0x44552211
         0x66, 0x3D, 0xAA, 0xDD, 0xEE, 0xFF,         // cmp eax,0xffeeddaa
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 3);
     assert_eq!("[085F:0100] 6639CB           Cmp32    ebx, ecx
@@ -244,7 +245,7 @@ fn can_disassemble_test16() {
         0xF7, 0xC3, 0x55, 0x44,                 // test bx,0x4455
         0xA9, 0xEE, 0xFF,                       // test ax,0xffee
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 3);
     assert_eq!("[085F:0100] 85CB             Test16   bx, cx
@@ -260,7 +261,7 @@ fn can_disassemble_test32() {
         0x66, 0xF7, 0xC3, 0x11, 0x22, 0x55, 0x44,   // test ebx,0x44552211
         0x66, 0xA9, 0xAA, 0xDD, 0xEE, 0xFF,         // test eax,0xffeeddaa
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 3);
     assert_eq!("[085F:0100] 6685CB           Test32   ebx, ecx
@@ -275,7 +276,7 @@ fn can_disassemble_not() {
         0xF7, 0xD3,                             // not bx
         0x66, 0xF7, 0xD3,                       // not ebx
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 2);
     assert_eq!("[085F:0100] F7D3             Not16    bx
@@ -289,7 +290,7 @@ fn can_disassemble_neg() {
         0xF7, 0xDB,                             // neg bx
         0x66, 0xF7, 0xDB,                       // neg ebx
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 2);
     assert_eq!("[085F:0100] F7DB             Neg16    bx
@@ -303,7 +304,7 @@ fn can_disassemble_mul() {
         0xF7, 0xE3,                             // mul bx
         0x66, 0xF7, 0xE3,                       // mul ebx
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 2);
     assert_eq!("[085F:0100] F7E3             Mul16    bx
@@ -324,7 +325,7 @@ fn can_disassemble_imul() {
         0x66, 0x69, 0xDA, 0x88, 0x66, 0x34, 0x12,   // imul ebx,edx,dword 0x12346688
     ];
 
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 8);
     assert_eq!("[085F:0100] F7EB             Imul16   bx
@@ -344,7 +345,7 @@ fn can_disassemble_div() {
         0xF7, 0xF3,                             // div bx
         0x66, 0xF7, 0xF3,                       // div ebx
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 2);
     assert_eq!("[085F:0100] F7F3             Div16    bx
@@ -358,7 +359,7 @@ fn can_disassemble_idiv() {
         0xF7, 0xFB,                             // idiv bx
         0x66, 0xF7, 0xFB,                       // idiv ebx
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 2);
     assert_eq!("[085F:0100] F7FB             Idiv16   bx
@@ -373,7 +374,7 @@ fn can_disassemble_movzx() {
         0x66, 0x0F, 0xB6, 0xC3,     // movzx eax,bl
         0x66, 0x0F, 0xB7, 0xC3,     // movzx eax,bx
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 3);
     assert_eq!("[085F:0100] 0FB6C3           Movzx16  ax, bl
@@ -390,7 +391,7 @@ fn can_disassemble_movsx() {
         0x66, 0x0F, 0xBE, 0x86, 0xF1, 0x01, // movsx eax, byte [ds:bp+0x01F1]
         0x66, 0x0F, 0xBF, 0xD9,             // movsx ebx,cx
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 4);
     assert_eq!("[085F:0100] 0FBED9           Movsx16  bx, cl
@@ -405,13 +406,14 @@ fn can_disassemble_fild() {
     let code: Vec<u8> = vec![
         0xDF, 0x06, 0x58, 0x80, // fild word [0x8058]
         0xDB, 0x05,             // fild dword [di]
-        //0xDF, 0x28,             // fild qword [bx+si]         XXX handle qword
+        0xDF, 0x28,             // fild qword [bx+si]
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
-    let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 2);
+    let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 3);
     assert_eq!("[085F:0100] DF065880         Fild     word [ds:0x8058]
-[085F:0104] DB05             Fild     dword [ds:di]", res);
+[085F:0104] DB05             Fild     dword [ds:di]
+[085F:0106] DF28             Fild     qword [ds:bx+si]", res);
 }
 
 #[test]
@@ -425,7 +427,7 @@ fn can_disassemble_fmul() {
         0xD8, 0x36, 0xF6, 0x01, // fdiv dword [0x1f6]
         0xD8, 0x7C, 0x04,       // fdivr dword [si+0x4]
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 6);
     assert_eq!("[085F:0100] D80EA410         Fmul     dword [ds:0x10A4]
@@ -445,7 +447,7 @@ fn can_disassemble_fsin() {
         0xD9, 0xFB,             // fsincos
         0xD9, 0xFC,             // frndint
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 4);
     assert_eq!("[085F:0100] D9FE             Fsin
@@ -462,15 +464,16 @@ fn can_disassemble_fist() {
         0xDF, 0x19,             // fistp word [bx+di]
         0xDB, 0x1E, 0x32, 0x05, // fistp dword [0x532]
         0xDB, 0x0A,             // fisttp dword [bp+si]
-        //0xDF, 0x3D,             // fistp qword [di]     XXX handle qword
+        0xDF, 0x3D,             // fistp qword [di]
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
-    let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 4);
+    let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 5);
     assert_eq!("[085F:0100] DF15             Fist     word [ds:di]
 [085F:0102] DF19             Fistp    word [ds:bx+di]
 [085F:0104] DB1E3205         Fistp    dword [ds:0x0532]
-[085F:0108] DB0A             Fisttp   dword [ds:bp+si]", res);
+[085F:0108] DB0A             Fisttp   dword [ds:bp+si]
+[085F:010A] DF3D             Fistp    qword [ds:di]", res);
 }
 
 #[test]
@@ -487,7 +490,7 @@ fn can_disassemble_fld() {
 
         0xD9, 0x06, 0xE9, 0x02, // fld dword [0x2e9]
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 7);
     assert_eq!("[085F:0100] D9C0             Fld      st0
@@ -509,7 +512,7 @@ fn can_disassemble_fst() {
         0xDD, 0xD9,             // fstp st1
         0xDD, 0xD1,             // fst st1
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 5);
     assert_eq!("[085F:0100] D99F4662         Fstp     dword [ds:bx+0x6246]
@@ -526,13 +529,37 @@ fn can_disassemble_fadd() {
         0xD8, 0xC1,             // fadd st1
         0xDE, 0xC1,             // faddp st1
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 2);
     assert_eq!("[085F:0100] D8C1             Fadd     st1
 [085F:0102] DEC1             Faddp    st1", res);
 }
 
+#[test]
+fn can_disassemble_fpu_dc() {
+    let mut machine = Machine::deterministic();
+    let code: Vec<u8> = vec![
+        0xDC, 0xC1,             // fadd st1,st0
+        0xDC, 0xCB,             // fmul st3,st0
+        0xDC, 0xE9,             // fsub st1,st0
+        0xDC, 0xE5,             // fsubr st5,st0
+        0xDC, 0x06, 0x10, 0x02, // fadd qword [0x210]
+        0xDD, 0x47, 0x08,       // fld qword [bx+0x8]
+        0xDB, 0x2F,             // fld tword [bx]
+    ];
+    machine.load_executable(&code, 0x085F).unwrap();
+
+    let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 7);
+    assert_eq!("[085F:0100] DCC1             Fadd     st1, st0
+[085F:0102] DCCB             Fmul     st3, st0
+[085F:0104] DCE9             Fsub     st1, st0
+[085F:0106] DCE5             Fsubr    st5, st0
+[085F:0108] DC061002         Fadd     qword [ds:0x0210]
+[085F:010C] DD4708           Fld      qword [ds:bx+0x08]
+[085F:010F] DB2F             Fld      tword [ds:bx]", res);
+}
+
 #[test]
 fn can_disassemble_fsub() {
     let mut machine = Machine::deterministic();
@@ -542,7 +569,7 @@ fn can_disassemble_fsub() {
         0xD8, 0x28,             // fsubr dword [bx+si]
         0xD8, 0x66, 0x19,       //  fsub dword [bp+0x19]
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 4);
     assert_eq!("[085F:0100] DEEA             Fsubp    st2
@@ -557,7 +584,7 @@ fn can_disassemble_fpatan() {
     let code: Vec<u8> = vec![
         0xD9, 0xF3,             // fpatan
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 1);
     assert_eq!("[085F:0100] D9F3             Fpatan", res);
@@ -569,7 +596,7 @@ fn can_disassemble_ffree() {
     let code: Vec<u8> = vec![
         0xDD, 0xC0,             // ffree st0
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 1);
     assert_eq!("[085F:0100] DDC0             Ffree    st0", res);
@@ -581,7 +608,7 @@ fn can_disassemble_fxch() {
     let code: Vec<u8> = vec![
         0xD9, 0xC9,             // fxch st1
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 1);
     assert_eq!("[085F:0100] D9C9             Fxch     st1", res);
@@ -593,7 +620,7 @@ fn can_disassemble_fldcw() {
     let code: Vec<u8> = vec![
         0xD9, 0x28,             // fldcw [bx+si]
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 1);
     assert_eq!("[085F:0100] D928             Fldcw    word [ds:bx+si]", res);
@@ -607,7 +634,7 @@ fn can_disassemble_fcom() {
         0xDE, 0x1F,             // ficomp word [bx]
         0xDA, 0x1F,             // ficomp dword [bx]
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 3);
     assert_eq!("[085F:0100] DE17             Ficom    word [ds:bx]
@@ -622,9 +649,111 @@ fn can_disassemble_finit() {
         0xDB, 0xE3,             // finit
         0xD9, 0xE4,             // ftst
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 2);
     assert_eq!("[085F:0100] DBE3             Finit
 [085F:0102] D9E4             Ftst", res);
 }
+
+#[test]
+fn can_disassemble_protected_mode() {
+    let mut machine = Machine::deterministic();
+    let code: Vec<u8> = vec![
+        0x0F, 0x01, 0x16, 0x00, 0x02, // lgdt [0x200]
+        0x0F, 0x01, 0xF0,             // lmsw ax
+        0x0F, 0x20, 0xC0,             // mov eax,cr0
+        0x0F, 0x22, 0xC0,             // mov cr0,eax
+        0x0F, 0x00, 0xD8,             // ltr ax
+        0x0F, 0x06,                   // clts
+    ];
+    machine.load_executable(&code, 0x085F).unwrap();
+
+    let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 6);
+    assert_eq!("[085F:0100] 0F01160002       Lgdt     word [ds:0x0200]
+[085F:0105] 0F01F0           Lmsw     ax
+[085F:0108] 0F20C0           Mov32    eax, cr0
+[085F:010B] 0F22C0           Mov32    cr0, eax
+[085F:010E] 0F00D8           Ltr      ax
+[085F:0111] 0F06             Clts", res);
+}
+
+#[test]
+fn can_disassemble_by_cpu_model() {
+    let mut machine = Machine::deterministic();
+    machine.set_cpu_model(CpuModel::I8086);
+    let code: Vec<u8> = vec![
+        0x0F,                   // pop cs (8086 only)
+        0x64, 0x02,             // jz 0x105 (alias of 0x74 on 8086)
+        0xC1,                   // ret (alias of 0xC3 on 8086)
+    ];
+    machine.load_executable(&code, 0x085F).unwrap();
+
+    let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 3);
+    assert_eq!("[085F:0100] 0F               Pop16    cs
+[085F:0101] 6402             Jz       0x0105
+[085F:0103] C1               Retn", res);
+
+    machine.set_cpu_model(CpuModel::I80286);
+    let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x101, 1);
+    assert_eq!("[085F:0101] 64               INVALID 64", res);
+}
+
+#[test]
+fn can_disassemble_0f_opcodes() {
+    let mut machine = Machine::deterministic();
+    machine.set_cpu_model(CpuModel::I80486);
+    let code: Vec<u8> = vec![
+        0x0F, 0x80, 0x00, 0x00, // jo 0x104
+        0x0F, 0x9D, 0xC0,       // setnl al
+        0x0F, 0xBA, 0xF3, 0x05, // btr bx,0x5
+        0x66, 0x0F, 0xBB, 0xC8, // btc eax,ecx
+        0x0F, 0xBD, 0xC3,       // bsr ax,bx
+        0x0F, 0xA5, 0xC3,       // shld bx,ax,cl
+        0x0F, 0xB2, 0x26, 0x00, 0x02, // lss sp,[0x200]
+        0x0F, 0xB0, 0xD1,       // cmpxchg cl,dl
+        0x66, 0x0F, 0xC1, 0x07, // xadd [bx],eax
+        0x0F, 0xC9,             // bswap ecx
+        0x0F, 0x09,             // wbinvd
+    ];
+    machine.load_executable(&code, 0x085F).unwrap();
+
+    let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 11);
+    assert_eq!("[085F:0100] 0F800000         Jo       0x0104
+[085F:0104] 0F9DC0           Setnl    al
+[085F:0107] 0FBAF305         Btr      bx, 0x05
+[085F:010B] 660FBBC8         Btc      eax, ecx
+[085F:010F] 0FBDC3           Bsr      ax, bx
+[085F:0112] 0FA5C3           Shld     bx, ax, cl
+[085F:0115] 0FB2260002       Lss      sp, word [ds:0x0200]
+[085F:011A] 0FB0D1           Cmpxchg8 cl, dl
+[085F:011D] 660FC107         Xadd32   dword [ds:bx], eax
+[085F:0121] 0FC9             Bswap    ecx
+[085F:0123] 0F09             Wbinvd", res);
+
+    machine.set_cpu_model(CpuModel::I80386);
+    let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x121, 1);
+    assert_eq!("[085F:0121] 0FC9             INVALID 0F, C9", res);
+}
+
+#[test]
+fn can_disassemble_32bit_addressing() {
+    let mut machine = Machine::deterministic();
+    let code: Vec<u8> = vec![
+        0x67, 0x8B, 0x04, 0x8B,                         // mov ax,[ebx+ecx*4]
+        0x66, 0x67, 0x8B, 0x44, 0x24, 0x04,             // mov eax,[esp+0x4]
+        0x67, 0x8D, 0x04, 0x45, 0x00, 0x10, 0x00, 0x00, // lea ax,[eax*2+0x1000]
+        0x67, 0x88, 0x85, 0x00, 0x01, 0x00, 0x00,       // mov [ebp+0x100],al
+        0x67, 0xA1, 0x00, 0x02, 0x00, 0x00,             // mov ax,[0x200]
+        0x67, 0x8B, 0x05, 0x00, 0x02, 0x00, 0x00,       // mov ax,[0x200]
+    ];
+    machine.load_executable(&code, 0x085F).unwrap();
+
+    let res = machine.cpu.decoder.disassemble_block_to_str(&mut machine.mmu, 0x85F, 0x100, 6);
+    assert_eq!("[085F:0100] 678B048B         Mov16    ax, word [ds:ebx+ecx*4]
+[085F:0104] 66678B442404     Mov32    eax, dword [ds:esp+0x04]
+[085F:010A] 678D044500100000 Lea16    ax, word [ds:eax*2+0x00001000]
+[085F:0112] 67888500010000   Mov8     byte [ds:ebp+0x00000100], al
+[085F:0119] 67A100020000     Mov16    ax, word [ds:0x00000200]
+[085F:011F] 678B0500020000   Mov16    ax, word [ds:0x00000200]", res);
+}
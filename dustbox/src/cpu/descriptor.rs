@@ -0,0 +1,206 @@
+#[cfg(test)]
+#[path = "./descriptor_test.rs"]
+mod descriptor_test;
+
+/// Protection Enable
+pub const CR0_PE: u32 = 0x0000_0001;
+/// Monitor Coprocessor
+pub const CR0_MP: u32 = 0x0000_0002;
+/// Emulation (no x87 present)
+pub const CR0_EM: u32 = 0x0000_0004;
+/// Task Switched
+pub const CR0_TS: u32 = 0x0000_0008;
+/// Extension Type (387 present)
+pub const CR0_ET: u32 = 0x0000_0010;
+/// Write Protect (486+), supervisor writes to read-only pages faults
+pub const CR0_WP: u32 = 0x0001_0000;
+/// Paging
+pub const CR0_PG: u32 = 0x8000_0000;
+
+/// 16-bit TSS (available)
+pub const DESC_TSS16: u8 = 0x1;
+/// LDT
+pub const DESC_LDT: u8 = 0x2;
+/// 16-bit TSS (busy)
+pub const DESC_TSS16_BUSY: u8 = 0x3;
+/// 16-bit call gate
+pub const DESC_CALL_GATE16: u8 = 0x4;
+/// task gate
+pub const DESC_TASK_GATE: u8 = 0x5;
+/// 16-bit interrupt gate
+pub const DESC_INT_GATE16: u8 = 0x6;
+/// 16-bit trap gate
+pub const DESC_TRAP_GATE16: u8 = 0x7;
+/// 32-bit TSS (available)
+pub const DESC_TSS32: u8 = 0x9;
+/// 32-bit TSS (busy)
+pub const DESC_TSS32_BUSY: u8 = 0xB;
+/// 32-bit call gate
+pub const DESC_CALL_GATE32: u8 = 0xC;
+/// 32-bit interrupt gate
+pub const DESC_INT_GATE32: u8 = 0xE;
+/// 32-bit trap gate
+pub const DESC_TRAP_GATE32: u8 = 0xF;
+
+/// A descriptor table register (GDTR, IDTR)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DescriptorTable {
+    pub base: u32,
+    pub limit: u16,
+}
+
+/// A 8-byte segment, system or gate descriptor, as stored in the GDT, LDT or IDT
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Descriptor {
+    raw: u64,
+}
+
+impl Descriptor {
+    pub fn from_u64(raw: u64) -> Self {
+        Descriptor { raw }
+    }
+
+    /// encodes a segment descriptor. `limit` is the raw 20-bit limit, `flags` is the G/D/L/AVL nibble
+    pub fn new(base: u32, limit: u32, access: u8, flags: u8) -> Self {
+        let raw = u64::from(limit & 0xFFFF)
+            | u64::from(base & 0x00FF_FFFF) << 16
+            | u64::from(access) << 40
+            | u64::from((limit >> 16) & 0xF) << 48
+            | u64::from(flags & 0xF) << 52
+            | u64::from(base >> 24) << 56;
+        Descriptor { raw }
+    }
+
+    /// the hidden descriptor cache of a segment register loaded in real mode
+    pub fn real_mode(segment: u16) -> Self {
+        // present, writable data segment
+        Descriptor::new(u32::from(segment) << 4, 0xFFFF, 0x93, 0)
+    }
+
+    pub fn u64(self) -> u64 {
+        self.raw
+    }
+
+    pub fn base(self) -> u32 {
+        ((self.raw >> 16) & 0x00FF_FFFF) as u32 | ((self.raw >> 32) & 0xFF00_0000) as u32
+    }
+
+    /// returns the segment limit in bytes, scaled by the granularity bit
+    pub fn limit(self) -> u32 {
+        let limit = (self.raw & 0xFFFF) as u32 | ((self.raw >> 32) & 0xF_0000) as u32;
+        if self.flags() & 0x8 != 0 {
+            (limit << 12) | 0xFFF
+        } else {
+            limit
+        }
+    }
+
+    /// the access byte: P, DPL, S and type
+    pub fn access(self) -> u8 {
+        (self.raw >> 40) as u8
+    }
+
+    /// the G, D/B, L and AVL bits
+    pub fn flags(self) -> u8 {
+        ((self.raw >> 52) & 0xF) as u8
+    }
+
+    pub fn is_present(self) -> bool {
+        self.access() & 0x80 != 0
+    }
+
+    /// descriptor privilege level
+    pub fn dpl(self) -> u8 {
+        (self.access() >> 5) & 3
+    }
+
+    /// returns true for code and data segments, false for system descriptors and gates
+    pub fn is_segment(self) -> bool {
+        self.access() & 0x10 != 0
+    }
+
+    /// returns the type field of a system descriptor or gate
+    pub fn system_type(self) -> u8 {
+        self.access() & 0xF
+    }
+
+    pub fn is_code(self) -> bool {
+        self.is_segment() && self.access() & 0x08 != 0
+    }
+
+    pub fn is_data(self) -> bool {
+        self.is_segment() && self.access() & 0x08 == 0
+    }
+
+    pub fn is_conforming(self) -> bool {
+        self.is_code() && self.access() & 0x04 != 0
+    }
+
+    pub fn is_readable(self) -> bool {
+        self.is_data() || (self.is_code() && self.access() & 0x02 != 0)
+    }
+
+    pub fn is_writable(self) -> bool {
+        self.is_data() && self.access() & 0x02 != 0
+    }
+
+    /// the D/B bit, set for 32-bit code and stack segments
+    pub fn is_32bit(self) -> bool {
+        self.flags() & 0x4 != 0
+    }
+
+    /// returns true for expand-down data segments, whose valid offsets are above the limit
+    pub fn is_expand_down(self) -> bool {
+        self.is_data() && self.access() & 0x04 != 0
+    }
+
+    /// returns true if the `len` bytes at `offset` are within the segment limit
+    pub fn contains(self, offset: u32, len: u32) -> bool {
+        let last = u64::from(offset) + u64::from(len.max(1)) - 1;
+        if self.is_expand_down() {
+            let upper = if self.is_32bit() { 0xFFFF_FFFF } else { 0xFFFF };
+            offset > self.limit() && last <= upper
+        } else {
+            last <= u64::from(self.limit())
+        }
+    }
+
+    /// returns true for call, interrupt and trap gates
+    pub fn is_gate(self) -> bool {
+        !self.is_segment() && match self.system_type() {
+            DESC_CALL_GATE16 | DESC_INT_GATE16 | DESC_TRAP_GATE16 |
+            DESC_CALL_GATE32 | DESC_INT_GATE32 | DESC_TRAP_GATE32 => true,
+            _ => false,
+        }
+    }
+
+    /// returns true if a 32-bit gate or TSS
+    pub fn is_32bit_system(self) -> bool {
+        self.system_type() & 0x8 != 0
+    }
+
+    /// the target code segment selector of a gate
+    pub fn gate_selector(self) -> u16 {
+        (self.raw >> 16) as u16
+    }
+
+    /// the target offset of a gate
+    pub fn gate_offset(self) -> u32 {
+        (self.raw & 0xFFFF) as u32 | ((self.raw >> 32) & 0xFFFF_0000) as u32
+    }
+
+    /// number of stack words (or dwords) copied by a call gate
+    pub fn gate_param_count(self) -> u8 {
+        ((self.raw >> 32) & 0x1F) as u8
+    }
+
+    /// sets the accessed bit of a code or data segment
+    pub fn set_accessed(&mut self) {
+        self.raw |= 1 << 40;
+    }
+
+    /// sets the busy bit of a TSS
+    pub fn set_busy(&mut self) {
+        self.raw |= 2 << 40;
+    }
+}
@@ -0,0 +1,57 @@
+use crate::cpu::descriptor::{Descriptor, DESC_CALL_GATE16};
+
+#[test]
+fn can_decode_segment_descriptor() {
+    // flat 4 GiB code segment, base 0, limit 0xFFFFF (4k granularity), 32-bit
+    let d = Descriptor::from_u64(0x00CF_9A00_0000_FFFF);
+    assert_eq!(0, d.base());
+    assert_eq!(0xFFFF_FFFF, d.limit());
+    assert_eq!(true, d.is_present());
+    assert_eq!(true, d.is_code());
+    assert_eq!(true, d.is_readable());
+    assert_eq!(false, d.is_writable());
+    assert_eq!(true, d.is_32bit());
+    assert_eq!(0, d.dpl());
+
+    // 64k data segment at 0x12_3450, dpl 3
+    let d = Descriptor::new(0x0012_3450, 0xFFFF, 0xF2, 0);
+    assert_eq!(0x0012_3450, d.base());
+    assert_eq!(0xFFFF, d.limit());
+    assert_eq!(true, d.is_data());
+    assert_eq!(true, d.is_writable());
+    assert_eq!(3, d.dpl());
+    assert_eq!(d, Descriptor::from_u64(d.u64()));
+}
+
+#[test]
+fn can_decode_gate_descriptor() {
+    // 16-bit call gate to 0008:1234, dpl 3, 2 parameters
+    let d = Descriptor::from_u64(0x0000_E402_0008_1234);
+    assert_eq!(true, d.is_gate());
+    assert_eq!(DESC_CALL_GATE16, d.system_type());
+    assert_eq!(0x0008, d.gate_selector());
+    assert_eq!(0x1234, d.gate_offset());
+    assert_eq!(2, d.gate_param_count());
+    assert_eq!(3, d.dpl());
+}
+
+#[test]
+fn can_check_segment_limit() {
+    // 16-bit data segment with a limit of 0xFFF
+    let d = Descriptor::new(0, 0x0FFF, 0x92, 0);
+    assert_eq!(true, d.contains(0x0FFC, 4));
+    assert_eq!(false, d.contains(0x0FFD, 4));
+    assert_eq!(false, d.contains(0xFFFF_FFFF, 1));
+
+    // expand-down 16-bit stack segment, valid from 0x1000 to 0xFFFF
+    let d = Descriptor::new(0, 0x0FFF, 0x96, 0);
+    assert_eq!(true, d.is_expand_down());
+    assert_eq!(false, d.contains(0x0FFF, 1));
+    assert_eq!(true, d.contains(0x1000, 2));
+    assert_eq!(false, d.contains(0xFFFF, 2));
+
+    // flat 32-bit segment
+    let d = Descriptor::from_u64(0x00CF_9200_0000_FFFF);
+    assert_eq!(true, d.contains(0xFFFF_FFFC, 4));
+    assert_eq!(false, d.contains(0xFFFF_FFFD, 4));
+}
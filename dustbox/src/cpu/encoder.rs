@@ -3,7 +3,8 @@ use std::fmt;
 use crate::cpu::instruction::{Instruction, ModRegRm};
 use crate::cpu::parameter::{Parameter, ParameterSet};
 use crate::cpu::segment::Segment;
-use crate::cpu::register::R;
+use crate::cpu::register::{R, AMode};
+use crate::cpu::decoder::AddressSize;
 use crate::cpu::op::{Op};
 
 #[cfg(test)]
@@ -67,24 +68,104 @@ impl Encoder {
             Segment::FS => out.push(0x64),
             Segment::GS => out.push(0x65),
         }
+        if op.address_size == AddressSize::_32bit {
+            out.push(0x67); // Address-size override prefix
+        }
 
         match op.command {
             Op::Daa => out.push(0x27),
             Op::Das => out.push(0x2F),
             Op::Aaa => out.push(0x37),
             Op::Aas => out.push(0x3F),
-            Op::Bsf => {
+            Op::Bsf | Op::Bsr => {
                 // bsf r16, r/m16
+                // bsr r16, r/m16
+                if op.params.dst.is_32bit() {
+                    out.push(0x66); // REX.W (Operand-size override prefix)
+                }
                 out.push(0x0F);
-                out.push(0xBC);
+                out.push(if op.command == Op::Bsf { 0xBC } else { 0xBD });
                 out.extend(self.encode_r_rm(&op.params));
             }
-            Op::Bt => {
-                // bt r/m16, r16
+            Op::Bt | Op::Bts | Op::Btr | Op::Btc => {
+                if op.params.dst.is_32bit() {
+                    out.push(0x66); // REX.W (Operand-size override prefix)
+                }
+                out.push(0x0F);
+                if let Parameter::Imm8(imm) = op.params.src {
+                    // bt r/m16, imm8
+                    out.push(0xBA);
+                    out.extend(self.encode_rm(&op.params.dst, Encoder::bt_index(&op.command)));
+                    out.push(imm);
+                } else {
+                    // bt r/m16, r16
+                    out.push(match op.command {
+                        Op::Bt => 0xA3,
+                        Op::Bts => 0xAB,
+                        Op::Btr => 0xB3,
+                        _ => 0xBB,
+                    });
+                    out.extend(self.encode_rm_r(&op.params));
+                }
+            }
+            Op::Bswap => {
+                // bswap r32
+                if let Parameter::Reg32(r) = op.params.dst {
+                    out.push(0x0F);
+                    out.push(0xC8 | r.index() as u8);
+                } else {
+                    return Err(EncodeError::UnhandledParameter(op.params.dst.clone()));
+                }
+            }
+            Op::Cmpxchg8 | Op::Xadd8 => {
+                // cmpxchg r/m8, r8
+                // xadd r/m8, r8
+                out.push(0x0F);
+                out.push(if op.command == Op::Cmpxchg8 { 0xB0 } else { 0xC0 });
+                out.extend(self.encode_rm_r(&op.params));
+            }
+            Op::Cmpxchg16 | Op::Cmpxchg32 | Op::Xadd16 | Op::Xadd32 => {
+                // cmpxchg r/m16, r16
+                // xadd r/m16, r16
+                if op.params.dst.is_32bit() {
+                    out.push(0x66); // REX.W (Operand-size override prefix)
+                }
                 out.push(0x0F);
-                out.push(0xA3); // XXX BT r/m16, imm8   form is 0xBA
+                match op.command {
+                    Op::Cmpxchg16 | Op::Cmpxchg32 => out.push(0xB1),
+                    _ => out.push(0xC1),
+                }
                 out.extend(self.encode_rm_r(&op.params));
             }
+            Op::Cpuid => {
+                out.push(0x0F);
+                out.push(0xA2);
+            }
+            Op::Invd => {
+                out.push(0x0F);
+                out.push(0x08);
+            }
+            Op::Wbinvd => {
+                out.push(0x0F);
+                out.push(0x09);
+            }
+            Op::Lss | Op::Lfs | Op::Lgs => {
+                // lss r16, m16
+                out.push(0x0F);
+                out.push(match op.command {
+                    Op::Lss => 0xB2,
+                    Op::Lfs => 0xB4,
+                    _ => 0xB5,
+                });
+                out.extend(self.encode_r_rm(&op.params));
+            }
+            Op::Seto | Op::Setno | Op::Setc | Op::Setnc | Op::Setz | Op::Setnz | Op::Setna | Op::Seta |
+            Op::Sets | Op::Setns | Op::Setpe | Op::Setpo | Op::Setl | Op::Setnl | Op::Setng | Op::Setg => {
+                // setcc r/m8
+                out.push(0x0F);
+                out.push(0x90 | Encoder::setcc_index(&op.command));
+                out.extend(self.encode_rm(&op.params.dst, 0));
+            }
             Op::Cmc => out.push(0xF5),
             Op::Clc => out.push(0xF8),
             Op::Stc => out.push(0xF9),
@@ -94,6 +175,10 @@ impl Encoder {
             Op::Std => out.push(0xFD),
             Op::Cbw => out.push(0x98),
             Op::Cwd16 => out.push(0x99),
+            Op::Cdq32 => {
+                out.push(0x66); // REX.W (Operand-size override prefix)
+                out.push(0x99);
+            }
             Op::Sahf => out.push(0x9E),
             Op::Lahf => out.push(0x9F),
             Op::Nop => out.push(0x90),
@@ -101,6 +186,16 @@ impl Encoder {
             Op::Xlatb => out.push(0xD7),
             Op::Cmpsb => out.push(0xA6),
             Op::Cmpsw => out.push(0xA7),
+            Op::Cmpsd => {
+                out.push(0x66); // REX.W (Operand-size override prefix)
+                out.push(0xA7);
+            }
+            Op::Scasb => out.push(0xAE),
+            Op::Scasw => out.push(0xAF),
+            Op::Scasd => {
+                out.push(0x66); // REX.W (Operand-size override prefix)
+                out.push(0xAF);
+            }
             Op::Aad => {
                 if let Parameter::Imm8(imm) = op.params.dst {
                     out.push(0xD5);
@@ -204,16 +299,27 @@ impl Encoder {
                  out.extend(self.encode_r_rm(&op.params)); // XXX 16-bit ver?!
                 // lea r16, m        di, [bx]  = 0b11_1111
             }
-            Op::Shld => {
-                // shld r/m16, r16, imm8
-                out.push(0x0F);
-                out.push(0xA4);
-                out.extend(self.encode_rm_r_imm(&op.params));
+            Op::Lea32 => {
+                // lea r32, m
+                out.push(0x66); // REX.W (Operand-size override prefix)
+                out.push(0x8D);
+                out.extend(self.encode_r_rm(&op.params));
             }
-            Op::Shrd => {
+            Op::Shld | Op::Shrd => {
+                // shld r/m16, r16, imm8
+                // shld r/m16, r16, cl
+                let opcode = if op.command == Op::Shld { 0xA4 } else { 0xAC };
+                if op.params.dst.is_32bit() {
+                    out.push(0x66); // REX.W (Operand-size override prefix)
+                }
                 out.push(0x0F);
-                out.push(0xAC);
-                out.extend(self.encode_rm_r_imm(&op.params));
+                if op.params.src2 == Parameter::Reg8(R::CL) {
+                    out.push(opcode + 1);
+                    out.extend(self.encode_rm_r(&op.params));
+                } else {
+                    out.push(opcode);
+                    out.extend(self.encode_rm_r_imm(&op.params));
+                }
             }
             Op::Movsx16 => {
                 // MOVSX r16, r/m8
@@ -306,7 +412,8 @@ impl Encoder {
                     Parameter::Ptr8(_, _) |
                     Parameter::Ptr8Amode(_, _) |
                     Parameter::Ptr8AmodeS8(_, _, _) |
-                    Parameter::Ptr8AmodeS16(_, _, _) => {
+                    Parameter::Ptr8AmodeS16(_, _, _) |
+                    Parameter::Ptr8AmodeS32(_, _, _) => {
                         if let Parameter::Ptr8(_, imm16) = op.params.dst {
                             if let Parameter::Reg8(r) =  op.params.src {
                                 if r == R::AL {
@@ -860,6 +967,38 @@ impl Encoder {
         }
     }
 
+    fn bt_index(op: &Op) -> u8 {
+        match *op {
+            Op::Bt  => 4,
+            Op::Bts => 5,
+            Op::Btr => 6,
+            Op::Btc => 7,
+            _ => panic!("bt_index {:?}", op),
+        }
+    }
+
+    fn setcc_index(op: &Op) -> u8 {
+        match *op {
+            Op::Seto  => 0x0,
+            Op::Setno => 0x1,
+            Op::Setc  => 0x2,
+            Op::Setnc => 0x3,
+            Op::Setz  => 0x4,
+            Op::Setnz => 0x5,
+            Op::Setna => 0x6,
+            Op::Seta  => 0x7,
+            Op::Sets  => 0x8,
+            Op::Setns => 0x9,
+            Op::Setpe => 0xA,
+            Op::Setpo => 0xB,
+            Op::Setl  => 0xC,
+            Op::Setnl => 0xD,
+            Op::Setng => 0xE,
+            Op::Setg  => 0xF,
+            _ => panic!("setcc_index {:?}", op),
+        }
+    }
+
     fn bitshift_instr8(&self, ins: &Instruction) -> Result<Vec<u8>, EncodeError> {
         let mut out = vec!();
         match ins.params.dst {
@@ -993,7 +1132,8 @@ impl Encoder {
     fn encode_r_rm(&self, params: &ParameterSet) -> Vec<u8> {
         match params.dst {
             Parameter::Reg8(ref r) |
-            Parameter::Reg16(ref r) => self.encode_rm(&params.src, r.index() as u8),
+            Parameter::Reg16(ref r) |
+            Parameter::Reg32(ref r) => self.encode_rm(&params.src, r.index() as u8),
             _ => unreachable!(),
         }
     }
@@ -1028,26 +1168,48 @@ impl Encoder {
         let mut out = Vec::new();
         match *dst {
             Parameter::Ptr8(_, imm16) |
-            Parameter::Ptr16(_, imm16) => {
+            Parameter::Ptr16(_, imm16) |
+            Parameter::Ptr32(_, imm16) => {
                 out.push(ModRegRm{md: 0, rm: 6, reg}.u8());
                 out.push(imm16 as u8);
                 out.push((imm16 >> 8) as u8);
             }
             Parameter::Ptr8Amode(_, ref amode) |
-            Parameter::Ptr16Amode(_, ref amode) => {
-                out.push(ModRegRm{md: 0, rm: amode.index() as u8, reg}.u8());
+            Parameter::Ptr16Amode(_, ref amode) |
+            Parameter::Ptr32Amode(_, ref amode) => {
+                match *amode {
+                    // [ebp] is encoded as [ebp+0], since mod 0 with ebp as base means no base register
+                    AMode::EBP | AMode::SIB(R::EBP, _, _) => {
+                        out.extend(self.encode_amode(amode, 1, reg));
+                        out.push(0);
+                    }
+                    _ => out.extend(self.encode_amode(amode, 0, reg)),
+                }
             }
             Parameter::Ptr8AmodeS8(_, ref amode, imm) |
-            Parameter::Ptr16AmodeS8(_, ref amode, imm) => {
-                out.push(ModRegRm{md: 1, rm: amode.index() as u8, reg}.u8());
+            Parameter::Ptr16AmodeS8(_, ref amode, imm) |
+            Parameter::Ptr32AmodeS8(_, ref amode, imm) => {
+                out.extend(self.encode_amode(amode, 1, reg));
                 out.push(imm as u8);
             },
             Parameter::Ptr8AmodeS16(_, ref amode, imm16) |
-            Parameter::Ptr16AmodeS16(_, ref amode, imm16) => {
+            Parameter::Ptr16AmodeS16(_, ref amode, imm16) |
+            Parameter::Ptr32AmodeS16(_, ref amode, imm16) => {
                 out.push(ModRegRm{md: 2, rm: amode.index() as u8, reg}.u8());
                 out.push(imm16 as u8);
                 out.push((imm16 >> 8) as u8);
             }
+            Parameter::Ptr8AmodeS32(_, ref amode, imm32) |
+            Parameter::Ptr16AmodeS32(_, ref amode, imm32) |
+            Parameter::Ptr32AmodeS32(_, ref amode, imm32) => {
+                // a displacement without a base register is encoded with mod 0
+                let md = match *amode {
+                    AMode::Disp32 | AMode::SIBIndex(_, _) => 0,
+                    _ => 2,
+                };
+                out.extend(self.encode_amode(amode, md, reg));
+                out.extend_from_slice(&(imm32 as u32).to_le_bytes());
+            }
             Parameter::Reg8(ref r) |
             Parameter::Reg16(ref r) |
             Parameter::Reg32(ref r) => {
@@ -1058,6 +1220,20 @@ impl Encoder {
         out
     }
 
+    /// encodes the ModRM byte of a memory operand, followed by a SIB byte if the addressing mode needs one
+    fn encode_amode(&self, amode: &AMode, md: u8, reg: u8) -> Vec<u8> {
+        let mut out = vec!(ModRegRm{md, rm: amode.index() as u8, reg}.u8());
+        match *amode {
+            // esp as base, no index
+            AMode::ESP => out.push(0x24),
+            AMode::SIB(base, index, scale) => out.push(sib(scale, index.index() as u8, base.index() as u8)),
+            // no base register
+            AMode::SIBIndex(index, scale) => out.push(sib(scale, index.index() as u8, 5)),
+            _ => {}
+        }
+        out
+    }
+
     fn encode_imm8(&self, param: &Parameter) -> Vec<u8> {
         let mut out = Vec::new();
         if let Parameter::Imm8(imm) = *param {
@@ -1067,3 +1243,8 @@ impl Encoder {
         panic!("not imm8 {:?}", param);
     }
 }
+
+/// returns a SIB (scale, index, base) byte
+fn sib(scale: u8, index: u8, base: u8) -> u8 {
+    (scale.trailing_zeros() as u8) << 6 | index << 3 | base
+}
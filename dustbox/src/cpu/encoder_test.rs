@@ -11,7 +11,8 @@ use crate::cpu::parameter::Parameter;
 use crate::cpu::instruction::Instruction;
 use crate::cpu::op::Op;
 use crate::cpu::register::{R, AMode};
-use crate::cpu::decoder::OperandSize;
+use crate::cpu::decoder::{OperandSize, AddressSize};
+use crate::cpu::CpuModel;
 use crate::machine::Machine;
 use crate::hex::hex_bytes;
 use crate::ndisasm::ndisasm_first_instr;
@@ -28,7 +29,7 @@ fn can_encode_random_seq() {
             *b = rng.gen();
         }
 
-        machine.load_executable(&code, 0x085F);
+        machine.load_executable(&code, 0x085F).unwrap();
 
         let encoder = Encoder::new();
 
@@ -56,7 +57,7 @@ fn can_encode_random_seq() {
 
                 // - if encode was successful, try to decode that seq again and make sure the resulting
                 //   ops are the same (this should ensure all cases code 2-way to the same values)
-                machine.load_executable(&enc, 0x085F);
+                machine.load_executable(&enc, 0x085F).unwrap();
                 let decoded = machine.cpu.decoder.decode_to_block(&mut machine.mmu, cs, 0x100, 1);
                 let reencoded_op = &decoded[0];
                 if op.instruction != reencoded_op.instruction {
@@ -631,6 +632,90 @@ fn can_encode_sar16() {
     assert_encdec(&op, "sar bx,byte 0x30", vec!(0xC1, 0xFB, 0x30));
 }
 
+#[test]
+fn can_encode_bt() {
+    let op = Instruction::new2(Op::Btr, Parameter::Reg16(R::BX), Parameter::Imm8(0x05));
+    assert_encdec(&op, "btr bx,byte 0x5", vec!(0x0F, 0xBA, 0xF3, 0x05));
+
+    let op = Instruction::new2(Op::Bts, Parameter::Ptr16Amode(Segment::Default, AMode::BX), Parameter::Reg16(R::DX));
+    assert_encdec(&op, "bts [bx],dx", vec!(0x0F, 0xAB, 0x17));
+
+    let mut op = Instruction::new2(Op::Btc, Parameter::Reg32(R::EAX), Parameter::Reg32(R::ECX));
+    op.op_size = OperandSize::_32bit;
+    assert_encdec(&op, "btc eax,ecx", vec!(0x66, 0x0F, 0xBB, 0xC8));
+}
+
+#[test]
+fn can_encode_bsr() {
+    let op = Instruction::new2(Op::Bsr, Parameter::Reg16(R::AX), Parameter::Reg16(R::BX));
+    assert_encdec(&op, "bsr ax,bx", vec!(0x0F, 0xBD, 0xC3));
+}
+
+#[test]
+fn can_encode_setcc() {
+    let op = Instruction::new1(Op::Setnl, Parameter::Reg8(R::AL));
+    assert_encdec(&op, "setnl al", vec!(0x0F, 0x9D, 0xC0));
+
+    let op = Instruction::new1(Op::Setpo, Parameter::Ptr8Amode(Segment::Default, AMode::SI));
+    assert_encdec(&op, "setpo [si]", vec!(0x0F, 0x9B, 0x04));
+}
+
+#[test]
+fn can_encode_486_ops() {
+    let op = Instruction::new2(Op::Cmpxchg8, Parameter::Reg8(R::CL), Parameter::Reg8(R::DL));
+    assert_encdec(&op, "cmpxchg cl,dl", vec!(0x0F, 0xB0, 0xD1));
+
+    let op = Instruction::new2(Op::Xadd16, Parameter::Reg16(R::BX), Parameter::Reg16(R::AX));
+    assert_encdec(&op, "xadd bx,ax", vec!(0x0F, 0xC1, 0xC3));
+
+    let op = Instruction::new1(Op::Bswap, Parameter::Reg32(R::ECX));
+    assert_encdec(&op, "bswap ecx", vec!(0x0F, 0xC9));
+
+    let op = Instruction::new(Op::Cpuid);
+    assert_encdec(&op, "cpuid", vec!(0x0F, 0xA2));
+}
+
+#[test]
+fn can_encode_shld_cl() {
+    let op = Instruction::new3(Op::Shld, Parameter::Reg16(R::BX), Parameter::Reg16(R::AX), Parameter::Reg8(R::CL));
+    assert_encdec(&op, "shld bx,ax,cl", vec!(0x0F, 0xA5, 0xC3));
+}
+
+#[test]
+fn can_encode_32bit_addressing() {
+    let mut op = Instruction::new2(Op::Mov8, Parameter::Reg8(R::AL), Parameter::Ptr8Amode(Segment::Default, AMode::SIB(R::EBX, R::ECX, 4)));
+    op.address_size = AddressSize::_32bit;
+    assert_encdec(&op, "mov al,[ebx+ecx*4]", vec!(0x67, 0x8A, 0x04, 0x8B));
+
+    let mut op = Instruction::new2(Op::Lea32, Parameter::Reg32(R::EDX), Parameter::Ptr32AmodeS8(Segment::Default, AMode::SIB(R::EBX, R::ECX, 2), 0x10));
+    op.op_size = OperandSize::_32bit;
+    op.address_size = AddressSize::_32bit;
+    assert_encdec(&op, "lea edx,[ebx+ecx*2+0x10]", vec!(0x67, 0x66, 0x8D, 0x54, 0x4B, 0x10));
+
+    let mut op = Instruction::new2(Op::Mov8, Parameter::Ptr8AmodeS32(Segment::Default, AMode::EBP, 0x100), Parameter::Reg8(R::AL));
+    op.address_size = AddressSize::_32bit;
+    assert_encdec(&op, "mov [ebp+0x100],al", vec!(0x67, 0x88, 0x85, 0x00, 0x01, 0x00, 0x00));
+
+    let mut op = Instruction::new2(Op::Mov8, Parameter::Reg8(R::CL), Parameter::Ptr8AmodeS32(Segment::Default, AMode::SIBIndex(R::ESI, 8), 0x200));
+    op.address_size = AddressSize::_32bit;
+    assert_encdec(&op, "mov cl,[esi*8+0x200]", vec!(0x67, 0x8A, 0x0C, 0xF5, 0x00, 0x02, 0x00, 0x00));
+
+    let mut op = Instruction::new2(Op::Mov8, Parameter::Reg8(R::AL), Parameter::Ptr8Amode(Segment::Default, AMode::EAX));
+    op.address_size = AddressSize::_32bit;
+    assert_encdec(&op, "mov al,[eax]", vec!(0x67, 0x8A, 0x00));
+}
+
+#[test]
+fn can_encode_32bit_string_ops() {
+    let mut op = Instruction::new(Op::Cmpsd);
+    op.op_size = OperandSize::_32bit;
+    assert_encdec(&op, "cmpsd", vec!(0x66, 0xA7));
+
+    let mut op = Instruction::new(Op::Scasd);
+    op.op_size = OperandSize::_32bit;
+    assert_encdec(&op, "scasd", vec!(0x66, 0xAF));
+}
+
 // TODO make this into a macro to retain caller line numbers in the asserts
 fn assert_encdec(op :&Instruction, expected_ndisasm: &str, expected_bytes: Vec<u8>) {
     let encoder = Encoder::new();
@@ -639,7 +724,8 @@ fn assert_encdec(op :&Instruction, expected_ndisasm: &str, expected_bytes: Vec<u
 
     let mut want_op = op.clone();
     let mut machine = Machine::deterministic();
-    machine.load_executable(&code, 0x085F);
+    machine.set_cpu_model(CpuModel::I80486);
+    machine.load_executable(&code, 0x085F).unwrap();
     let cs = machine.cpu.get_r16(R::CS);
     let ops = machine.cpu.decoder.decode_to_block(&mut machine.mmu, cs, 0x100, 1);
     let decoded_op = &ops[0].instruction;
@@ -5,25 +5,100 @@
 mod flag_test;
 
 /// https://en.wikipedia.org/wiki/FLAGS_register
-#[derive(Copy, Clone, Debug, Default, PartialEq)]
+///
+/// The arithmetic flags (CF, PF, AF, ZF, SF, OF) are evaluated lazily: ALU ops record
+/// their operands and result, and each flag is computed from them when it is read.
+#[derive(Copy, Clone, Debug, Default)]
 pub struct Flags {
     // ____ O_I_ SZ_A _P_C
-    pub carry: bool, // 0: carry flag
+    carry: bool, // 0: carry flag
     reserved1: bool, // 1: reserved, always 1 in EFLAGS
-    pub parity: bool, // 2: parity flag
+    parity: bool, // 2: parity flag
     reserved3: bool,
-    pub adjust: bool, // 4: adjust flag
+    adjust: bool, // 4: adjust flag
     reserved5: bool,
-    pub zero: bool, // 6: zero flag
-    pub sign: bool, // 7: sign flag
+    zero: bool, // 6: zero flag
+    sign: bool, // 7: sign flag
     pub trap: bool, // 8: trap flag (single step)
     pub interrupt: bool, // 9: interrupt flag
     pub direction: bool, // 10: direction flag (control with cld, std)
-    pub overflow: bool, // 11: overflow flag
+    overflow: bool, // 11: overflow flag
     iopl12: bool, // 12: I/O privilege level (286+ only), always 1 on 8086 and 186
     iopl13: bool, // 13 --""---
     nested_task: bool, // 14: Nested task flag (286+ only), always 1 on 8086 and 186
     reserved15: bool, // 15: Reserved, always 1 on 8086 and 186, always 0 on later models
+    pub resume: bool, // 16: Resume flag (386+ only), suppresses instruction breakpoints for one instruction
+    pub virtual_8086: bool, // 17: Virtual 8086 mode (386+ only)
+    pub alignment_check: bool, // 18: Alignment check (486+ only)
+    pub id: bool, // 21: Able to use CPUID instruction (Pentium+ and late 486)
+
+    /// the flags (FLAG_* mask) that are not yet computed from `lazy`
+    pending: u16,
+
+    /// the last operation recorded by an ALU op
+    lazy: LazyResult,
+}
+
+/// the kind of operation the lazily evaluated flags are computed from
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum LazyOp {
+    /// add, adc, inc
+    Add,
+
+    /// sub, sbb, cmp, dec, neg
+    Sub,
+
+    /// and, or, xor, test: only SF, ZF and PF are derived from the result
+    Logic,
+}
+
+impl Default for LazyOp {
+    fn default() -> Self {
+        LazyOp::Logic
+    }
+}
+
+/// operands and result of the last ALU op, as passed to the eager set_* helpers
+#[derive(Copy, Clone, Debug, Default)]
+struct LazyResult {
+    op: LazyOp,
+
+    /// the sign bit of the operand size (0x80, 0x8000 or 0x8000_0000)
+    sign_bit: usize,
+
+    /// the unmasked result, holding the carry or borrow above the sign bit
+    res: usize,
+    src: usize,
+    dst: usize,
+}
+
+impl LazyResult {
+    fn carry(&self) -> bool {
+        self.res & (self.sign_bit << 1) != 0
+    }
+
+    fn parity(&self) -> bool {
+        PARITY_LOOKUP[self.res & 0xFF] != 0
+    }
+
+    fn adjust(&self) -> bool {
+        (self.res ^ (self.src ^ self.dst)) & 0x10 != 0
+    }
+
+    fn zero(&self) -> bool {
+        self.res & ((self.sign_bit << 1) - 1) == 0
+    }
+
+    fn sign(&self) -> bool {
+        self.res & self.sign_bit != 0
+    }
+
+    fn overflow(&self) -> bool {
+        match self.op {
+            LazyOp::Sub => (self.dst ^ self.src) & (self.dst ^ self.res) & self.sign_bit != 0,
+            _ => (self.res ^ self.src) & (self.res ^ self.dst) & self.sign_bit != 0,
+        }
+    }
 }
 
 // XXX make use of flag mask
@@ -37,6 +112,15 @@ pub const FLAG_IF: u16 = 0x0000_0200;
 pub const FLAG_DF: u16 = 0x0000_0400;
 pub const FLAG_OF: u16 = 0x0000_0800;
 
+/// the RF bit of EFLAGS, loaded by IRETD
+pub const EFLAGS_RF: u32 = 0x0001_0000;
+
+/// the VM bit of EFLAGS, only visible in the interrupt frame pushed from virtual 8086 mode
+pub const EFLAGS_VM: u32 = 0x0002_0000;
+
+/// the flags set from the result of an arithmetic operation
+const FLAG_ARITHMETIC: u16 = FLAG_CF | FLAG_PF | FLAG_AF | FLAG_ZF | FLAG_SF | FLAG_OF;
+
 static PARITY_LOOKUP: [u16; 256] = [
     FLAG_PF, 0, 0, FLAG_PF, 0, FLAG_PF, FLAG_PF, 0, 0, FLAG_PF, FLAG_PF, 0, FLAG_PF, 0, 0, FLAG_PF,
     0, FLAG_PF, FLAG_PF, 0, FLAG_PF, 0, 0, FLAG_PF, FLAG_PF, 0, 0, FLAG_PF, 0, FLAG_PF, FLAG_PF, 0,
@@ -75,6 +159,12 @@ impl Flags {
             iopl13: false,
             nested_task: false,
             reserved15: false, // bit 15
+            resume: false, // bit 16
+            virtual_8086: false, // bit 17
+            alignment_check: false, // bit 18
+            id: false, // bit 21
+            pending: 0,
+            lazy: LazyResult::default(),
         }
     }
 
@@ -84,53 +174,214 @@ impl Flags {
         f
     }
 
+    /// records the result of an ALU op, the flags in `mask` are computed from it when read
+    fn record(&mut self, op: LazyOp, sign_bit: usize, res: usize, src: usize, dst: usize, mask: u16) {
+        // flags still pending from the previous op, that are not replaced by this one
+        let keep = self.pending & !mask;
+        if keep != 0 {
+            self.resolve(keep);
+        }
+        self.lazy = LazyResult { op, sign_bit, res, src, dst };
+        self.pending = mask;
+    }
+
+    /// computes the pending flags in `mask` from the recorded ALU op
+    fn resolve(&mut self, mask: u16) {
+        let mask = self.pending & mask;
+        if mask & FLAG_CF != 0 {
+            self.carry = self.lazy.carry();
+        }
+        if mask & FLAG_PF != 0 {
+            self.parity = self.lazy.parity();
+        }
+        if mask & FLAG_AF != 0 {
+            self.adjust = self.lazy.adjust();
+        }
+        if mask & FLAG_ZF != 0 {
+            self.zero = self.lazy.zero();
+        }
+        if mask & FLAG_SF != 0 {
+            self.sign = self.lazy.sign();
+        }
+        if mask & FLAG_OF != 0 {
+            self.overflow = self.lazy.overflow();
+        }
+        self.pending &= !mask;
+    }
+
+    /// The CF, OF, SF, ZF, AF, and PF flags are set according to the result of an addition.
+    /// `res` is the unmasked sum of `src` and `dst`, for ADC `src` includes the carry.
+    pub fn set_add_u8(&mut self, res: usize, src: usize, dst: usize) {
+        self.record(LazyOp::Add, 0x80, res, src, dst, FLAG_ARITHMETIC);
+    }
+
+    pub fn set_add_u16(&mut self, res: usize, src: usize, dst: usize) {
+        self.record(LazyOp::Add, 0x8000, res, src, dst, FLAG_ARITHMETIC);
+    }
+
+    pub fn set_add_u32(&mut self, res: usize, src: usize, dst: usize) {
+        self.record(LazyOp::Add, 0x8000_0000, res, src, dst, FLAG_ARITHMETIC);
+    }
+
+    /// The CF, OF, SF, ZF, AF, and PF flags are set according to the result of `dst` - `src`.
+    pub fn set_sub_u8(&mut self, res: usize, src: usize, dst: usize) {
+        self.record(LazyOp::Sub, 0x80, res, src, dst, FLAG_ARITHMETIC);
+    }
+
+    pub fn set_sub_u16(&mut self, res: usize, src: usize, dst: usize) {
+        self.record(LazyOp::Sub, 0x8000, res, src, dst, FLAG_ARITHMETIC);
+    }
+
+    pub fn set_sub_u32(&mut self, res: usize, src: usize, dst: usize) {
+        self.record(LazyOp::Sub, 0x8000_0000, res, src, dst, FLAG_ARITHMETIC);
+    }
+
+    /// The CF flag is not affected. The OF, SF, ZF, AF, and PF flags are set according to the result.
+    pub fn set_inc_u8(&mut self, res: usize, dst: usize) {
+        self.record(LazyOp::Add, 0x80, res, 1, dst, FLAG_ARITHMETIC & !FLAG_CF);
+    }
+
+    pub fn set_inc_u16(&mut self, res: usize, dst: usize) {
+        self.record(LazyOp::Add, 0x8000, res, 1, dst, FLAG_ARITHMETIC & !FLAG_CF);
+    }
+
+    pub fn set_inc_u32(&mut self, res: usize, dst: usize) {
+        self.record(LazyOp::Add, 0x8000_0000, res, 1, dst, FLAG_ARITHMETIC & !FLAG_CF);
+    }
+
+    /// The CF flag is not affected. The OF, SF, ZF, AF, and PF flags are set according to the result.
+    pub fn set_dec_u8(&mut self, res: usize, dst: usize) {
+        self.record(LazyOp::Sub, 0x80, res, 1, dst, FLAG_ARITHMETIC & !FLAG_CF);
+    }
+
+    pub fn set_dec_u16(&mut self, res: usize, dst: usize) {
+        self.record(LazyOp::Sub, 0x8000, res, 1, dst, FLAG_ARITHMETIC & !FLAG_CF);
+    }
+
+    pub fn set_dec_u32(&mut self, res: usize, dst: usize) {
+        self.record(LazyOp::Sub, 0x8000_0000, res, 1, dst, FLAG_ARITHMETIC & !FLAG_CF);
+    }
+
+    /// The OF and CF flags are cleared; the SF, ZF, and PF flags are set according
+    /// to the result. The AF flag is not affected.
+    pub fn set_logic_u8(&mut self, res: usize) {
+        self.record(LazyOp::Logic, 0x80, res, 0, 0, FLAG_SF | FLAG_ZF | FLAG_PF);
+        self.carry = false;
+        self.overflow = false;
+    }
+
+    pub fn set_logic_u16(&mut self, res: usize) {
+        self.record(LazyOp::Logic, 0x8000, res, 0, 0, FLAG_SF | FLAG_ZF | FLAG_PF);
+        self.carry = false;
+        self.overflow = false;
+    }
+
+    pub fn set_logic_u32(&mut self, res: usize) {
+        self.record(LazyOp::Logic, 0x8000_0000, res, 0, 0, FLAG_SF | FLAG_ZF | FLAG_PF);
+        self.carry = false;
+        self.overflow = false;
+    }
+
+    pub fn carry(&self) -> bool {
+        if self.pending & FLAG_CF != 0 { self.lazy.carry() } else { self.carry }
+    }
+
+    pub fn parity(&self) -> bool {
+        if self.pending & FLAG_PF != 0 { self.lazy.parity() } else { self.parity }
+    }
+
+    pub fn adjust(&self) -> bool {
+        if self.pending & FLAG_AF != 0 { self.lazy.adjust() } else { self.adjust }
+    }
+
+    pub fn zero(&self) -> bool {
+        if self.pending & FLAG_ZF != 0 { self.lazy.zero() } else { self.zero }
+    }
+
+    pub fn sign(&self) -> bool {
+        if self.pending & FLAG_SF != 0 { self.lazy.sign() } else { self.sign }
+    }
+
+    pub fn overflow(&self) -> bool {
+        if self.pending & FLAG_OF != 0 { self.lazy.overflow() } else { self.overflow }
+    }
+
+    pub fn set_carry(&mut self, val: bool) {
+        self.carry = val;
+        self.pending &= !FLAG_CF;
+    }
+
+    pub fn set_parity(&mut self, val: bool) {
+        self.parity = val;
+        self.pending &= !FLAG_PF;
+    }
+
+    pub fn set_adjust(&mut self, val: bool) {
+        self.adjust = val;
+        self.pending &= !FLAG_AF;
+    }
+
+    pub fn set_zero(&mut self, val: bool) {
+        self.zero = val;
+        self.pending &= !FLAG_ZF;
+    }
+
+    pub fn set_sign(&mut self, val: bool) {
+        self.sign = val;
+        self.pending &= !FLAG_SF;
+    }
+
+    pub fn set_overflow(&mut self, val: bool) {
+        self.overflow = val;
+        self.pending &= !FLAG_OF;
+    }
+
     /// sets sign, zero, parity flags according to `b`
     pub fn set_szp(&mut self, b: bool) {
-        self.sign = b;
-        self.zero = b;
-        self.parity = b;
+        self.set_sign(b);
+        self.set_zero(b);
+        self.set_parity(b);
     }
 
     /// Set equal to the most-significant bit of the result,
     /// which is the sign bit of a signed integer.
     /// (0 indicates a positive value and 1 indicates a negative value.)
     pub fn set_sign_u8(&mut self, v: usize) {
-        self.sign = v & 0x80 != 0;
+        self.set_sign(v & 0x80 != 0);
     }
 
     pub fn set_sign_u16(&mut self, v: usize) {
-        self.sign = v & 0x8000 != 0;
+        self.set_sign(v & 0x8000 != 0);
     }
 
     pub fn set_sign_u32(&mut self, v: usize) {
-        self.sign = v & 0x8000_0000 != 0;
+        self.set_sign(v & 0x8000_0000 != 0);
     }
 
     /// Set if the least-significant byte of the result contains an
     /// even number of 1 bits; cleared otherwise.
-    pub fn set_parity(&mut self, v: usize) {
-        // TODO later: rework flag register to be a u16 directly, use FLAG_PF
-        self.parity = PARITY_LOOKUP[v & 0xFF] != 0
+    pub fn set_parity_u8(&mut self, v: usize) {
+        self.set_parity(PARITY_LOOKUP[v & 0xFF] != 0);
     }
 
     /// Zero flag — Set if the result is zero; cleared otherwise.
     pub fn set_zero_u8(&mut self, v: usize) {
-        self.zero = v.trailing_zeros() >= 8;
+        self.set_zero(v.trailing_zeros() >= 8);
     }
 
     pub fn set_zero_u16(&mut self, v: usize) {
-        self.zero = v.trailing_zeros() >= 16;
+        self.set_zero(v.trailing_zeros() >= 16);
     }
 
     pub fn set_zero_u32(&mut self, v: usize) {
-        self.zero = v.trailing_zeros() >= 32;
+        self.set_zero(v.trailing_zeros() >= 32);
     }
 
     /// Set if an arithmetic operation generates a carry or a borrow out
     /// of bit 3 of the result; cleared otherwise. This flag is used in
     /// binary-coded decimal (BCD) arithmetic.
-    pub fn set_adjust(&mut self, res: usize, v1: usize, v2: usize) {
-        self.adjust = (res ^ (v1 ^ v2)) & 0x10 != 0;
+    pub fn set_adjust_from(&mut self, res: usize, v1: usize, v2: usize) {
+        self.set_adjust((res ^ (v1 ^ v2)) & 0x10 != 0);
     }
 
     /// Set if the integer result is too large a positive number or too
@@ -138,46 +389,47 @@ impl Flags {
     /// destination operand; cleared otherwise. This flag indicates an
     /// overflow condition for signed-integer (two’s complement) arithmetic.
     pub fn set_overflow_add_u8(&mut self, res: usize, v1: usize, v2: usize) {
-        self.overflow = (res ^ v1) & (res ^ v2) & 0x80 != 0;
+        self.set_overflow((res ^ v1) & (res ^ v2) & 0x80 != 0);
     }
 
     pub fn set_overflow_add_u16(&mut self, res: usize, v1: usize, v2: usize) {
-        self.overflow = (res ^ v1) & (res ^ v2) & 0x8000 != 0;
+        self.set_overflow((res ^ v1) & (res ^ v2) & 0x8000 != 0);
     }
 
     pub fn set_overflow_add_u32(&mut self, res: usize, v1: usize, v2: usize) {
-        self.overflow = (res ^ v1) & (res ^ v2) & 0x8000_0000 != 0;
+        self.set_overflow((res ^ v1) & (res ^ v2) & 0x8000_0000 != 0);
     }
 
     pub fn set_overflow_sub_u8(&mut self, res: usize, v1: usize, v2: usize) {
-        self.overflow = (v2 ^ v1) & (v2 ^ res) & 0x80 != 0;
+        self.set_overflow((v2 ^ v1) & (v2 ^ res) & 0x80 != 0);
     }
 
     pub fn set_overflow_sub_u16(&mut self, res: usize, v1: usize, v2: usize) {
-        self.overflow = (v2 ^ v1) & (v2 ^ res) & 0x8000 != 0;
+        self.set_overflow((v2 ^ v1) & (v2 ^ res) & 0x8000 != 0);
     }
 
     pub fn set_overflow_sub_u32(&mut self, res: usize, v1: usize, v2: usize) {
-        self.overflow = (v2 ^ v1) & (v2 ^ res) & 0x8000_0000 != 0;
+        self.set_overflow((v2 ^ v1) & (v2 ^ res) & 0x8000_0000 != 0);
     }
 
     /// Set if an arithmetic operation generates a carry or a borrow out of
     /// the most-significant bit of the result; cleared otherwise. This flag
     /// indicates an overflow condition for unsigned-integer arithmetic.
     pub fn set_carry_u8(&mut self, res: usize) {
-        self.carry = res & 0x100 != 0;
+        self.set_carry(res & 0x100 != 0);
     }
 
     pub fn set_carry_u16(&mut self, res: usize) {
-        self.carry = res & 0x1_0000 != 0;
+        self.set_carry(res & 0x1_0000 != 0);
     }
 
     pub fn set_carry_u32(&mut self, res: usize) {
-        self.carry = res & 0x1_0000_0000 != 0;
+        self.set_carry(res & 0x1_0000_0000 != 0);
     }
 
     /// initializes the flags with a packed u16
     pub fn set_u16(&mut self, val: u16) {
+        self.pending = 0;
         self.carry       = val & 0x1 != 0;
         //self.reserved1   = val & 0x2 != 0;
         self.parity      = val & 0x4 != 0;
@@ -194,7 +446,7 @@ impl Flags {
     }
 
     pub fn carry_val(&self) -> usize {
-        if self.carry {
+        if self.carry() {
             1
         } else {
             0
@@ -202,7 +454,7 @@ impl Flags {
     }
 
     pub fn carry_numeric(&self) -> String {
-        format!("{}", if self.carry {
+        format!("{}", if self.carry() {
             1
         } else {
             0
@@ -210,7 +462,7 @@ impl Flags {
     }
 
     pub fn zero_numeric(&self) -> String {
-        format!("{}", if self.zero {
+        format!("{}", if self.zero() {
             1
         } else {
             0
@@ -218,11 +470,11 @@ impl Flags {
     }
 
     pub fn sign_numeric(&self) -> String {
-        format!("{}", if self.sign { 1 } else { 0 })
+        format!("{}", if self.sign() { 1 } else { 0 })
     }
 
     pub fn overflow_numeric(&self) -> String {
-        format!("{}", if self.overflow {
+        format!("{}", if self.overflow() {
             1
         } else {
             0
@@ -230,7 +482,7 @@ impl Flags {
     }
 
     pub fn adjust_numeric(&self) -> String {
-        format!("{}", if self.adjust {
+        format!("{}", if self.adjust() {
             1
         } else {
             0
@@ -238,7 +490,7 @@ impl Flags {
     }
 
     pub fn parity_numeric(&self) -> String {
-        format!("{}", if self.parity {
+        format!("{}", if self.parity() {
             1
         } else {
             0
@@ -264,22 +516,22 @@ impl Flags {
     /// returns the FLAGS register
     pub fn u16(&self) -> u16 {
         let mut val = 0 as u16;
-        if self.carry {
+        if self.carry() {
             val |= 1;
         }
         if self.reserved1 {
             val |= 1 << 1;
         }
-        if self.parity {
+        if self.parity() {
             val |= 1 << 2;
         }
-        if self.adjust {
+        if self.adjust() {
             val |= 1 << 4;
         }
-        if self.zero {
+        if self.zero() {
             val |= 1 << 6;
         }
-        if self.sign {
+        if self.sign() {
             val |= 1 << 7;
         }
         if self.trap {
@@ -291,7 +543,7 @@ impl Flags {
         if self.direction {
             val |= 1 << 10;
         }
-        if self.overflow {
+        if self.overflow() {
             val |= 1 << 11;
         }
         if self.iopl12 {
@@ -305,5 +557,26 @@ impl Flags {
         }
         val
     }
+
+    /// returns the I/O privilege level (bits 12-13)
+    pub fn iopl(&self) -> u8 {
+        let mut val = 0;
+        if self.iopl12 {
+            val |= 1;
+        }
+        if self.iopl13 {
+            val |= 2;
+        }
+        val
+    }
+
+    pub fn set_iopl(&mut self, val: u8) {
+        self.iopl12 = val & 1 != 0;
+        self.iopl13 = val & 2 != 0;
+    }
+
+    pub fn set_nested_task(&mut self, val: bool) {
+        self.nested_task = val;
+    }
 }
 
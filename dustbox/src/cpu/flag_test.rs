@@ -6,3 +6,46 @@ fn can_pack_unpack_flags() {
     flags.set_u16(0xFFFF);
     assert_eq!(0x0DD5, flags.u16());
 }
+
+#[test]
+fn lazy_flags_match_eager_flags() {
+    for dst in 0..=0xFFusize {
+        for src in 0..=0xFFusize {
+            let mut lazy = Flags::new();
+            let mut eager = Flags::new();
+            let res = dst + src;
+            lazy.set_add_u8(res, src, dst);
+            eager.set_carry_u8(res);
+            eager.set_parity_u8(res);
+            eager.set_adjust_from(res, src, dst);
+            eager.set_zero_u8(res);
+            eager.set_sign_u8(res);
+            eager.set_overflow_add_u8(res, src, dst);
+            assert_eq!(eager.u16(), lazy.u16());
+
+            let res = dst.wrapping_sub(src);
+            lazy.set_sub_u8(res, src, dst);
+            eager.set_carry_u8(res);
+            eager.set_parity_u8(res);
+            eager.set_adjust_from(res, src, dst);
+            eager.set_zero_u8(res);
+            eager.set_sign_u8(res);
+            eager.set_overflow_sub_u8(res, src, dst);
+            assert_eq!(eager.u16(), lazy.u16());
+        }
+    }
+}
+
+#[test]
+fn can_keep_pending_flags_not_affected_by_next_op() {
+    let mut flags = Flags::new();
+    flags.set_sub_u16(0usize.wrapping_sub(1), 1, 0); // cmp 0,1 sets CF
+    flags.set_inc_u16(0x1_0000, 0xFFFF); // inc does not affect CF
+    assert_eq!(true, flags.carry());
+    assert_eq!(true, flags.zero());
+
+    flags.set_logic_u8(0x80);
+    assert_eq!(false, flags.carry());
+    assert_eq!(true, flags.sign());
+    assert_eq!(true, flags.adjust()); // AF kept from the inc
+}
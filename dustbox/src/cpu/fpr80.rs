@@ -0,0 +1,696 @@
+use std::cmp::Ordering;
+
+use crate::cpu::fpu::{FPU_SW_IE, FPU_SW_DE, FPU_SW_ZE, FPU_SW_OE, FPU_SW_UE, FPU_SW_PE};
+
+#[cfg(test)]
+#[path = "./fpr80_test.rs"]
+mod fpr80_test;
+
+const EXP_BIAS: i32 = 16383;
+const EXP_MAX: u16 = 0x7FFF;
+const SIGN_BIT: u16 = 0x8000;
+
+/// the explicit integer bit of the mantissa, set for normal values
+const INTEGER_BIT: u64 = 0x8000_0000_0000_0000;
+
+/// the most significant fraction bit, set for quiet NaNs
+const QUIET_BIT: u64 = 0x4000_0000_0000_0000;
+
+/// rounding control, bits 10-11 of the fpu control word
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rounding {
+    Nearest,
+    Down,
+    Up,
+    Chop,
+}
+
+impl Rounding {
+    pub fn from_control_word(cw: u16) -> Self {
+        match (cw >> 10) & 3 {
+            0 => Rounding::Nearest,
+            1 => Rounding::Down,
+            2 => Rounding::Up,
+            _ => Rounding::Chop,
+        }
+    }
+
+    /// returns true if a value with the discarded bits `rest` is rounded away from zero.
+    /// `half` is the weight of the most significant discarded bit, `odd` is the lsb of the kept bits
+    fn round_up(self, negative: bool, rest: u128, half: u128, odd: bool) -> bool {
+        if rest == 0 {
+            return false;
+        }
+        match self {
+            Rounding::Nearest => rest > half || (rest == half && odd),
+            Rounding::Down => negative,
+            Rounding::Up => !negative,
+            Rounding::Chop => false,
+        }
+    }
+}
+
+/// the kind of value held in a FPR80
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FloatClass {
+    Zero,
+    Denormal,
+    Normal,
+    Infinity,
+    NaN,
+    /// encodings rejected by the 80387 and later (unnormals, pseudo-NaNs etc)
+    Unsupported,
+}
+
+/// A 80-bit FPU register
+/// The FPU has eight 80-bit registers (st0, st1 etc), holding a sign bit, a 15-bit biased
+/// exponent and a 64-bit mantissa with an explicit integer bit
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct FPR80 {
+    /// sign and biased exponent
+    se: u16,
+
+    mantissa: u64,
+}
+
+impl FPR80 {
+    pub fn new(negative: bool, exponent: u16, mantissa: u64) -> Self {
+        let sign = if negative { SIGN_BIT } else { 0 };
+        FPR80 { se: sign | (exponent & EXP_MAX), mantissa }
+    }
+
+    pub fn zero(negative: bool) -> Self {
+        FPR80::new(negative, 0, 0)
+    }
+
+    pub fn infinity(negative: bool) -> Self {
+        FPR80::new(negative, EXP_MAX, INTEGER_BIT)
+    }
+
+    /// the "real indefinite" QNaN, returned by masked invalid operations
+    pub fn indefinite() -> Self {
+        FPR80::new(true, EXP_MAX, INTEGER_BIT | QUIET_BIT)
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.se & SIGN_BIT != 0
+    }
+
+    pub fn exponent(self) -> u16 {
+        self.se & EXP_MAX
+    }
+
+    pub fn mantissa(self) -> u64 {
+        self.mantissa
+    }
+
+    pub fn class(self) -> FloatClass {
+        let exponent = self.exponent();
+        if exponent == EXP_MAX {
+            if self.mantissa & INTEGER_BIT == 0 {
+                FloatClass::Unsupported
+            } else if self.mantissa << 1 == 0 {
+                FloatClass::Infinity
+            } else {
+                FloatClass::NaN
+            }
+        } else if exponent == 0 {
+            // pseudo-denormals, with the integer bit set, are accepted as denormals
+            if self.mantissa == 0 {
+                FloatClass::Zero
+            } else {
+                FloatClass::Denormal
+            }
+        } else if self.mantissa & INTEGER_BIT == 0 {
+            FloatClass::Unsupported
+        } else {
+            FloatClass::Normal
+        }
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.class() == FloatClass::Zero
+    }
+
+    pub fn is_nan(self) -> bool {
+        self.class() == FloatClass::NaN
+    }
+
+    /// returns true for NaNs that signal invalid operation when used as a operand
+    pub fn is_signaling(self) -> bool {
+        self.is_nan() && self.mantissa & QUIET_BIT == 0
+    }
+
+    pub fn abs(self) -> Self {
+        FPR80 { se: self.se & !SIGN_BIT, mantissa: self.mantissa }
+    }
+
+    /// returns the value with the sign inverted (FCHS)
+    pub fn chs(self) -> Self {
+        FPR80 { se: self.se ^ SIGN_BIT, mantissa: self.mantissa }
+    }
+
+    /// returns the result of a operation on a NaN or unsupported operand, and the exception flags
+    pub fn invalid_result(self) -> (FPR80, u16) {
+        invalid_operand(self, self)
+    }
+
+    fn quiet(self) -> Self {
+        FPR80 { se: self.se, mantissa: self.mantissa | QUIET_BIT }
+    }
+
+    /// returns sign, unbiased-style exponent and mantissa of a normal or denormal value,
+    /// normalized so the integer bit is set. the value is mantissa * 2^(exponent - 16383 - 63)
+    fn unpack(self) -> (bool, i32, u64) {
+        let exponent = i32::from(self.exponent()).max(1);
+        let lz = self.mantissa.leading_zeros();
+        (self.is_negative(), exponent - lz as i32, self.mantissa << lz)
+    }
+
+    /// decodes a 80-bit extended precision value (m80fp)
+    pub fn from_bytes(b: &[u8]) -> Self {
+        let mut mantissa: u64 = 0;
+        for (i, v) in b[0..8].iter().enumerate() {
+            mantissa |= u64::from(*v) << (i * 8);
+        }
+        FPR80 { se: u16::from(b[9]) << 8 | u16::from(b[8]), mantissa }
+    }
+
+    /// encodes as a 80-bit extended precision value (m80fp)
+    pub fn to_bytes(self) -> [u8; 10] {
+        let mut res = [0u8; 10];
+        for (i, v) in res[0..8].iter_mut().enumerate() {
+            *v = (self.mantissa >> (i * 8)) as u8;
+        }
+        res[8] = self.se as u8;
+        res[9] = (self.se >> 8) as u8;
+        res
+    }
+
+    /// converts a double precision value (m64fp), which is always exact
+    pub fn from_f64(val: f64) -> Self {
+        let bits = val.to_bits();
+        let negative = bits >> 63 != 0;
+        let exponent = ((bits >> 52) & 0x7FF) as i32;
+        let fraction = bits & 0x000F_FFFF_FFFF_FFFF;
+
+        if exponent == 0 && fraction == 0 {
+            FPR80::zero(negative)
+        } else if exponent == 0x7FF {
+            // infinity or nan
+            FPR80::new(negative, EXP_MAX, INTEGER_BIT | (fraction << 11))
+        } else if exponent == 0 {
+            // denormal f64, normalize it
+            let shift = fraction.leading_zeros() as i32;
+            let e = 1 - 1023 + EXP_BIAS - (shift - 11);
+            FPR80::new(negative, e as u16, fraction << shift)
+        } else {
+            let e = exponent - 1023 + EXP_BIAS;
+            FPR80::new(negative, e as u16, INTEGER_BIT | (fraction << 11))
+        }
+    }
+
+    /// converts a single precision value (m32fp), which is always exact
+    pub fn from_f32(val: f32) -> Self {
+        FPR80::from_f64(f64::from(val))
+    }
+
+    /// returns the value rounded to the nearest double precision value
+    pub fn f64(self) -> f64 {
+        self.to_f64(Rounding::Nearest).0
+    }
+
+    /// converts to double precision (m64fp), returns the value and the exception flags
+    pub fn to_f64(self, rc: Rounding) -> (f64, u16) {
+        let (bits, flags) = self.to_ieee(rc, 52, 11);
+        (f64::from_bits(bits), flags)
+    }
+
+    /// converts to single precision (m32fp), returns the value and the exception flags
+    pub fn to_f32(self, rc: Rounding) -> (f32, u16) {
+        let (bits, flags) = self.to_ieee(rc, 23, 8);
+        (f32::from_bits(bits as u32), flags)
+    }
+
+    /// rounds to a IEEE 754 binary format with `frac_bits` fraction bits and `exp_bits` exponent bits
+    fn to_ieee(self, rc: Rounding, frac_bits: u32, exp_bits: u32) -> (u64, u16) {
+        let frac_mask = (1u64 << frac_bits) - 1;
+        let exp_max = (1u64 << exp_bits) - 1;
+        let bias = (exp_max >> 1) as i32;
+        let negative = self.is_negative();
+        let sign = u64::from(negative) << (frac_bits + exp_bits);
+        match self.class() {
+            FloatClass::Zero => return (sign, 0),
+            FloatClass::Infinity => return (sign | exp_max << frac_bits, 0),
+            FloatClass::NaN => {
+                let flags = if self.is_signaling() { FPU_SW_IE } else { 0 };
+                let payload = (self.mantissa >> (63 - frac_bits)) & frac_mask;
+                return (sign | exp_max << frac_bits | payload | 1 << (frac_bits - 1), flags);
+            }
+            FloatClass::Unsupported => {
+                let indefinite = 1 << (frac_bits + exp_bits) | exp_max << frac_bits | 1 << (frac_bits - 1);
+                return (indefinite, FPU_SW_IE);
+            }
+            FloatClass::Normal | FloatClass::Denormal => {}
+        }
+
+        let (_, e, m) = self.unpack();
+        let mut exponent = e - EXP_BIAS;
+        let min_exponent = 1 - bias;
+        let tiny = exponent < min_exponent;
+        let mut sig = u128::from(m) << 64;
+        if tiny {
+            sig = shift_right_sticky(sig, (min_exponent - exponent) as u32);
+        }
+        let drop = 128 - (frac_bits + 1);
+        let rest = sig & ((1u128 << drop) - 1);
+        let mut keep = (sig >> drop) as u64;
+        let mut flags = 0;
+        if rest != 0 {
+            flags |= FPU_SW_PE;
+            if tiny {
+                flags |= FPU_SW_UE;
+            }
+        }
+        if rc.round_up(negative, rest, 1u128 << (drop - 1), keep & 1 != 0) {
+            keep += 1;
+        }
+        if tiny {
+            // a carry into the exponent field makes it the smallest normal value
+            return (sign | keep, flags);
+        }
+        if keep >> (frac_bits + 1) != 0 {
+            keep >>= 1;
+            exponent += 1;
+        }
+        if exponent > bias {
+            let largest = if overflows_to_infinity(negative, rc) {
+                exp_max << frac_bits
+            } else {
+                (exp_max - 1) << frac_bits | frac_mask
+            };
+            return (sign | largest, flags | FPU_SW_OE | FPU_SW_PE);
+        }
+        (sign | ((exponent + bias) as u64) << frac_bits | (keep & frac_mask), flags)
+    }
+
+    /// converts a signed integer (m16int, m32int, m64int), which is always exact
+    pub fn from_i64(val: i64) -> Self {
+        FPR80::from_integer(val < 0, val.unsigned_abs())
+    }
+
+    /// converts a integer of magnitude `val`, which is always exact
+    pub fn from_integer(negative: bool, val: u64) -> Self {
+        if val == 0 {
+            return FPR80::zero(negative);
+        }
+        let lz = val.leading_zeros();
+        FPR80::new(negative, (EXP_BIAS + 63 - lz as i32) as u16, val << lz)
+    }
+
+    /// rounds to a integer by `rc`. returns the sign, the magnitude and true if the result is inexact,
+    /// or None if the value is not finite or the magnitude doesn't fit in 64 bits
+    pub fn to_integer(self, rc: Rounding) -> Option<(bool, u64, bool)> {
+        match self.class() {
+            FloatClass::Zero => return Some((self.is_negative(), 0, false)),
+            FloatClass::Normal | FloatClass::Denormal => {}
+            _ => return None,
+        }
+        let (negative, e, m) = self.unpack();
+        let exponent = e - EXP_BIAS;
+        if exponent > 63 {
+            return None;
+        }
+        // integer part in the high 64 bits, fraction in the low 64 bits
+        let sig = shift_right_sticky(u128::from(m) << 64, (63 - exponent) as u32);
+        let rest = sig & u128::from(u64::MAX);
+        let mut int = sig >> 64;
+        if rc.round_up(negative, rest, 1 << 63, int & 1 != 0) {
+            int += 1;
+        }
+        if int > u128::from(u64::MAX) {
+            return None;
+        }
+        Some((negative, int as u64, rest != 0))
+    }
+
+    /// rounds to a integral value by `rc` (FRNDINT)
+    pub fn round_to_integer(self, rc: Rounding) -> (FPR80, u16) {
+        match self.class() {
+            FloatClass::Zero | FloatClass::Infinity => return (self, 0),
+            FloatClass::NaN | FloatClass::Unsupported => return self.invalid_result(),
+            FloatClass::Normal | FloatClass::Denormal => {}
+        }
+        match self.to_integer(rc) {
+            Some((negative, val, inexact)) => {
+                (FPR80::from_integer(negative, val), if inexact { FPU_SW_PE } else { 0 })
+            }
+            // too large to have a fraction
+            None => (self, 0),
+        }
+    }
+
+    /// returns self + other, rounded to `precision` (24, 53 or 64) mantissa bits by `rc`
+    pub fn add(self, other: FPR80, precision: u32, rc: Rounding) -> (FPR80, u16) {
+        let (a, b) = (self, other);
+        match (a.class(), b.class()) {
+            (FloatClass::NaN, _) | (_, FloatClass::NaN) |
+            (FloatClass::Unsupported, _) | (_, FloatClass::Unsupported) => invalid_operand(a, b),
+            (FloatClass::Infinity, FloatClass::Infinity) => {
+                if a.is_negative() != b.is_negative() {
+                    (FPR80::indefinite(), FPU_SW_IE)
+                } else {
+                    (a, 0)
+                }
+            }
+            (FloatClass::Infinity, _) => (a, 0),
+            (_, FloatClass::Infinity) => (b, 0),
+            (FloatClass::Zero, FloatClass::Zero) => {
+                if a.is_negative() == b.is_negative() {
+                    (a, 0)
+                } else {
+                    (FPR80::zero(rc == Rounding::Down), 0)
+                }
+            }
+            (FloatClass::Zero, _) => (b, denormal_flag(b, b)),
+            (_, FloatClass::Zero) => (a, denormal_flag(a, a)),
+            _ => {
+                let flags = denormal_flag(a, b);
+                let (sa, ea, ma) = a.unpack();
+                let (sb, eb, mb) = b.unpack();
+                let ((sa, ea, ma), (sb, eb, mb)) = if ea >= eb {
+                    ((sa, ea, ma), (sb, eb, mb))
+                } else {
+                    ((sb, eb, mb), (sa, ea, ma))
+                };
+                // 62 guard bits below the mantissa, and room for the carry above it
+                let x = u128::from(ma) << 62;
+                let y = shift_right_sticky(u128::from(mb) << 62, (ea - eb) as u32);
+                let (negative, sig) = if sa == sb {
+                    (sa, x + y)
+                } else if x >= y {
+                    (sa, x - y)
+                } else {
+                    (sb, y - x)
+                };
+                if sig == 0 {
+                    return (FPR80::zero(rc == Rounding::Down), flags);
+                }
+                let (res, f) = round_pack(negative, ea + 2, sig, precision, rc);
+                (res, f | flags)
+            }
+        }
+    }
+
+    /// returns self - other, rounded to `precision` mantissa bits by `rc`
+    pub fn sub(self, other: FPR80, precision: u32, rc: Rounding) -> (FPR80, u16) {
+        if other.is_nan() {
+            return invalid_operand(self, other);
+        }
+        self.add(other.chs(), precision, rc)
+    }
+
+    /// returns self * other, rounded to `precision` mantissa bits by `rc`
+    pub fn mul(self, other: FPR80, precision: u32, rc: Rounding) -> (FPR80, u16) {
+        let (a, b) = (self, other);
+        let negative = a.is_negative() != b.is_negative();
+        match (a.class(), b.class()) {
+            (FloatClass::NaN, _) | (_, FloatClass::NaN) |
+            (FloatClass::Unsupported, _) | (_, FloatClass::Unsupported) => invalid_operand(a, b),
+            (FloatClass::Infinity, FloatClass::Zero) | (FloatClass::Zero, FloatClass::Infinity) => {
+                (FPR80::indefinite(), FPU_SW_IE)
+            }
+            (FloatClass::Infinity, _) | (_, FloatClass::Infinity) => (FPR80::infinity(negative), 0),
+            (FloatClass::Zero, _) | (_, FloatClass::Zero) => (FPR80::zero(negative), denormal_flag(a, b)),
+            _ => {
+                let flags = denormal_flag(a, b);
+                let (_, ea, ma) = a.unpack();
+                let (_, eb, mb) = b.unpack();
+                let sig = u128::from(ma) * u128::from(mb);
+                let (res, f) = round_pack(negative, ea + eb - EXP_BIAS + 1, sig, precision, rc);
+                (res, f | flags)
+            }
+        }
+    }
+
+    /// returns self / other, rounded to `precision` mantissa bits by `rc`
+    pub fn div(self, other: FPR80, precision: u32, rc: Rounding) -> (FPR80, u16) {
+        let (a, b) = (self, other);
+        let negative = a.is_negative() != b.is_negative();
+        match (a.class(), b.class()) {
+            (FloatClass::NaN, _) | (_, FloatClass::NaN) |
+            (FloatClass::Unsupported, _) | (_, FloatClass::Unsupported) => invalid_operand(a, b),
+            (FloatClass::Infinity, FloatClass::Infinity) | (FloatClass::Zero, FloatClass::Zero) => {
+                (FPR80::indefinite(), FPU_SW_IE)
+            }
+            (FloatClass::Infinity, _) => (FPR80::infinity(negative), denormal_flag(b, b)),
+            (_, FloatClass::Infinity) => (FPR80::zero(negative), denormal_flag(a, a)),
+            (_, FloatClass::Zero) => (FPR80::infinity(negative), FPU_SW_ZE | denormal_flag(a, a)),
+            (FloatClass::Zero, _) => (FPR80::zero(negative), denormal_flag(b, b)),
+            _ => {
+                let flags = denormal_flag(a, b);
+                let (_, ea, ma) = a.unpack();
+                let (_, eb, mb) = b.unpack();
+                // 66 quotient bits, the remainder makes the sticky bit
+                let n = u128::from(ma) << 64;
+                let d = u128::from(mb);
+                let (q1, r1) = (n / d, n % d);
+                let (q2, r2) = ((r1 << 2) / d, (r1 << 2) % d);
+                let sig = ((q1 << 2 | q2) << 60) | u128::from(r2 != 0);
+                let (res, f) = round_pack(negative, ea - eb + EXP_BIAS + 1, sig, precision, rc);
+                (res, f | flags)
+            }
+        }
+    }
+
+    /// returns the square root, rounded to `precision` mantissa bits by `rc`
+    pub fn sqrt(self, precision: u32, rc: Rounding) -> (FPR80, u16) {
+        match self.class() {
+            FloatClass::NaN | FloatClass::Unsupported => return self.invalid_result(),
+            FloatClass::Zero => return (self, 0),
+            _ if self.is_negative() => return (FPR80::indefinite(), FPU_SW_IE),
+            FloatClass::Infinity => return (self, 0),
+            FloatClass::Normal | FloatClass::Denormal => {}
+        }
+        let flags = denormal_flag(self, self);
+        let (_, e, m) = self.unpack();
+        // value = m * 2^exponent, scaled up by a even power of two
+        let exponent = e - EXP_BIAS - 63;
+        let shift = if exponent & 1 == 0 { 64 } else { 63 };
+        let (root, rem) = isqrt(u128::from(m) << shift);
+        // the root is never exactly halfway, so the remainder decides the guard and sticky bits
+        let guard = rem > u128::from(root);
+        let sig = u128::from(root) << 62 | u128::from(guard) << 61 | u128::from(rem != 0);
+        let (res, f) = round_pack(false, (exponent - shift) / 2 + 65 + EXP_BIAS, sig, precision, rc);
+        (res, f | flags)
+    }
+
+    /// compares with `other`, returns None if they are unordered
+    pub fn compare(self, other: FPR80) -> Option<Ordering> {
+        let (a, b) = (self, other);
+        let unordered = |v: FPR80| v.class() == FloatClass::NaN || v.class() == FloatClass::Unsupported;
+        if unordered(a) || unordered(b) {
+            return None;
+        }
+        if a.is_zero() && b.is_zero() {
+            return Some(Ordering::Equal);
+        }
+        if a.is_negative() != b.is_negative() {
+            return Some(if a.is_negative() { Ordering::Less } else { Ordering::Greater });
+        }
+        let ord = a.magnitude().cmp(&b.magnitude());
+        Some(if a.is_negative() { ord.reverse() } else { ord })
+    }
+
+    /// returns a key ordering the magnitudes of non-NaN values
+    fn magnitude(self) -> (i32, u64) {
+        match self.class() {
+            FloatClass::Zero => (i32::MIN, 0),
+            FloatClass::Infinity => (i32::MAX, 0),
+            _ => {
+                let (_, e, m) = self.unpack();
+                (e, m)
+            }
+        }
+    }
+
+    /// returns self * 2^trunc(by) (FSCALE)
+    pub fn scale(self, by: FPR80, rc: Rounding) -> (FPR80, u16) {
+        match (self.class(), by.class()) {
+            (FloatClass::NaN, _) | (_, FloatClass::NaN) |
+            (FloatClass::Unsupported, _) | (_, FloatClass::Unsupported) => invalid_operand(self, by),
+            (FloatClass::Zero, FloatClass::Infinity) if !by.is_negative() => (FPR80::indefinite(), FPU_SW_IE),
+            (FloatClass::Infinity, FloatClass::Infinity) if by.is_negative() => (FPR80::indefinite(), FPU_SW_IE),
+            (_, FloatClass::Infinity) if by.is_negative() => (FPR80::zero(self.is_negative()), 0),
+            (FloatClass::Zero, _) => (self, 0),
+            (_, FloatClass::Infinity) | (FloatClass::Infinity, _) => (FPR80::infinity(self.is_negative()), 0),
+            _ => {
+                // anything past +-0x10000 overflows or underflows the exponent range anyway
+                let n = match by.to_integer(Rounding::Chop) {
+                    Some((negative, val, _)) if val < 0x1_0000 => if negative { -(val as i32) } else { val as i32 },
+                    _ => if by.is_negative() { -0x1_0000 } else { 0x1_0000 },
+                };
+                let (negative, e, m) = self.unpack();
+                round_pack(negative, e + n, u128::from(m) << 64, 64, rc)
+            }
+        }
+    }
+
+    /// splits a finite, non-zero value into its unbiased exponent and significand (FXTRACT)
+    pub fn extract(self) -> (FPR80, FPR80) {
+        let (negative, e, m) = self.unpack();
+        (FPR80::from_i64(i64::from(e - EXP_BIAS)), FPR80::new(negative, EXP_BIAS as u16, m))
+    }
+
+    /// partial remainder of self / divisor (FPREM, FPREM1). `ieee` rounds the quotient to nearest
+    /// instead of truncating it. returns the remainder, the low bits of the quotient, true if the
+    /// reduction is incomplete (the exponents differ by more than 63) and the exception flags
+    pub fn remainder(self, divisor: FPR80, ieee: bool) -> (FPR80, u64, bool, u16) {
+        match (self.class(), divisor.class()) {
+            (FloatClass::NaN, _) | (_, FloatClass::NaN) |
+            (FloatClass::Unsupported, _) | (_, FloatClass::Unsupported) => {
+                let (res, flags) = invalid_operand(self, divisor);
+                return (res, 0, false, flags);
+            }
+            (FloatClass::Infinity, _) | (_, FloatClass::Zero) => return (FPR80::indefinite(), 0, false, FPU_SW_IE),
+            (FloatClass::Zero, _) | (_, FloatClass::Infinity) => return (self, 0, false, 0),
+            _ => {}
+        }
+        let flags = denormal_flag(self, divisor);
+        let (negative, ea, ma) = self.unpack();
+        let (_, eb, mb) = divisor.unpack();
+        // the 387 reduces the exponent difference by up to 63 bits per instruction
+        let partial = ea - eb > 63;
+        let eb = if partial { ea - 32 } else { eb };
+        let d = ea - eb;
+        if d < -1 {
+            return (self, 0, false, flags);
+        }
+        let (x, y, e) = if d >= 0 {
+            (u128::from(ma) << d, u128::from(mb), eb)
+        } else {
+            (u128::from(ma), u128::from(mb) << 1, ea)
+        };
+        let (mut q, mut r) = (x / y, x % y);
+        let mut negative_res = negative;
+        if ieee && !partial && (2 * r > y || (2 * r == y && q & 1 != 0)) {
+            q += 1;
+            r = y - r;
+            negative_res = !negative;
+        }
+        if r == 0 {
+            return (FPR80::zero(negative), q as u64, partial, flags);
+        }
+        let (res, _) = round_pack(negative_res, e + 64, r, 64, Rounding::Nearest);
+        (res, q as u64, partial, flags)
+    }
+}
+
+/// rounds the exact value sig * 2^(e - 16383 - 127) to `precision` mantissa bits by `rc`.
+/// returns the result and the exception flags
+fn round_pack(negative: bool, e: i32, sig: u128, precision: u32, rc: Rounding) -> (FPR80, u16) {
+    if sig == 0 {
+        return (FPR80::zero(negative), 0);
+    }
+    let lz = sig.leading_zeros();
+    let mut sig = sig << lz;
+    let mut e = e - lz as i32;
+    let tiny = e < 1;
+    if tiny {
+        sig = shift_right_sticky(sig, (1 - e) as u32);
+        e = 1;
+    }
+    let drop = 128 - precision;
+    let rest = sig & ((1u128 << drop) - 1);
+    let mut keep = sig >> drop;
+    let mut flags = 0;
+    if rest != 0 {
+        flags |= FPU_SW_PE;
+        if tiny {
+            flags |= FPU_SW_UE;
+        }
+    }
+    if rc.round_up(negative, rest, 1u128 << (drop - 1), keep & 1 != 0) {
+        keep += 1;
+        if keep >> precision != 0 {
+            keep >>= 1;
+            e += 1;
+        }
+    }
+    if e >= i32::from(EXP_MAX) {
+        let res = if overflows_to_infinity(negative, rc) {
+            FPR80::infinity(negative)
+        } else {
+            FPR80::new(negative, EXP_MAX - 1, u64::MAX << (64 - precision))
+        };
+        return (res, flags | FPU_SW_OE | FPU_SW_PE);
+    }
+    let mantissa = (keep << (64 - precision)) as u64;
+    // denormal results lack the integer bit
+    let exponent = if mantissa & INTEGER_BIT != 0 { e as u16 } else { 0 };
+    (FPR80::new(negative, exponent, mantissa), flags)
+}
+
+/// returns true if a overflowing result is rounded to infinity, rather than to the largest finite value
+fn overflows_to_infinity(negative: bool, rc: Rounding) -> bool {
+    match rc {
+        Rounding::Nearest => true,
+        Rounding::Down => negative,
+        Rounding::Up => !negative,
+        Rounding::Chop => false,
+    }
+}
+
+/// returns the result of a operation with a NaN or unsupported operand
+fn invalid_operand(a: FPR80, b: FPR80) -> (FPR80, u16) {
+    if a.class() == FloatClass::Unsupported || b.class() == FloatClass::Unsupported {
+        return (FPR80::indefinite(), FPU_SW_IE);
+    }
+    let flags = if a.is_signaling() || b.is_signaling() { FPU_SW_IE } else { 0 };
+    // with two NaN operands, the one with the larger significand is returned
+    let res = match (a.is_nan(), b.is_nan()) {
+        (true, true) => if a.mantissa >= b.mantissa { a } else { b },
+        (true, false) => a,
+        _ => b,
+    };
+    (res.quiet(), flags)
+}
+
+fn denormal_flag(a: FPR80, b: FPR80) -> u16 {
+    if a.class() == FloatClass::Denormal || b.class() == FloatClass::Denormal {
+        FPU_SW_DE
+    } else {
+        0
+    }
+}
+
+/// shifts `val` right by `n` bits, setting the lsb if any 1 bits were shifted out
+fn shift_right_sticky(val: u128, n: u32) -> u128 {
+    if n == 0 {
+        val
+    } else if n >= 128 {
+        u128::from(val != 0)
+    } else {
+        val >> n | u128::from(val << (128 - n) != 0)
+    }
+}
+
+/// returns the integer square root of `n`, and the remainder n - root^2
+fn isqrt(n: u128) -> (u64, u128) {
+    let mut rem = n;
+    let mut root: u128 = 0;
+    let mut bit: u128 = 1 << 126;
+    while bit > n {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if rem >= root + bit {
+            rem -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    (root as u64, rem)
+}
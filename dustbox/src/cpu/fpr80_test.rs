@@ -0,0 +1,133 @@
+use std::cmp::Ordering;
+
+use crate::cpu::fpr80::{FPR80, FloatClass, Rounding};
+use crate::cpu::fpu::{FPU_SW_IE, FPU_SW_PE, FPU_SW_ZE};
+
+fn fpr(val: f64) -> FPR80 {
+    FPR80::from_f64(val)
+}
+
+#[test]
+fn can_classify() {
+    assert_eq!(FloatClass::Zero, FPR80::zero(true).class());
+    assert_eq!(FloatClass::Normal, fpr(1.5).class());
+    assert_eq!(FloatClass::Denormal, FPR80::new(false, 0, 1).class());
+    assert_eq!(FloatClass::Infinity, FPR80::infinity(false).class());
+    assert_eq!(FloatClass::NaN, FPR80::indefinite().class());
+    assert_eq!(FloatClass::Unsupported, FPR80::new(false, 0x3FFF, 0x4000_0000_0000_0000).class()); // unnormal
+}
+
+#[test]
+fn can_divide_in_extended_precision() {
+    let (res, flags) = fpr(1.0).div(fpr(3.0), 64, Rounding::Nearest);
+    assert_eq!(FPR80::new(false, 0x3FFD, 0xAAAA_AAAA_AAAA_AAAB), res);
+    assert_eq!(FPU_SW_PE, flags);
+
+    let (res, _) = fpr(1.0).div(fpr(3.0), 64, Rounding::Chop);
+    assert_eq!(FPR80::new(false, 0x3FFD, 0xAAAA_AAAA_AAAA_AAAA), res);
+
+    // rounded to double precision, the mantissa is padded with zeroes
+    let (res, _) = fpr(1.0).div(fpr(3.0), 53, Rounding::Nearest);
+    assert_eq!(1.0 / 3.0, res.f64());
+    assert_eq!(0, res.mantissa() & 0x7FF);
+
+    let (res, flags) = fpr(1.0).div(FPR80::zero(false), 64, Rounding::Nearest);
+    assert_eq!(FPR80::infinity(false), res);
+    assert_eq!(FPU_SW_ZE, flags);
+}
+
+#[test]
+fn can_add_in_extended_precision() {
+    let tiny = FPR80::new(false, 0x3FFF - 63, 0x8000_0000_0000_0000); // 2^-63
+    let (res, flags) = fpr(1.0).add(tiny, 64, Rounding::Nearest);
+    assert_eq!(FPR80::new(false, 0x3FFF, 0x8000_0000_0000_0001), res);
+    assert_eq!(0, flags);
+
+    let (res, flags) = fpr(1.0).add(tiny, 53, Rounding::Nearest);
+    assert_eq!(fpr(1.0), res);
+    assert_eq!(FPU_SW_PE, flags);
+
+    let (res, _) = res.sub(fpr(1.0), 64, Rounding::Nearest);
+    assert!(res.is_zero() && !res.is_negative());
+    let (res, _) = fpr(1.0).sub(fpr(1.0), 64, Rounding::Down);
+    assert!(res.is_zero() && res.is_negative());
+
+    let (res, flags) = FPR80::infinity(false).add(FPR80::infinity(true), 64, Rounding::Nearest);
+    assert_eq!(FPR80::indefinite(), res);
+    assert_eq!(FPU_SW_IE, flags);
+}
+
+#[test]
+fn can_multiply() {
+    let (res, flags) = fpr(-1.5).mul(fpr(2.5), 64, Rounding::Nearest);
+    assert_eq!(fpr(-3.75), res);
+    assert_eq!(0, flags);
+
+    let (res, _) = FPR80::zero(false).mul(FPR80::infinity(false), 64, Rounding::Nearest);
+    assert_eq!(FPR80::indefinite(), res);
+}
+
+#[test]
+fn can_sqrt() {
+    let (res, flags) = fpr(2.0).sqrt(64, Rounding::Nearest);
+    assert_eq!(FPR80::new(false, 0x3FFF, 0xB504_F333_F9DE_6484), res);
+    assert_eq!(FPU_SW_PE, flags);
+
+    let (res, flags) = fpr(9.0).sqrt(64, Rounding::Nearest);
+    assert_eq!(fpr(3.0), res);
+    assert_eq!(0, flags);
+
+    let (res, flags) = fpr(-1.0).sqrt(64, Rounding::Nearest);
+    assert_eq!(FPR80::indefinite(), res);
+    assert_eq!(FPU_SW_IE, flags);
+}
+
+#[test]
+fn can_convert_to_integer() {
+    assert_eq!(Some((false, 2, true)), fpr(2.5).to_integer(Rounding::Nearest));
+    assert_eq!(Some((false, 4, true)), fpr(3.5).to_integer(Rounding::Nearest));
+    assert_eq!(Some((true, 3, true)), fpr(-2.5).to_integer(Rounding::Down));
+    assert_eq!(Some((true, 2, true)), fpr(-2.5).to_integer(Rounding::Up));
+    assert_eq!(Some((false, 2, true)), fpr(2.9).to_integer(Rounding::Chop));
+    assert_eq!(Some((false, 7, false)), fpr(7.0).to_integer(Rounding::Nearest));
+
+    // integers beyond 2^53 are exact
+    let big = FPR80::from_integer(false, 999_999_999_999_999_999);
+    assert_eq!(Some((false, 999_999_999_999_999_999, false)), big.to_integer(Rounding::Nearest));
+    assert_eq!(None, FPR80::infinity(false).to_integer(Rounding::Nearest));
+}
+
+#[test]
+fn can_convert_to_ieee() {
+    let (res, flags) = fpr(0.1).to_f64(Rounding::Nearest);
+    assert_eq!(0.1, res);
+    assert_eq!(0, flags);
+
+    let (res, flags) = fpr(0.1).to_f32(Rounding::Nearest);
+    assert_eq!(0.1_f32, res);
+    assert_eq!(FPU_SW_PE, flags);
+
+    // the 387 rom pi rounds to the double precision pi
+    let pi = FPR80::new(false, 0x4000, 0xC90F_DAA2_2168_C235);
+    assert_eq!(std::f64::consts::PI, pi.f64());
+}
+
+#[test]
+fn can_compare() {
+    assert_eq!(Some(Ordering::Less), fpr(-2.0).compare(fpr(1.0)));
+    assert_eq!(Some(Ordering::Equal), FPR80::zero(true).compare(FPR80::zero(false)));
+    assert_eq!(Some(Ordering::Greater), FPR80::infinity(false).compare(fpr(1e300)));
+    assert_eq!(None, FPR80::indefinite().compare(fpr(1.0)));
+}
+
+#[test]
+fn can_take_partial_remainder() {
+    let (res, q, partial, _) = fpr(7.0).remainder(fpr(2.0), false);
+    assert_eq!((fpr(1.0), 3, false), (res, q, partial));
+
+    let (res, q, partial, _) = fpr(7.0).remainder(fpr(2.0), true);
+    assert_eq!((fpr(-1.0), 4, false), (res, q, partial));
+
+    let (_, _, partial, _) = fpr(1e30).remainder(fpr(3.0), false);
+    assert!(partial);
+}
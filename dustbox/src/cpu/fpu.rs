@@ -0,0 +1,504 @@
+use std::cmp::Ordering;
+
+use crate::cpu::op::Op;
+use crate::cpu::fpr80::{FPR80, FloatClass, Rounding};
+
+#[cfg(test)]
+#[path = "./fpu_test.rs"]
+mod fpu_test;
+
+// status word bits
+pub const FPU_SW_IE: u16 = 0x0001; // invalid operation
+pub const FPU_SW_DE: u16 = 0x0002; // denormalized operand
+pub const FPU_SW_ZE: u16 = 0x0004; // zero divide
+pub const FPU_SW_OE: u16 = 0x0008; // overflow
+pub const FPU_SW_UE: u16 = 0x0010; // underflow
+pub const FPU_SW_PE: u16 = 0x0020; // precision
+pub const FPU_SW_SF: u16 = 0x0040; // stack fault
+pub const FPU_SW_ES: u16 = 0x0080; // exception summary status
+pub const FPU_SW_C0: u16 = 0x0100;
+pub const FPU_SW_C1: u16 = 0x0200;
+pub const FPU_SW_C2: u16 = 0x0400;
+pub const FPU_SW_TOP: u16 = 0x3800;
+pub const FPU_SW_C3: u16 = 0x4000;
+pub const FPU_SW_B: u16 = 0x8000; // busy
+
+/// control word after FNINIT: all exceptions masked, 64-bit precision, round to nearest
+const FPU_CW_DEFAULT: u16 = 0x037F;
+
+/// the largest magnitude (+1) that fits in a 18 digit packed BCD
+const BCD_LIMIT: u64 = 1_000_000_000_000_000_000;
+
+/// the contents of a fpu register, as reported by the tag word
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FPUTag {
+    Valid = 0,
+    Zero = 1,
+    Special = 2,
+    Empty = 3,
+}
+
+impl FPUTag {
+    fn from_value(val: FPR80) -> Self {
+        match val.class() {
+            FloatClass::Zero => FPUTag::Zero,
+            FloatClass::Normal => FPUTag::Valid,
+            _ => FPUTag::Special,
+        }
+    }
+}
+
+/// The x87 floating point unit
+#[derive(Clone)]
+pub struct FPU {
+    /// the physical registers R0-R7. st(i) is found at R((top + i) & 7)
+    regs: [FPR80; 8],
+
+    tags: [FPUTag; 8],
+
+    /// top of stack (status word bits 11-13)
+    top: usize,
+
+    pub control_word: u16,
+
+    /// status word, without the TOP bits
+    status: u16,
+}
+
+impl FPU {
+    pub fn default() -> Self {
+        FPU {
+            regs: [FPR80::default(); 8],
+            tags: [FPUTag::Empty; 8],
+            top: 0,
+            control_word: FPU_CW_DEFAULT,
+            status: 0,
+        }
+    }
+
+    /// resets the fpu to its initial state (FNINIT)
+    pub fn init(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn status_word(&self) -> u16 {
+        (self.status & !FPU_SW_TOP) | ((self.top as u16) << 11)
+    }
+
+    pub fn set_status_word(&mut self, val: u16) {
+        self.top = ((val & FPU_SW_TOP) >> 11) as usize;
+        self.status = val & !FPU_SW_TOP;
+    }
+
+    pub fn tag_word(&self) -> u16 {
+        let mut res = 0;
+        for (i, tag) in self.tags.iter().enumerate() {
+            res |= (*tag as u16) << (i * 2);
+        }
+        res
+    }
+
+    /// only the empty / non-empty state is used, the rest of the tag is derived from the register contents
+    pub fn set_tag_word(&mut self, val: u16) {
+        for i in 0..8 {
+            self.tags[i] = if (val >> (i * 2)) & 3 == FPUTag::Empty as u16 {
+                FPUTag::Empty
+            } else {
+                FPUTag::from_value(self.regs[i])
+            };
+        }
+    }
+
+    /// returns the tag of st(i)
+    pub fn tag(&self, i: usize) -> FPUTag {
+        self.tags[self.phys(i)]
+    }
+
+    /// clears the exception flags (FNCLEX)
+    pub fn clear_exceptions(&mut self) {
+        self.status &= !(0x00FF | FPU_SW_B);
+    }
+
+    /// sets exception flags. unmasked exceptions also set the ES and B bits
+    pub fn raise(&mut self, flags: u16) {
+        self.status |= flags;
+        if flags & !self.control_word & 0x3F != 0 {
+            self.status |= FPU_SW_ES | FPU_SW_B;
+        }
+    }
+
+    /// sets the condition code bits C3, C2, C1 and C0
+    pub fn set_condition(&mut self, c3: bool, c2: bool, c1: bool, c0: bool) {
+        self.status &= !(FPU_SW_C3 | FPU_SW_C2 | FPU_SW_C1 | FPU_SW_C0);
+        if c3 {
+            self.status |= FPU_SW_C3;
+        }
+        if c2 {
+            self.status |= FPU_SW_C2;
+        }
+        if c1 {
+            self.status |= FPU_SW_C1;
+        }
+        if c0 {
+            self.status |= FPU_SW_C0;
+        }
+    }
+
+    pub fn set_c1(&mut self, val: bool) {
+        if val {
+            self.status |= FPU_SW_C1;
+        } else {
+            self.status &= !FPU_SW_C1;
+        }
+    }
+
+    pub fn set_c2(&mut self, val: bool) {
+        if val {
+            self.status |= FPU_SW_C2;
+        } else {
+            self.status &= !FPU_SW_C2;
+        }
+    }
+
+    /// maps st(i) to a physical register
+    fn phys(&self, i: usize) -> usize {
+        (self.top + i) & 7
+    }
+
+    pub fn is_empty(&self, i: usize) -> bool {
+        self.tag(i) == FPUTag::Empty
+    }
+
+    /// returns the value of st(i)
+    pub fn st(&mut self, i: usize) -> FPR80 {
+        if self.is_empty(i) {
+            // stack underflow
+            self.set_c1(false);
+            self.raise(FPU_SW_IE | FPU_SW_SF);
+            return FPR80::indefinite();
+        }
+        self.regs[self.phys(i)]
+    }
+
+    pub fn set_st(&mut self, i: usize, val: FPR80) {
+        let p = self.phys(i);
+        self.regs[p] = val;
+        self.tags[p] = FPUTag::from_value(val);
+    }
+
+    /// decrements TOP and stores `val` in st0
+    pub fn push(&mut self, val: FPR80) {
+        self.top = self.phys(7);
+        if !self.is_empty(0) {
+            // stack overflow, the register is overwritten by the "indefinite" value
+            self.set_c1(true);
+            self.raise(FPU_SW_IE | FPU_SW_SF);
+            self.set_st(0, FPR80::indefinite());
+            return;
+        }
+        self.set_st(0, val);
+    }
+
+    /// marks st0 as empty and increments TOP
+    pub fn pop(&mut self) {
+        let p = self.phys(0);
+        self.tags[p] = FPUTag::Empty;
+        self.top = self.phys(1);
+    }
+
+    /// marks st(i) as empty (FFREE)
+    pub fn free(&mut self, i: usize) {
+        let p = self.phys(i);
+        self.tags[p] = FPUTag::Empty;
+    }
+
+    /// exchanges st0 with st(i) (FXCH)
+    pub fn exchange(&mut self, i: usize) {
+        let a = self.st(0);
+        let b = self.st(i);
+        self.set_st(0, b);
+        self.set_st(i, a);
+        self.set_c1(false);
+    }
+
+    /// FDECSTP
+    pub fn decrement_top(&mut self) {
+        self.top = self.phys(7);
+        self.set_c1(false);
+    }
+
+    /// FINCSTP
+    pub fn increment_top(&mut self) {
+        self.top = self.phys(1);
+        self.set_c1(false);
+    }
+
+    /// sets C3, C2 and C0 according to the comparison of `a` with `b`.
+    /// `ordered` comparisons (FCOM) signals invalid operation on NaN operands, FUCOM only on SNaN
+    pub fn compare(&mut self, a: FPR80, b: FPR80, ordered: bool) {
+        match a.compare(b) {
+            None => {
+                if ordered || a.is_signaling() || b.is_signaling() {
+                    self.raise(FPU_SW_IE);
+                }
+                self.set_condition(true, true, false, true);
+            }
+            Some(Ordering::Greater) => self.set_condition(false, false, false, false),
+            Some(Ordering::Less) => self.set_condition(false, false, false, true),
+            Some(Ordering::Equal) => self.set_condition(true, false, false, false),
+        }
+    }
+
+    /// classifies st0 (FXAM)
+    pub fn examine(&mut self) {
+        let val = self.regs[self.phys(0)];
+        let sign = val.is_negative();
+        if self.is_empty(0) {
+            self.set_condition(true, false, sign, true);
+            return;
+        }
+        match val.class() {
+            FloatClass::Unsupported => self.set_condition(false, false, sign, false),
+            FloatClass::NaN => self.set_condition(false, false, sign, true),
+            FloatClass::Normal => self.set_condition(false, true, sign, false),
+            FloatClass::Infinity => self.set_condition(false, true, sign, true),
+            FloatClass::Zero => self.set_condition(true, false, sign, false),
+            FloatClass::Denormal => self.set_condition(true, true, sign, false),
+        }
+    }
+
+    /// returns the mantissa size of arithmetic results from the precision control of the control word
+    pub fn precision(&self) -> u32 {
+        match (self.control_word >> 8) & 3 {
+            0 => 24,
+            2 => 53,
+            _ => 64,
+        }
+    }
+
+    /// returns the rounding control of the control word
+    pub fn rounding(&self) -> Rounding {
+        Rounding::from_control_word(self.control_word)
+    }
+
+    /// performs the arithmetic operation of `op` on `dst` and `src`, returns the result
+    /// rounded to the precision and by the rounding control of the control word
+    pub fn arithmetic(&mut self, op: &Op, dst: FPR80, src: FPR80) -> FPR80 {
+        let (precision, rc) = (self.precision(), self.rounding());
+        let (res, flags) = match *op {
+            Op::Fadd | Op::Faddp | Op::Fiadd => dst.add(src, precision, rc),
+            Op::Fmul | Op::Fmulp | Op::Fimul => dst.mul(src, precision, rc),
+            Op::Fsub | Op::Fsubp | Op::Fisub => dst.sub(src, precision, rc),
+            Op::Fsubr | Op::Fsubrp | Op::Fisubr => src.sub(dst, precision, rc),
+            Op::Fdiv | Op::Fdivp | Op::Fidiv => dst.div(src, precision, rc),
+            Op::Fdivr | Op::Fdivrp | Op::Fidivr => src.div(dst, precision, rc),
+            _ => unreachable!(),
+        };
+        self.raise(flags);
+        res
+    }
+
+    /// returns the square root of `val` (FSQRT)
+    pub fn square_root(&mut self, val: FPR80) -> FPR80 {
+        let (res, flags) = val.sqrt(self.precision(), self.rounding());
+        self.raise(flags);
+        res
+    }
+
+    /// rounds `val` to a integral value according to the rounding control of the control word (FRNDINT)
+    pub fn round(&mut self, val: FPR80) -> FPR80 {
+        let (res, flags) = val.round_to_integer(self.rounding());
+        self.raise(flags);
+        res
+    }
+
+    /// converts `val` to a signed integer of `bits` size, rounded by the rounding control or truncated.
+    /// out of range values signals invalid operation and returns the "integer indefinite" value
+    pub fn integer(&mut self, val: FPR80, bits: u32, truncate: bool) -> i64 {
+        let rc = if truncate { Rounding::Chop } else { self.rounding() };
+        let limit = 1u64 << (bits - 1);
+        match val.to_integer(rc) {
+            Some((negative, n, inexact)) if n < limit || (negative && n == limit) => {
+                if inexact {
+                    self.raise(FPU_SW_PE);
+                }
+                if negative {
+                    (n as i64).wrapping_neg()
+                } else {
+                    n as i64
+                }
+            }
+            _ => {
+                self.raise(FPU_SW_IE);
+                i64::MIN >> (64 - bits)
+            }
+        }
+    }
+
+    /// converts `val` to a 18 digit packed BCD, rounded by the rounding control (FBSTP).
+    /// out of range values signals invalid operation and returns the "BCD indefinite" value
+    pub fn bcd(&mut self, val: FPR80) -> [u8; 10] {
+        let res = match val.to_integer(self.rounding()) {
+            Some((negative, n, inexact)) => integer_to_bcd(negative, n).map(|bcd| (bcd, inexact)),
+            None => None,
+        };
+        match res {
+            Some((bcd, inexact)) => {
+                if inexact {
+                    self.raise(FPU_SW_PE);
+                }
+                bcd
+            }
+            None => {
+                self.raise(FPU_SW_IE);
+                BCD_INDEFINITE
+            }
+        }
+    }
+
+    /// converts `val` to a m32fp or m64fp operand, rounded by the rounding control
+    pub fn float(&mut self, val: FPR80, bits: u32) -> u64 {
+        let (res, flags) = if bits == 32 {
+            let (res, flags) = val.to_f32(self.rounding());
+            (u64::from(res.to_bits()), flags)
+        } else {
+            let (res, flags) = val.to_f64(self.rounding());
+            (res.to_bits(), flags)
+        };
+        self.raise(flags);
+        res
+    }
+
+    /// returns the value of the constant loaded by `op`, as stored in the 80387 constant rom
+    pub fn constant(op: &Op) -> FPR80 {
+        match *op {
+            Op::Fld1 => FPR80::new(false, 0x3FFF, 0x8000_0000_0000_0000),
+            Op::Fldl2t => FPR80::new(false, 0x4000, 0xD49A_784B_CD1B_8AFE),
+            Op::Fldl2e => FPR80::new(false, 0x3FFF, 0xB8AA_3B29_5C17_F0BC),
+            Op::Fldpi => FPR80::new(false, 0x4000, 0xC90F_DAA2_2168_C235),
+            Op::Fldlg2 => FPR80::new(false, 0x3FFD, 0x9A20_9A84_FBCF_F799),
+            Op::Fldln2 => FPR80::new(false, 0x3FFE, 0xB172_17F7_D1CF_79AC),
+            Op::Fldz => FPR80::zero(false),
+            _ => unreachable!(),
+        }
+    }
+
+    /// partial remainder of st0 / st1 (FPREM, FPREM1). sets C0, C3, C1 to the low bits of the quotient,
+    /// or C2 if the reduction is incomplete
+    pub fn partial_remainder(&mut self, ieee: bool) {
+        let dividend = self.st(0);
+        let divisor = self.st(1);
+        let (res, q, partial, flags) = dividend.remainder(divisor, ieee);
+        self.raise(flags);
+        self.set_st(0, res);
+        if partial {
+            self.set_condition(false, true, false, false);
+        } else {
+            self.set_condition(q & 2 != 0, false, q & 1 != 0, q & 4 != 0);
+        }
+    }
+
+    /// st0 = st0 * 2^trunc(st1) (FSCALE)
+    pub fn scale(&mut self) {
+        let val = self.st(0);
+        let n = self.st(1);
+        let (res, flags) = val.scale(n, self.rounding());
+        self.raise(flags);
+        self.set_st(0, res);
+    }
+
+    /// splits st0 into exponent (st1) and significand (st0) (FXTRACT)
+    pub fn extract(&mut self) {
+        let val = self.st(0);
+        match val.class() {
+            FloatClass::Zero => {
+                self.raise(FPU_SW_ZE);
+                self.set_st(0, FPR80::infinity(true));
+                self.push(val);
+            }
+            FloatClass::Infinity => {
+                self.set_st(0, val.abs());
+                self.push(val);
+            }
+            FloatClass::NaN | FloatClass::Unsupported => {
+                let (res, flags) = val.invalid_result();
+                self.raise(flags);
+                self.set_st(0, res);
+                self.push(res);
+            }
+            FloatClass::Normal | FloatClass::Denormal => {
+                let (exponent, significand) = val.extract();
+                self.set_st(0, exponent);
+                self.push(significand);
+            }
+        }
+    }
+
+    /// applies the trigonometric function `op` to st0 (FSIN, FCOS, FPTAN, FSINCOS).
+    /// operands out of range (|x| >= 2^63) sets C2 and leaves st0 unchanged.
+    /// NOTE: like the other transcendental instructions, this is evaluated in double precision
+    pub fn trigonometric(&mut self, op: &Op) {
+        let val = self.st(0).f64();
+        if val.abs() >= 2f64.powi(63) {
+            self.set_c2(true);
+            return;
+        }
+        self.set_c2(false);
+        match *op {
+            Op::Fsin => self.set_st(0, FPR80::from_f64(val.sin())),
+            Op::Fcos => self.set_st(0, FPR80::from_f64(val.cos())),
+            Op::Fptan => {
+                self.set_st(0, FPR80::from_f64(val.tan()));
+                self.push(FPR80::from_f64(1.0));
+            }
+            Op::Fsincos => {
+                self.set_st(0, FPR80::from_f64(val.sin()));
+                self.push(FPR80::from_f64(val.cos()));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// returns a copy of st(i) without affecting the status word, as found in FSAVE images
+    pub fn register(&self, i: usize) -> FPR80 {
+        self.regs[self.phys(i)]
+    }
+
+    /// restores st(i) from a FRSTOR image, the tag word is restored separately
+    pub fn set_register(&mut self, i: usize, val: FPR80) {
+        let p = self.phys(i);
+        self.regs[p] = val;
+    }
+}
+
+/// decodes a 80-bit packed BCD value (m80bcd)
+pub fn bcd_to_fpr80(b: &[u8]) -> FPR80 {
+    let mut res: u64 = 0;
+    for v in b[0..9].iter().rev() {
+        res = res * 100 + u64::from(v >> 4) * 10 + u64::from(v & 0xF);
+    }
+    FPR80::from_integer(b[9] & 0x80 != 0, res)
+}
+
+/// encodes a integer of magnitude `n` as 80-bit packed BCD (m80bcd). returns None if out of range
+pub fn integer_to_bcd(negative: bool, n: u64) -> Option<[u8; 10]> {
+    if n >= BCD_LIMIT {
+        return None;
+    }
+    let mut n = n;
+    let mut res = [0u8; 10];
+    for v in res[0..9].iter_mut() {
+        let lo = (n % 10) as u8;
+        n /= 10;
+        let hi = (n % 10) as u8;
+        n /= 10;
+        *v = hi << 4 | lo;
+    }
+    if negative {
+        res[9] = 0x80;
+    }
+    Some(res)
+}
+
+/// the "BCD indefinite" value, stored by FBSTP on invalid operation
+pub const BCD_INDEFINITE: [u8; 10] = [0, 0, 0, 0, 0, 0, 0, 0xC0, 0xFF, 0xFF];
@@ -0,0 +1,91 @@
+use crate::cpu::fpu::{FPU, FPUTag, FPU_SW_C0, FPU_SW_C3, FPU_SW_IE, FPU_SW_SF, bcd_to_fpr80, integer_to_bcd};
+use crate::cpu::fpr80::FPR80;
+use crate::cpu::op::Op;
+
+#[test]
+fn can_convert_fpr80() {
+    // 1.0
+    let one = [0, 0, 0, 0, 0, 0, 0, 0x80, 0xFF, 0x3F];
+    assert_eq!(1.0, FPR80::from_bytes(&one).f64());
+    assert_eq!(one, FPR80::from_f64(1.0).to_bytes());
+
+    // -3.5
+    let val = [0, 0, 0, 0, 0, 0, 0, 0xE0, 0x00, 0xC0];
+    assert_eq!(-3.5, FPR80::from_bytes(&val).f64());
+    assert_eq!(val, FPR80::from_f64(-3.5).to_bytes());
+
+    assert_eq!([0; 10], FPR80::from_f64(0.0).to_bytes());
+    assert_eq!(std::f64::consts::PI, FPR80::from_bytes(&FPR80::from_f64(std::f64::consts::PI).to_bytes()).f64());
+
+    // all 64 mantissa bits survive a round trip
+    let val = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x3F];
+    assert_eq!(val, FPR80::from_bytes(&val).to_bytes());
+}
+
+#[test]
+fn can_push_and_pop() {
+    let mut fpu = FPU::default();
+    assert_eq!(0xFFFF, fpu.tag_word());
+    fpu.push(FPR80::from_f64(1.0));
+    fpu.push(FPR80::from_f64(0.0));
+    assert_eq!(6, (fpu.status_word() >> 11) & 7); // TOP
+    assert_eq!(0.0, fpu.st(0).f64());
+    assert_eq!(1.0, fpu.st(1).f64());
+    assert_eq!(FPUTag::Zero, fpu.tag(0));
+    assert_eq!(FPUTag::Valid, fpu.tag(1));
+    assert_eq!(0x1FFF, fpu.tag_word());
+
+    fpu.pop();
+    fpu.pop();
+    assert_eq!(0, fpu.status_word());
+
+    // stack underflow
+    assert!(fpu.st(0).is_nan());
+    assert_eq!(FPU_SW_IE | FPU_SW_SF, fpu.status_word() & (FPU_SW_IE | FPU_SW_SF));
+}
+
+#[test]
+fn can_compare() {
+    let mut fpu = FPU::default();
+    let two = FPR80::from_f64(2.0);
+    fpu.compare(FPR80::from_f64(1.0), two, true);
+    assert_eq!(FPU_SW_C0, fpu.status_word());
+    fpu.compare(two, two, true);
+    assert_eq!(FPU_SW_C3, fpu.status_word());
+    fpu.compare(FPR80::from_f64(3.0), two, true);
+    assert_eq!(0, fpu.status_word());
+}
+
+#[test]
+fn can_round() {
+    let mut fpu = FPU::default();
+    assert_eq!(2.0, fpu.round(FPR80::from_f64(2.5)).f64());
+    assert_eq!(4.0, fpu.round(FPR80::from_f64(3.5)).f64());
+    assert_eq!(-2.0, fpu.round(FPR80::from_f64(-2.5)).f64());
+    fpu.control_word |= 0x0C00; // truncate
+    assert_eq!(2.0, fpu.round(FPR80::from_f64(2.9)).f64());
+    assert_eq!(-2.0, fpu.round(FPR80::from_f64(-2.9)).f64());
+}
+
+#[test]
+fn can_convert_bcd() {
+    let bcd = [0x34, 0x12, 0, 0, 0, 0, 0, 0, 0, 0x80];
+    assert_eq!(-1234.0, bcd_to_fpr80(&bcd).f64());
+    assert_eq!(Some(bcd), integer_to_bcd(true, 1234));
+
+    // all 18 digits are kept
+    let bcd = [0x99, 0x99, 0x99, 0x99, 0x99, 0x99, 0x99, 0x99, 0x99, 0];
+    assert_eq!(FPR80::from_integer(false, 999_999_999_999_999_999), bcd_to_fpr80(&bcd));
+    assert_eq!(Some(bcd), integer_to_bcd(false, 999_999_999_999_999_999));
+    assert_eq!(None, integer_to_bcd(false, 1_000_000_000_000_000_000));
+}
+
+#[test]
+fn can_control_precision() {
+    let mut fpu = FPU::default();
+    let one = FPR80::from_f64(1.0);
+    let tiny = FPR80::new(false, 0x3FFF - 63, 0x8000_0000_0000_0000); // 2^-63
+    assert_ne!(one, fpu.arithmetic(&Op::Fadd, one, tiny));
+    fpu.control_word = (fpu.control_word & !0x0300) | 0x0200; // 53 bits
+    assert_eq!(one, fpu.arithmetic(&Op::Fadd, one, tiny));
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use crate::cpu::instruction::Instruction;
+
+#[cfg(test)]
+#[path = "./instruction_cache_test.rs"]
+mod instruction_cache_test;
+
+/// holds decoded instructions keyed by the physical address of their first byte,
+/// so that hot loops are not decoded again on each iteration
+#[derive(Clone, Default)]
+pub struct InstructionCache {
+    entries: HashMap<u32, CachedInstruction>,
+
+    /// length of the longest cached instruction, used to find entries overlapping a write
+    max_length: u32,
+
+    /// number of lookups served from the cache
+    pub hits: usize,
+
+    /// number of lookups that needed the instruction to be decoded
+    pub misses: usize,
+}
+
+#[derive(Clone)]
+struct CachedInstruction {
+    /// the segment:offset the instruction was decoded at. relative branches are decoded to
+    /// absolute offsets, so an alias of the same physical address can not reuse the entry
+    segment: u16,
+    offset: u32,
+
+    /// decoded for a 32-bit code segment
+    code32: bool,
+
+    instruction: Instruction,
+}
+
+impl InstructionCache {
+    /// returns the instruction decoded at physical address `addr`, if any
+    pub fn get(&mut self, addr: u32, segment: u16, offset: u32, code32: bool) -> Option<&Instruction> {
+        match self.entries.get(&addr) {
+            Some(e) if e.segment == segment && e.offset == offset && e.code32 == code32 => {
+                self.hits += 1;
+                Some(&e.instruction)
+            }
+            _ => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, addr: u32, segment: u16, offset: u32, code32: bool, instruction: Instruction) {
+        let length = u32::from(instruction.length);
+        if length > self.max_length {
+            self.max_length = length;
+        }
+        self.entries.insert(addr, CachedInstruction { segment, offset, code32, instruction });
+    }
+
+    /// removes all instructions overlapping the `len` bytes written at physical address `addr`
+    pub fn invalidate(&mut self, addr: u32, len: u32) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let first = addr.saturating_sub(self.max_length.saturating_sub(1));
+        for start in first..addr + len {
+            let overlaps = match self.entries.get(&start) {
+                Some(e) => start + u32::from(e.instruction.length) > addr,
+                None => false,
+            };
+            if overlaps {
+                self.entries.remove(&start);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.max_length = 0;
+    }
+
+    /// returns the number of cached instructions
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,41 @@
+use crate::cpu::{InstructionCache, Instruction, Op, Parameter, R};
+
+#[test]
+fn can_invalidate_overlapping_instructions() {
+    let mut cache = InstructionCache::default();
+    let mut op = Instruction::new2(Op::Mov16, Parameter::Reg16(R::CX), Parameter::Imm16(0xFFFF));
+    op.length = 3;
+    cache.insert(0x8790, 0x085F, 0x0100, false, op.clone());
+    cache.insert(0x8793, 0x085F, 0x0103, false, op);
+    assert_eq!(2, cache.len());
+
+    // a write after the end of the first instruction only affects the second
+    cache.invalidate(0x8793, 1);
+    assert_eq!(true, cache.get(0x8790, 0x085F, 0x0100, false).is_some());
+    assert_eq!(true, cache.get(0x8793, 0x085F, 0x0103, false).is_none());
+
+    // a word write to the byte before the instruction overlaps it
+    cache.invalidate(0x878F, 2);
+    assert_eq!(true, cache.is_empty());
+}
+
+#[test]
+fn can_not_reuse_instruction_of_aliased_address() {
+    let mut cache = InstructionCache::default();
+    let mut op = Instruction::new1(Op::JmpShort, Parameter::Imm16(0x0100));
+    op.length = 2;
+    cache.insert(0x8790, 0x085F, 0x0100, false, op);
+    assert_eq!(true, cache.get(0x8790, 0x0860, 0x00F0, false).is_none());
+    assert_eq!(0, cache.hits);
+    assert_eq!(1, cache.misses);
+}
+
+#[test]
+fn can_not_reuse_instruction_of_other_code_size() {
+    let mut cache = InstructionCache::default();
+    let mut op = Instruction::new1(Op::JmpShort, Parameter::Imm16(0x0100));
+    op.length = 2;
+    cache.insert(0x8790, 0x0008, 0x0100, false, op);
+    assert_eq!(true, cache.get(0x8790, 0x0008, 0x0100, true).is_none());
+    assert_eq!(true, cache.get(0x8790, 0x0008, 0x0100, false).is_some());
+}
@@ -1,11 +1,17 @@
 // these modules are re-exported as a single module
 
+pub use self::debug_register::*;
+mod debug_register;
+
 pub use self::decoder::*;
 mod decoder;
 
 pub use self::instruction::*;
 mod instruction;
 
+pub use self::instruction_cache::*;
+mod instruction_cache;
+
 pub use self::segment::*;
 mod segment;
 
@@ -24,11 +30,27 @@ mod op;
 pub use self::encoder::*;
 mod encoder;
 
+pub use self::fpu::*;
+mod fpu;
+
+pub use self::fpr80::*;
+mod fpr80;
+
+pub use self::descriptor::*;
+mod descriptor;
+
+pub use self::model::*;
+mod model;
+
+pub use self::timing::*;
+mod timing;
+
 use std::u8;
 use std::num::Wrapping;
 
+use crate::bios::BIOS;
 use crate::machine::{DEBUG_MARK_STACK, STACK_MARKER};
-use crate::memory::{MMU, MemoryAddress};
+use crate::memory::{MMU, MemoryAddress, PageFault};
 
 /// prints diagnostics if writes to memory close to SS:SP occurs
 const DEBUG_PARAMS_TOUCHING_STACK: bool = false;
@@ -36,17 +58,58 @@ const DEBUG_PARAMS_TOUCHING_STACK: bool = false;
 /// prints diagnostics of stack usage (push / pop)
 const DEBUG_STACK: bool = false;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Exception {
     // http://wiki.osdev.org/Interrupt_Vector_Table
     DIV0 = 0,    // Divide by 0
+    DB = 1,      // Debug
+    BP = 3,      // Breakpoint (INT3)
+    OF = 4,      // Overflow (INTO)
+    BR = 5,      // Bound range exceeded
     UD = 6,      // Invalid opcode (UD2)
+    NM = 7,      // Device not available
     DF = 8,      // Double fault
     TS = 10,     // Invalid TSS
     NP = 11,     // Segment not present
     SS = 12,     // Stack-segment fault
     GP = 13,     // General protection fault
     PF = 14,     // Page fault
+    MF = 16,     // x87 floating-point exception
+}
+
+impl Exception {
+    /// returns true if the exception pushes an error code in protected mode
+    pub fn has_error_code(self) -> bool {
+        match self {
+            Exception::DF | Exception::TS | Exception::NP | Exception::SS | Exception::GP | Exception::PF => true,
+            _ => false,
+        }
+    }
+
+    /// returns true for traps, which are reported with IP pointing after the instruction.
+    /// faults are reported with IP pointing at the faulting instruction, so it can be restarted
+    pub fn is_trap(self) -> bool {
+        match self {
+            Exception::DB | Exception::BP | Exception::OF => true,
+            _ => false,
+        }
+    }
+
+    fn is_contributory(self) -> bool {
+        match self {
+            Exception::DIV0 | Exception::TS | Exception::NP | Exception::SS | Exception::GP => true,
+            _ => false,
+        }
+    }
+
+    /// returns true if `second`, raised while delivering this exception, causes a double fault
+    fn escalates_to_double_fault(self, second: Exception) -> bool {
+        if self == Exception::PF {
+            second.is_contributory() || second == Exception::PF
+        } else {
+            self.is_contributory() && second.is_contributory()
+        }
+    }
 }
 
 pub struct CPU {
@@ -59,11 +122,62 @@ pub struct CPU {
     /// signals to debugger we hit an error (used by debugger)
     pub fatal_error: bool,
 
+    /// set by HLT, the cpu idles until the next hardware interrupt
+    pub halted: bool,
+
     /// toggles non-deterministic behaviour (used by tests)
     pub deterministic: bool,
 
     pub decoder: Decoder,
+
+    /// previously decoded instructions, invalidated by writes to them
+    pub instruction_cache: InstructionCache,
+
     pub clock_hz: usize,
+
+    /// the emulated cpu generation, use set_model to change
+    pub model: CpuModel,
+
+    /// identification returned by CPUID, None if the instruction is not supported.
+    /// set from the model by set_model, and can be changed afterwards
+    pub identity: Option<CpuIdentity>,
+
+    /// x87 floating point unit
+    pub fpu: FPU,
+
+    /// control registers
+    pub cr0: u32,
+    pub cr2: u32,
+    pub cr3: u32,
+
+    /// debug registers
+    pub debug: DebugRegisters,
+
+    /// global descriptor table register
+    pub gdtr: DescriptorTable,
+
+    /// interrupt descriptor table register
+    pub idtr: DescriptorTable,
+
+    /// local descriptor table selector, and its cached descriptor
+    pub ldtr: u16,
+    ldt: Descriptor,
+
+    /// task register selector, and its cached descriptor
+    pub tr: u16,
+    tss: Descriptor,
+
+    /// hidden descriptor caches of ES, CS, SS, DS, FS, GS (protected mode)
+    pub descriptors: [Descriptor; 6],
+
+    /// current privilege level. usually the RPL of CS, but it is 0 right after entering protected mode
+    cpl: u8,
+
+    /// offset of the instruction being executed, reported by faults
+    pub instruction_ip: u32,
+
+    /// exception raised by the current instruction, delivered by handle_exception
+    pending_exception: Option<(Exception, usize)>,
 }
 
 impl CPU {
@@ -73,9 +187,28 @@ impl CPU {
             cycle_count: 0,
             regs: RegisterState::default(),
             fatal_error: false,
+            halted: false,
             deterministic: false,
             decoder: Decoder::default(),
-            clock_hz: 5_000_000, // Intel 8086: 0.330 MIPS at 5.000 MHz
+            instruction_cache: InstructionCache::default(),
+            clock_hz: CpuModel::default().clock_hz(),
+            model: CpuModel::default(),
+            identity: CpuModel::default().identity(),
+            fpu: FPU::default(),
+            cr0: 0,
+            cr2: 0,
+            cr3: 0,
+            debug: DebugRegisters::default(),
+            gdtr: DescriptorTable::default(),
+            idtr: DescriptorTable { base: 0, limit: 0x3FF },
+            ldtr: 0,
+            ldt: Descriptor::default(),
+            tr: 0,
+            tss: Descriptor::default(),
+            descriptors: [Descriptor::default(); 6],
+            cpl: 0,
+            instruction_ip: 0,
+            pending_exception: None,
         }
     }
 
@@ -85,6 +218,81 @@ impl CPU {
         res
     }
 
+    /// changes the emulated cpu model, and the clock frequency to match it
+    pub fn set_model(&mut self, model: CpuModel) {
+        self.model = model;
+        self.clock_hz = model.clock_hz();
+        self.decoder.set_model(model);
+        self.instruction_cache.clear();
+        self.identity = model.identity();
+        if self.identity.is_none() {
+            self.regs.flags.id = false;
+        }
+    }
+
+    /// returns the FLAGS register as pushed by PUSHF and interrupts
+    pub fn flags_u16(&self) -> u16 {
+        self.model.flags_image(self.regs.flags.u16(), self.is_protected_mode())
+    }
+
+    /// sets the FLAGS register from POPF and IRET. IOPL and NT can only be changed
+    /// by 80386+ in real mode, or at privilege level 0 in protected mode
+    pub fn set_flags_u16(&mut self, val: u16) {
+        self.regs.flags.set_u16(val);
+        if !self.is_protected_mode() || self.cpl() <= self.regs.flags.iopl() {
+            self.regs.flags.interrupt = val & FLAG_IF != 0;
+        }
+        let can_set_iopl = if self.is_protected_mode() {
+            self.cpl() == 0
+        } else {
+            self.model.has_real_mode_iopl()
+        };
+        if can_set_iopl {
+            self.regs.flags.set_iopl(((val >> 12) & 3) as u8);
+            self.regs.flags.set_nested_task(val & 0x4000 != 0);
+        }
+    }
+
+    /// returns the EFLAGS register as pushed by PUSHFD
+    pub fn flags_u32(&self) -> u32 {
+        let mut val = u32::from(self.flags_u16());
+        if self.regs.flags.alignment_check {
+            val |= 1 << 18;
+        }
+        if self.regs.flags.id {
+            val |= 1 << 21;
+        }
+        val
+    }
+
+    /// sets the EFLAGS register from POPFD. the ID bit (21) can only be changed if CPUID is supported
+    pub fn set_flags_u32(&mut self, val: u32) {
+        self.set_flags_u16(val as u16);
+        if self.model.has_alignment_check() {
+            self.regs.flags.alignment_check = val & (1 << 18) != 0;
+        }
+        if self.identity.is_some() {
+            self.regs.flags.id = val & (1 << 21) != 0;
+        }
+    }
+
+    /// executes CPUID, raising #UD if it is not supported
+    pub fn cpuid(&mut self) {
+        let (eax, ebx, ecx, edx) = match self.identity {
+            Some(ref identity) => identity.leaf(self.get_r32(R::EAX)),
+            None => return self.exception(&Exception::UD, 0),
+        };
+        self.set_r32(R::EAX, eax);
+        self.set_r32(R::EBX, ebx);
+        self.set_r32(R::ECX, ecx);
+        self.set_r32(R::EDX, edx);
+    }
+
+    /// reads the count of a shift or rotate instruction, masked as done by the cpu model
+    pub fn read_shift_count(&mut self, mmu: &MMU, p: &Parameter) -> usize {
+        self.read_parameter_value(mmu, p) & self.model.shift_count_mask()
+    }
+
     pub fn get_r8(&self, r: R) -> u8 {
         self.regs.get_r8(r)
     }
@@ -109,8 +317,19 @@ impl CPU {
         self.regs.set_r32(r, val);
     }
 
+    /// delivers a hardware interrupt (IRQ), which unlike INT n is not checked against the gate DPL
+    pub fn hardware_interrupt(&mut self, mmu: &mut MMU, int: u8) {
+        if self.is_protected_mode() {
+            return self.protected_mode_interrupt(mmu, int, None, false);
+        }
+        self.execute_interrupt(mmu, int);
+    }
+
     pub fn execute_interrupt(&mut self, mmu: &mut MMU, int: u8) {
-        let flags = self.regs.flags.u16();
+        if self.is_protected_mode() {
+            return self.protected_mode_interrupt(mmu, int, None, true);
+        }
+        let flags = self.flags_u16();
         self.push16(mmu, flags);
         mmu.flags_address = MemoryAddress::RealSegmentOffset(self.get_r16(R::SS), self.get_r16(R::SP));
 
@@ -118,192 +337,1089 @@ impl CPU {
         self.regs.flags.trap = false;
         let (cs, ip) = self.get_address_pair();
         self.push16(mmu, cs);
-        self.push16(mmu, ip);
+        self.push16(mmu, ip as u16);
         let base = 0;
         let idx = u16::from(int) << 2;
         let ip = mmu.read_u16(base, idx);
         let cs = mmu.read_u16(base, idx + 2);
         // println!("int: jumping to interrupt handler for interrupt {:02X} pos at {:04X}:{:04X} = {:04X}:{:04X}", int, base, idx, cs, ip);
-        self.regs.ip = ip;
+        self.regs.ip = u32::from(ip);
         self.set_r16(R::CS, cs);
     }
 
+    /// raises an exception. the caller should abort the current instruction, the exception
+    /// is delivered to the guest by handle_exception once the instruction returns
     pub fn exception(&mut self, which: &Exception, error: usize) {
-        /*
-        #define CPU_INT_SOFTWARE    0x1
-        #define CPU_INT_EXCEPTION   0x2
-        #define CPU_INT_HAS_ERROR   0x4
-        #define CPU_INT_NOIOPLCHECK 0x8
-        */
-        println!("Exception {:?}, error {}", which, error);
+        if self.pending_exception.is_none() {
+            self.pending_exception = Some((*which, error));
+        }
+    }
+
+    /// raises a page fault, reporting the faulting linear address in CR2
+    pub fn page_fault(&mut self, fault: PageFault) {
+        if self.pending_exception.is_none() {
+            self.cr2 = fault.address;
+            self.exception(&Exception::PF, usize::from(fault.error));
+        }
+    }
+
+    /// returns true if the current instruction raised a fault, which restarts the instruction
+    pub fn fault_pending(&self) -> bool {
+        match self.pending_exception {
+            Some((which, _)) => !which.is_trap(),
+            None => false,
+        }
+    }
+
+    /// returns true if the current instruction raised an exception
+    pub fn exception_pending(&self) -> bool {
+        self.pending_exception.is_some()
+    }
+
+    /// delivers a pending exception through the IVT (real mode) or the IDT (protected mode).
+    /// exceptions raised during delivery escalates to a double fault, and then to a shutdown
+    pub fn handle_exception(&mut self, mmu: &mut MMU) {
+        let mut delivering: Option<Exception> = None;
+        while let Some((which, error)) = self.pending_exception.take() {
+            let which = match delivering {
+                Some(Exception::DF) => {
+                    println!("[{:04X}:{:04X}] triple fault, shutting down", self.get_r16(R::CS), self.instruction_ip);
+                    self.fatal_error = true;
+                    return;
+                }
+                Some(prev) if prev.escalates_to_double_fault(which) => Exception::DF,
+                _ => which,
+            };
+            let error = if which == Exception::DF { 0 } else { error };
+            // the 8086 reports divide errors with IP pointing after the instruction
+            let trap = which.is_trap() || (which == Exception::DIV0 && self.model == CpuModel::I8086);
+            if !trap {
+                self.regs.ip = self.instruction_ip;
+            }
+            if !self.is_protected_mode() {
+                let vector = mmu.read_u16(0, (which as u16) << 2 | 2);
+                if vector == BIOS::ROM_SEG && !which.is_trap() {
+                    // still pointing at the default handler of the BIOS, which would fault again
+                    println!("[{:04X}:{:04X}] unhandled exception {:?}", self.get_r16(R::CS), self.regs.ip, which);
+                    self.fatal_error = true;
+                    return;
+                }
+                self.execute_interrupt(mmu, which as u8);
+                return;
+            }
+            delivering = Some(which);
+            let error = if which.has_error_code() {
+                Some(error as u16)
+            } else {
+                None
+            };
+            self.protected_mode_interrupt(mmu, which as u8, error, false);
+            if let Some(fault) = mmu.take_page_fault() {
+                self.page_fault(fault);
+            }
+        }
+    }
+
+    /// returns true if CR0.PE is set
+    pub fn is_protected_mode(&self) -> bool {
+        self.cr0 & CR0_PE != 0
+    }
+
+    /// returns true if EFLAGS.VM is set, running real mode code under a protected mode monitor
+    pub fn is_virtual_8086(&self) -> bool {
+        self.is_protected_mode() && self.regs.flags.virtual_8086
+    }
+
+    /// returns true if segment registers are loaded as in real mode, without descriptors
+    fn has_real_mode_segments(&self) -> bool {
+        !self.is_protected_mode() || self.regs.flags.virtual_8086
+    }
+
+    /// returns the current privilege level, always 3 in virtual 8086 mode
+    pub fn cpl(&self) -> u8 {
+        if self.is_protected_mode() {
+            self.cpl
+        } else {
+            0
+        }
+    }
+
+    pub fn get_control_register(&self, r: R) -> u32 {
+        match r {
+            R::CR0 => self.cr0,
+            R::CR2 => self.cr2,
+            R::CR3 => self.cr3,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn set_control_register(&mut self, mmu: &mut MMU, r: R, val: u32) {
+        match r {
+            R::CR0 => self.set_cr0(mmu, val),
+            R::CR2 => self.cr2 = val,
+            R::CR3 => {
+                self.cr3 = val;
+                mmu.paging.set_page_directory(val);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// writes a debug register, updating the data breakpoints watched by the MMU
+    pub fn set_debug_register(&mut self, mmu: &mut MMU, r: R, val: u32) {
+        self.debug.set(r, val);
+        mmu.set_data_breakpoints(self.debug.data_breakpoints());
+    }
+
+    /// returns false and raises #GP if not at privilege level 0, or #DB if DR7.GD is set (MOV DRn)
+    pub fn check_debug_register_access(&mut self) -> bool {
+        if !self.check_privileged() {
+            return false;
+        }
+        if self.debug.dr7 & DR7_GD != 0 {
+            // the handler is entered with GD cleared, so it can access the debug registers
+            self.debug.dr7 &= !DR7_GD;
+            self.debug_exception(DR6_BD);
+            return false;
+        }
+        true
+    }
+
+    /// raises a debug exception (INT 1), reporting the `status` bits in DR6
+    pub fn debug_exception(&mut self, status: u32) {
+        if self.pending_exception.is_none() {
+            self.debug.dr6 |= status;
+            self.exception(&Exception::DB, 0);
+        }
+    }
 
-        // CPU_Interrupt(which,CPU_INT_EXCEPTION | ((which>=8) ? CPU_INT_HAS_ERROR : 0),reg_eip);
+    /// writes CR0, switching the address translation when the PE bit changes
+    pub fn set_cr0(&mut self, mmu: &mut MMU, val: u32) {
+        if val & CR0_PG != 0 && val & CR0_PE == 0 {
+            return self.exception(&Exception::GP, 0);
+        }
+        let was_protected = self.is_protected_mode();
+        self.cr0 = val | CR0_ET;
+        if !was_protected && self.is_protected_mode() {
+            self.cpl = 0;
+            mmu.paging.user_mode = false;
+            // the segment registers keep their real mode base until they are reloaded
+            for r in &[R::ES, R::CS, R::SS, R::DS, R::FS, R::GS] {
+                let seg = self.get_r16(*r);
+                self.descriptors[r.index()] = Descriptor::real_mode(seg);
+            }
+        }
+        mmu.set_paging(self.cr0 & CR0_PG != 0, self.cr0 & CR0_WP != 0);
+    }
+
+    /// reads the descriptor for `selector` from the GDT or LDT. returns None if outside of the table limit
+    pub fn read_descriptor(&self, mmu: &MMU, selector: u16) -> Option<Descriptor> {
+        let (base, limit) = if selector & 4 == 0 {
+            (self.gdtr.base, u32::from(self.gdtr.limit))
+        } else if self.ldtr & !3 == 0 {
+            return None;
+        } else {
+            (self.ldt.base(), self.ldt.limit())
+        };
+        let offset = u32::from(selector & !7);
+        if offset + 7 > limit {
+            return None;
+        }
+        Some(Descriptor::from_u64(mmu.read_u64_linear(base + offset)))
+    }
+
+    /// writes the access byte of `desc` back to the descriptor table
+    fn write_descriptor_access(&self, mmu: &mut MMU, selector: u16, desc: Descriptor) {
+        let base = if selector & 4 == 0 {
+            self.gdtr.base
+        } else {
+            self.ldt.base()
+        };
+        mmu.write_u8_linear(base + u32::from(selector & !7) + 5, desc.access());
+    }
+
+    /// reads a descriptor, raising #GP for a null or out of range selector
+    fn fetch_descriptor(&mut self, mmu: &MMU, selector: u16) -> Option<Descriptor> {
+        if selector & !3 == 0 {
+            self.exception(&Exception::GP, 0);
+            return None;
+        }
+        let desc = self.read_descriptor(mmu, selector);
+        if desc.is_none() {
+            self.exception(&Exception::GP, usize::from(selector & !3));
+        }
+        desc
+    }
+
+    /// loads a segment register. in protected mode the descriptor is checked and cached.
+    /// returns false if the load raised an exception
+    pub fn load_segment(&mut self, mmu: &mut MMU, r: R, selector: u16) -> bool {
+        if self.has_real_mode_segments() {
+            self.set_r16(r, selector);
+            return true;
+        }
+        let cpl = self.cpl();
+        self.load_segment_at(mmu, r, selector, cpl)
+    }
+
+    /// loads a data or stack segment register, checked against privilege level `cpl`
+    fn load_segment_at(&mut self, mmu: &mut MMU, r: R, selector: u16, cpl: u8) -> bool {
+        let rpl = (selector & 3) as u8;
+        if selector & !3 == 0 {
+            if r == R::SS {
+                self.exception(&Exception::GP, 0);
+                return false;
+            }
+            // a null selector can be loaded, but faults on use
+            self.descriptors[r.index()] = Descriptor::default();
+            self.set_r16(r, selector);
+            return true;
+        }
+        let desc = match self.fetch_descriptor(mmu, selector) {
+            Some(desc) => desc,
+            None => return false,
+        };
+        let valid = if r == R::SS {
+            desc.is_writable() && rpl == cpl && desc.dpl() == cpl
+        } else {
+            desc.is_readable() && (desc.is_conforming() || cpl.max(rpl) <= desc.dpl())
+        };
+        if !valid {
+            self.exception(&Exception::GP, usize::from(selector & !3));
+            return false;
+        }
+        if !desc.is_present() {
+            let which = if r == R::SS { Exception::SS } else { Exception::NP };
+            self.exception(&which, usize::from(selector & !3));
+            return false;
+        }
+        self.set_segment_descriptor(mmu, r, selector, desc);
+        true
+    }
+
+    /// updates a segment register and its hidden descriptor cache
+    fn set_segment_descriptor(&mut self, mmu: &mut MMU, r: R, selector: u16, mut desc: Descriptor) {
+        desc.set_accessed();
+        self.write_descriptor_access(mmu, selector, desc);
+        self.descriptors[r.index()] = desc;
+        if r == R::CS {
+            self.cpl = (selector & 3) as u8;
+            mmu.paging.user_mode = self.cpl == 3;
+        }
+        self.set_r16(r, selector);
+    }
+
+    /// returns true if CS is a 32-bit code segment (the D bit), defaulting to 32-bit operands and addresses
+    pub fn is_code32(&self) -> bool {
+        !self.has_real_mode_segments() && self.descriptors[R::CS.index()].is_32bit()
+    }
+
+    /// returns true if SS is a 32-bit stack segment (the B bit), addressed through ESP instead of SP
+    pub fn is_stack32(&self) -> bool {
+        !self.has_real_mode_segments() && self.descriptors[R::SS.index()].is_32bit()
+    }
+
+    /// returns SP, or ESP for a 32-bit stack segment
+    pub fn stack_pointer(&self) -> u32 {
+        if self.is_stack32() {
+            self.get_r32(R::ESP)
+        } else {
+            u32::from(self.get_r16(R::SP))
+        }
+    }
+
+    /// sets SP, or ESP for a 32-bit stack segment
+    pub fn set_stack_pointer(&mut self, val: u32) {
+        if self.is_stack32() {
+            self.set_r32(R::ESP, val);
+        } else {
+            self.set_r16(R::SP, val as u16);
+        }
+    }
+
+    /// sets EIP to `offset` after a far transfer, truncated to 16 bits by a 16-bit code segment
+    fn set_far_ip(&mut self, offset: u32) {
+        self.regs.ip = if self.is_code32() {
+            offset
+        } else {
+            offset & 0xFFFF
+        };
+    }
+
+    /// translates `offset` in segment register `r` to a linear address, for a access of `len` bytes.
+    /// in protected mode the access is checked against the type and limit of the cached descriptor.
+    /// returns None and raises #GP, or #SS for the stack segment, on a violation
+    pub fn segment_address(&mut self, r: R, offset: u32, len: u32, write: bool) -> Option<u32> {
+        let addr = self.checked_segment_address(r, offset, len, write);
+        if addr.is_none() {
+            let fault = if r == R::SS { Exception::SS } else { Exception::GP };
+            self.exception(&fault, 0);
+        }
+        addr
+    }
+
+    /// like segment_address, but returns None on a violation without raising a exception
+    pub fn checked_segment_address(&self, r: R, offset: u32, len: u32, write: bool) -> Option<u32> {
+        if self.has_real_mode_segments() {
+            if offset > 0xFFFF {
+                return None;
+            }
+            return Some(self.segment_base(r).wrapping_add(offset));
+        }
+        let desc = self.descriptors[r.index()];
+        let permitted = desc.is_present() && if write {
+            desc.is_writable()
+        } else {
+            desc.is_readable()
+        };
+        if !permitted || !desc.contains(offset, len) {
+            return None;
+        }
+        Some(desc.base().wrapping_add(offset))
+    }
+
+    /// returns the linear address of the instruction at `eip`. returns None and raises #GP
+    /// if it is outside of the CS limit
+    pub fn fetch_address(&mut self, eip: u32) -> Option<u32> {
+        let limit = if self.has_real_mode_segments() {
+            0xFFFF
+        } else {
+            self.descriptors[R::CS.index()].limit()
+        };
+        if eip > limit {
+            self.exception(&Exception::GP, 0);
+            return None;
+        }
+        Some(self.segment_base(R::CS).wrapping_add(eip))
+    }
+
+    /// returns the linear base address of segment register `r`
+    pub fn segment_base(&self, r: R) -> u32 {
+        if self.has_real_mode_segments() {
+            u32::from(self.get_r16(r)) << 4
+        } else {
+            self.descriptors[r.index()].base()
+        }
+    }
+
+    /// reads `len` (1, 2, 4 or 8) bytes at `offset` in segment register `r`. returns 0 if the access faulted
+    pub fn read_mem(&mut self, mmu: &MMU, r: R, offset: u32, len: u32) -> u64 {
+        match self.segment_address(r, offset, len, false) {
+            Some(addr) => mmu.read_at(addr, len),
+            None => 0,
+        }
+    }
+
+    /// writes the `len` (1, 2, 4 or 8) low bytes of `data` at `offset` in segment register `r`.
+    /// the write is dropped if the access faulted
+    pub fn write_mem(&mut self, mmu: &mut MMU, r: R, offset: u32, len: u32, data: u64) {
+        if let Some(addr) = self.segment_address(r, offset, len, true) {
+            mmu.write_at(addr, len, data);
+        }
+    }
+
+    /// reads `len` bytes at `offset` in segment register `r`. returns zeroes if the access faulted
+    pub fn read_mem_bytes(&mut self, mmu: &MMU, r: R, offset: u32, len: u32) -> Vec<u8> {
+        match self.segment_address(r, offset, len, false) {
+            Some(addr) => mmu.read_bytes(addr, len as usize),
+            None => vec![0; len as usize],
+        }
+    }
+
+    /// writes `data` at `offset` in segment register `r`. the write is dropped if the access faulted
+    pub fn write_mem_bytes(&mut self, mmu: &mut MMU, r: R, offset: u32, data: &[u8]) {
+        if let Some(addr) = self.segment_address(r, offset, data.len() as u32, true) {
+            mmu.write_bytes(addr, data);
+        }
+    }
+
+    /// checks that `desc` is a present code segment that can be entered without a privilege change
+    fn check_code_segment(&mut self, selector: u16, desc: Descriptor) -> bool {
+        let cpl = self.cpl();
+        let rpl = (selector & 3) as u8;
+        let valid = desc.is_code() && if desc.is_conforming() {
+            desc.dpl() <= cpl
+        } else {
+            rpl <= cpl && desc.dpl() == cpl
+        };
+        if !valid {
+            self.exception(&Exception::GP, usize::from(selector & !3));
+            return false;
+        }
+        if !desc.is_present() {
+            self.exception(&Exception::NP, usize::from(selector & !3));
+            return false;
+        }
+        true
+    }
+
+    /// validates a call gate, returns the target selector, offset and code segment descriptor
+    fn call_gate_target(&mut self, mmu: &MMU, selector: u16, gate: Descriptor) -> Option<(u16, u32, Descriptor)> {
+        let cpl = self.cpl();
+        let rpl = (selector & 3) as u8;
+        if gate.dpl() < cpl || gate.dpl() < rpl {
+            self.exception(&Exception::GP, usize::from(selector & !3));
+            return None;
+        }
+        if !gate.is_present() {
+            self.exception(&Exception::NP, usize::from(selector & !3));
+            return None;
+        }
+        let target = gate.gate_selector();
+        let desc = self.fetch_descriptor(mmu, target)?;
+        if !desc.is_code() || desc.dpl() > cpl {
+            self.exception(&Exception::GP, usize::from(target & !3));
+            return None;
+        }
+        if !desc.is_present() {
+            self.exception(&Exception::NP, usize::from(target & !3));
+            return None;
+        }
+        Some((target, gate.gate_offset(), desc))
+    }
+
+    /// returns the SS:ESP for privilege level `dpl` from the current TSS
+    fn tss_stack(&mut self, mmu: &MMU, dpl: u8) -> Option<(u16, u32)> {
+        if self.tr & !3 == 0 {
+            self.exception(&Exception::TS, 0);
+            return None;
+        }
+        let base = self.tss.base();
+        if self.tss.is_32bit_system() {
+            let pos = base + 4 + u32::from(dpl) * 8;
+            Some((mmu.read_u16_linear(pos + 4), mmu.read_u32_linear(pos)))
+        } else {
+            let pos = base + 2 + u32::from(dpl) * 4;
+            Some((mmu.read_u16_linear(pos + 2), u32::from(mmu.read_u16_linear(pos))))
+        }
+    }
+
+    /// switches to the inner stack for privilege level `dpl`, pushing the old SS:ESP
+    fn switch_to_inner_stack(&mut self, mmu: &mut MMU, dpl: u8, push32: bool) -> bool {
+        let (old_ss, old_sp) = (self.get_r16(R::SS), self.stack_pointer());
+        let (ss, sp) = match self.tss_stack(mmu, dpl) {
+            Some(v) => v,
+            None => return false,
+        };
+        if !self.load_segment_at(mmu, R::SS, ss, dpl) {
+            return false;
+        }
+        // the inner stack is accessed at the new privilege level
+        mmu.paging.user_mode = dpl == 3;
+        self.set_stack_pointer(sp);
+        self.push_gate_value(mmu, push32, u32::from(old_ss));
+        self.push_gate_value(mmu, push32, old_sp);
+        true
+    }
+
+    fn push_gate_value(&mut self, mmu: &mut MMU, push32: bool, data: u32) {
+        if push32 {
+            self.push32(mmu, data);
+        } else {
+            self.push16(mmu, data as u16);
+        }
+    }
+
+    /// loads null into ES, DS, FS and GS if they are not accessible after a return to an outer privilege level
+    fn invalidate_data_segments(&mut self) {
+        let cpl = self.cpl();
+        for r in &[R::ES, R::DS, R::FS, R::GS] {
+            let desc = self.descriptors[r.index()];
+            if (desc.is_data() || !desc.is_conforming()) && desc.dpl() < cpl {
+                self.descriptors[r.index()] = Descriptor::default();
+                self.set_r16(*r, 0);
+            }
+        }
+    }
+
+    /// far jump to selector:offset. in protected mode, the selector may reference a code segment or a call gate
+    pub fn jmp_far(&mut self, mmu: &mut MMU, selector: u16, offset: u32) {
+        if self.has_real_mode_segments() {
+            self.set_r16(R::CS, selector);
+            self.regs.ip = offset;
+            return;
+        }
+        let desc = match self.fetch_descriptor(mmu, selector) {
+            Some(desc) => desc,
+            None => return,
+        };
+        let cpl = self.cpl();
+        if desc.is_segment() {
+            if self.check_code_segment(selector, desc) {
+                self.set_segment_descriptor(mmu, R::CS, (selector & !3) | u16::from(cpl), desc);
+                self.set_far_ip(offset);
+            }
+            return;
+        }
+        match desc.system_type() {
+            DESC_CALL_GATE16 | DESC_CALL_GATE32 => {
+                if let Some((target, target_offset, target_desc)) = self.call_gate_target(mmu, selector, desc) {
+                    // a jump through a call gate can't change privilege level
+                    if self.check_code_segment(target & !3, target_desc) {
+                        self.set_segment_descriptor(mmu, R::CS, (target & !3) | u16::from(cpl), target_desc);
+                        self.set_far_ip(target_offset);
+                    }
+                }
+            }
+            _ => {
+                println!("XXX jmp far: task switch through {:04X} is not supported", selector);
+                self.exception(&Exception::GP, usize::from(selector & !3));
+            }
+        }
+    }
+
+    /// far call to selector:offset, pushing a 32-bit return address if `op32` is set.
+    /// in protected mode, the selector may reference a code segment or a call gate
+    pub fn call_far(&mut self, mmu: &mut MMU, selector: u16, offset: u32, op32: bool) {
+        let (cs, ip) = self.get_address_pair();
+        if self.has_real_mode_segments() {
+            self.push_gate_value(mmu, op32, u32::from(cs));
+            self.push_gate_value(mmu, op32, ip);
+            self.set_r16(R::CS, selector);
+            self.regs.ip = offset;
+            return;
+        }
+        let desc = match self.fetch_descriptor(mmu, selector) {
+            Some(desc) => desc,
+            None => return,
+        };
+        let cpl = self.cpl();
+        if desc.is_segment() {
+            if self.check_code_segment(selector, desc) {
+                self.push_gate_value(mmu, op32, u32::from(cs));
+                self.push_gate_value(mmu, op32, ip);
+                self.set_segment_descriptor(mmu, R::CS, (selector & !3) | u16::from(cpl), desc);
+                self.set_far_ip(offset);
+            }
+            return;
+        }
+        match desc.system_type() {
+            DESC_CALL_GATE16 | DESC_CALL_GATE32 => {
+                let (target, target_offset, target_desc) = match self.call_gate_target(mmu, selector, desc) {
+                    Some(v) => v,
+                    None => return,
+                };
+                let gate32 = desc.is_32bit_system();
+                let new_cpl = if !target_desc.is_conforming() && target_desc.dpl() < cpl {
+                    // call to a more privileged level: switch stack and copy the parameters
+                    let dpl = target_desc.dpl();
+                    let old_sp = self.stack_pointer();
+                    let size = if gate32 { 4 } else { 2 };
+                    let params: Vec<u32> = (0..u32::from(desc.gate_param_count()))
+                        .map(|i| self.read_mem(mmu, R::SS, old_sp.wrapping_add(i * size), size) as u32)
+                        .collect();
+                    if !self.switch_to_inner_stack(mmu, dpl, gate32) {
+                        return;
+                    }
+                    for param in params.iter().rev() {
+                        if gate32 {
+                            self.push32(mmu, *param);
+                        } else {
+                            self.push16(mmu, *param as u16);
+                        }
+                    }
+                    dpl
+                } else {
+                    cpl
+                };
+                self.push_gate_value(mmu, gate32, u32::from(cs));
+                self.push_gate_value(mmu, gate32, ip);
+                self.set_segment_descriptor(mmu, R::CS, (target & !3) | u16::from(new_cpl), target_desc);
+                self.set_far_ip(target_offset);
+            }
+            _ => {
+                println!("XXX call far: task switch through {:04X} is not supported", selector);
+                self.exception(&Exception::GP, usize::from(selector & !3));
+            }
+        }
+    }
+
+    /// checks the code segment popped by a far return or iret.
+    fn check_return_segment(&mut self, mmu: &MMU, selector: u16) -> Option<Descriptor> {
+        let desc = self.fetch_descriptor(mmu, selector)?;
+        let rpl = (selector & 3) as u8;
+        let valid = rpl >= self.cpl() && desc.is_code() && if desc.is_conforming() {
+            desc.dpl() <= rpl
+        } else {
+            desc.dpl() == rpl
+        };
+        if !valid {
+            self.exception(&Exception::GP, usize::from(selector & !3));
+            return None;
+        }
+        if !desc.is_present() {
+            self.exception(&Exception::NP, usize::from(selector & !3));
+            return None;
+        }
+        Some(desc)
+    }
+
+    /// returns to the outer privilege level of `selector`, popping SS:ESP
+    fn return_to_outer_level(&mut self, mmu: &mut MMU, selector: u16, desc: Descriptor, imm: u16, pop32: bool) {
+        let (sp, ss) = if pop32 {
+            (self.pop32(mmu), self.pop32(mmu) as u16)
+        } else {
+            (u32::from(self.pop16(mmu)), self.pop16(mmu))
+        };
+        let rpl = (selector & 3) as u8;
+        if !self.load_segment_at(mmu, R::SS, ss, rpl) {
+            return;
+        }
+        self.set_segment_descriptor(mmu, R::CS, selector, desc);
+        self.set_stack_pointer(sp.wrapping_add(u32::from(imm)));
+        self.invalidate_data_segments();
+    }
+
+    /// pops a 16-bit, or 32-bit if `op32` is set, value from the stack
+    fn pop_sized(&mut self, mmu: &mut MMU, op32: bool) -> u32 {
+        if op32 {
+            self.pop32(mmu)
+        } else {
+            u32::from(self.pop16(mmu))
+        }
+    }
+
+    /// far return, releasing `imm` bytes of parameters from the stack. 32-bit values are popped if `op32` is set
+    pub fn ret_far(&mut self, mmu: &mut MMU, imm: u16, op32: bool) {
+        let ip = self.pop_sized(mmu, op32);
+        let cs = self.pop_sized(mmu, op32) as u16;
+        if self.has_real_mode_segments() {
+            self.set_r16(R::CS, cs);
+            self.regs.ip = ip;
+            let sp = self.stack_pointer().wrapping_add(u32::from(imm));
+            self.set_stack_pointer(sp);
+            return;
+        }
+        let desc = match self.check_return_segment(mmu, cs) {
+            Some(desc) => desc,
+            None => return,
+        };
+        let sp = self.stack_pointer().wrapping_add(u32::from(imm));
+        self.set_stack_pointer(sp);
+        if (cs & 3) as u8 > self.cpl() {
+            self.return_to_outer_level(mmu, cs, desc, imm, op32);
+        } else {
+            self.set_segment_descriptor(mmu, R::CS, cs, desc);
+        }
+        self.set_far_ip(ip);
+    }
+
+    /// interrupt return, popping 32-bit values if `op32` is set (IRETD)
+    pub fn iret(&mut self, mmu: &mut MMU, op32: bool) {
+        let ip = self.pop_sized(mmu, op32);
+        let cs = self.pop_sized(mmu, op32) as u16;
+        let flags = self.pop_sized(mmu, op32);
+        if op32 {
+            self.regs.flags.resume = flags & EFLAGS_RF != 0;
+        }
+        if self.has_real_mode_segments() {
+            self.regs.ip = ip;
+            self.set_r16(R::CS, cs);
+            if self.is_virtual_8086() {
+                // only reached with IOPL 3. IOPL and VM are kept
+                self.regs.flags.interrupt = flags & u32::from(FLAG_IF) != 0;
+            }
+            if op32 {
+                self.set_flags_u32(flags);
+            } else {
+                self.set_flags_u16(flags as u16);
+            }
+            return;
+        }
+        if op32 && flags & EFLAGS_VM != 0 && self.cpl() == 0 {
+            return self.return_to_virtual_8086(mmu, ip, cs, flags);
+        }
+        // XXX nested task return is not supported
+        let desc = match self.check_return_segment(mmu, cs) {
+            Some(desc) => desc,
+            None => return,
+        };
+        let cpl = self.cpl();
+        let iopl = self.regs.flags.iopl();
+        self.regs.flags.set_u16(flags as u16);
+        self.regs.flags.set_nested_task(flags & 0x4000 != 0);
+        if op32 && self.model.has_alignment_check() {
+            self.regs.flags.alignment_check = flags & (1 << 18) != 0;
+        }
+        if cpl <= iopl {
+            self.regs.flags.interrupt = flags & u32::from(FLAG_IF) != 0;
+        }
+        if cpl == 0 {
+            self.regs.flags.set_iopl(((flags >> 12) & 3) as u8);
+        }
+        if (cs & 3) as u8 > cpl {
+            self.return_to_outer_level(mmu, cs, desc, 0, op32);
+        } else {
+            self.set_segment_descriptor(mmu, R::CS, cs, desc);
+        }
+        self.set_far_ip(ip);
+    }
+
+    /// IRETD from privilege level 0 with EFLAGS.VM set: pops the virtual 8086 mode
+    /// stack and segment registers, and resumes execution of the real mode code
+    fn return_to_virtual_8086(&mut self, mmu: &mut MMU, ip: u32, cs: u16, flags: u32) {
+        let esp = self.pop32(mmu);
+        let ss = self.pop32(mmu) as u16;
+        let es = self.pop32(mmu) as u16;
+        let ds = self.pop32(mmu) as u16;
+        let fs = self.pop32(mmu) as u16;
+        let gs = self.pop32(mmu) as u16;
+
+        self.set_flags_u32(flags);
+        self.regs.flags.interrupt = flags & u32::from(FLAG_IF) != 0;
+        self.regs.flags.virtual_8086 = true;
+        self.cpl = 3;
+        mmu.paging.user_mode = true;
+        // segments are addressed as in real mode, with paging still applied
+        for (r, selector) in &[(R::CS, cs), (R::SS, ss), (R::ES, es), (R::DS, ds), (R::FS, fs), (R::GS, gs)] {
+            self.set_r16(*r, *selector);
+            self.descriptors[r.index()] = Descriptor::real_mode(*selector);
+        }
+        self.set_r32(R::ESP, esp);
+        self.regs.ip = ip & 0xFFFF;
+    }
+
+    /// leaves virtual 8086 mode for an interrupt handler at privilege level 0. the data segment
+    /// registers and SS:ESP are pushed on the ring 0 stack from the TSS, and the data segment
+    /// registers are loaded with null
+    fn leave_virtual_8086(&mut self, mmu: &mut MMU) -> bool {
+        let (old_ss, old_esp) = (self.get_r16(R::SS), self.get_r32(R::ESP));
+        let (ss, sp) = match self.tss_stack(mmu, 0) {
+            Some(v) => v,
+            None => return false,
+        };
+        self.regs.flags.virtual_8086 = false;
+        if !self.load_segment_at(mmu, R::SS, ss, 0) {
+            self.regs.flags.virtual_8086 = true;
+            return false;
+        }
+        mmu.paging.user_mode = false;
+        self.set_stack_pointer(sp);
+        for r in &[R::GS, R::FS, R::DS, R::ES] {
+            let selector = self.get_r16(*r);
+            self.push32(mmu, u32::from(selector));
+            self.set_r16(*r, 0);
+            self.descriptors[r.index()] = Descriptor::default();
+        }
+        self.push32(mmu, u32::from(old_ss));
+        self.push32(mmu, old_esp);
+        true
+    }
+
+    /// delivers a interrupt through the IDT. the gate DPL is only checked for `software` interrupts (INT n)
+    fn protected_mode_interrupt(&mut self, mmu: &mut MMU, int: u8, error_code: Option<u16>, software: bool) {
+        let offset = u16::from(int) * 8;
+        let error = usize::from(offset + 2);
+        if offset + 7 > self.idtr.limit {
+            return self.exception(&Exception::GP, error);
+        }
+        let gate = Descriptor::from_u64(mmu.read_u64_linear(self.idtr.base + u32::from(offset)));
+        let cpl = self.cpl();
+        let interrupt_gate = match gate.system_type() {
+            DESC_INT_GATE16 | DESC_INT_GATE32 => true,
+            DESC_TRAP_GATE16 | DESC_TRAP_GATE32 => false,
+            DESC_TASK_GATE => {
+                println!("XXX interrupt {:02X}: task gates are not supported", int);
+                return self.exception(&Exception::GP, error);
+            }
+            _ => return self.exception(&Exception::GP, error),
+        };
+        if gate.is_segment() || (software && gate.dpl() < cpl) {
+            return self.exception(&Exception::GP, error);
+        }
+        if !gate.is_present() {
+            return self.exception(&Exception::NP, error);
+        }
+        let target = gate.gate_selector();
+        let desc = match self.fetch_descriptor(mmu, target) {
+            Some(desc) => desc,
+            None => return,
+        };
+        if !desc.is_code() || desc.dpl() > cpl {
+            return self.exception(&Exception::GP, usize::from(target & !3));
+        }
+        if !desc.is_present() {
+            return self.exception(&Exception::NP, usize::from(target & !3));
+        }
+
+        let gate32 = gate.is_32bit_system();
+        let (cs, ip) = self.get_address_pair();
+        if self.is_virtual_8086() {
+            // the handler must be a 32-bit gate to a ring 0 non-conforming code segment
+            if !gate32 || desc.is_conforming() || desc.dpl() != 0 {
+                return self.exception(&Exception::GP, usize::from(target & !3));
+            }
+            let flags = self.flags_u32() | EFLAGS_VM;
+            if !self.leave_virtual_8086(mmu) {
+                return;
+            }
+            self.push32(mmu, flags);
+            self.push32(mmu, u32::from(cs));
+            self.push32(mmu, ip);
+            if let Some(error_code) = error_code {
+                self.push32(mmu, u32::from(error_code));
+            }
+            self.regs.flags.trap = false;
+            self.regs.flags.set_nested_task(false);
+            if interrupt_gate {
+                self.regs.flags.interrupt = false;
+            }
+            self.set_segment_descriptor(mmu, R::CS, target & !3, desc);
+            self.set_far_ip(gate.gate_offset());
+            return;
+        }
+
+        let flags = if gate32 {
+            self.flags_u32()
+        } else {
+            u32::from(self.flags_u16())
+        };
+        let new_cpl = if !desc.is_conforming() && desc.dpl() < cpl {
+            if !self.switch_to_inner_stack(mmu, desc.dpl(), gate32) {
+                return;
+            }
+            desc.dpl()
+        } else {
+            cpl
+        };
+        self.push_gate_value(mmu, gate32, flags);
+        self.push_gate_value(mmu, gate32, u32::from(cs));
+        self.push_gate_value(mmu, gate32, ip);
+        if let Some(error_code) = error_code {
+            self.push_gate_value(mmu, gate32, u32::from(error_code));
+        }
+
+        self.regs.flags.trap = false;
+        self.regs.flags.set_nested_task(false);
+        if interrupt_gate {
+            self.regs.flags.interrupt = false;
+        }
+        self.set_segment_descriptor(mmu, R::CS, (target & !3) | u16::from(new_cpl), desc);
+        let offset = if gate32 {
+            gate.gate_offset()
+        } else {
+            gate.gate_offset() & 0xFFFF
+        };
+        self.set_far_ip(offset);
+    }
+
+    /// returns false and raises #GP if not running at privilege level 0
+    pub fn check_privileged(&mut self) -> bool {
+        if self.cpl() != 0 {
+            self.exception(&Exception::GP, 0);
+            return false;
+        }
+        true
+    }
+
+    /// returns false and raises #GP if CLI or STI is executed with CPL > IOPL
+    pub fn check_iopl(&mut self) -> bool {
+        if self.cpl() > self.regs.flags.iopl() {
+            self.exception(&Exception::GP, 0);
+            return false;
+        }
+        true
+    }
+
+    /// returns false and raises #GP if an IOPL-sensitive instruction (PUSHF, POPF, INT n, IRET)
+    /// is executed in virtual 8086 mode with IOPL < 3, so the monitor can emulate it
+    pub fn check_virtual_8086_iopl(&mut self) -> bool {
+        if self.is_virtual_8086() && self.regs.flags.iopl() < 3 {
+            self.exception(&Exception::GP, 0);
+            return false;
+        }
+        true
+    }
+
+    /// returns false and raises #GP if I/O to `len` ports at `port` is not permitted. at CPL > IOPL,
+    /// and always in virtual 8086 mode, the I/O permission bitmap of the 32-bit TSS is consulted
+    pub fn check_io_permission(&mut self, mmu: &MMU, port: u16, len: u16) -> bool {
+        if !self.is_protected_mode() || (!self.is_virtual_8086() && self.cpl() <= self.regs.flags.iopl()) {
+            return true;
+        }
+        let permitted = self.tr & !3 != 0 && self.tss.is_32bit_system() && {
+            let base = self.tss.base();
+            let pos = u32::from(mmu.read_u16_linear(base + 0x66)) + u32::from(port / 8);
+            let mask = ((1 << len) - 1) << (port % 8);
+            // the bits are read as a word, so its last byte must also be within the TSS limit
+            let last = pos + 1;
+            last <= self.tss.limit() && mmu.read_u16_linear(base + pos) & mask == 0
+        };
+        if !permitted {
+            self.exception(&Exception::GP, 0);
+        }
+        permitted
+    }
+
+    /// returns false and raises #UD if not in protected mode, or in virtual 8086 mode
+    pub fn check_protected_mode(&mut self) -> bool {
+        if !self.is_protected_mode() || self.is_virtual_8086() {
+            self.exception(&Exception::UD, 0);
+            return false;
+        }
+        true
+    }
+
+    /// loads the local descriptor table register (LLDT)
+    pub fn load_ldt(&mut self, mmu: &MMU, selector: u16) {
+        if selector & !3 == 0 {
+            self.ldtr = selector;
+            self.ldt = Descriptor::default();
+            return;
+        }
+        if selector & 4 != 0 {
+            return self.exception(&Exception::GP, usize::from(selector & !3));
+        }
+        let desc = match self.fetch_descriptor(mmu, selector) {
+            Some(desc) => desc,
+            None => return,
+        };
+        if desc.is_segment() || desc.system_type() != DESC_LDT {
+            return self.exception(&Exception::GP, usize::from(selector & !3));
+        }
+        if !desc.is_present() {
+            return self.exception(&Exception::NP, usize::from(selector & !3));
+        }
+        self.ldtr = selector;
+        self.ldt = desc;
+    }
+
+    /// loads the task register and marks the TSS busy (LTR)
+    pub fn load_task_register(&mut self, mmu: &mut MMU, selector: u16) {
+        if selector & 4 != 0 {
+            return self.exception(&Exception::GP, usize::from(selector & !3));
+        }
+        let mut desc = match self.fetch_descriptor(mmu, selector) {
+            Some(desc) => desc,
+            None => return,
+        };
+        if desc.is_segment() || (desc.system_type() != DESC_TSS16 && desc.system_type() != DESC_TSS32) {
+            return self.exception(&Exception::GP, usize::from(selector & !3));
+        }
+        if !desc.is_present() {
+            return self.exception(&Exception::NP, usize::from(selector & !3));
+        }
+        desc.set_busy();
+        self.write_descriptor_access(mmu, selector, desc);
+        self.tr = selector;
+        self.tss = desc;
+    }
+
+    /// returns the descriptor of `selector` if it is visible at the current privilege level (LAR, LSL, VERR, VERW)
+    pub fn visible_descriptor(&self, mmu: &MMU, selector: u16) -> Option<Descriptor> {
+        if selector & !3 == 0 {
+            return None;
+        }
+        let desc = self.read_descriptor(mmu, selector)?;
+        let rpl = (selector & 3) as u8;
+        if desc.is_conforming() || desc.dpl() >= self.cpl().max(rpl) {
+            Some(desc)
+        } else {
+            None
+        }
     }
 
     pub fn cmp8(&mut self, dst: usize, src: usize) {
         let res = (Wrapping(dst) - Wrapping(src)).0;
 
         // The CF, OF, SF, ZF, AF, and PF flags are set according to the result.
-        self.regs.flags.set_carry_u8(res);
-        self.regs.flags.set_overflow_sub_u8(res, src, dst);
-        self.regs.flags.set_sign_u8(res);
-        self.regs.flags.set_zero_u8(res);
-        self.regs.flags.set_adjust(res, src, dst);
-        self.regs.flags.set_parity(res);
+        self.regs.flags.set_sub_u8(res, src, dst);
     }
 
     pub fn cmp16(&mut self, dst: usize, src: usize) {
         let res = (Wrapping(dst) - Wrapping(src)).0;
 
         // The CF, OF, SF, ZF, AF, and PF flags are set according to the result.
-        self.regs.flags.set_carry_u16(res);
-        self.regs.flags.set_overflow_sub_u16(res, src, dst);
-        self.regs.flags.set_sign_u16(res);
-        self.regs.flags.set_zero_u16(res);
-        self.regs.flags.set_adjust(res, src, dst);
-        self.regs.flags.set_parity(res);
+        self.regs.flags.set_sub_u16(res, src, dst);
     }
 
     pub fn cmp32(&mut self, dst: usize, src: usize) {
         let res = (Wrapping(dst) - Wrapping(src)).0;
 
         // The CF, OF, SF, ZF, AF, and PF flags are set according to the result.
-        self.regs.flags.set_carry_u32(res);
-        self.regs.flags.set_overflow_sub_u32(res, src, dst);
-        self.regs.flags.set_sign_u32(res);
-        self.regs.flags.set_zero_u32(res);
-        self.regs.flags.set_adjust(res, src, dst);
-        self.regs.flags.set_parity(res);
+        self.regs.flags.set_sub_u32(res, src, dst);
     }
 
     pub fn push16(&mut self, mmu: &mut MMU, data: u16) {
-        let sp = (Wrapping(self.get_r16(R::SP)) - Wrapping(2)).0;
-        self.set_r16(R::SP, sp);
-        let ss = self.get_r16(R::SS);
+        let sp = self.stack_pointer().wrapping_sub(2) & self.stack_mask();
         if DEBUG_STACK {
-            println!("[{}] push16 {:04X} to {:04X}:{:04X}", self.get_memory_address(), data, ss, sp);
+            println!("[{}] push16 {:04X} to {:04X}:{:04X}", self.get_memory_address(), data, self.get_r16(R::SS), sp);
         }
         if DEBUG_MARK_STACK && data == STACK_MARKER {
-            println!("[{}] push16 {:04X} to {:04X}:{:04X} STACK MARKER", self.get_memory_address(), data, ss, sp);
+            println!("[{}] push16 {:04X} to {:04X}:{:04X} STACK MARKER", self.get_memory_address(), data, self.get_r16(R::SS), sp);
+        }
+        if let Some(addr) = self.segment_address(R::SS, sp, 2, true) {
+            self.set_stack_pointer(sp);
+            mmu.write_at(addr, 2, u64::from(data));
         }
-        mmu.write_u16(ss, sp, data);
     }
 
     pub fn push32(&mut self, mmu: &mut MMU, data: u32) {
-        let sp = (Wrapping(self.get_r16(R::SP)) - Wrapping(4)).0;
-        self.set_r16(R::SP, sp);
-        let ss = self.get_r16(R::SS);
+        let sp = self.stack_pointer().wrapping_sub(4) & self.stack_mask();
         if DEBUG_STACK {
-            println!("[{}] push32 {:04X} to {:04X}:{:04X}", self.get_memory_address(), data, ss, sp);
+            println!("[{}] push32 {:04X} to {:04X}:{:04X}", self.get_memory_address(), data, self.get_r16(R::SS), sp);
+        }
+        if let Some(addr) = self.segment_address(R::SS, sp, 4, true) {
+            self.set_stack_pointer(sp);
+            mmu.write_at(addr, 4, u64::from(data));
         }
-        mmu.write_u32(ss, sp, data);
     }
 
     pub fn pop16(&mut self, mmu: &mut MMU) -> u16 {
-        let ss = self.get_r16(R::SS);
-        let sp = self.get_r16(R::SP);
-        let data = mmu.read_u16(ss, self.get_r16(R::SP));
+        let sp = self.stack_pointer();
+        let data = match self.segment_address(R::SS, sp, 2, false) {
+            Some(addr) => mmu.read_at(addr, 2) as u16,
+            None => return 0,
+        };
         if DEBUG_STACK {
-            println!("[{}] pop16 {:04X} from {:04X}:{:04X}", self.get_memory_address(), data, ss, sp);
+            println!("[{}] pop16 {:04X} from {:04X}:{:04X}", self.get_memory_address(), data, self.get_r16(R::SS), sp);
         }
-        let sp = (Wrapping(sp) + Wrapping(2)).0;
-        self.set_r16(R::SP, sp);
+        self.set_stack_pointer(sp.wrapping_add(2) & self.stack_mask());
         data
     }
 
     pub fn pop32(&mut self, mmu: &mut MMU) -> u32 {
-        let ss = self.get_r16(R::SS);
-        let sp = self.get_r16(R::SP);
-        let data = mmu.read_u32(ss, sp);
+        let sp = self.stack_pointer();
+        let data = match self.segment_address(R::SS, sp, 4, false) {
+            Some(addr) => mmu.read_at(addr, 4) as u32,
+            None => return 0,
+        };
         if DEBUG_STACK {
-            println!("[{}] pop32 {:04X} from {:04X}:{:04X}", self.get_memory_address(), data, ss, sp);
+            println!("[{}] pop32 {:04X} from {:04X}:{:04X}", self.get_memory_address(), data, self.get_r16(R::SS), sp);
         }
-        let sp = (Wrapping(sp) + Wrapping(4)).0;
-        self.set_r16(R::SP, sp);
+        self.set_stack_pointer(sp.wrapping_add(4) & self.stack_mask());
         data
     }
 
+    /// the stack pointer wraps at 64k, or at 4G for a 32-bit stack segment
+    fn stack_mask(&self) -> u32 {
+        if self.is_stack32() {
+            0xFFFF_FFFF
+        } else {
+            0xFFFF
+        }
+    }
+
     /// returns the absoute address of CS:IP
     pub fn get_address(&self) -> u32 {
         self.get_memory_address().value()
     }
 
-    /// returns cs, ip
-    pub fn get_address_pair(&self) -> (u16, u16) {
+    /// returns cs, eip
+    pub fn get_address_pair(&self) -> (u16, u32) {
         (self.get_r16(R::CS), self.regs.ip)
     }
 
     /// returns the address of CS:IP as a MemoryAddress::RealSegmentOffset
     pub fn get_memory_address(&self) -> MemoryAddress {
-        MemoryAddress::RealSegmentOffset(self.get_r16(R::CS), self.regs.ip)
-    }
-
-    fn read_u8(&mut self, mmu: &MMU) -> u8 {
-        let (seg, off) = self.get_address_pair();
-        let b = mmu.read_u8(seg, off);
-        self.regs.ip += 1;
-        b
-    }
-
-    fn read_u16(&mut self, mmu: &MMU) -> u16 {
-        let lo = self.read_u8(mmu);
-        let hi = self.read_u8(mmu);
-        u16::from(hi) << 8 | u16::from(lo)
-    }
-
-    fn read_s8(&mut self,  mmu: &MMU) -> i8 {
-        self.read_u8(mmu) as i8
-    }
-
-    fn read_s16(&mut self, mmu: &MMU) -> i16 {
-        self.read_u16(mmu) as i16
-    }
-
-    fn read_rel8(&mut self, mmu: &MMU) -> u16 {
-        let val = self.read_u8(mmu) as i8;
-        (self.regs.ip as i16 + i16::from(val)) as u16
-    }
-
-    fn read_rel16(&mut self, mmu: &MMU) -> u16 {
-        let val = self.read_u16(mmu) as i16;
-        (self.regs.ip as i16 + val) as u16
-    }
-
-    /// returns "segment, offset" pair
-    fn get_amode_addr(&self, amode: &AMode) -> (u16, u16) {
-        match *amode {
-            AMode::BX  => (self.get_r16(R::DS), self.get_r16(R::BX)),
-            AMode::BP  => (self.get_r16(R::SS), self.get_r16(R::BP)),
-            AMode::SI  => (self.get_r16(R::DS), self.get_r16(R::SI)),
-            AMode::DI  => (self.get_r16(R::DS), self.get_r16(R::DI)),
-            AMode::BXSI => (self.get_r16(R::DS), self.get_r16(R::BX).wrapping_add(self.get_r16(R::SI))),
-            AMode::BXDI => (self.get_r16(R::DS), self.get_r16(R::BX).wrapping_add(self.get_r16(R::DI))),
-            AMode::BPSI => (self.get_r16(R::SS), self.get_r16(R::BP).wrapping_add(self.get_r16(R::SI))),
-            AMode::BPDI => (self.get_r16(R::SS), self.get_r16(R::BP).wrapping_add(self.get_r16(R::DI))),
-            _ => panic!("xxx"),
-        }
+        MemoryAddress::RealSegmentOffset(self.get_r16(R::CS), self.regs.ip as u16)
     }
 
     /// used by lds, les
-    pub fn read_segment_selector(&self, mmu: &MMU, p: &Parameter) -> (u16, u16) {
-        let (segment, offset) = match *p {
-            Parameter::Ptr16(seg, imm) => (self.segment(seg), imm),
-            Parameter::Ptr16Amode(_, ref amode) => self.get_amode_addr(amode),
-            Parameter::Ptr16AmodeS8(_, ref amode, imms) => {
-                let (seg, off) = self.get_amode_addr(amode);
-                (seg, (i32::from(off) + i32::from(imms)) as u16)
-            }
-            /*
-            Parameter::Ptr16AmodeS16(_, ref amode, imms) => {
-                let (seg, off) = self.get_amode_addr(amode);
-                (seg, (i32::from(off) + i32::from(imms)) as u16)
-            }
-            */
-            _ => panic!("unhandled parameter {:?}", p),
-        };
-
-        let o_val = mmu.read_u16(segment, offset);
-        let s_val = mmu.read_u16(segment, offset + 2);
+    pub fn read_segment_selector(&mut self, mmu: &MMU, p: &Parameter) -> (u16, u16) {
+        let (r, offset) = self.parameter_segment_offset(p);
+        let o_val = self.read_mem(mmu, r, offset, 2) as u16;
+        let s_val = self.read_mem(mmu, r, offset.wrapping_add(2), 2) as u16;
         (s_val, o_val)
     }
 
@@ -313,7 +1429,13 @@ impl CPU {
             Parameter::Ptr16Amode(_, ref amode) => self.amode(amode),
             Parameter::Ptr16AmodeS8(_, ref amode, imm) => (Wrapping(self.amode(amode)) + Wrapping(imm as usize)).0,
             Parameter::Ptr16AmodeS16(_, ref amode, imm) => (Wrapping(self.amode(amode)) + Wrapping(imm as usize)).0,
+            Parameter::Ptr16AmodeS32(_, ref amode, imm) => (Wrapping(self.amode(amode)) + Wrapping(imm as usize)).0,
             Parameter::Ptr16(_, imm) => imm as usize,
+            Parameter::Ptr32Amode(_, ref amode) => self.amode(amode),
+            Parameter::Ptr32AmodeS8(_, ref amode, imm) => (Wrapping(self.amode(amode)) + Wrapping(imm as usize)).0,
+            Parameter::Ptr32AmodeS16(_, ref amode, imm) => (Wrapping(self.amode(amode)) + Wrapping(imm as usize)).0,
+            Parameter::Ptr32AmodeS32(_, ref amode, imm) => (Wrapping(self.amode(amode)) + Wrapping(imm as usize)).0,
+            Parameter::Ptr32(_, imm) => imm as usize,
             _ => panic!("unhandled parameter: {:?} at {:06X}", p, self.get_address()),
         }
     }
@@ -337,48 +1459,19 @@ impl CPU {
             Parameter::Reg16(r) => self.get_r16(r) as usize,
             Parameter::Reg32(r) => self.get_r32(r) as usize,
             Parameter::SReg16(sr) => self.get_r16(sr) as usize,
-            Parameter::Ptr8(seg, imm) => mmu.read_u8(self.segment(seg), imm) as usize,
-            Parameter::Ptr8Amode(seg, ref amode) => {
-                let seg = self.segment(seg);
-                let offset = self.amode(amode) as u16;
-                mmu.read_u8(seg, offset) as usize
-            }
-            Parameter::Ptr8AmodeS8(seg, ref amode, imm) => {
-                let seg = self.segment(seg);
-                let offset = (Wrapping(self.amode(amode) as u16) + Wrapping(imm as u16)).0;
-                mmu.read_u8(seg, offset) as usize
-            }
-            Parameter::Ptr8AmodeS16(seg, ref amode, imm) => {
-                let seg = self.segment(seg);
-                let offset = (Wrapping(self.amode(amode) as u16) + Wrapping(imm as u16)).0;
-                mmu.read_u8(seg, offset) as usize
-            }
-            Parameter::Ptr16(seg, imm) => mmu.read_u16(self.segment(seg), imm) as usize,
-            Parameter::Ptr16Amode(seg, ref amode) => {
-                let seg = self.segment(seg);
-                let offset = self.amode(amode) as u16;
-                mmu.read_u16(seg, offset) as usize
-            }
-            Parameter::Ptr16AmodeS8(seg, ref amode, imm) => {
-                let seg = self.segment(seg);
-                let offset = (Wrapping(self.amode(amode) as u16) + Wrapping(imm as u16)).0;
-                mmu.read_u16(seg, offset) as usize
-            }
-            Parameter::Ptr16AmodeS16(seg, ref amode, imm) => {
-                let seg = self.segment(seg);
-                let offset = (Wrapping(self.amode(amode) as u16) + Wrapping(imm as u16)).0;
-                mmu.read_u16(seg, offset) as usize
-            }
-            Parameter::Ptr32(seg, offset) => mmu.read_u32(self.segment(seg), offset) as usize,
-            Parameter::Ptr32Amode(seg, ref amode) => {
-                let seg = self.segment(seg);
-                let offset = self.amode(amode) as u16;
-                mmu.read_u32(seg, offset) as usize
-            }
-            Parameter::Ptr32AmodeS8(seg, ref amode, imm) => {
-                let seg = self.segment(seg);
-                let offset = (Wrapping(self.amode(amode) as u16) + Wrapping(imm as u16)).0;
-                mmu.read_u32(seg, offset) as usize
+            Parameter::CReg32(r) => self.get_control_register(r) as usize,
+            Parameter::DReg32(r) => self.debug.get(r) as usize,
+            Parameter::Ptr8(..) | Parameter::Ptr8Amode(..) | Parameter::Ptr8AmodeS8(..) | Parameter::Ptr8AmodeS16(..) | Parameter::Ptr8AmodeS32(..) => {
+                let (r, offset) = self.parameter_segment_offset(p);
+                self.read_mem(mmu, r, offset, 1) as usize
+            }
+            Parameter::Ptr16(..) | Parameter::Ptr16Amode(..) | Parameter::Ptr16AmodeS8(..) | Parameter::Ptr16AmodeS16(..) | Parameter::Ptr16AmodeS32(..) => {
+                let (r, offset) = self.parameter_segment_offset(p);
+                self.read_mem(mmu, r, offset, 2) as usize
+            }
+            Parameter::Ptr32(..) | Parameter::Ptr32Amode(..) | Parameter::Ptr32AmodeS8(..) | Parameter::Ptr32AmodeS16(..) | Parameter::Ptr32AmodeS32(..) => {
+                let (r, offset) = self.parameter_segment_offset(p);
+                self.read_mem(mmu, r, offset, 4) as usize
             }
             _ => {
                 let (seg, off) = self.get_address_pair();
@@ -390,28 +1483,10 @@ impl CPU {
     pub fn write_parameter_u8(&mut self, mmu: &mut MMU, p: &Parameter, data: u8) {
         match *p {
             Parameter::Reg8(r) => self.set_r8(r, data),
-            Parameter::Ptr8(seg, offset) => {
-                let seg = self.segment(seg);
-                self.debug_write_u8(seg, offset, data);
-                mmu.write_u8(seg, offset, data);
-            }
-            Parameter::Ptr8Amode(seg, ref amode) => {
-                let seg = self.segment(seg);
-                let offset = self.amode(amode) as u16;
-                self.debug_write_u8(seg, offset, data);
-                mmu.write_u8(seg, offset, data);
-            }
-            Parameter::Ptr8AmodeS8(seg, ref amode, imm) => {
-                let seg = self.segment(seg);
-                let offset = (Wrapping(self.amode(amode) as u16) + Wrapping(imm as u16)).0;
-                self.debug_write_u8(seg, offset, data);
-                mmu.write_u8(seg, offset, data);
-            }
-            Parameter::Ptr8AmodeS16(seg, ref amode, imm) => {
-                let seg = self.segment(seg);
-                let offset = (Wrapping(self.amode(amode) as u16) + Wrapping(imm as u16)).0;
-                self.debug_write_u8(seg, offset, data);
-                mmu.write_u8(seg, offset, data);
+            Parameter::Ptr8(..) | Parameter::Ptr8Amode(..) | Parameter::Ptr8AmodeS8(..) | Parameter::Ptr8AmodeS16(..) | Parameter::Ptr8AmodeS32(..) => {
+                let (r, offset) = self.parameter_segment_offset(p);
+                self.debug_write_u8(r, offset, data);
+                self.write_mem(mmu, r, offset, 1, u64::from(data));
             }
             _ => panic!("write_parameter_u8 unhandled type {:?} at {:06X}", p, self.get_address()),
         }
@@ -419,75 +1494,187 @@ impl CPU {
 
     pub fn write_parameter_u16(&mut self, mmu: &mut MMU, segment: Segment, p: &Parameter, data: u16) {
         match *p {
-            Parameter::Reg16(r) |
-            Parameter::SReg16(r) => self.set_r16(r, data),
+            Parameter::Reg16(r) => self.set_r16(r, data),
+            Parameter::SReg16(r) => {
+                self.load_segment(mmu, r, data);
+            }
             Parameter::Imm16(imm) => {
-                let seg = self.segment(segment);
-                self.debug_write_u16(seg, imm, data);
-                mmu.write_u16(seg, imm, data);
-            }
-            Parameter::Ptr16(seg, offset) => {
-                let seg = self.segment(seg);
-                self.debug_write_u16(seg, offset, data);
-                mmu.write_u16(seg, offset, data);
-            }
-            Parameter::Ptr16Amode(seg, ref amode) => {
-                let seg = self.segment(seg);
-                let offset = self.amode(amode) as u16;
-                self.debug_write_u16(seg, offset, data);
-                mmu.write_u16(seg, offset, data);
-            }
-            Parameter::Ptr16AmodeS8(seg, ref amode, imm) => {
-                let seg = self.segment(seg);
-                let offset = (Wrapping(self.amode(amode) as u16) + Wrapping(imm as u16)).0;
-                self.debug_write_u16(seg, offset, data);
-                mmu.write_u16(seg, offset, data);
-            }
-            Parameter::Ptr16AmodeS16(seg, ref amode, imm) => {
-                let seg = self.segment(seg);
-                let offset = (Wrapping(self.amode(amode) as u16) + Wrapping(imm as u16)).0;
-                self.debug_write_u16(seg, offset, data);
-                mmu.write_u16(seg, offset, data);
+                let r = segment.as_register();
+                self.debug_write_u16(r, u32::from(imm), data);
+                self.write_mem(mmu, r, u32::from(imm), 2, u64::from(data));
+            }
+            Parameter::Ptr16(..) | Parameter::Ptr16Amode(..) | Parameter::Ptr16AmodeS8(..) | Parameter::Ptr16AmodeS16(..) | Parameter::Ptr16AmodeS32(..) => {
+                let (r, offset) = self.parameter_segment_offset(p);
+                self.debug_write_u16(r, offset, data);
+                self.write_mem(mmu, r, offset, 2, u64::from(data));
             }
             _ => panic!("unhandled type {:?} at {:06X}", p, self.get_address()),
         }
     }
 
-    pub fn write_parameter_u32(&mut self, mmu: &mut MMU, _segment: Segment, p: &Parameter, data: u32) {
+    pub fn write_parameter_u32(&mut self, mmu: &mut MMU, segment: Segment, p: &Parameter, data: u32) {
         match *p {
             Parameter::Reg32(r) => self.set_r32(r, data),
-            Parameter::Ptr32(seg, offset) => {
-                let seg = self.segment(seg);
-                self.debug_write_u32(seg, offset, data);
-                mmu.write_u32(seg, offset, data);
-            }
-            Parameter::Ptr32Amode(seg, ref amode) => {
-                let seg = self.segment(seg);
-                let offset = self.amode(amode);
-                self.debug_write_u32(seg, offset as u16, data);
-                mmu.write_u32(seg, offset as u16, data);
-            }
-            Parameter::Ptr32AmodeS8(seg, ref amode, imm) => {
-                let seg = self.segment(seg);
-                let offset = (Wrapping(self.amode(amode) as u16) + Wrapping(imm as u16)).0;
-                self.debug_write_u32(seg, offset as u16, data);
-                mmu.write_u32(seg, offset, data);
-            }
-            Parameter::Ptr32AmodeS16(seg, ref amode, imm) => {
-                let seg = self.segment(seg);
-                let offset = (Wrapping(self.amode(amode) as u16) + Wrapping(imm as u16)).0;
-                self.debug_write_u32(seg, offset as u16, data);
-                mmu.write_u32(seg, offset, data);
+            Parameter::CReg32(r) => self.set_control_register(mmu, r, data),
+            Parameter::DReg32(r) => self.set_debug_register(mmu, r, data),
+            Parameter::Imm16(imm) => {
+                let r = segment.as_register();
+                self.debug_write_u32(r, u32::from(imm), data);
+                self.write_mem(mmu, r, u32::from(imm), 4, u64::from(data));
+            }
+            Parameter::Ptr32(..) | Parameter::Ptr32Amode(..) | Parameter::Ptr32AmodeS8(..) | Parameter::Ptr32AmodeS16(..) | Parameter::Ptr32AmodeS32(..) => {
+                let (r, offset) = self.parameter_segment_offset(p);
+                self.debug_write_u32(r, offset, data);
+                self.write_mem(mmu, r, offset, 4, u64::from(data));
             }
             _ => panic!("unhandled type {:?} at {:06X}", p, self.get_address()),
         }
     }
 
-    fn debug_write_u8(&self, seg: u16, off: u16, data: u8) {
+    /// returns the segment register used by a memory operand. BP and ESP based
+    /// addressing defaults to SS, everything else to DS
+    fn amode_segment(seg: Segment, amode: &AMode) -> R {
+        if seg == Segment::Default && amode.is_stack_based() {
+            R::SS
+        } else {
+            seg.as_register()
+        }
+    }
+
+    /// returns the segment register and offset of a memory operand. effective addresses from
+    /// 16-bit addressing modes wrap at 64k, 32-bit ones are carried on to the segment limit check
+    pub fn parameter_segment_offset(&self, p: &Parameter) -> (R, u32) {
+        match *p {
+            Parameter::Ptr8(seg, imm) |
+            Parameter::Ptr16(seg, imm) |
+            Parameter::Ptr32(seg, imm) |
+            Parameter::Ptr64(seg, imm) |
+            Parameter::Ptr80(seg, imm) => (seg.as_register(), u32::from(imm)),
+            Parameter::Ptr8Amode(seg, ref amode) |
+            Parameter::Ptr16Amode(seg, ref amode) |
+            Parameter::Ptr32Amode(seg, ref amode) |
+            Parameter::Ptr64Amode(seg, ref amode) |
+            Parameter::Ptr80Amode(seg, ref amode) => (CPU::amode_segment(seg, amode), self.effective_address(amode, 0)),
+            Parameter::Ptr8AmodeS8(seg, ref amode, imm) |
+            Parameter::Ptr16AmodeS8(seg, ref amode, imm) |
+            Parameter::Ptr32AmodeS8(seg, ref amode, imm) |
+            Parameter::Ptr64AmodeS8(seg, ref amode, imm) |
+            Parameter::Ptr80AmodeS8(seg, ref amode, imm) => {
+                (CPU::amode_segment(seg, amode), self.effective_address(amode, i32::from(imm)))
+            }
+            Parameter::Ptr8AmodeS16(seg, ref amode, imm) |
+            Parameter::Ptr16AmodeS16(seg, ref amode, imm) |
+            Parameter::Ptr32AmodeS16(seg, ref amode, imm) |
+            Parameter::Ptr64AmodeS16(seg, ref amode, imm) |
+            Parameter::Ptr80AmodeS16(seg, ref amode, imm) => {
+                (CPU::amode_segment(seg, amode), self.effective_address(amode, i32::from(imm)))
+            }
+            Parameter::Ptr8AmodeS32(seg, ref amode, imm) |
+            Parameter::Ptr16AmodeS32(seg, ref amode, imm) |
+            Parameter::Ptr32AmodeS32(seg, ref amode, imm) |
+            Parameter::Ptr64AmodeS32(seg, ref amode, imm) |
+            Parameter::Ptr80AmodeS32(seg, ref amode, imm) => {
+                (CPU::amode_segment(seg, amode), self.effective_address(amode, imm))
+            }
+            _ => panic!("parameter_segment_offset unhandled type {:?} at {:06X}", p, self.get_address()),
+        }
+    }
+
+    /// returns the effective address of `amode` plus `disp`, truncated to 16 bits for 16-bit addressing modes
+    fn effective_address(&self, amode: &AMode, disp: i32) -> u32 {
+        let ea = (self.amode(amode) as u32).wrapping_add(disp as u32);
+        if amode.is_32bit() {
+            ea
+        } else {
+            ea & 0xFFFF
+        }
+    }
+
+    /// reads a fpu register, or a m32fp, m64fp or m80fp operand
+    pub fn read_parameter_float(&mut self, mmu: &MMU, p: &Parameter) -> FPR80 {
+        match *p {
+            Parameter::FPR80(r) => self.fpu.st(r.index()),
+            Parameter::Ptr32(..) | Parameter::Ptr32Amode(..) | Parameter::Ptr32AmodeS8(..) | Parameter::Ptr32AmodeS16(..) | Parameter::Ptr32AmodeS32(..) => {
+                let (r, off) = self.parameter_segment_offset(p);
+                FPR80::from_f32(f32::from_bits(self.read_mem(mmu, r, off, 4) as u32))
+            }
+            Parameter::Ptr64(..) | Parameter::Ptr64Amode(..) | Parameter::Ptr64AmodeS8(..) | Parameter::Ptr64AmodeS16(..) | Parameter::Ptr64AmodeS32(..) => {
+                let (r, off) = self.parameter_segment_offset(p);
+                FPR80::from_f64(f64::from_bits(self.read_mem(mmu, r, off, 8)))
+            }
+            Parameter::Ptr80(..) | Parameter::Ptr80Amode(..) | Parameter::Ptr80AmodeS8(..) | Parameter::Ptr80AmodeS16(..) | Parameter::Ptr80AmodeS32(..) => {
+                let (r, off) = self.parameter_segment_offset(p);
+                FPR80::from_bytes(&self.read_mem_bytes(mmu, r, off, 10))
+            }
+            _ => panic!("read_parameter_float unhandled type {:?} at {:06X}", p, self.get_address()),
+        }
+    }
+
+    /// writes to a fpu register, or a m32fp, m64fp or m80fp operand
+    pub fn write_parameter_float(&mut self, mmu: &mut MMU, p: &Parameter, data: FPR80) {
+        match *p {
+            Parameter::FPR80(r) => self.fpu.set_st(r.index(), data),
+            Parameter::Ptr32(..) | Parameter::Ptr32Amode(..) | Parameter::Ptr32AmodeS8(..) | Parameter::Ptr32AmodeS16(..) | Parameter::Ptr32AmodeS32(..) => {
+                let (r, off) = self.parameter_segment_offset(p);
+                let val = self.fpu.float(data, 32);
+                self.write_mem(mmu, r, off, 4, val);
+            }
+            Parameter::Ptr64(..) | Parameter::Ptr64Amode(..) | Parameter::Ptr64AmodeS8(..) | Parameter::Ptr64AmodeS16(..) | Parameter::Ptr64AmodeS32(..) => {
+                let (r, off) = self.parameter_segment_offset(p);
+                let val = self.fpu.float(data, 64);
+                self.write_mem(mmu, r, off, 8, val);
+            }
+            Parameter::Ptr80(..) | Parameter::Ptr80Amode(..) | Parameter::Ptr80AmodeS8(..) | Parameter::Ptr80AmodeS16(..) | Parameter::Ptr80AmodeS32(..) => {
+                let (r, off) = self.parameter_segment_offset(p);
+                self.write_mem_bytes(mmu, r, off, &data.to_bytes());
+            }
+            _ => panic!("write_parameter_float unhandled type {:?} at {:06X}", p, self.get_address()),
+        }
+    }
+
+    /// reads a m16int, m32int or m64int operand
+    pub fn read_parameter_int(&mut self, mmu: &MMU, p: &Parameter) -> i64 {
+        let (r, off) = self.parameter_segment_offset(p);
+        match *p {
+            Parameter::Ptr16(..) | Parameter::Ptr16Amode(..) | Parameter::Ptr16AmodeS8(..) | Parameter::Ptr16AmodeS16(..) | Parameter::Ptr16AmodeS32(..) => {
+                i64::from(self.read_mem(mmu, r, off, 2) as i16)
+            }
+            Parameter::Ptr32(..) | Parameter::Ptr32Amode(..) | Parameter::Ptr32AmodeS8(..) | Parameter::Ptr32AmodeS16(..) | Parameter::Ptr32AmodeS32(..) => {
+                i64::from(self.read_mem(mmu, r, off, 4) as i32)
+            }
+            Parameter::Ptr64(..) | Parameter::Ptr64Amode(..) | Parameter::Ptr64AmodeS8(..) | Parameter::Ptr64AmodeS16(..) | Parameter::Ptr64AmodeS32(..) => {
+                self.read_mem(mmu, r, off, 8) as i64
+            }
+            _ => panic!("read_parameter_int unhandled type {:?} at {:06X}", p, self.get_address()),
+        }
+    }
+
+    /// writes a value rounded by the rounding control, or truncated, to a m16int, m32int or m64int operand
+    pub fn write_parameter_int(&mut self, mmu: &mut MMU, p: &Parameter, data: FPR80, truncate: bool) {
+        let (r, off) = self.parameter_segment_offset(p);
+        match *p {
+            Parameter::Ptr16(..) | Parameter::Ptr16Amode(..) | Parameter::Ptr16AmodeS8(..) | Parameter::Ptr16AmodeS16(..) | Parameter::Ptr16AmodeS32(..) => {
+                let v = self.fpu.integer(data, 16, truncate);
+                self.write_mem(mmu, r, off, 2, u64::from(v as u16));
+            }
+            Parameter::Ptr32(..) | Parameter::Ptr32Amode(..) | Parameter::Ptr32AmodeS8(..) | Parameter::Ptr32AmodeS16(..) | Parameter::Ptr32AmodeS32(..) => {
+                let v = self.fpu.integer(data, 32, truncate);
+                self.write_mem(mmu, r, off, 4, u64::from(v as u32));
+            }
+            Parameter::Ptr64(..) | Parameter::Ptr64Amode(..) | Parameter::Ptr64AmodeS8(..) | Parameter::Ptr64AmodeS16(..) | Parameter::Ptr64AmodeS32(..) => {
+                let v = self.fpu.integer(data, 64, truncate);
+                self.write_mem(mmu, r, off, 8, v as u64);
+            }
+            _ => panic!("write_parameter_int unhandled type {:?} at {:06X}", p, self.get_address()),
+        }
+    }
+
+    fn debug_write_u8(&self, r: R, off: u32, data: u8) {
         if !DEBUG_PARAMS_TOUCHING_STACK {
             return;
         }
-        let pos = MemoryAddress::RealSegmentOffset(seg, off).value() as isize;
+        let seg = self.get_r16(r);
+        let pos = self.segment_base(r).wrapping_add(off) as isize;
         let stack = MemoryAddress::RealSegmentOffset(self.get_r16(R::SS), self.get_r16(R::SP));
         let code = MemoryAddress::RealSegmentOffset(self.get_r16(R::CS), self.get_r16(R::IP));
         let dist = (pos - stack.value() as isize).abs();
@@ -497,11 +1684,12 @@ impl CPU {
         }
     }
 
-    fn debug_write_u16(&self, seg: u16, off: u16, data: u16) {
+    fn debug_write_u16(&self, r: R, off: u32, data: u16) {
         if !DEBUG_PARAMS_TOUCHING_STACK {
             return;
         }
-        let pos = MemoryAddress::RealSegmentOffset(seg, off).value() as isize;
+        let seg = self.get_r16(r);
+        let pos = self.segment_base(r).wrapping_add(off) as isize;
         let stack = MemoryAddress::RealSegmentOffset(self.get_r16(R::SS), self.get_r16(R::SP));
         let code = MemoryAddress::RealSegmentOffset(self.get_r16(R::CS), self.get_r16(R::IP));
         let dist = (pos - stack.value() as isize).abs();
@@ -511,11 +1699,12 @@ impl CPU {
         }
     }
 
-    fn debug_write_u32(&self, seg: u16, off: u16, data: u32) {
+    fn debug_write_u32(&self, r: R, off: u32, data: u32) {
         if !DEBUG_PARAMS_TOUCHING_STACK {
             return;
         }
-        let pos = MemoryAddress::RealSegmentOffset(seg, off).value() as isize;
+        let seg = self.get_r16(r);
+        let pos = self.segment_base(r).wrapping_add(off) as isize;
         let stack = MemoryAddress::RealSegmentOffset(self.get_r16(R::SS), self.get_r16(R::SP));
         let code = MemoryAddress::RealSegmentOffset(self.get_r16(R::CS), self.get_r16(R::IP));
         let dist = (pos - stack.value() as isize).abs();
@@ -549,21 +1738,27 @@ impl CPU {
             AMode::EBP => self.get_r32(R::EBP) as usize,
             AMode::ESI => self.get_r32(R::ESI) as usize,
             AMode::EDI => self.get_r32(R::EDI) as usize,
+
+            AMode::SIB(base, index, scale) => {
+                self.get_r32(base).wrapping_add(self.get_r32(index).wrapping_mul(u32::from(scale))) as usize
+            }
+            AMode::SIBIndex(index, scale) => self.get_r32(index).wrapping_mul(u32::from(scale)) as usize,
+            AMode::Disp32 => 0,
         }
     }
 
     /// used by aaa, aas
     pub fn adjb(&mut self, param1: i8, param2: i8) {
-        if self.regs.flags.adjust || (self.get_r8(R::AL) & 0xf) > 9 {
+        if self.regs.flags.adjust() || (self.get_r8(R::AL) & 0xf) > 9 {
             let al = (i16::from(self.get_r8(R::AL)) + i16::from(param1)) as u8;
             let ah = (i16::from(self.get_r8(R::AH)) + i16::from(param2)) as u8;
             self.set_r8(R::AL, al);
             self.set_r8(R::AH, ah);
-            self.regs.flags.adjust = true;
-            self.regs.flags.carry = true;
+            self.regs.flags.set_adjust(true);
+            self.regs.flags.set_carry(true);
         } else {
-            self.regs.flags.adjust = false;
-            self.regs.flags.carry = false;
+            self.regs.flags.set_adjust(false);
+            self.regs.flags.set_carry(false);
         }
         let al = self.get_r8(R::AL);
         self.set_r8(R::AL, al & 0x0F);
@@ -572,27 +1767,27 @@ impl CPU {
     /// used by daa, das
     pub fn adj4(&mut self, param1: i16, param2: i16) {
         let mut al = self.get_r8(R::AL);
-        if ((al & 0x0F) > 0x09) || self.regs.flags.adjust {
-            if (al > 0x99) || self.regs.flags.carry {
+        if ((al & 0x0F) > 0x09) || self.regs.flags.adjust() {
+            if (al > 0x99) || self.regs.flags.carry() {
                 al = (i16::from(al) + param2) as u8;
-                self.regs.flags.carry = true;
+                self.regs.flags.set_carry(true);
             } else {
-                self.regs.flags.carry = false;
+                self.regs.flags.set_carry(false);
             }
             al = (i16::from(al) + param1) as u8;
-            self.regs.flags.adjust = true;
+            self.regs.flags.set_adjust(true);
         } else {
-            if (al > 0x99) || self.regs.flags.carry {
+            if (al > 0x99) || self.regs.flags.carry() {
                 al = (i16::from(al) + param2) as u8;
-                self.regs.flags.carry = true;
+                self.regs.flags.set_carry(true);
             } else {
-                self.regs.flags.carry = false;
+                self.regs.flags.set_carry(false);
             }
-            self.regs.flags.adjust = false;
+            self.regs.flags.set_adjust(false);
         }
         self.set_r8(R::AL, al);
-        self.regs.flags.sign = al & 0x80 != 0;
-        self.regs.flags.zero = al == 0;
-        self.regs.flags.set_parity(al as usize);
+        self.regs.flags.set_sign(al & 0x80 != 0);
+        self.regs.flags.set_zero(al == 0);
+        self.regs.flags.set_parity_u8(al as usize);
     }
 }
@@ -0,0 +1,165 @@
+use std::fmt;
+
+/// CPUID leaf 1 feature flags, reported in EDX
+pub const CPUID_FPU: u32 = 0x0000_0001;
+pub const CPUID_VME: u32 = 0x0000_0002;
+pub const CPUID_DE: u32 = 0x0000_0004;
+
+/// The CPU generation being emulated. Affects decoding and the behaviour used by
+/// CPU detection routines (PUSH SP, FLAGS bits 12-15, shift count masking, POP CS)
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum CpuModel {
+    I8086,
+    I80186,
+    I80286,
+    I80386,
+    I80486,
+}
+
+impl Default for CpuModel {
+    fn default() -> Self {
+        CpuModel::I80386
+    }
+}
+
+impl fmt::Display for CpuModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            CpuModel::I8086 => "8086",
+            CpuModel::I80186 => "80186",
+            CpuModel::I80286 => "80286",
+            CpuModel::I80386 => "80386",
+            CpuModel::I80486 => "80486",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl CpuModel {
+    /// parses a model name such as "8086", "286" or "80386"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim_start_matches("80") {
+            "86" => Some(CpuModel::I8086),
+            "186" => Some(CpuModel::I80186),
+            "286" => Some(CpuModel::I80286),
+            "386" => Some(CpuModel::I80386),
+            "486" => Some(CpuModel::I80486),
+            _ => None,
+        }
+    }
+
+    /// the clock frequency of a typical system with this cpu
+    pub fn clock_hz(self) -> usize {
+        match self {
+            CpuModel::I8086 => 5_000_000,   // Intel 8086: 0.330 MIPS at 5.000 MHz
+            CpuModel::I80186 => 8_000_000,
+            CpuModel::I80286 => 12_000_000,
+            CpuModel::I80386 => 33_000_000,
+            CpuModel::I80486 => 66_000_000,
+        }
+    }
+
+    /// 8086 and 80186 pushes the decremented value of SP with PUSH SP, later models the original value
+    pub fn pushes_decremented_sp(self) -> bool {
+        self < CpuModel::I80286
+    }
+
+    /// 80186 and later masks shift and rotate counts to 5 bits, the 8086 uses all 8 bits of the count
+    pub fn shift_count_mask(self) -> usize {
+        if self == CpuModel::I8086 {
+            0xFF
+        } else {
+            0x1F
+        }
+    }
+
+    /// returns the FLAGS register as seen by PUSHF. bits 12-15 are always set on 8086 and 80186.
+    /// on 80286 they are always clear in real mode, and bit 15 is always clear on later models
+    pub fn flags_image(self, flags: u16, protected_mode: bool) -> u16 {
+        match self {
+            CpuModel::I8086 | CpuModel::I80186 => flags | 0xF000,
+            CpuModel::I80286 if !protected_mode => flags & 0x0FFF,
+            _ => flags & 0x7FFF,
+        }
+    }
+
+    /// returns true if the IOPL and NT bits of FLAGS can be changed by POPF in real mode
+    pub fn has_real_mode_iopl(self) -> bool {
+        self >= CpuModel::I80386
+    }
+
+    /// returns true if the EFLAGS AC bit (18) can be changed, which is used to tell a 80486 from a 80386
+    pub fn has_alignment_check(self) -> bool {
+        self >= CpuModel::I80486
+    }
+
+    /// returns the identification reported by CPUID, or None if the model lacks the instruction.
+    /// the 80486 reports a 486 DX4, which was the first 486 to implement CPUID
+    pub fn identity(self) -> Option<CpuIdentity> {
+        match self {
+            CpuModel::I80486 => Some(CpuIdentity {
+                vendor: "GenuineIntel".to_string(),
+                family: 4,
+                model: 8,
+                stepping: 0,
+                features: CPUID_FPU,
+            }),
+            _ => None,
+        }
+    }
+
+    /// returns the first model implementing the one-byte opcode `b`
+    pub fn first_with_opcode(b: u8) -> Self {
+        match b {
+            0x60..=0x62 | 0x68..=0x6F | 0xC0 | 0xC1 | 0xC8 | 0xC9 => CpuModel::I80186,
+            0x0F | 0x63 => CpuModel::I80286,
+            0x64..=0x67 => CpuModel::I80386,
+            _ => CpuModel::I8086,
+        }
+    }
+
+    /// returns the first model implementing the two-byte opcode 0F `b`
+    pub fn first_with_opcode_0f(b: u8) -> Self {
+        match b {
+            0x00..=0x06 => CpuModel::I80286,
+            0x08 | 0x09 | 0xA2 | 0xB0 | 0xB1 | 0xC0 | 0xC1 | 0xC8..=0xCF => CpuModel::I80486,
+            _ => CpuModel::I80386,
+        }
+    }
+}
+
+/// the processor identification returned by CPUID
+#[derive(Clone, Debug, PartialEq)]
+pub struct CpuIdentity {
+    /// vendor string such as "GenuineIntel", padded or truncated to 12 characters
+    pub vendor: String,
+
+    pub family: u8,
+    pub model: u8,
+    pub stepping: u8,
+
+    /// the CPUID_* feature flags
+    pub features: u32,
+}
+
+impl CpuIdentity {
+    /// returns EAX, EBX, ECX and EDX for CPUID leaf `eax`. unsupported leafs returns zeros
+    pub fn leaf(&self, eax: u32) -> (u32, u32, u32, u32) {
+        match eax {
+            0 => {
+                let mut vendor = [b' '; 12];
+                for (dst, src) in vendor.iter_mut().zip(self.vendor.bytes()) {
+                    *dst = src;
+                }
+                let part = |i: usize| u32::from_le_bytes([vendor[i], vendor[i + 1], vendor[i + 2], vendor[i + 3]]);
+                // the vendor string is returned in EBX, EDX, ECX order
+                (1, part(0), part(8), part(4))
+            }
+            1 => {
+                let signature = u32::from(self.family & 0xF) << 8 | u32::from(self.model & 0xF) << 4 | u32::from(self.stepping & 0xF);
+                (signature, 0, 0, self.features)
+            }
+            _ => (0, 0, 0, 0),
+        }
+    }
+}
@@ -27,10 +27,24 @@ pub enum Op {
     /// Bit Scan Forward
     Bsf,
 
+    /// Bit Scan Reverse
+    Bsr,
+
+    /// Byte Swap
+    Bswap,
+
     /// Bit Test
     Bt,
 
+    /// Bit Test and Complement
+    Btc,
+
+    /// Bit Test and Reset
+    Btr,
+
+    /// Bit Test and Set
     Bts,
+
     CallNear, CallFar,
 
     /// Convert Byte to Word
@@ -45,15 +59,27 @@ pub enum Op {
     /// Clear Interrupt Flag
     Cli,
 
+    /// Clear Task-Switched Flag in CR0
+    Clts,
+
     /// Complement Carry Flag
     Cmc,
 
     Cmp8, Cmp16, Cmp32,
-    Cmpsb, Cmpsw,
+    Cmpsb, Cmpsw, Cmpsd,
+
+    /// Compare and Exchange
+    Cmpxchg8, Cmpxchg16, Cmpxchg32,
+
+    /// CPU Identification
+    Cpuid,
 
     /// Convert Word to Doubleword
     Cwd16, Cwde32,
 
+    /// Convert Doubleword to Quadword
+    Cdq32,
+
     /// Decimal Adjust AL after Addition
     Daa,
 
@@ -75,12 +101,20 @@ pub enum Op {
     Inc8, Inc16, Inc32,
 
     /// Input from Port to String
-    Insb, Insw,
+    Insb, Insw, Insd,
 
     Int,
     Into,
+
+    /// Invalidate Internal Caches
+    Invd,
+
+    /// 16 bit interrupt return
     Iret,
 
+    /// 32 bit interrupt return, can return to virtual 8086 mode
+    Iretd,
+
     /// Jump if above (CF=0 and ZF=0).    (alias: jnbe)
     Ja,
 
@@ -145,13 +179,31 @@ pub enum Op {
 
     /// Load Effective Address
     /// Computes the effective address of the source operand and stores it in the destination operand.
-    Lea16,
+    Lea16, Lea32,
 
     Leave,
 
     /// Load ES:r16 with far pointer from memory.
     Les,
 
+    /// Load FS:r16 with far pointer from memory.
+    Lfs,
+
+    /// Load Global Descriptor Table Register
+    Lgdt,
+
+    /// Load GS:r16 with far pointer from memory.
+    Lgs,
+
+    /// Load Interrupt Descriptor Table Register
+    Lidt,
+
+    /// Load Local Descriptor Table Register
+    Lldt,
+
+    /// Load Machine Status Word
+    Lmsw,
+
     /// Load byte at address DS:(E)SI into AL.
     Lodsb,
 
@@ -170,6 +222,15 @@ pub enum Op {
     /// Decrement count (cx); jump short if count ≠ 0 and ZF = 0.
     Loopne,
 
+    /// Load Segment Limit
+    Lsl16,
+
+    /// Load SS:r16 with far pointer from memory.
+    Lss,
+
+    /// Load Task Register
+    Ltr,
+
     Mov8, Mov16, Mov32,
     Movsb, Movsw, Movsd,
 
@@ -185,7 +246,7 @@ pub enum Op {
     Not8, Not16, Not32,
     Or8, Or16, Or32,
     Out8, Out16,
-    Outsb, Outsw,
+    Outsb, Outsw, Outsd,
     Pop16, Pop32,
 
     /// Pop DI, SI, BP, BX, DX, CX, and AX.
@@ -197,6 +258,9 @@ pub enum Op {
     /// Pop top of stack into lower 16 bits of EFLAGS.
     Popf,
 
+    /// Pop top of stack into EFLAGS.
+    Popfd,
+
     Push16, Push32,
 
     /// Push AX, CX, DX, BX, original SP, BP, SI, and DI.
@@ -208,6 +272,9 @@ pub enum Op {
     /// push 16 bit FLAGS register onto stack
     Pushf,
 
+    /// push 32 bit EFLAGS register onto stack
+    Pushfd,
+
     Rcl8, Rcl16, Rcl32,
 
     /// Rotate 9 bits (CF, r/m8) right
@@ -238,7 +305,11 @@ pub enum Op {
     /// Integer Subtraction with Borrow
     Sbb8, Sbb16, Sbb32,
 
-    Scasb, Scasw,
+    Scasb, Scasw, Scasd,
+
+    /// seta: Set byte if above (CF=0 and ZF=0).
+    /// alias setnbe: Set byte if not below or equal (CF=0 and ZF=0).
+    Seta,
 
     /// setc: Set byte if carry (CF=1).
     /// alias setb: Set byte if below (CF=1).
@@ -248,10 +319,57 @@ pub enum Op {
     /// alias setnle: Set byte if not less or equal (ZF=0 and SF=OF).
     Setg,
 
+    /// setl: Set byte if less (SF ≠ OF).
+    /// alias setnge: Set byte if not greater or equal (SF ≠ OF).
+    Setl,
+
+    /// setna: Set byte if not above (CF=1 or ZF=1).
+    /// alias setbe: Set byte if below or equal (CF=1 or ZF=1).
+    Setna,
+
+    /// setnc: Set byte if not carry (CF=0).
+    /// alias setae, setnb: Set byte if above or equal (CF=0).
+    Setnc,
+
+    /// setng: Set byte if not greater (ZF=1 or SF ≠ OF).
+    /// alias setle: Set byte if less or equal (ZF=1 or SF ≠ OF).
+    Setng,
+
+    /// setnl: Set byte if not less (SF=OF).
+    /// alias setge: Set byte if greater or equal (SF=OF).
+    Setnl,
+
+    /// setno: Set byte if not overflow (OF=0).
+    Setno,
+
+    /// setns: Set byte if not sign (SF=0).
+    Setns,
+
     /// setnz: Set byte if not zero (ZF=0).
     /// alias setne: Set byte if not equal (ZF=0).
     Setnz,
 
+    /// seto: Set byte if overflow (OF=1).
+    Seto,
+
+    /// setpe: Set byte if parity even (PF=1).
+    /// alias setp
+    Setpe,
+
+    /// setpo: Set byte if parity odd (PF=0).
+    /// alias setnp
+    Setpo,
+
+    /// sets: Set byte if sign (SF=1).
+    Sets,
+
+    /// setz: Set byte if zero (ZF=1).
+    /// alias sete: Set byte if equal (ZF=1).
+    Setz,
+
+    /// Store Global Descriptor Table Register
+    Sgdt,
+
     /// Multiply `dst` by 2, `src` times (alias sal)
     Shl8,
     /// Multiply `dst` by 2, `src` times (alias sal)
@@ -267,8 +385,15 @@ pub enum Op {
     /// Double Precision Shift Right
     Shrd,
 
+    /// Store Interrupt Descriptor Table Register
+    Sidt,
+
+    /// Store Local Descriptor Table Register
     Sldt,
 
+    /// Store Machine Status Word
+    Smsw,
+
     // Set Carry Flag
     Stc,
 
@@ -279,9 +404,25 @@ pub enum Op {
     Sti,
 
     Stosb, Stosw, Stosd,
+
+    /// Store Task Register
+    Str,
+
     Sub8, Sub16, Sub32,
     Test8, Test16, Test32,
 
+    /// Verify a Segment for Reading
+    Verr,
+
+    /// Verify a Segment for Writing
+    Verw,
+
+    /// Write Back and Invalidate Cache
+    Wbinvd,
+
+    /// Exchange and Add
+    Xadd8, Xadd16, Xadd32,
+
     /// Exchange Register/Memory with Register
     Xchg8, Xchg16, Xchg32,
 
@@ -289,26 +430,41 @@ pub enum Op {
 
     Xor8, Xor16, Xor32,
 
+    /// (FPU) Compute 2^x - 1
+    F2xm1,
+
     /// (FPU) Absolute Value
     Fabs,
 
     /// (FPU) Add
-    Fadd, Faddp,
+    Fadd, Faddp, Fiadd,
+
+    /// (FPU) Load Binary Coded Decimal
+    Fbld,
+
+    /// (FPU) Store BCD Integer and Pop
+    Fbstp,
 
     /// (FPU) Change Sign
     Fchs,
 
+    /// (FPU) Clear Exceptions
+    Fclex,
+
     /// (FPU) Compare Floating Point Values
-    Fcom, Fcomp,
+    Fcom, Fcomp, Fcompp,
 
     /// (FPU) Cosine
     Fcos,
 
+    /// (FPU) Decrement Stack-Top Pointer
+    Fdecstp,
+
     /// (FPU) Divide
     Fdiv, Fdivp, Fidiv,
 
     /// (FPU) Reverse Divide
-    Fdivr,
+    Fdivr, Fdivrp, Fidivr,
 
     /// (FPU) Free Floating-Point Register
     Ffree,
@@ -319,6 +475,9 @@ pub enum Op {
     /// (FPU) Load Integer
     Fild,
 
+    /// (FPU) Increment Stack-Top Pointer
+    Fincstp,
+
     /// (FPU) Initialize Floating-Point Unit
     Finit,
 
@@ -340,6 +499,12 @@ pub enum Op {
     /// (FPU) Load Constant log₂e
     Fldl2e,
 
+    /// (FPU) Load Constant log₁₀2
+    Fldlg2,
+
+    /// (FPU) Load Constant logₑ2
+    Fldln2,
+
     /// (FPU) Load Constant +0.0
     Fldz,
 
@@ -349,22 +514,46 @@ pub enum Op {
     /// (FPU) Load x87 FPU Control Word
     Fldcw,
 
+    /// (FPU) Load x87 FPU Environment
+    Fldenv,
+
     /// (FPU) Multiply
-    Fmul, Fimul,
+    Fmul, Fmulp, Fimul,
+
+    /// (FPU) No Operation
+    Fnop,
 
     /// (FPU) Partial Arctangent
     Fpatan,
 
+    /// (FPU) Partial Remainder
+    Fprem,
+
+    /// (FPU) IEEE Partial Remainder
+    Fprem1,
+
+    /// (FPU) Partial Tangent
+    Fptan,
+
     /// (FPU) Round to Integer
     Frndint,
 
+    /// (FPU) Restore x87 FPU State
+    Frstor,
+
+    /// (FPU) Store x87 FPU State
+    Fnsave,
+
+    /// (FPU) Scale
+    Fscale,
+
     /// (FPU) Sine
     Fsin,
 
     /// (FPU) Sine and Cosine
     Fsincos,
 
-    /// (FPU)
+    /// (FPU) Square Root
     Fsqrt,
 
     /// (FPU) Store Floating Point Value
@@ -376,21 +565,39 @@ pub enum Op {
     /// (FPU) Store x87 FPU Control Word
     Fnstcw,
 
+    /// (FPU) Store x87 FPU Environment
+    Fnstenv,
+
     /// (FPU) Subtract
-    Fsub, Fsubp,
+    Fsub, Fsubp, Fisub,
 
     /// (FPU) Reverse Subtract
-    Fsubr, Fsubrp,
+    Fsubr, Fsubrp, Fisubr,
 
     /// (FPU) Test
     Ftst,
 
+    /// (FPU) Unordered Compare Floating Point Values
+    Fucom, Fucomp, Fucompp,
+
     /// (FPU) Wait
     Fwait,
 
+    /// (FPU) Examine
+    Fxam,
+
     /// (FPU) Exchange Register Contents
     Fxch,
 
+    /// (FPU) Extract Exponent and Significand
+    Fxtract,
+
+    /// (FPU) Compute y * log₂x
+    Fyl2x,
+
+    /// (FPU) Compute y * log₂(x + 1)
+    Fyl2xp1,
+
     /// Initial state
     Uninitialized,
 
@@ -40,27 +40,47 @@ pub enum Parameter {
     Reg32(R),
     /// 80-bit fpu register
     FPR80(R),
+    /// 32-bit control register
+    CReg32(R),
+    /// 32-bit debug register
+    DReg32(R),
 
     Imm8(u8),                           // byte 0x80
     ImmS8(i8),                          // byte +0x3f
     Imm16(u16),                         // word 0x8000
     Imm32(u32),                         // dword 0x8000_0000
     Ptr16Imm(u16, u16),                 // jmp far u16:u16
+    Ptr16Imm32(u16, u32),               // jmp far u16:u32
 
     Ptr8(Segment, u16),                 // byte [u16], like "byte [0x4040]"
     Ptr8Amode(Segment, AMode),          // byte [amode], like "byte [bx]"
     Ptr8AmodeS8(Segment, AMode, i8),    // byte [amode+s8], like "byte [bp-0x20]"
     Ptr8AmodeS16(Segment, AMode, i16),  // byte [amode+s16], like "byte [bp-0x2020]"
+    Ptr8AmodeS32(Segment, AMode, i32),  // byte [amode+s32], like "byte [ebp+eax*4-0x20202020]"
 
     Ptr16(Segment, u16),                // word [u16], like "word [0x4040]"
     Ptr16Amode(Segment, AMode),         // word [amode], like "word [bx]"
     Ptr16AmodeS8(Segment, AMode, i8),   // word [amode+s8], like "word [bp-0x20]"
     Ptr16AmodeS16(Segment, AMode, i16), // word [amode+s16], like "word [bp-0x2020]"
+    Ptr16AmodeS32(Segment, AMode, i32), // word [amode+s32], like "word [ebp+eax*4-0x20202020]"
 
     Ptr32(Segment, u16),                // dword [u16], like "dword [0x4040]"
     Ptr32Amode(Segment, AMode),         // dword [amode], like "dword [bx]"
     Ptr32AmodeS8(Segment, AMode, i8),   // dword [amode+s8], like "dword [bp-0x20]"
     Ptr32AmodeS16(Segment, AMode, i16), // dword [amode+s16], like "dword [bp-0x2020]"
+    Ptr32AmodeS32(Segment, AMode, i32), // dword [amode+s32], like "dword [ebp+eax*4-0x20202020]"
+
+    Ptr64(Segment, u16),                // qword [u16], like "qword [0x4040]"
+    Ptr64Amode(Segment, AMode),         // qword [amode], like "qword [bx]"
+    Ptr64AmodeS8(Segment, AMode, i8),   // qword [amode+s8], like "qword [bp-0x20]"
+    Ptr64AmodeS16(Segment, AMode, i16), // qword [amode+s16], like "qword [bp-0x2020]"
+    Ptr64AmodeS32(Segment, AMode, i32), // qword [amode+s32], like "qword [ebp+eax*4-0x20202020]"
+
+    Ptr80(Segment, u16),                // tword [u16], like "tword [0x4040]"
+    Ptr80Amode(Segment, AMode),         // tword [amode], like "tword [bx]"
+    Ptr80AmodeS8(Segment, AMode, i8),   // tword [amode+s8], like "tword [bp-0x20]"
+    Ptr80AmodeS16(Segment, AMode, i16), // tword [amode+s16], like "tword [bp-0x2020]"
+    Ptr80AmodeS32(Segment, AMode, i32), // tword [amode+s32], like "tword [ebp+eax*4-0x20202020]"
     None,
 }
 
@@ -71,7 +91,9 @@ impl fmt::Display for Parameter {
             Parameter::Reg16(ref r) |
             Parameter::Reg32(ref r) |
             Parameter::SReg16(ref r) |
-            Parameter::FPR80(ref r) => write!(f, "{}", r),
+            Parameter::FPR80(ref r) |
+            Parameter::CReg32(ref r) |
+            Parameter::DReg32(ref r) => write!(f, "{}", r),
 
             Parameter::Imm8(imm) => write!(f, "0x{:02X}", imm),
             Parameter::Imm16(imm) => write!(f, "0x{:04X}", imm),
@@ -87,6 +109,7 @@ impl fmt::Display for Parameter {
                 }
             ),
             Parameter::Ptr16Imm(seg, v) => write!(f, "{:04X}:{:04X}", seg, v),
+            Parameter::Ptr16Imm32(seg, v) => write!(f, "{:04X}:{:08X}", seg, v),
             Parameter::Ptr8(seg, v) => write!(f, "byte [{}:0x{:04X}]", seg, v),
             Parameter::Ptr8Amode(seg, ref amode) => write!(f, "byte [{}:{}]", seg, amode),
             Parameter::Ptr8AmodeS8(seg, ref amode, imm) => write!(
@@ -113,6 +136,7 @@ impl fmt::Display for Parameter {
                     imm
                 }
             ),
+            Parameter::Ptr8AmodeS32(seg, ref amode, imm) => write!(f, "byte [{}:{}]", seg, fmt_disp32(amode, imm)),
             Parameter::Ptr16(seg, v) => write!(f, "word [{}:0x{:04X}]", seg, v),
             Parameter::Ptr16Amode(seg, ref amode) => write!(f, "word [{}:{}]", seg, amode),
             Parameter::Ptr16AmodeS8(seg, ref amode, imm) => write!(
@@ -139,6 +163,7 @@ impl fmt::Display for Parameter {
                     imm
                 }
             ),
+            Parameter::Ptr16AmodeS32(seg, ref amode, imm) => write!(f, "word [{}:{}]", seg, fmt_disp32(amode, imm)),
             Parameter::Ptr32(seg, v) => write!(f, "dword [{}:0x{:04X}]", seg, v),
             Parameter::Ptr32Amode(seg, ref amode) => write!(f, "dword [{}:{}]", seg, amode),
             Parameter::Ptr32AmodeS8(seg, ref amode, imm) => write!(
@@ -165,6 +190,61 @@ impl fmt::Display for Parameter {
                     imm
                 }
             ),
+            Parameter::Ptr32AmodeS32(seg, ref amode, imm) => write!(f, "dword [{}:{}]", seg, fmt_disp32(amode, imm)),
+            Parameter::Ptr64(seg, v) => write!(f, "qword [{}:0x{:04X}]", seg, v),
+            Parameter::Ptr64Amode(seg, ref amode) => write!(f, "qword [{}:{}]", seg, amode),
+            Parameter::Ptr64AmodeS8(seg, ref amode, imm) => write!(
+                f,
+                "qword [{}:{}{}0x{:02X}]",
+                seg,
+                amode,
+                if imm < 0 { "-" } else { "+" },
+                if imm < 0 {
+                    (Wrapping(0) - Wrapping(imm)).0
+                } else {
+                    imm
+                }
+            ),
+            Parameter::Ptr64AmodeS16(seg, ref amode, imm) => write!(
+                f,
+                "qword [{}:{}{}0x{:04X}]",
+                seg,
+                amode,
+                if imm < 0 { "-" } else { "+" },
+                if imm < 0 {
+                    (Wrapping(0) - Wrapping(imm)).0
+                } else {
+                    imm
+                }
+            ),
+            Parameter::Ptr64AmodeS32(seg, ref amode, imm) => write!(f, "qword [{}:{}]", seg, fmt_disp32(amode, imm)),
+            Parameter::Ptr80(seg, v) => write!(f, "tword [{}:0x{:04X}]", seg, v),
+            Parameter::Ptr80Amode(seg, ref amode) => write!(f, "tword [{}:{}]", seg, amode),
+            Parameter::Ptr80AmodeS8(seg, ref amode, imm) => write!(
+                f,
+                "tword [{}:{}{}0x{:02X}]",
+                seg,
+                amode,
+                if imm < 0 { "-" } else { "+" },
+                if imm < 0 {
+                    (Wrapping(0) - Wrapping(imm)).0
+                } else {
+                    imm
+                }
+            ),
+            Parameter::Ptr80AmodeS16(seg, ref amode, imm) => write!(
+                f,
+                "tword [{}:{}{}0x{:04X}]",
+                seg,
+                amode,
+                if imm < 0 { "-" } else { "+" },
+                if imm < 0 {
+                    (Wrapping(0) - Wrapping(imm)).0
+                } else {
+                    imm
+                }
+            ),
+            Parameter::Ptr80AmodeS32(seg, ref amode, imm) => write!(f, "tword [{}:{}]", seg, fmt_disp32(amode, imm)),
             Parameter::None => write!(f, ""),
         }
     }
@@ -186,12 +266,15 @@ impl Parameter {
             Parameter::Ptr8(_, _) |
             Parameter::Ptr16(_, _) |
             Parameter::Ptr16Imm(_, _) |
+            Parameter::Ptr16Imm32(_, _) |
             Parameter::Ptr8Amode(_, _) |
             Parameter::Ptr8AmodeS8(_, _, _) |
             Parameter::Ptr8AmodeS16(_, _, _) |
             Parameter::Ptr16Amode(_, _) |
             Parameter::Ptr16AmodeS8(_, _, _) |
-            Parameter::Ptr16AmodeS16(_, _, _) => true,
+            Parameter::Ptr16AmodeS16(_, _, _) |
+            Parameter::Ptr8AmodeS32(_, _, _) |
+            Parameter::Ptr16AmodeS32(_, _, _) => true,
             _ => false,
         }
     }
@@ -206,7 +289,51 @@ impl Parameter {
         }
     }
 
+    /// returns true for 32-bit register and dword memory operands
+    pub fn is_32bit(&self) -> bool {
+        match *self {
+            Parameter::Reg32(_) |
+            Parameter::Ptr32(_, _) |
+            Parameter::Ptr32Amode(_, _) |
+            Parameter::Ptr32AmodeS8(_, _, _) |
+            Parameter::Ptr32AmodeS16(_, _, _) |
+            Parameter::Ptr32AmodeS32(_, _, _) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_control_register(&self) -> bool {
+        match *self {
+            Parameter::CReg32(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_debug_register(&self) -> bool {
+        match *self {
+            Parameter::DReg32(_) => true,
+            _ => false,
+        }
+    }
+
     pub fn is_none(&self) -> bool {
         *self == Parameter::None
     }
 }
+
+/// formats a addressing mode with a 32-bit displacement, like "ebx+0x00001000"
+fn fmt_disp32(amode: &AMode, imm: i32) -> String {
+    if *amode == AMode::Disp32 {
+        return format!("0x{:08X}", imm as u32);
+    }
+    format!(
+        "{}{}0x{:08X}",
+        amode,
+        if imm < 0 { "-" } else { "+" },
+        if imm < 0 {
+            (Wrapping(0) - Wrapping(imm)).0
+        } else {
+            imm
+        }
+    )
+}
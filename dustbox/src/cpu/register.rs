@@ -8,12 +8,6 @@ use crate::cpu::decoder::AddressSize;
 #[path = "./register_test.rs"]
 mod register_test;
 
-/// A 80-bit FPU register
-/// The FPU has eight 80-bit registers (st0, st1 etc)
-pub struct FPR80 {
-    val: u128,
-}
-
 /// A 32-bit general purpose register (AL->AX->EAX)
 #[derive(Copy, Clone, Debug, Default)]
 pub struct GPR {
@@ -58,6 +52,8 @@ pub enum R {
     IP,                                     // 16-bit ip
     EAX, ECX, EDX, EBX, ESP, EBP, ESI, EDI, // 32-bit gpr
     ST0, ST1, ST2, ST3, ST4, ST5, ST6, ST7, // 80-bit fpu registers
+    CR0, CR2, CR3,                          // 32-bit control registers
+    DR0, DR1, DR2, DR3, DR6, DR7,           // 32-bit debug registers
 }
 
 impl fmt::Display for R {
@@ -105,6 +101,17 @@ impl fmt::Display for R {
             R::ST5 => "st5",
             R::ST6 => "st6",
             R::ST7 => "st7",
+
+            R::CR0 => "cr0",
+            R::CR2 => "cr2",
+            R::CR3 => "cr3",
+
+            R::DR0 => "dr0",
+            R::DR1 => "dr1",
+            R::DR2 => "dr2",
+            R::DR3 => "dr3",
+            R::DR6 => "dr6",
+            R::DR7 => "dr7",
         };
         write!(f, "{}", s)
     }
@@ -113,14 +120,14 @@ impl fmt::Display for R {
 impl R {
     pub fn index(self) -> usize {
           match self {
-            R::AL | R::AX | R::EAX | R::ES => 0,
-            R::CL | R::CX | R::ECX | R::CS => 1,
-            R::DL | R::DX | R::EDX | R::SS => 2,
-            R::BL | R::BX | R::EBX | R::DS => 3,
-            R::AH | R::SP | R::ESP | R::FS => 4,
-            R::CH | R::BP | R::EBP | R::GS => 5,
-            R::DH | R::SI | R::ESI => 6,
-            R::BH | R::DI | R::EDI => 7,
+            R::AL | R::AX | R::EAX | R::ES | R::ST0 | R::CR0 | R::DR0 => 0,
+            R::CL | R::CX | R::ECX | R::CS | R::ST1 | R::DR1 => 1,
+            R::DL | R::DX | R::EDX | R::SS | R::ST2 | R::CR2 | R::DR2 => 2,
+            R::BL | R::BX | R::EBX | R::DS | R::ST3 | R::CR3 | R::DR3 => 3,
+            R::AH | R::SP | R::ESP | R::FS | R::ST4 => 4,
+            R::CH | R::BP | R::EBP | R::GS | R::ST5 => 5,
+            R::DH | R::SI | R::ESI | R::ST6 | R::DR6 => 6,
+            R::BH | R::DI | R::EDI | R::ST7 | R::DR7 => 7,
             _ => unreachable!(),
         }
     }
@@ -214,6 +221,27 @@ pub fn fpr(v: u8) -> R {
     }
 }
 
+pub fn cr(v: u8) -> Option<R> {
+    match v {
+        0 => Some(R::CR0),
+        2 => Some(R::CR2),
+        3 => Some(R::CR3),
+        _ => None,
+    }
+}
+
+/// DR4 and DR5 are aliases of DR6 and DR7 on the 386 and 486
+pub fn dr(v: u8) -> R {
+    match v {
+        0 => R::DR0,
+        1 => R::DR1,
+        2 => R::DR2,
+        3 => R::DR3,
+        4 | 6 => R::DR6,
+        5 | 7 => R::DR7,
+        _ => unreachable!(),
+    }
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum AMode {
@@ -222,6 +250,13 @@ pub enum AMode {
 
     // 32-bit addressing modes
     EAX, ECX, EDX, EBX, ESP, EBP, ESI, EDI,
+
+    /// 32-bit base + index * scale, from a SIB byte
+    SIB(R, R, u8),
+    /// 32-bit index * scale without a base register, from a SIB byte
+    SIBIndex(R, u8),
+    /// no base or index register, only the 32-bit displacement
+    Disp32,
 }
 
 impl fmt::Display for AMode {
@@ -244,24 +279,48 @@ impl fmt::Display for AMode {
             AMode::EBP => "ebp",
             AMode::ESI => "esi",
             AMode::EDI => "edi",
+
+            AMode::SIB(base, index, 1) => return write!(f, "{}+{}", base, index),
+            AMode::SIB(base, index, scale) => return write!(f, "{}+{}*{}", base, index, scale),
+            AMode::SIBIndex(index, scale) => return write!(f, "{}*{}", index, scale),
+            AMode::Disp32 => "",
         };
         write!(f, "{}", s)
     }
 }
 
 impl AMode {
+   /// returns the ModRM rm value, which is 4 for addressing modes needing a SIB byte
    pub fn index(&self) -> usize {
         match *self {
             AMode::BXSI | AMode::EAX => 0,
             AMode::BXDI | AMode::ECX => 1,
             AMode::BPSI | AMode::EDX => 2,
             AMode::BPDI | AMode::EBX => 3,
-            AMode::SI | AMode::ESP => 4,
-            AMode::DI | AMode::EBP => 5,
+            AMode::SI | AMode::ESP | AMode::SIB(..) | AMode::SIBIndex(..) => 4,
+            AMode::DI | AMode::EBP | AMode::Disp32 => 5,
             AMode::BP | AMode::ESI => 6,
             AMode::BX | AMode::EDI => 7,
         }
     }
+
+    /// returns true for addressing modes only encodable with a 32-bit address size
+    pub fn is_32bit(&self) -> bool {
+        match *self {
+            AMode::BXSI | AMode::BXDI | AMode::BPSI | AMode::BPDI |
+            AMode::SI | AMode::DI | AMode::BP | AMode::BX => false,
+            _ => true,
+        }
+    }
+
+    /// returns true if the address is based on BP, EBP or ESP, which are addressed through SS by default
+    pub fn is_stack_based(&self) -> bool {
+        match *self {
+            AMode::BPSI | AMode::BPDI | AMode::BP | AMode::EBP | AMode::ESP => true,
+            AMode::SIB(base, ..) => base == R::EBP || base == R::ESP,
+            _ => false,
+        }
+    }
 }
 
 impl AddressSize {
@@ -299,7 +358,8 @@ impl AddressSize {
 
 #[derive(Clone, Default)]
 pub struct RegisterState {
-    pub ip: u16,
+    /// the instruction pointer (EIP). only the low 16 bits are used by 16-bit code segments
+    pub ip: u32,
     pub gpr: [GPR; 8 + 6 + 1],   // 8 general purpose registers, 6 segment registers, 1 ip
     pub sreg16: [u16; 6],        // segment registers
     pub flags: Flags,
@@ -350,7 +410,7 @@ impl RegisterState {
             R::DS => self.sreg16[3],
             R::FS => self.sreg16[4],
             R::GS => self.sreg16[5],
-            R::IP => self.ip,
+            R::IP => self.ip as u16,
             _ => unreachable!(),
         }
     }
@@ -0,0 +1,306 @@
+// Instruction timings, in clock cycles, for the emulated cpu models.
+// Values are taken from the Intel programmer's reference manuals and are approximate,
+// cases like odd address word access and wait states are not accounted for.
+
+use crate::cpu::{AMode, CpuModel, Instruction, Op, Parameter, RepeatMode, Segment};
+
+#[cfg(test)]
+#[path = "./timing_test.rs"]
+mod timing_test;
+
+/// clock cycles for 8086, 80186, 80286, 80386 and 80486
+type Cycles = [u16; 5];
+
+struct Timing {
+    /// register or immediate operands
+    reg: Cycles,
+    /// memory source operand (excluding effective address calculation on 8086)
+    mem: Cycles,
+    /// memory destination operand (read-modify-write or store)
+    rmw: Cycles,
+    /// added when a conditional branch is taken
+    taken: Cycles,
+}
+
+/// timing of a string instruction
+struct StringTiming {
+    /// without a REP prefix
+    single: Cycles,
+    /// fixed cost of a REP prefixed instruction
+    rep_setup: Cycles,
+    /// cost of each REP iteration
+    rep_iteration: Cycles,
+}
+
+fn t(reg: Cycles, mem: Cycles) -> Timing {
+    Timing { reg, mem, rmw: mem, taken: [0; 5] }
+}
+
+fn t_rmw(reg: Cycles, mem: Cycles, rmw: Cycles) -> Timing {
+    Timing { reg, mem, rmw, taken: [0; 5] }
+}
+
+fn t_branch(not_taken: Cycles, taken: Cycles) -> Timing {
+    Timing { reg: not_taken, mem: not_taken, rmw: not_taken, taken }
+}
+
+fn model_index(model: CpuModel) -> usize {
+    match model {
+        CpuModel::I8086 => 0,
+        CpuModel::I80186 => 1,
+        CpuModel::I80286 => 2,
+        CpuModel::I80386 => 3,
+        CpuModel::I80486 => 4,
+    }
+}
+
+/// returns the number of clock cycles used to execute `op` once on `model`.
+/// `taken` is true if a conditional branch was taken.
+/// for a string instruction with a REP prefix, this is the cost of one iteration
+pub fn instruction_cycles(model: CpuModel, op: &Instruction, taken: bool) -> usize {
+    let i = model_index(model);
+    if let Some(st) = string_timing(&op.command) {
+        return if op.repeat == RepeatMode::None {
+            st.single[i] as usize
+        } else {
+            st.rep_iteration[i] as usize
+        };
+    }
+
+    let timing = op_timing(&op.command);
+    let mut cycles = if is_memory(&op.params.dst) {
+        timing.rmw[i]
+    } else if is_memory(&op.params.src) {
+        timing.mem[i]
+    } else {
+        timing.reg[i]
+    } as usize;
+    if taken {
+        cycles += timing.taken[i] as usize;
+    }
+    cycles + effective_address_cycles(model, op)
+}
+
+/// returns the fixed setup cost of a REP prefixed string instruction, charged once
+pub fn rep_setup_cycles(model: CpuModel, op: &Instruction) -> usize {
+    if op.repeat == RepeatMode::None {
+        return 0;
+    }
+    match string_timing(&op.command) {
+        Some(st) => st.rep_setup[model_index(model)] as usize,
+        None => 0,
+    }
+}
+
+/// returns the effective address calculation cost of the memory operand of `op`.
+/// the 8086 calculates effective addresses separately, while later models mostly
+/// include it in the instruction timing
+fn effective_address_cycles(model: CpuModel, op: &Instruction) -> usize {
+    let p = if is_memory(&op.params.dst) {
+        &op.params.dst
+    } else if is_memory(&op.params.src) {
+        &op.params.src
+    } else {
+        return 0;
+    };
+    let (amode, displacement) = match p {
+        Parameter::Ptr8(_, _) | Parameter::Ptr16(_, _) | Parameter::Ptr32(_, _) |
+        Parameter::Ptr64(_, _) | Parameter::Ptr80(_, _) => (None, true),
+        Parameter::Ptr8Amode(_, amode) | Parameter::Ptr16Amode(_, amode) | Parameter::Ptr32Amode(_, amode) |
+        Parameter::Ptr64Amode(_, amode) | Parameter::Ptr80Amode(_, amode) => (Some(amode), false),
+        Parameter::Ptr8AmodeS8(_, amode, _) | Parameter::Ptr16AmodeS8(_, amode, _) | Parameter::Ptr32AmodeS8(_, amode, _) |
+        Parameter::Ptr64AmodeS8(_, amode, _) | Parameter::Ptr80AmodeS8(_, amode, _) |
+        Parameter::Ptr8AmodeS16(_, amode, _) | Parameter::Ptr16AmodeS16(_, amode, _) | Parameter::Ptr32AmodeS16(_, amode, _) |
+        Parameter::Ptr64AmodeS16(_, amode, _) | Parameter::Ptr80AmodeS16(_, amode, _) |
+        Parameter::Ptr8AmodeS32(_, amode, _) | Parameter::Ptr16AmodeS32(_, amode, _) | Parameter::Ptr32AmodeS32(_, amode, _) |
+        Parameter::Ptr64AmodeS32(_, amode, _) | Parameter::Ptr80AmodeS32(_, amode, _) => (Some(amode), true),
+        _ => return 0,
+    };
+    let base_index = match amode {
+        Some(AMode::BXSI) | Some(AMode::BXDI) | Some(AMode::BPSI) | Some(AMode::BPDI) => true,
+        _ => false,
+    };
+    let scaled_index = match amode {
+        Some(AMode::SIB(..)) | Some(AMode::SIBIndex(..)) => true,
+        _ => false,
+    };
+
+    match model {
+        CpuModel::I8086 => {
+            let mut cycles = match amode {
+                None => 6,
+                Some(AMode::BX) | Some(AMode::BP) | Some(AMode::SI) | Some(AMode::DI) => 5,
+                Some(AMode::BPDI) | Some(AMode::BXSI) => 7,
+                Some(AMode::BPSI) | Some(AMode::BXDI) => 8,
+                _ => 0,
+            };
+            if displacement && amode.is_some() {
+                cycles += 4;
+            }
+            if op.segment_prefix != Segment::Default {
+                cycles += 2;
+            }
+            cycles
+        }
+        CpuModel::I80286 if base_index && displacement => 1,
+        // 80386 and 80486 spends an extra cycle on a SIB index register
+        CpuModel::I80386 | CpuModel::I80486 if scaled_index => 1,
+        _ => 0,
+    }
+}
+
+/// returns true if `p` is a memory operand
+fn is_memory(p: &Parameter) -> bool {
+    match *p {
+        Parameter::Ptr8(_, _) | Parameter::Ptr8Amode(_, _) | Parameter::Ptr8AmodeS8(_, _, _) | Parameter::Ptr8AmodeS16(_, _, _) |
+        Parameter::Ptr16(_, _) | Parameter::Ptr16Amode(_, _) | Parameter::Ptr16AmodeS8(_, _, _) | Parameter::Ptr16AmodeS16(_, _, _) |
+        Parameter::Ptr32(_, _) | Parameter::Ptr32Amode(_, _) | Parameter::Ptr32AmodeS8(_, _, _) | Parameter::Ptr32AmodeS16(_, _, _) |
+        Parameter::Ptr64(_, _) | Parameter::Ptr64Amode(_, _) | Parameter::Ptr64AmodeS8(_, _, _) | Parameter::Ptr64AmodeS16(_, _, _) |
+        Parameter::Ptr80(_, _) | Parameter::Ptr80Amode(_, _) | Parameter::Ptr80AmodeS8(_, _, _) | Parameter::Ptr80AmodeS16(_, _, _) |
+        Parameter::Ptr8AmodeS32(_, _, _) | Parameter::Ptr16AmodeS32(_, _, _) | Parameter::Ptr32AmodeS32(_, _, _) |
+        Parameter::Ptr64AmodeS32(_, _, _) | Parameter::Ptr80AmodeS32(_, _, _) => true,
+        _ => false,
+    }
+}
+
+fn string_timing(op: &Op) -> Option<StringTiming> {
+    let (single, rep_setup, rep_iteration) = match *op {
+        Op::Movsb | Op::Movsw | Op::Movsd => ([18, 9, 5, 7, 7], [9, 8, 5, 5, 12], [17, 8, 4, 4, 3]),
+        Op::Cmpsb | Op::Cmpsw | Op::Cmpsd => ([22, 22, 8, 10, 8], [9, 5, 5, 5, 7], [22, 22, 9, 9, 7]),
+        Op::Scasb | Op::Scasw | Op::Scasd => ([15, 15, 7, 7, 6], [9, 5, 5, 5, 7], [15, 15, 8, 8, 5]),
+        Op::Lodsb | Op::Lodsw | Op::Lodsd => ([12, 10, 5, 5, 5], [9, 6, 5, 5, 7], [13, 11, 4, 6, 4]),
+        Op::Stosb | Op::Stosw | Op::Stosd => ([11, 10, 3, 4, 5], [9, 6, 4, 5, 7], [10, 9, 3, 5, 3]),
+        Op::Insb | Op::Insw | Op::Insd => ([14, 14, 5, 15, 17], [8, 8, 5, 13, 16], [8, 8, 4, 6, 8]),
+        Op::Outsb | Op::Outsw | Op::Outsd => ([14, 14, 5, 14, 17], [8, 8, 5, 12, 17], [8, 8, 4, 5, 11]),
+        _ => return None,
+    };
+    Some(StringTiming { single, rep_setup, rep_iteration })
+}
+
+fn op_timing(op: &Op) -> Timing {
+    match *op {
+        Op::Add8 | Op::Add16 | Op::Add32 | Op::Adc8 | Op::Adc16 | Op::Adc32 |
+        Op::Sub8 | Op::Sub16 | Op::Sub32 | Op::Sbb8 | Op::Sbb16 | Op::Sbb32 |
+        Op::And8 | Op::And16 | Op::And32 | Op::Or8 | Op::Or16 | Op::Or32 |
+        Op::Xor8 | Op::Xor16 | Op::Xor32 => t_rmw([3, 3, 2, 2, 1], [9, 10, 7, 6, 2], [16, 15, 7, 7, 3]),
+        Op::Cmp8 | Op::Cmp16 | Op::Cmp32 => t([3, 3, 2, 2, 1], [9, 10, 6, 6, 2]),
+        Op::Test8 | Op::Test16 | Op::Test32 => t([3, 3, 2, 2, 1], [9, 10, 6, 5, 2]),
+        Op::Mov8 | Op::Mov16 | Op::Mov32 => t_rmw([2, 2, 2, 2, 1], [8, 9, 5, 4, 1], [9, 12, 3, 2, 1]),
+        Op::Inc8 | Op::Inc16 | Op::Inc32 |
+        Op::Dec8 | Op::Dec16 | Op::Dec32 => t([3, 3, 2, 2, 1], [15, 15, 7, 6, 3]),
+        Op::Neg8 | Op::Neg16 | Op::Neg32 |
+        Op::Not8 | Op::Not16 | Op::Not32 => t([3, 3, 2, 2, 1], [16, 13, 7, 6, 3]),
+
+        // XXX shifts by CL or imm8 are charged as a single bit shift
+        Op::Shl8 | Op::Shl16 | Op::Shl32 | Op::Shr8 | Op::Shr16 | Op::Shr32 |
+        Op::Sar8 | Op::Sar16 | Op::Sar32 | Op::Rol8 | Op::Rol16 | Op::Rol32 |
+        Op::Ror8 | Op::Ror16 | Op::Ror32 => t([2, 2, 2, 3, 3], [15, 15, 7, 7, 4]),
+        Op::Rcl8 | Op::Rcl16 | Op::Rcl32 |
+        Op::Rcr8 | Op::Rcr16 | Op::Rcr32 => t([2, 2, 2, 9, 3], [15, 15, 7, 10, 4]),
+        Op::Shld | Op::Shrd => t([3, 3, 3, 3, 2], [7, 7, 7, 7, 3]),
+
+        Op::Mul8 => t([77, 27, 13, 14, 13], [83, 33, 16, 17, 13]),
+        Op::Mul16 => t([118, 36, 21, 22, 13], [124, 42, 24, 25, 13]),
+        Op::Mul32 => t([118, 36, 21, 38, 13], [124, 42, 24, 41, 13]),
+        Op::Imul8 => t([90, 27, 13, 14, 13], [96, 33, 16, 17, 13]),
+        Op::Imul16 => t([134, 36, 21, 22, 13], [140, 42, 24, 25, 13]),
+        Op::Imul32 => t([134, 36, 21, 38, 13], [140, 42, 24, 41, 13]),
+        Op::Div8 => t([85, 29, 14, 14, 16], [91, 35, 17, 17, 16]),
+        Op::Div16 => t([153, 38, 22, 22, 24], [159, 44, 25, 25, 24]),
+        Op::Div32 => t([153, 38, 22, 38, 40], [159, 44, 25, 41, 40]),
+        Op::Idiv8 => t([107, 44, 17, 19, 19], [113, 50, 20, 22, 20]),
+        Op::Idiv16 => t([175, 53, 25, 27, 27], [181, 59, 28, 30, 28]),
+        Op::Idiv32 => t([175, 53, 25, 43, 43], [181, 59, 28, 46, 44]),
+
+        Op::Push16 | Op::Push32 => t([11, 10, 3, 2, 1], [16, 16, 5, 5, 4]),
+        Op::Pop16 | Op::Pop32 => t([8, 10, 5, 4, 1], [17, 20, 5, 5, 6]),
+        Op::Pushf | Op::Pushfd => t([10, 9, 3, 4, 4], [10, 9, 3, 4, 4]),
+        Op::Popf | Op::Popfd => t([8, 8, 5, 5, 9], [8, 8, 5, 5, 9]),
+        Op::Pusha16 | Op::Pushad32 => t([36, 36, 17, 18, 11], [36, 36, 17, 18, 11]),
+        Op::Popa16 | Op::Popad32 => t([51, 51, 19, 24, 9], [51, 51, 19, 24, 9]),
+
+        Op::CallNear => t([19, 15, 7, 7, 3], [21, 19, 11, 10, 5]),
+        Op::CallFar => t([28, 23, 13, 17, 18], [37, 38, 16, 22, 17]),
+        Op::Retn => t([16, 16, 11, 10, 5], [16, 16, 11, 10, 5]),
+        Op::RetImm16 => t([20, 18, 11, 10, 5], [20, 18, 11, 10, 5]),
+        Op::Retf => t([26, 22, 15, 18, 13], [26, 22, 15, 18, 13]),
+        Op::JmpShort | Op::JmpNear => t([15, 14, 7, 7, 3], [18, 17, 11, 10, 5]),
+        Op::JmpFar => t([15, 14, 11, 12, 17], [24, 26, 15, 17, 13]),
+        Op::Ja | Op::Jc | Op::Jg | Op::Jl | Op::Jna | Op::Jnc | Op::Jng | Op::Jnl |
+        Op::Jno | Op::Jns | Op::Jnz | Op::Jo | Op::Jpe | Op::Jpo | Op::Js | Op::Jz => t_branch([4, 4, 3, 3, 1], [12, 9, 4, 4, 2]),
+        Op::Loop => t_branch([5, 5, 4, 11, 6], [12, 10, 4, 2, 1]),
+        Op::Loope | Op::Loopne => t_branch([6, 6, 4, 11, 6], [12, 10, 4, 2, 3]),
+        Op::Jcxz => t_branch([6, 5, 4, 5, 5], [12, 11, 4, 4, 3]),
+        Op::Int => t([51, 47, 23, 37, 30], [51, 47, 23, 37, 30]),
+        Op::Into => t_branch([4, 4, 3, 3, 3], [49, 44, 21, 32, 25]),
+        Op::Iret | Op::Iretd => t([24, 28, 17, 22, 15], [24, 28, 17, 22, 15]),
+
+        Op::In8 | Op::In16 => t([10, 10, 5, 12, 14], [10, 10, 5, 12, 14]),
+        Op::Out8 | Op::Out16 => t([10, 9, 3, 10, 16], [10, 9, 3, 10, 16]),
+
+        Op::Clc | Op::Stc | Op::Cmc | Op::Cld | Op::Std => t([2, 2, 2, 2, 2], [2, 2, 2, 2, 2]),
+        Op::Cli => t([2, 2, 3, 3, 5], [2, 2, 3, 3, 5]),
+        Op::Sti => t([2, 2, 2, 3, 5], [2, 2, 2, 3, 5]),
+        Op::Lahf | Op::Sahf => t([4, 2, 2, 2, 2], [4, 2, 2, 2, 2]),
+        Op::Cbw | Op::Cwde32 => t([2, 2, 2, 3, 3], [2, 2, 2, 3, 3]),
+        Op::Cwd16 | Op::Cdq32 => t([5, 4, 2, 2, 3], [5, 4, 2, 2, 3]),
+        Op::Salc => t([3, 3, 2, 2, 2], [3, 3, 2, 2, 2]),
+        Op::Lea16 | Op::Lea32 => t([2, 6, 3, 2, 1], [2, 6, 3, 2, 1]),
+        Op::Lds | Op::Les | Op::Lfs | Op::Lgs | Op::Lss => t([16, 18, 7, 7, 6], [16, 18, 7, 7, 6]),
+        Op::Xchg8 | Op::Xchg16 | Op::Xchg32 => t([4, 4, 3, 3, 3], [17, 17, 5, 5, 5]),
+        Op::Xlatb => t([11, 11, 5, 5, 4], [11, 11, 5, 5, 4]),
+        Op::Aaa | Op::Aas => t([4, 8, 3, 4, 3], [4, 8, 3, 4, 3]),
+        Op::Daa | Op::Das => t([4, 4, 3, 4, 2], [4, 4, 3, 4, 2]),
+        Op::Aam => t([83, 19, 16, 17, 15], [83, 19, 16, 17, 15]),
+        Op::Aad => t([60, 15, 14, 19, 14], [60, 15, 14, 19, 14]),
+        Op::Enter => t([15, 15, 11, 10, 14], [15, 15, 11, 10, 14]),
+        Op::Leave => t([8, 8, 5, 4, 5], [8, 8, 5, 4, 5]),
+        Op::Bound => t([33, 33, 13, 10, 7], [33, 33, 13, 10, 7]),
+        Op::Hlt => t([2, 2, 2, 5, 4], [2, 2, 2, 5, 4]),
+        Op::Nop => t([3, 3, 3, 3, 1], [3, 3, 3, 3, 1]),
+
+        Op::Bt => t([3, 3, 3, 3, 3], [12, 12, 12, 12, 8]),
+        Op::Bts | Op::Btr | Op::Btc => t([6, 6, 6, 6, 6], [13, 13, 13, 13, 13]),
+        Op::Bsf | Op::Bsr => t([10, 10, 10, 10, 6], [13, 13, 13, 13, 7]),
+        Op::Bswap => t([1, 1, 1, 1, 1], [1, 1, 1, 1, 1]),
+        Op::Cpuid => t([14, 14, 14, 14, 14], [14, 14, 14, 14, 14]),
+        Op::Cmpxchg8 | Op::Cmpxchg16 | Op::Cmpxchg32 => t([6, 6, 6, 6, 6], [10, 10, 10, 10, 10]),
+        Op::Xadd8 | Op::Xadd16 | Op::Xadd32 => t([3, 3, 3, 3, 3], [4, 4, 4, 4, 4]),
+        Op::Invd | Op::Wbinvd => t([4, 4, 4, 4, 4], [4, 4, 4, 4, 4]),
+        Op::Movzx16 | Op::Movzx32 | Op::Movsx16 | Op::Movsx32 => t([3, 3, 3, 3, 3], [6, 6, 6, 6, 3]),
+        Op::Seta | Op::Setc | Op::Setg | Op::Setl | Op::Setna | Op::Setnc | Op::Setng | Op::Setnl |
+        Op::Setno | Op::Setns | Op::Setnz | Op::Seto | Op::Setpe | Op::Setpo | Op::Sets | Op::Setz => t([4, 4, 4, 4, 3], [5, 5, 5, 5, 4]),
+
+        Op::Lgdt | Op::Lidt => t([11, 11, 11, 11, 11], [11, 11, 11, 11, 11]),
+        Op::Sgdt | Op::Sidt => t([11, 11, 11, 9, 10], [11, 11, 11, 9, 10]),
+        Op::Lldt => t([17, 17, 17, 20, 11], [19, 19, 19, 20, 11]),
+        Op::Ltr => t([17, 17, 17, 23, 20], [19, 19, 19, 27, 20]),
+        Op::Sldt | Op::Str | Op::Smsw => t([2, 2, 2, 2, 2], [3, 3, 3, 2, 3]),
+        Op::Lmsw => t([3, 3, 3, 10, 13], [6, 6, 6, 10, 13]),
+        Op::Clts => t([2, 2, 2, 5, 7], [2, 2, 2, 5, 7]),
+        Op::Arpl => t([10, 10, 10, 20, 9], [11, 11, 11, 21, 9]),
+        Op::Lar16 | Op::Lsl16 => t([14, 14, 14, 15, 11], [16, 16, 16, 16, 11]),
+        Op::Verr | Op::Verw => t([14, 14, 14, 10, 11], [16, 16, 16, 11, 11]),
+
+        Op::Fadd | Op::Faddp | Op::Fiadd | Op::Fsub | Op::Fsubp | Op::Fisub |
+        Op::Fsubr | Op::Fsubrp | Op::Fisubr | Op::Fcom | Op::Fcomp | Op::Fcompp |
+        Op::Ficom | Op::Ficomp | Op::Fucom | Op::Fucomp | Op::Fucompp => t([85, 85, 85, 23, 8], [105, 105, 105, 30, 10]),
+        Op::Fmul | Op::Fmulp | Op::Fimul => t([130, 130, 130, 46, 16], [135, 135, 135, 50, 14]),
+        Op::Fdiv | Op::Fdivp | Op::Fidiv |
+        Op::Fdivr | Op::Fdivrp | Op::Fidivr => t([200, 200, 200, 88, 73], [220, 220, 220, 94, 73]),
+        Op::Fsqrt => t([183, 183, 183, 122, 83], [183, 183, 183, 122, 83]),
+        Op::Fprem | Op::Fprem1 => t([125, 125, 125, 74, 70], [125, 125, 125, 74, 70]),
+        Op::Frndint | Op::Fscale | Op::Fxtract => t([50, 50, 50, 67, 30], [50, 50, 50, 67, 30]),
+        Op::F2xm1 | Op::Fpatan | Op::Fptan | Op::Fyl2x | Op::Fyl2xp1 |
+        Op::Fsin | Op::Fcos | Op::Fsincos => t([500, 500, 500, 300, 250], [500, 500, 500, 300, 250]),
+        Op::Fbld | Op::Fbstp => t([300, 300, 300, 100, 100], [300, 300, 300, 100, 100]),
+        Op::Fldenv | Op::Fnstenv | Op::Frstor | Op::Fnsave => t([200, 200, 200, 100, 100], [200, 200, 200, 100, 100]),
+        Op::Fabs | Op::Fchs | Op::Fclex | Op::Fdecstp | Op::Ffree | Op::Fild |
+        Op::Fincstp | Op::Finit | Op::Fist | Op::Fistp | Op::Fisttp | Op::Fld | Op::Fld1 |
+        Op::Fldl2t | Op::Fldl2e | Op::Fldlg2 | Op::Fldln2 | Op::Fldz | Op::Fldpi | Op::Fldcw |
+        Op::Fnop | Op::Fst | Op::Fstp | Op::Fstsw | Op::Fnstcw | Op::Ftst | Op::Fwait |
+        Op::Fxam | Op::Fxch => t([15, 15, 15, 12, 4], [50, 50, 50, 40, 3]),
+
+        _ => t([2, 2, 2, 2, 1], [8, 8, 5, 4, 1]),
+    }
+}
@@ -0,0 +1,62 @@
+use crate::cpu::{CpuModel, instruction_cycles, rep_setup_cycles};
+use crate::machine::Machine;
+
+#[test]
+fn can_time_memory_operands() {
+    let mut machine = Machine::deterministic();
+    let code: Vec<u8> = vec![
+        0x01, 0xD8,             // add ax,bx
+        0x01, 0x07,             // add [bx],ax
+        0x03, 0x41, 0x10,       // add ax,[bx+di+0x10]
+        0x26, 0x8B, 0x06, 0x00, 0x02, // mov ax,[es:0x200]
+    ];
+    machine.load_executable(&code, 0x085F).unwrap();
+    let ops = machine.cpu.decoder.decode_to_block(&mut machine.mmu, 0x085F, 0x100, 4);
+
+    assert_eq!(3, instruction_cycles(CpuModel::I8086, &ops[0].instruction, false));
+    assert_eq!(2, instruction_cycles(CpuModel::I80386, &ops[0].instruction, false));
+
+    // 8086: 16 + EA (5)
+    assert_eq!(21, instruction_cycles(CpuModel::I8086, &ops[1].instruction, false));
+    assert_eq!(7, instruction_cycles(CpuModel::I80386, &ops[1].instruction, false));
+
+    // 8086: 9 + EA (8 + 4), 80286: 7 + 1
+    assert_eq!(21, instruction_cycles(CpuModel::I8086, &ops[2].instruction, false));
+    assert_eq!(8, instruction_cycles(CpuModel::I80286, &ops[2].instruction, false));
+
+    // 8086: 8 + EA (6 + 2 for segment override)
+    assert_eq!(16, instruction_cycles(CpuModel::I8086, &ops[3].instruction, false));
+    assert_eq!(1, instruction_cycles(CpuModel::I80486, &ops[3].instruction, false));
+}
+
+#[test]
+fn can_time_branches_and_rep() {
+    let mut machine = Machine::deterministic();
+    let code: Vec<u8> = vec![
+        0x74, 0x00, // jz 0x102
+        0xF3, 0xA4, // rep movsb
+    ];
+    machine.load_executable(&code, 0x085F).unwrap();
+    let ops = machine.cpu.decoder.decode_to_block(&mut machine.mmu, 0x085F, 0x100, 2);
+
+    assert_eq!(4, instruction_cycles(CpuModel::I8086, &ops[0].instruction, false));
+    assert_eq!(16, instruction_cycles(CpuModel::I8086, &ops[0].instruction, true));
+
+    assert_eq!(17, instruction_cycles(CpuModel::I8086, &ops[1].instruction, false));
+    assert_eq!(9, rep_setup_cycles(CpuModel::I8086, &ops[1].instruction));
+}
+
+#[test]
+fn can_count_cycles_while_executing() {
+    let code: Vec<u8> = vec![
+        0xB9, 0x03, 0x00, // mov cx,0x3
+        0xF3, 0xAA,       // rep stosb
+    ];
+    let mut machine = Machine::deterministic();
+    machine.set_cpu_model(CpuModel::I8086);
+    machine.load_executable(&code, 0x085F).unwrap();
+    let start = machine.cpu.cycle_count;
+    machine.execute_instructions(4); // mov, 3 x stosb
+    // mov: 2, rep stosb: 9 + 3 * 10
+    assert_eq!(2 + 9 + 3 * 10, machine.cpu.cycle_count - start);
+}
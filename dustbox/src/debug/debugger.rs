@@ -87,8 +87,9 @@ impl Debugger {
     pub fn step_over(&mut self) {
         let mut decoder = Decoder::default();
         let cs = self.machine.cpu.get_r16(R::CS);
-        let op = decoder.get_instruction_info(&mut self.machine.mmu, cs, self.machine.cpu.regs.ip);
-        let dst = MemoryAddress::RealSegmentOffset(cs, self.machine.cpu.regs.ip + op.bytes.len() as u16);
+        let ip = self.machine.cpu.get_r16(R::IP);
+        let op = decoder.get_instruction_info(&mut self.machine.mmu, cs, ip);
+        let dst = MemoryAddress::RealSegmentOffset(cs, ip + op.bytes.len() as u16);
         println!("Step-over running to {:04X}:{:04X}", dst.segment(), dst.offset());
 
         let mut cnt = 0;
@@ -113,7 +114,7 @@ impl Debugger {
 
     pub fn disasm_n_instructions_to_text(&mut self, n: usize) -> String {
         let mut decoder = Decoder::default();
-        decoder.disassemble_block_to_str(&mut self.machine.mmu, self.machine.cpu.get_r16(R::CS), self.machine.cpu.regs.ip, n)
+        decoder.disassemble_block_to_str(&mut self.machine.mmu, self.machine.cpu.get_r16(R::CS), self.machine.cpu.get_r16(R::IP), n)
     }
 
     pub fn dump_memory(&self, filename: &str, base: u32, len: u32) -> Result<usize, IoError> {
@@ -305,7 +306,7 @@ impl Debugger {
             }
             "d" | "disasm" => {
                 let mut decoder = Decoder::default();
-                let op = decoder.get_instruction_info(&mut self.machine.mmu, self.machine.cpu.get_r16(R::CS), self.machine.cpu.regs.ip);
+                let op = decoder.get_instruction_info(&mut self.machine.mmu, self.machine.cpu.get_r16(R::CS), self.machine.cpu.get_r16(R::IP));
                 println!("{:?}", op);
                 println!("{}", op);
             }
@@ -403,9 +404,9 @@ impl Debugger {
     /// Loads a .com or .exe file
     pub fn load_executable(&mut self, filename: &str) {
         self.machine.hard_reset();
-        if let Some(e) = self.machine.load_executable_file(filename) {
-            panic!("error {}", e);
-        };
+        if let Err(e) = self.machine.load_executable_file(filename) {
+            println!("failed to load {}: {}", filename, e);
+        }
     }
 
     fn show_flat_address(&mut self) {
@@ -246,7 +246,7 @@ impl ProgramTracer {
         self.trace_r16(R::SS, machine.cpu.get_r16(R::SS));
 
         // tell tracer to start at CS:IP
-        let ma = MemoryAddress::RealSegmentOffset(machine.cpu.get_r16(R::CS), machine.cpu.regs.ip);
+        let ma = MemoryAddress::RealSegmentOffset(machine.cpu.get_r16(R::CS), machine.cpu.get_r16(R::IP));
         self.seen_addresses.push(SeenAddress{ma, visited: false, sources: SeenSources::default()});
 
         println!("; starting tracing disassembly at {}", ma);
@@ -259,12 +259,12 @@ impl Component for DOS {
                     println!("OPEN - OPEN EXISTING FILE {}, mode {:02X}, attr {:02X}", to_load.display(), mode, attr);
                     // CF clear if successful and AX = file handle
                     let handle = self.open_existing_file(to_load);
-                    cpu.regs.flags.carry = false;
+                    cpu.regs.flags.set_carry(false);
                     cpu.set_r16(R::AX, handle);
                 } else {
                     // CF set on error and AX = error code (01h,02h,03h,04h,05h,0Ch,56h) (see #01680 at AH=59h)
                     println!("OPEN - OPEN EXISTING FILE {} - NOT FOUND", to_load.display());
-                    cpu.regs.flags.carry = true;
+                    cpu.regs.flags.set_carry(true);
                     cpu.set_r16(R::AX, 0x0002); // 2 = "file not found"
                 }
             }
@@ -275,10 +275,10 @@ impl Component for DOS {
                     println!("CLOSE - CLOSE FILE, handle {:04X}", handle);
                     self.file_handles.remove(&handle);
                     // CF clear if successful and AX destroyed
-                    cpu.regs.flags.carry = false;
+                    cpu.regs.flags.set_carry(false);
                 } else {
                     // CF set on error and AX = error code (06h) (see #01680 at AH=59h/BX=0000h)
-                    cpu.regs.flags.carry = true;
+                    cpu.regs.flags.set_carry(true);
                     println!("XXX - ignoring close unknown handle {}", handle);
                 }
             }
@@ -302,13 +302,18 @@ impl Component for DOS {
                                 mmu.write(ds, dx, &buf);
 
                                 // XXX set AX to number of bytes that was read
-                                cpu.regs.flags.carry = false;
+                                cpu.regs.flags.set_carry(false);
                                 cpu.set_r16(R::AX, read_bytes as u16);
                                 if read_bytes != len {
                                     println!("--- wanted {} bytes, read {} bytes", len, read_bytes);
                                 }
                             }
-                            Err(e) => panic!(e),
+                            Err(e) => {
+                                // CF set on error and AX = error code (05h,06h) (see #01680 at AH=59h/BX=0000h)
+                                println!("ERROR: read from {} failed: {}", path.display(), e);
+                                cpu.regs.flags.set_carry(true);
+                                cpu.set_r16(R::AX, 0x0005); // 5 = "access denied"
+                            }
                         };
                     }
                 }
@@ -345,18 +350,10 @@ impl Component for DOS {
                 println!("  -- DATA: {} {}", hex_bytes(&data), bytes_to_ascii(&data));
             }
             0x43 => {
-                match cpu.get_r8(R::AL) {
-                    0x00 => {
-                        // EXTENDED MEMORY SPECIFICATION (XMS) v2+ - INSTALLATION CHECK
-                        // Return:
-                        // AL = 80h XMS driver installed
-                        // AL <> 80h no driver
-                        cpu.set_r8(R::AL, 0); // signals that XMS is not installed
-                        println!("XXX DOS - XMS INSTALLATION CHECK");
-                    }
-                    _ => println!("int21 (dos) error: xms ah=43, al={:02X}",
-                        cpu.get_r8(R::AL)),
-                }
+                // DOS 2+ - GET/SET FILE ATTRIBUTES
+                // AL = 00h get, 01h set
+                // DS:DX -> ASCIZ filename
+                println!("XXX DOS - GET/SET FILE ATTRIBUTES, al={:02X}", cpu.get_r8(R::AL));
             }
             0x44 => {
                 match cpu.get_r8(R::AL) {
@@ -414,7 +411,7 @@ impl Component for DOS {
                 // SIGNAL FAILURE
                 cpu.set_r16(R::AX, 0x0008); // out of memory
                 cpu.set_r16(R::BX, 0x0000);
-                cpu.regs.flags.carry = true;
+                cpu.regs.flags.set_carry(true);
             }
             0x49 => {
                 // DOS 2+ - FREE MEMORY
@@ -425,7 +422,7 @@ impl Component for DOS {
                 // AX = error code (07h,09h) (see #01680 at AH=59h/BX=0000h)
                 println!("XXX impl DOS 2+ - FREE MEMORY. es={:04X}",
                         cpu.get_r16(R::ES));
-                cpu.regs.flags.carry = false; // fake success
+                cpu.regs.flags.set_carry(false); // fake success
             }
             0x4A => {
                 // DOS 2+ - RESIZE MEMORY BLOCK
@@ -439,7 +436,7 @@ impl Component for DOS {
                 println!("XXX impl DOS 2+ - RESIZE MEMORY BLOCK. bx={:04X}, es={:04X}",
                         cpu.get_r16(R::BX),
                         cpu.get_r16(R::ES));
-                cpu.regs.flags.carry = false; // fake success
+                cpu.regs.flags.set_carry(false); // fake success
             }
             0x4B => {
                 // DOS 2+ - EXEC - LOAD AND/OR EXECUTE PROGRAM
@@ -0,0 +1,621 @@
+// LIM EMS 4.0 expanded memory manager, as provided by EMM386.EXE
+// http://www.phatcode.net/res/218/files/limems40.txt
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::cpu::{CPU, R};
+use crate::machine::Component;
+use crate::memory::{MMU, MemoryAddress, MemoryHandler};
+
+#[cfg(test)]
+#[path = "./ems_test.rs"]
+mod ems_test;
+
+/// segment of the driver header in the BIOS ROM. INT 67h points into it, and programs
+/// detect the driver by the "EMMXXXX0" device name at offset 000Ah of the vector segment
+pub const EMS_SEG: u16 = 0xF020;
+
+/// offset of the INT 67h handler in EMS_SEG
+pub const EMS_ENTRY: u16 = 0x0012;
+
+/// size of the logical and physical pages, in bytes
+const PAGE_SIZE: u32 = 0x4000;
+
+/// number of logical pages, LIM EMS 4.0 manages at most 32 MiB of expanded memory
+const MAX_PAGES: u64 = 2048;
+
+/// number of physical pages in the 64 KiB page frame
+const FRAME_PAGES: usize = 4;
+
+/// number of handles, including the system handle 0
+const MAX_HANDLES: usize = 255;
+
+/// size of the page map used by function 4Eh, as a (handle, logical page) word pair per physical page
+const PAGE_MAP_SIZE: u8 = (FRAME_PAGES * 4) as u8;
+
+/// status codes, returned in AH
+const ERR_INVALID_HANDLE: u8 = 0x83;
+const ERR_UNDEFINED_FUNCTION: u8 = 0x84;
+const ERR_OUT_OF_HANDLES: u8 = 0x85;
+const ERR_SAVED_CONTEXT: u8 = 0x86;
+const ERR_MORE_THAN_TOTAL: u8 = 0x87;
+const ERR_MORE_THAN_FREE: u8 = 0x88;
+const ERR_ZERO_PAGES: u8 = 0x89;
+const ERR_INVALID_LOGICAL_PAGE: u8 = 0x8A;
+const ERR_INVALID_PHYSICAL_PAGE: u8 = 0x8B;
+const ERR_ALREADY_SAVED: u8 = 0x8D;
+const ERR_NOT_SAVED: u8 = 0x8E;
+const ERR_INVALID_SUBFUNCTION: u8 = 0x8F;
+const ERR_NAME_NOT_FOUND: u8 = 0xA0;
+const ERR_NAME_EXISTS: u8 = 0xA1;
+
+/// the logical page mapped at a physical page, as (handle, logical page)
+type Mapping = Option<(u16, u16)>;
+
+/// the expanded memory, accessed through the page frame
+struct PageFrame {
+    /// physical address of the page frame
+    base: u32,
+
+    memory: Vec<u8>,
+
+    /// the page of `memory` mapped at each physical page
+    pages: [Option<usize>; FRAME_PAGES],
+}
+
+impl PageFrame {
+    /// returns the offset in `memory` of physical address `addr`, if its page is mapped
+    fn offset(&self, addr: u32) -> Option<usize> {
+        let offset = addr - self.base;
+        self.pages[(offset / PAGE_SIZE) as usize]
+            .map(|page| page * PAGE_SIZE as usize + (offset % PAGE_SIZE) as usize)
+    }
+}
+
+impl MemoryHandler for PageFrame {
+    fn read_u8(&mut self, addr: u32) -> u8 {
+        match self.offset(addr) {
+            Some(offset) => self.memory[offset],
+            None => 0xFF, // unmapped pages reads as a open bus
+        }
+    }
+
+    fn write_u8(&mut self, addr: u32, data: u8) {
+        if let Some(offset) = self.offset(addr) {
+            self.memory[offset] = data;
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct Handle {
+    /// the page of expanded memory holding each logical page
+    pages: Vec<usize>,
+
+    name: [u8; 8],
+
+    /// the page frame mapping saved by function 47h
+    saved_map: Option<[Mapping; FRAME_PAGES]>,
+}
+
+pub struct EMS {
+    /// segment of the page frame
+    frame_segment: u16,
+
+    frame: Rc<RefCell<PageFrame>>,
+
+    /// set for each page of expanded memory allocated to a handle
+    used_pages: Vec<bool>,
+
+    /// open handles, by number
+    handles: BTreeMap<u16, Handle>,
+
+    /// the logical page mapped at each physical page
+    mapping: [Mapping; FRAME_PAGES],
+}
+
+impl Component for EMS {
+    fn int(&mut self, int: u8, cpu: &mut CPU, mmu: &mut MMU) -> bool {
+        if int != 0x67 {
+            return false;
+        }
+        let res = match cpu.get_r8(R::AH) {
+            0x40 => {
+                // LIM EMS - GET MANAGER STATUS
+                Ok(())
+            }
+            0x41 => {
+                // LIM EMS - GET PAGE FRAME SEGMENT
+                // Return: BX = segment of page frame
+                cpu.set_r16(R::BX, self.frame_segment);
+                Ok(())
+            }
+            0x42 => {
+                // LIM EMS - GET NUMBER OF PAGES
+                // Return: BX = number of unallocated pages, DX = total number of pages
+                cpu.set_r16(R::BX, self.free_pages() as u16);
+                cpu.set_r16(R::DX, self.used_pages.len() as u16);
+                Ok(())
+            }
+            0x43 => {
+                // LIM EMS - GET HANDLE AND ALLOCATE MEMORY
+                // BX = number of logical pages to allocate
+                // Return: DX = handle
+                self.allocate(usize::from(cpu.get_r16(R::BX)), false)
+                    .map(|handle| cpu.set_r16(R::DX, handle))
+            }
+            0x44 => {
+                // LIM EMS - MAP MEMORY
+                // AL = physical page number (0-3)
+                // BX = logical page number, or FFFFh to unmap (EMS 4.0)
+                // DX = handle
+                self.map_page(mmu, usize::from(cpu.get_r8(R::AL)), cpu.get_r16(R::DX), cpu.get_r16(R::BX))
+            }
+            0x45 => {
+                // LIM EMS - RELEASE HANDLE AND MEMORY
+                // DX = EMM handle
+                self.deallocate(mmu, cpu.get_r16(R::DX))
+            }
+            0x46 => {
+                // LIM EMS - GET EMM VERSION
+                // Return: AL = EMM version number in BCD
+                cpu.set_r8(R::AL, 0x40);
+                Ok(())
+            }
+            0x47 => {
+                // LIM EMS - SAVE MAPPING CONTEXT
+                // DX = handle
+                let mapping = self.mapping;
+                match self.handles.get_mut(&cpu.get_r16(R::DX)) {
+                    None => Err(ERR_INVALID_HANDLE),
+                    Some(handle) if handle.saved_map.is_some() => Err(ERR_ALREADY_SAVED),
+                    Some(handle) => {
+                        handle.saved_map = Some(mapping);
+                        Ok(())
+                    }
+                }
+            }
+            0x48 => {
+                // LIM EMS - RESTORE MAPPING CONTEXT
+                // DX = handle
+                match self.handles.get_mut(&cpu.get_r16(R::DX)) {
+                    None => Err(ERR_INVALID_HANDLE),
+                    Some(handle) => match handle.saved_map.take() {
+                        None => Err(ERR_NOT_SAVED),
+                        Some(saved_map) => {
+                            self.set_page_map(mmu, saved_map);
+                            Ok(())
+                        }
+                    }
+                }
+            }
+            0x4B => {
+                // LIM EMS - GET NUMBER OF EMM HANDLES
+                // Return: BX = number of open handles, including the system handle
+                cpu.set_r16(R::BX, self.handles.len() as u16);
+                Ok(())
+            }
+            0x4C => {
+                // LIM EMS - GET PAGES OWNED BY HANDLE
+                // DX = handle
+                // Return: BX = number of logical pages allocated to handle
+                match self.handles.get(&cpu.get_r16(R::DX)) {
+                    None => Err(ERR_INVALID_HANDLE),
+                    Some(handle) => {
+                        cpu.set_r16(R::BX, handle.pages.len() as u16);
+                        Ok(())
+                    }
+                }
+            }
+            0x4D => {
+                // LIM EMS - GET PAGES FOR ALL HANDLES
+                // ES:DI -> array to receive handle, page count word pairs
+                // Return: BX = number of active handles
+                let (es, mut di) = (cpu.get_r16(R::ES), cpu.get_r16(R::DI));
+                for (number, handle) in &self.handles {
+                    mmu.write_u16(es, di, *number);
+                    mmu.write_u16(es, di.wrapping_add(2), handle.pages.len() as u16);
+                    di = di.wrapping_add(4);
+                }
+                cpu.set_r16(R::BX, self.handles.len() as u16);
+                Ok(())
+            }
+            0x4E => {
+                // LIM EMS - GET OR SET PAGE MAP
+                // AL = 00h get to ES:DI, 01h set from DS:SI, 02h get and set, 03h get size of map
+                match cpu.get_r8(R::AL) {
+                    0x00 => {
+                        self.write_page_map(mmu, cpu.get_r16(R::ES), cpu.get_r16(R::DI));
+                        Ok(())
+                    }
+                    0x01 => {
+                        let map = self.read_page_map(mmu, cpu.get_r16(R::DS), cpu.get_r16(R::SI));
+                        self.set_page_map(mmu, map);
+                        Ok(())
+                    }
+                    0x02 => {
+                        self.write_page_map(mmu, cpu.get_r16(R::ES), cpu.get_r16(R::DI));
+                        let map = self.read_page_map(mmu, cpu.get_r16(R::DS), cpu.get_r16(R::SI));
+                        self.set_page_map(mmu, map);
+                        Ok(())
+                    }
+                    0x03 => {
+                        // Return: AL = size of the page map in bytes
+                        cpu.set_r8(R::AL, PAGE_MAP_SIZE);
+                        Ok(())
+                    }
+                    _ => Err(ERR_INVALID_SUBFUNCTION),
+                }
+            }
+            0x50 => {
+                // LIM EMS 4.0 - MAP/UNMAP MULTIPLE HANDLE PAGES
+                // AL = 00h by physical page number, 01h by segment address
+                // CX = number of entries
+                // DX = handle
+                // DS:SI -> array of logical page, physical page or segment word pairs
+                self.map_multiple(cpu, mmu)
+            }
+            0x51 => {
+                // LIM EMS 4.0 - REALLOCATE PAGES
+                // DX = handle
+                // BX = number of pages to be allocated to handle
+                // Return: BX = actual number of pages allocated to handle
+                let res = self.reallocate(mmu, cpu.get_r16(R::DX), usize::from(cpu.get_r16(R::BX)));
+                if let Some(handle) = self.handles.get(&cpu.get_r16(R::DX)) {
+                    cpu.set_r16(R::BX, handle.pages.len() as u16);
+                }
+                res
+            }
+            0x53 => {
+                // LIM EMS 4.0 - GET/SET HANDLE NAME
+                // AL = 00h get name to ES:DI, 01h set name from DS:SI
+                // DX = handle
+                self.handle_name(cpu, mmu)
+            }
+            0x54 => {
+                // LIM EMS 4.0 - GET HANDLE DIRECTORY
+                // AL = 00h get directory to ES:DI, 01h search for named handle at DS:SI, 02h get total number of handles
+                self.handle_directory(cpu, mmu)
+            }
+            0x58 => {
+                // LIM EMS 4.0 - GET MAPPABLE PHYSICAL ADDRESS ARRAY
+                // AL = 00h get array to ES:DI, 01h get number of entries
+                // Return: CX = number of entries
+                match cpu.get_r8(R::AL) {
+                    0x00 => {
+                        let (es, di) = (cpu.get_r16(R::ES), cpu.get_r16(R::DI));
+                        for page in 0..FRAME_PAGES as u16 {
+                            mmu.write_u16(es, di.wrapping_add(page * 4), self.frame_segment + page * (PAGE_SIZE >> 4) as u16);
+                            mmu.write_u16(es, di.wrapping_add(page * 4 + 2), page);
+                        }
+                        cpu.set_r16(R::CX, FRAME_PAGES as u16);
+                        Ok(())
+                    }
+                    0x01 => {
+                        cpu.set_r16(R::CX, FRAME_PAGES as u16);
+                        Ok(())
+                    }
+                    _ => Err(ERR_INVALID_SUBFUNCTION),
+                }
+            }
+            0x59 => {
+                // LIM EMS 4.0 - GET EXPANDED MEMORY HARDWARE INFORMATION
+                // AL = 00h get hardware configuration array to ES:DI, 01h get unallocated raw page count
+                match cpu.get_r8(R::AL) {
+                    0x00 => {
+                        let (es, di) = (cpu.get_r16(R::ES), cpu.get_r16(R::DI));
+                        mmu.write_u16(es, di, (PAGE_SIZE >> 4) as u16); // raw page size in paragraphs
+                        mmu.write_u16(es, di.wrapping_add(2), 0); // alternate register sets
+                        mmu.write_u16(es, di.wrapping_add(4), u16::from(PAGE_MAP_SIZE)); // context save area size
+                        mmu.write_u16(es, di.wrapping_add(6), 0); // DMA register sets
+                        mmu.write_u16(es, di.wrapping_add(8), 0); // DMA channel operation
+                        Ok(())
+                    }
+                    0x01 => {
+                        // Return: BX = unallocated raw pages, DX = total raw pages
+                        cpu.set_r16(R::BX, self.free_pages() as u16);
+                        cpu.set_r16(R::DX, self.used_pages.len() as u16);
+                        Ok(())
+                    }
+                    _ => Err(ERR_INVALID_SUBFUNCTION),
+                }
+            }
+            0x5A => {
+                // LIM EMS 4.0 - ALLOCATE STANDARD/RAW PAGES
+                // AL = 00h standard pages, 01h raw pages
+                // BX = number of pages to allocate, may be zero
+                // Return: DX = handle
+                if cpu.get_r8(R::AL) > 0x01 {
+                    Err(ERR_INVALID_SUBFUNCTION)
+                } else {
+                    self.allocate(usize::from(cpu.get_r16(R::BX)), true)
+                        .map(|handle| cpu.set_r16(R::DX, handle))
+                }
+            }
+            _ => {
+                println!("ems error: unknown function AH={:02X}, AL={:02X}", cpu.get_r8(R::AH), cpu.get_r8(R::AL));
+                Err(ERR_UNDEFINED_FUNCTION)
+            }
+        };
+
+        // Return: AH = status, 00h on success
+        cpu.set_r8(R::AH, match res {
+            Ok(()) => 0x00,
+            Err(code) => code,
+        });
+        true
+    }
+}
+
+impl EMS {
+    /// returns a manager of `memory_kb` KiB of expanded memory, with the page frame at `frame_segment`
+    pub fn new(memory_kb: u32, frame_segment: u16) -> Self {
+        let pages = (u64::from(memory_kb) * 1024 / u64::from(PAGE_SIZE)).min(MAX_PAGES) as usize;
+        let frame = PageFrame {
+            base: u32::from(frame_segment) << 4,
+            memory: vec![0; pages * PAGE_SIZE as usize],
+            pages: [None; FRAME_PAGES],
+        };
+        let mut handles = BTreeMap::new();
+        handles.insert(0, Handle::default()); // the system handle
+        EMS {
+            frame_segment,
+            frame: Rc::new(RefCell::new(frame)),
+            used_pages: vec![false; pages],
+            handles,
+            mapping: [None; FRAME_PAGES],
+        }
+    }
+
+    /// writes the driver header into the BIOS ROM, points INT 67h to it and maps the page frame
+    pub fn init(&self, mmu: &mut MMU) {
+        let mut header = Vec::new();
+        header.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]); // next driver
+        header.extend_from_slice(&[0x00, 0xC0]); // attributes: character device, IOCTL supported
+        header.extend_from_slice(&[0x13, 0x00]); // strategy routine
+        header.extend_from_slice(&[0x13, 0x00]); // interrupt routine
+        header.extend_from_slice(b"EMMXXXX0");
+        header.push(0xCF); // iret, the INT 67h handler at EMS_ENTRY
+        header.push(0xCB); // retf, the strategy and interrupt routines
+
+        // the rom is mapped read-only
+        mmu.memory.write(MemoryAddress::RealSegmentOffset(EMS_SEG, 0).value(), &header);
+
+        mmu.write_u16(0x0000, 0x67 * 4, EMS_ENTRY);
+        mmu.write_u16(0x0000, 0x67 * 4 + 2, EMS_SEG);
+
+        let base = self.frame.borrow().base;
+        mmu.map_mmio(base, FRAME_PAGES as u32 * PAGE_SIZE, self.frame.clone());
+    }
+
+    fn free_pages(&self) -> usize {
+        self.used_pages.iter().filter(|used| !**used).count()
+    }
+
+    /// marks `count` free pages of expanded memory as used and returns them
+    fn take_pages(&mut self, count: usize) -> Vec<usize> {
+        let pages: Vec<usize> = self.used_pages.iter().enumerate()
+            .filter(|(_, used)| !**used)
+            .map(|(page, _)| page)
+            .take(count)
+            .collect();
+        for page in &pages {
+            self.used_pages[*page] = true;
+        }
+        pages
+    }
+
+    /// checks that `count` more pages can be allocated
+    fn check_free(&self, count: usize) -> Result<(), u8> {
+        if count > self.used_pages.len() {
+            Err(ERR_MORE_THAN_TOTAL)
+        } else if count > self.free_pages() {
+            Err(ERR_MORE_THAN_FREE)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// allocates a handle owning `count` pages, returning its number
+    fn allocate(&mut self, count: usize, allow_zero: bool) -> Result<u16, u8> {
+        if self.handles.len() >= MAX_HANDLES {
+            return Err(ERR_OUT_OF_HANDLES);
+        }
+        if count == 0 && !allow_zero {
+            return Err(ERR_ZERO_PAGES);
+        }
+        self.check_free(count)?;
+        let number = (1..MAX_HANDLES as u16).find(|number| !self.handles.contains_key(number)).unwrap();
+        let pages = self.take_pages(count);
+        self.handles.insert(number, Handle { pages, ..Handle::default() });
+        Ok(number)
+    }
+
+    /// releases the pages of `number` and closes it. the system handle stays open, without pages
+    fn deallocate(&mut self, mmu: &mut MMU, number: u16) -> Result<(), u8> {
+        match self.handles.get(&number) {
+            None => return Err(ERR_INVALID_HANDLE),
+            Some(handle) if handle.saved_map.is_some() => return Err(ERR_SAVED_CONTEXT),
+            Some(_) => {}
+        }
+        self.reallocate(mmu, number, 0)?;
+        if number != 0 {
+            self.handles.remove(&number);
+        }
+        Ok(())
+    }
+
+    /// resizes `number` to `count` pages. logical pages past the new size are unmapped
+    fn reallocate(&mut self, mmu: &mut MMU, number: u16, count: usize) -> Result<(), u8> {
+        let current = match self.handles.get(&number) {
+            Some(handle) => handle.pages.len(),
+            None => return Err(ERR_INVALID_HANDLE),
+        };
+        if count > current {
+            self.check_free(count - current)?;
+            let pages = self.take_pages(count - current);
+            self.handles.get_mut(&number).unwrap().pages.extend(pages);
+        } else {
+            let released = self.handles.get_mut(&number).unwrap().pages.split_off(count);
+            for page in released {
+                self.used_pages[page] = false;
+            }
+            for physical in 0..FRAME_PAGES {
+                if let Some((handle, logical)) = self.mapping[physical] {
+                    if handle == number && usize::from(logical) >= count {
+                        self.set_mapping(mmu, physical, None);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// maps logical page `logical` of handle `number` at `physical`, or unmaps it for logical page FFFFh
+    fn map_page(&mut self, mmu: &mut MMU, physical: usize, number: u16, logical: u16) -> Result<(), u8> {
+        let handle = self.handles.get(&number).ok_or(ERR_INVALID_HANDLE)?;
+        if physical >= FRAME_PAGES {
+            return Err(ERR_INVALID_PHYSICAL_PAGE);
+        }
+        if logical == 0xFFFF {
+            self.set_mapping(mmu, physical, None);
+            return Ok(());
+        }
+        if usize::from(logical) >= handle.pages.len() {
+            return Err(ERR_INVALID_LOGICAL_PAGE);
+        }
+        self.set_mapping(mmu, physical, Some((number, logical)));
+        Ok(())
+    }
+
+    /// applies the entries of the array at DS:SI for function 50h
+    fn map_multiple(&mut self, cpu: &CPU, mmu: &mut MMU) -> Result<(), u8> {
+        let by_segment = match cpu.get_r8(R::AL) {
+            0x00 => false,
+            0x01 => true,
+            _ => return Err(ERR_INVALID_SUBFUNCTION),
+        };
+        let (ds, si) = (cpu.get_r16(R::DS), cpu.get_r16(R::SI));
+        for entry in 0..cpu.get_r16(R::CX) {
+            let logical = mmu.read_u16(ds, si.wrapping_add(entry * 4));
+            let target = mmu.read_u16(ds, si.wrapping_add(entry * 4 + 2));
+            let physical = if by_segment {
+                let offset = u32::from(target.wrapping_sub(self.frame_segment)) << 4;
+                if offset % PAGE_SIZE != 0 {
+                    return Err(ERR_INVALID_PHYSICAL_PAGE);
+                }
+                (offset / PAGE_SIZE) as usize
+            } else {
+                usize::from(target)
+            };
+            self.map_page(mmu, physical, cpu.get_r16(R::DX), logical)?;
+        }
+        Ok(())
+    }
+
+    /// sets the logical page mapped at `physical`, and the page of expanded memory the frame exposes
+    fn set_mapping(&mut self, mmu: &mut MMU, physical: usize, mapping: Mapping) {
+        let page = mapping.and_then(|(number, logical)| {
+            self.handles.get(&number).and_then(|handle| handle.pages.get(usize::from(logical)).copied())
+        });
+        self.mapping[physical] = if page.is_some() { mapping } else { None };
+
+        let mut frame = self.frame.borrow_mut();
+        frame.pages[physical] = page;
+
+        // the frame contents changed without a write through the mmu
+        mmu.invalidate_code(frame.base + physical as u32 * PAGE_SIZE, PAGE_SIZE);
+    }
+
+    fn set_page_map(&mut self, mmu: &mut MMU, map: [Mapping; FRAME_PAGES]) {
+        for (physical, mapping) in map.iter().enumerate() {
+            self.set_mapping(mmu, physical, *mapping);
+        }
+    }
+
+    /// writes the page map of function 4Eh at `seg:offset`
+    fn write_page_map(&self, mmu: &mut MMU, seg: u16, offset: u16) {
+        for (physical, mapping) in self.mapping.iter().enumerate() {
+            let (number, logical) = mapping.unwrap_or((0xFFFF, 0xFFFF));
+            let offset = offset.wrapping_add(physical as u16 * 4);
+            mmu.write_u16(seg, offset, number);
+            mmu.write_u16(seg, offset.wrapping_add(2), logical);
+        }
+    }
+
+    /// reads the page map of function 4Eh from `seg:offset`
+    fn read_page_map(&self, mmu: &MMU, seg: u16, offset: u16) -> [Mapping; FRAME_PAGES] {
+        let mut map = [None; FRAME_PAGES];
+        for (physical, mapping) in map.iter_mut().enumerate() {
+            let offset = offset.wrapping_add(physical as u16 * 4);
+            let number = mmu.read_u16(seg, offset);
+            if number != 0xFFFF {
+                *mapping = Some((number, mmu.read_u16(seg, offset.wrapping_add(2))));
+            }
+        }
+        map
+    }
+
+    /// function 53h
+    fn handle_name(&mut self, cpu: &CPU, mmu: &mut MMU) -> Result<(), u8> {
+        let number = cpu.get_r16(R::DX);
+        if !self.handles.contains_key(&number) {
+            return Err(ERR_INVALID_HANDLE);
+        }
+        match cpu.get_r8(R::AL) {
+            0x00 => {
+                mmu.write(cpu.get_r16(R::ES), cpu.get_r16(R::DI), &self.handles[&number].name);
+                Ok(())
+            }
+            0x01 => {
+                let mut name = [0; 8];
+                name.copy_from_slice(&mmu.read(cpu.get_r16(R::DS), cpu.get_r16(R::SI), 8));
+                if name != [0; 8] && self.handles.iter().any(|(other, handle)| *other != number && handle.name == name) {
+                    return Err(ERR_NAME_EXISTS);
+                }
+                self.handles.get_mut(&number).unwrap().name = name;
+                Ok(())
+            }
+            _ => Err(ERR_INVALID_SUBFUNCTION),
+        }
+    }
+
+    /// function 54h
+    fn handle_directory(&self, cpu: &mut CPU, mmu: &mut MMU) -> Result<(), u8> {
+        match cpu.get_r8(R::AL) {
+            0x00 => {
+                // Return: AL = number of entries, of a handle word and a 8 byte name
+                let (es, mut di) = (cpu.get_r16(R::ES), cpu.get_r16(R::DI));
+                for (number, handle) in &self.handles {
+                    mmu.write_u16(es, di, *number);
+                    mmu.write(es, di.wrapping_add(2), &handle.name);
+                    di = di.wrapping_add(10);
+                }
+                cpu.set_r8(R::AL, self.handles.len() as u8);
+                Ok(())
+            }
+            0x01 => {
+                // Return: DX = handle
+                let name = mmu.read(cpu.get_r16(R::DS), cpu.get_r16(R::SI), 8);
+                if name == [0; 8] {
+                    return Err(ERR_NAME_EXISTS);
+                }
+                match self.handles.iter().find(|(_, handle)| handle.name[..] == name[..]) {
+                    Some((number, _)) => {
+                        cpu.set_r16(R::DX, *number);
+                        Ok(())
+                    }
+                    None => Err(ERR_NAME_NOT_FOUND),
+                }
+            }
+            0x02 => {
+                // Return: BX = total number of handles
+                cpu.set_r16(R::BX, MAX_HANDLES as u16);
+                Ok(())
+            }
+            _ => Err(ERR_INVALID_SUBFUNCTION),
+        }
+    }
+}
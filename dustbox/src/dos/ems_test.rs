@@ -0,0 +1,169 @@
+use crate::cpu::{CPU, R};
+use crate::dos::EMS;
+use crate::machine::Component;
+use crate::memory::MMU;
+
+/// returns a manager of 256 KiB of expanded memory with the page frame at E000h
+fn ems_with_memory() -> (EMS, CPU, MMU) {
+    let mut mmu = MMU::default();
+    let ems = EMS::new(256, 0xE000);
+    ems.init(&mut mmu);
+    (ems, CPU::deterministic(), mmu)
+}
+
+fn call(ems: &mut EMS, cpu: &mut CPU, mmu: &mut MMU, ax: u16) -> u8 {
+    cpu.set_r16(R::AX, ax);
+    ems.int(0x67, cpu, mmu);
+    cpu.get_r8(R::AH)
+}
+
+#[test]
+fn can_allocate_and_map_pages() {
+    let (mut ems, mut cpu, mut mmu) = ems_with_memory();
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4600));
+    assert_eq!(0x40, cpu.get_r8(R::AL));
+
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4200));
+    assert_eq!(16, cpu.get_r16(R::BX));
+    assert_eq!(16, cpu.get_r16(R::DX));
+
+    cpu.set_r16(R::BX, 0);
+    assert_eq!(0x89, call(&mut ems, &mut cpu, &mut mmu, 0x4300));
+    cpu.set_r16(R::BX, 17);
+    assert_eq!(0x87, call(&mut ems, &mut cpu, &mut mmu, 0x4300));
+    cpu.set_r16(R::BX, 4);
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4300));
+    let handle = cpu.get_r16(R::DX);
+    cpu.set_r16(R::BX, 13);
+    assert_eq!(0x88, call(&mut ems, &mut cpu, &mut mmu, 0x4300));
+
+    // logical page 1 at physical page 0, then at physical page 3
+    cpu.set_r16(R::BX, 1);
+    cpu.set_r16(R::DX, handle);
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4400));
+    mmu.write_u16(0xE000, 0x0010, 0x1234);
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4403));
+    assert_eq!(0x1234, mmu.read_u16(0xE000, 0xC010));
+
+    // unmapped pages reads as FFh
+    cpu.set_r16(R::BX, 0xFFFF);
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4400));
+    assert_eq!(0xFFFF, mmu.read_u16(0xE000, 0x0010));
+
+    cpu.set_r16(R::BX, 4);
+    assert_eq!(0x8A, call(&mut ems, &mut cpu, &mut mmu, 0x4400));
+    cpu.set_r16(R::BX, 0);
+    assert_eq!(0x8B, call(&mut ems, &mut cpu, &mut mmu, 0x4404));
+    cpu.set_r16(R::DX, 0x0042);
+    assert_eq!(0x83, call(&mut ems, &mut cpu, &mut mmu, 0x4400));
+
+    // shrinking the handle unmaps its pages past the new size
+    cpu.set_r16(R::DX, handle);
+    cpu.set_r16(R::BX, 1);
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x5100));
+    assert_eq!(1, cpu.get_r16(R::BX));
+    assert_eq!(0xFFFF, mmu.read_u16(0xE000, 0xC010));
+
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4500));
+    assert_eq!(0x83, call(&mut ems, &mut cpu, &mut mmu, 0x4500));
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4200));
+    assert_eq!(16, cpu.get_r16(R::BX));
+
+    // the expanded memory is limited to 32 MiB
+    let mut ems = EMS::new(u32::MAX, 0xE000);
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4200));
+    assert_eq!(2048, cpu.get_r16(R::DX));
+}
+
+#[test]
+fn can_save_and_restore_page_map() {
+    let (mut ems, mut cpu, mut mmu) = ems_with_memory();
+    cpu.set_r16(R::BX, 2);
+    call(&mut ems, &mut cpu, &mut mmu, 0x4300);
+    let handle = cpu.get_r16(R::DX);
+
+    cpu.set_r16(R::BX, 0);
+    call(&mut ems, &mut cpu, &mut mmu, 0x4400);
+    mmu.write_u8(0xE000, 0x0000, 0xAA);
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4700));
+    assert_eq!(0x8D, call(&mut ems, &mut cpu, &mut mmu, 0x4700));
+
+    cpu.set_r16(R::BX, 1);
+    call(&mut ems, &mut cpu, &mut mmu, 0x4400);
+    mmu.write_u8(0xE000, 0x0000, 0xBB);
+
+    // a handle with a saved context can't be released
+    assert_eq!(0x86, call(&mut ems, &mut cpu, &mut mmu, 0x4500));
+
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4800));
+    assert_eq!(0xAA, mmu.read_u8(0xE000, 0x0000));
+    assert_eq!(0x8E, call(&mut ems, &mut cpu, &mut mmu, 0x4800));
+
+    // get the page map to 1000:0000, and set it back after remapping
+    cpu.set_r16(R::ES, 0x1000);
+    cpu.set_r16(R::DI, 0x0000);
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4E00));
+    assert_eq!(handle, mmu.read_u16(0x1000, 0x0000));
+    assert_eq!(0x0000, mmu.read_u16(0x1000, 0x0002));
+    assert_eq!(0xFFFF, mmu.read_u16(0x1000, 0x0004));
+    cpu.set_r16(R::DX, handle);
+    cpu.set_r16(R::BX, 1);
+    call(&mut ems, &mut cpu, &mut mmu, 0x4400);
+    cpu.set_r16(R::DS, 0x1000);
+    cpu.set_r16(R::SI, 0x0000);
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4E01));
+    assert_eq!(0xAA, mmu.read_u8(0xE000, 0x0000));
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4E03));
+    assert_eq!(16, cpu.get_r8(R::AL));
+
+    // map logical pages 1 and 0 by segment address
+    mmu.write_u16(0x1000, 0x0100, 1);
+    mmu.write_u16(0x1000, 0x0102, 0xE400);
+    mmu.write_u16(0x1000, 0x0104, 0);
+    mmu.write_u16(0x1000, 0x0106, 0xEC00);
+    cpu.set_r16(R::SI, 0x0100);
+    cpu.set_r16(R::CX, 2);
+    cpu.set_r16(R::DX, handle);
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x5001));
+    assert_eq!(0xBB, mmu.read_u8(0xE400, 0x0000));
+    assert_eq!(0xAA, mmu.read_u8(0xEC00, 0x0000));
+}
+
+#[test]
+fn can_name_handles() {
+    let (mut ems, mut cpu, mut mmu) = ems_with_memory();
+
+    // EMS 4.0 allows allocating zero pages
+    cpu.set_r16(R::BX, 0);
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x5A00));
+    let first = cpu.get_r16(R::DX);
+    cpu.set_r16(R::BX, 1);
+    call(&mut ems, &mut cpu, &mut mmu, 0x4300);
+    let second = cpu.get_r16(R::DX);
+
+    mmu.write(0x1000, 0x0000, b"DUSTBOX\0");
+    cpu.set_r16(R::DS, 0x1000);
+    cpu.set_r16(R::SI, 0x0000);
+    cpu.set_r16(R::DX, first);
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x5301));
+    cpu.set_r16(R::DX, second);
+    assert_eq!(0xA1, call(&mut ems, &mut cpu, &mut mmu, 0x5301));
+
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x5401));
+    assert_eq!(first, cpu.get_r16(R::DX));
+
+    // the system handle and the two allocated handles
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4B00));
+    assert_eq!(3, cpu.get_r16(R::BX));
+    cpu.set_r16(R::ES, 0x2000);
+    cpu.set_r16(R::DI, 0x0000);
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4D00));
+    assert_eq!(3, cpu.get_r16(R::BX));
+    assert_eq!(second, mmu.read_u16(0x2000, 0x0008));
+    assert_eq!(1, mmu.read_u16(0x2000, 0x000A));
+
+    cpu.set_r16(R::DX, first);
+    assert_eq!(0x00, call(&mut ems, &mut cpu, &mut mmu, 0x4500));
+    assert_eq!(0xA0, call(&mut ems, &mut cpu, &mut mmu, 0x5401));
+    assert_eq!(0x84, call(&mut ems, &mut cpu, &mut mmu, 0x6000));
+}
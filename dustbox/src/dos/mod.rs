@@ -2,3 +2,9 @@
 
 pub use self::dos::*;
 mod dos;
+
+pub use self::ems::*;
+mod ems;
+
+pub use self::xms::*;
+mod xms;
@@ -0,0 +1,463 @@
+// XMS 3.0 driver, as provided by HIMEM.SYS
+// http://www.phatcode.net/res/219/files/xms30.txt
+
+use std::collections::BTreeMap;
+
+use crate::bios::BIOS;
+use crate::cpu::{CPU, R};
+use crate::machine::Component;
+use crate::memory::{MMU, MemoryAddress};
+
+#[cfg(test)]
+#[path = "./xms_test.rs"]
+mod xms_test;
+
+/// offset of the driver entry point in the BIOS ROM segment, returned by INT 2F AX=4310h
+pub const XMS_ENTRY: u16 = 0x0100;
+
+/// extended memory blocks are allocated above the High Memory Area (HMA) at 1 MiB
+const EMB_START: u32 = 0x11_0000;
+
+/// number of EMB handles, as the HIMEM.SYS default
+const MAX_HANDLES: usize = 32;
+
+/// error codes, returned in BL
+const ERR_NOT_IMPLEMENTED: u8 = 0x80;
+const ERR_HMA_MISSING: u8 = 0x90;
+const ERR_HMA_IN_USE: u8 = 0x91;
+const ERR_HMA_NOT_ALLOCATED: u8 = 0x93;
+const ERR_A20_STILL_ENABLED: u8 = 0x94;
+const ERR_OUT_OF_MEMORY: u8 = 0xA0;
+const ERR_OUT_OF_HANDLES: u8 = 0xA1;
+const ERR_INVALID_HANDLE: u8 = 0xA2;
+const ERR_INVALID_SOURCE_HANDLE: u8 = 0xA3;
+const ERR_INVALID_SOURCE_OFFSET: u8 = 0xA4;
+const ERR_INVALID_DEST_HANDLE: u8 = 0xA5;
+const ERR_INVALID_DEST_OFFSET: u8 = 0xA6;
+const ERR_INVALID_LENGTH: u8 = 0xA7;
+const ERR_NOT_LOCKED: u8 = 0xAA;
+const ERR_LOCKED: u8 = 0xAB;
+const ERR_LOCK_OVERFLOW: u8 = 0xAC;
+const ERR_NO_UMB: u8 = 0xB1;
+
+/// a extended memory block (EMB)
+#[derive(Clone, Debug)]
+struct ExtendedMemoryBlock {
+    /// physical address of the block
+    address: u32,
+
+    size_kb: u32,
+
+    lock_count: u8,
+}
+
+#[derive(Clone)]
+pub struct XMS {
+    /// end of the installed memory, in bytes
+    memory_end: u32,
+
+    hma_allocated: bool,
+
+    /// set by the global enable A20 function
+    a20_global: bool,
+
+    /// number of local enable A20 calls not undone by local disable A20
+    a20_local: u16,
+
+    /// allocated blocks, by handle
+    blocks: BTreeMap<u16, ExtendedMemoryBlock>,
+}
+
+impl Component for XMS {
+    /// handles the XMS functions of the multiplex interrupt 0x2F
+    fn int(&mut self, int: u8, cpu: &mut CPU, _mmu: &mut MMU) -> bool {
+        if int != 0x2F {
+            return false;
+        }
+        match cpu.get_r16(R::AX) {
+            0x4300 => {
+                // EXTENDED MEMORY SPECIFICATION (XMS) v2+ - INSTALLATION CHECK
+                // Return: AL = 80h XMS driver installed
+                cpu.set_r8(R::AL, 0x80);
+            }
+            0x4310 => {
+                // EXTENDED MEMORY SPECIFICATION (XMS) v2+ - GET DRIVER ADDRESS
+                // Return: ES:BX -> driver entry point
+                cpu.set_r16(R::ES, BIOS::ROM_SEG);
+                cpu.set_r16(R::BX, XMS_ENTRY);
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl XMS {
+    /// returns a driver managing the memory above 1 MiB, up to `memory_end` bytes
+    pub fn new(memory_end: u32) -> Self {
+        XMS {
+            memory_end,
+            hma_allocated: false,
+            a20_global: false,
+            a20_local: 0,
+            blocks: BTreeMap::new(),
+        }
+    }
+
+    /// writes the driver entry point into the BIOS ROM, bypassing its read-only mapping
+    pub fn init(&self, mmu: &mut MMU) {
+        let addr = MemoryAddress::RealSegmentOffset(BIOS::ROM_SEG, XMS_ENTRY).value();
+        mmu.memory.write_u8(addr, 0xCB); // retf
+    }
+
+    fn hma_exists(&self) -> bool {
+        self.memory_end >= EMB_START
+    }
+
+    /// handles a call to the driver entry point, with the function number in AH.
+    /// returns the state of the A20 gate to apply, for the A20 functions
+    pub fn call(&mut self, cpu: &mut CPU, mmu: &mut MMU) -> Option<bool> {
+        let mut a20 = None;
+        let res = match cpu.get_r8(R::AH) {
+            0x00 => {
+                // GET XMS VERSION NUMBER
+                // Return: AX = XMS version, BX = driver revision, DX = 0001h if the HMA exists
+                cpu.set_r16(R::AX, 0x0300);
+                cpu.set_r16(R::BX, 0x0301);
+                cpu.set_r16(R::DX, u16::from(self.hma_exists()));
+                return None;
+            }
+            0x01 => {
+                // REQUEST HIGH MEMORY AREA
+                // DX = memory in bytes (FFFFh for applications)
+                if !self.hma_exists() {
+                    Err(ERR_HMA_MISSING)
+                } else if self.hma_allocated {
+                    Err(ERR_HMA_IN_USE)
+                } else {
+                    self.hma_allocated = true;
+                    Ok(())
+                }
+            }
+            0x02 => {
+                // RELEASE HIGH MEMORY AREA
+                if !self.hma_exists() {
+                    Err(ERR_HMA_MISSING)
+                } else if !self.hma_allocated {
+                    Err(ERR_HMA_NOT_ALLOCATED)
+                } else {
+                    self.hma_allocated = false;
+                    Ok(())
+                }
+            }
+            0x03 => {
+                // GLOBAL ENABLE A20
+                self.a20_global = true;
+                a20 = Some(true);
+                Ok(())
+            }
+            0x04 => {
+                // GLOBAL DISABLE A20
+                self.a20_global = false;
+                if self.a20_local > 0 {
+                    Err(ERR_A20_STILL_ENABLED)
+                } else {
+                    a20 = Some(false);
+                    Ok(())
+                }
+            }
+            0x05 => {
+                // LOCAL ENABLE A20
+                self.a20_local = self.a20_local.saturating_add(1);
+                a20 = Some(true);
+                Ok(())
+            }
+            0x06 => {
+                // LOCAL DISABLE A20
+                self.a20_local = self.a20_local.saturating_sub(1);
+                if self.a20_local > 0 || self.a20_global {
+                    Err(ERR_A20_STILL_ENABLED)
+                } else {
+                    a20 = Some(false);
+                    Ok(())
+                }
+            }
+            0x07 => {
+                // QUERY A20
+                // Return: AX = 0001h if A20 is enabled
+                cpu.set_r16(R::AX, u16::from(mmu.a20_enabled()));
+                cpu.set_r8(R::BL, 0);
+                return None;
+            }
+            0x08 => {
+                // QUERY FREE EXTENDED MEMORY
+                // Return: AX = largest free block in KiB, DX = total free memory in KiB
+                let (largest, total) = self.free_kb();
+                cpu.set_r16(R::AX, largest.min(0xFFFF) as u16);
+                cpu.set_r16(R::DX, total.min(0xFFFF) as u16);
+                cpu.set_r8(R::BL, if total == 0 { ERR_OUT_OF_MEMORY } else { 0 });
+                return None;
+            }
+            0x88 => {
+                // QUERY ANY FREE EXTENDED MEMORY (XMS 3.0)
+                // Return: EAX = largest free block in KiB, ECX = highest address, EDX = total free memory in KiB
+                let (largest, total) = self.free_kb();
+                cpu.set_r32(R::EAX, largest);
+                cpu.set_r32(R::ECX, self.memory_end - 1);
+                cpu.set_r32(R::EDX, total);
+                cpu.set_r8(R::BL, if total == 0 { ERR_OUT_OF_MEMORY } else { 0 });
+                return None;
+            }
+            0x09 | 0x89 => {
+                // ALLOCATE EXTENDED MEMORY BLOCK
+                // DX = size in KiB (EDX for 89h)
+                // Return: DX = handle
+                let size_kb = if cpu.get_r8(R::AH) == 0x09 {
+                    u32::from(cpu.get_r16(R::DX))
+                } else {
+                    cpu.get_r32(R::EDX)
+                };
+                self.allocate(size_kb).map(|handle| cpu.set_r16(R::DX, handle))
+            }
+            0x0A => {
+                // FREE EXTENDED MEMORY BLOCK
+                // DX = handle
+                match self.blocks.get(&cpu.get_r16(R::DX)) {
+                    None => Err(ERR_INVALID_HANDLE),
+                    Some(block) if block.lock_count > 0 => Err(ERR_LOCKED),
+                    Some(_) => {
+                        self.blocks.remove(&cpu.get_r16(R::DX));
+                        Ok(())
+                    }
+                }
+            }
+            0x0B => {
+                // MOVE EXTENDED MEMORY BLOCK
+                // DS:SI -> EMM structure
+                self.move_block(cpu, mmu)
+            }
+            0x0C => {
+                // LOCK EXTENDED MEMORY BLOCK
+                // DX = handle
+                // Return: DX:BX = 32-bit physical address of the block
+                match self.blocks.get_mut(&cpu.get_r16(R::DX)) {
+                    None => Err(ERR_INVALID_HANDLE),
+                    Some(block) if block.lock_count == 0xFF => Err(ERR_LOCK_OVERFLOW),
+                    Some(block) => {
+                        block.lock_count += 1;
+                        cpu.set_r16(R::DX, (block.address >> 16) as u16);
+                        cpu.set_r16(R::BX, block.address as u16);
+                        Ok(())
+                    }
+                }
+            }
+            0x0D => {
+                // UNLOCK EXTENDED MEMORY BLOCK
+                // DX = handle
+                match self.blocks.get_mut(&cpu.get_r16(R::DX)) {
+                    None => Err(ERR_INVALID_HANDLE),
+                    Some(block) if block.lock_count == 0 => Err(ERR_NOT_LOCKED),
+                    Some(block) => {
+                        block.lock_count -= 1;
+                        Ok(())
+                    }
+                }
+            }
+            0x0E | 0x8E => {
+                // GET EMB HANDLE INFORMATION
+                // DX = handle
+                // Return: BH = lock count, BL = free handles (CX for 8Eh), DX = size in KiB (EDX for 8Eh)
+                let free_handles = (MAX_HANDLES - self.blocks.len()) as u16;
+                match self.blocks.get(&cpu.get_r16(R::DX)) {
+                    None => Err(ERR_INVALID_HANDLE),
+                    Some(block) => {
+                        cpu.set_r8(R::BH, block.lock_count);
+                        if cpu.get_r8(R::AH) == 0x0E {
+                            cpu.set_r8(R::BL, free_handles as u8);
+                            cpu.set_r16(R::DX, block.size_kb.min(0xFFFF) as u16);
+                        } else {
+                            cpu.set_r16(R::CX, free_handles);
+                            cpu.set_r32(R::EDX, block.size_kb);
+                        }
+                        Ok(())
+                    }
+                }
+            }
+            0x0F | 0x8F => {
+                // REALLOCATE EXTENDED MEMORY BLOCK
+                // BX = new size in KiB (EBX for 8Fh), DX = handle
+                let size_kb = if cpu.get_r8(R::AH) == 0x0F {
+                    u32::from(cpu.get_r16(R::BX))
+                } else {
+                    cpu.get_r32(R::EBX)
+                };
+                self.reallocate(cpu.get_r16(R::DX), size_kb, mmu)
+            }
+            0x10..=0x12 => {
+                // REQUEST / RELEASE / REALLOCATE UPPER MEMORY BLOCK
+                // Return: DX = size of largest available UMB in paragraphs
+                cpu.set_r16(R::DX, 0);
+                Err(ERR_NO_UMB)
+            }
+            _ => {
+                println!("xms error: unknown function AH={:02X}", cpu.get_r8(R::AH));
+                Err(ERR_NOT_IMPLEMENTED)
+            }
+        };
+
+        // Return: AX = 0001h on success, else AX = 0000h and BL = error code
+        match res {
+            Ok(()) => cpu.set_r16(R::AX, 1),
+            Err(code) => {
+                cpu.set_r16(R::AX, 0);
+                cpu.set_r8(R::BL, code);
+            }
+        }
+        a20
+    }
+
+    /// returns the unallocated ranges of extended memory, as (physical address, size in KiB)
+    fn free_ranges(&self) -> Vec<(u32, u32)> {
+        let mut used: Vec<(u32, u32)> = self.blocks.values()
+            .filter(|block| block.size_kb > 0)
+            .map(|block| (block.address, block.size_kb))
+            .collect();
+        used.sort();
+
+        let mut res = Vec::new();
+        let mut start = EMB_START;
+        for (address, size_kb) in used {
+            if address > start {
+                res.push((start, (address - start) / 1024));
+            }
+            start = start.max(address + size_kb * 1024);
+        }
+        if self.memory_end > start {
+            res.push((start, (self.memory_end - start) / 1024));
+        }
+        res
+    }
+
+    /// returns the largest free block and the total free memory, in KiB
+    fn free_kb(&self) -> (u32, u32) {
+        self.free_ranges().iter().fold((0, 0), |(largest, total), (_, size_kb)| {
+            (largest.max(*size_kb), total + size_kb)
+        })
+    }
+
+    /// returns the address of the first free range holding `size_kb`
+    fn find_free(&self, size_kb: u32) -> Option<u32> {
+        if size_kb == 0 {
+            return Some(EMB_START);
+        }
+        self.free_ranges().iter().find(|(_, free_kb)| *free_kb >= size_kb).map(|(address, _)| *address)
+    }
+
+    /// allocates a block of `size_kb`, returning its handle
+    fn allocate(&mut self, size_kb: u32) -> Result<u16, u8> {
+        if self.blocks.len() >= MAX_HANDLES {
+            return Err(ERR_OUT_OF_HANDLES);
+        }
+        let address = self.find_free(size_kb).ok_or(ERR_OUT_OF_MEMORY)?;
+        let handle = (1..).find(|handle| !self.blocks.contains_key(handle)).unwrap();
+        self.blocks.insert(handle, ExtendedMemoryBlock { address, size_kb, lock_count: 0 });
+        Ok(handle)
+    }
+
+    /// resizes the block `handle` to `size_kb`, moving its contents if it can't grow in place
+    fn reallocate(&mut self, handle: u16, size_kb: u32, mmu: &mut MMU) -> Result<(), u8> {
+        let block = match self.blocks.remove(&handle) {
+            Some(block) => block,
+            None => return Err(ERR_INVALID_HANDLE),
+        };
+        if block.lock_count > 0 {
+            self.blocks.insert(handle, block);
+            return Err(ERR_LOCKED);
+        }
+
+        // a 32-bit size in KiB may not fit in the address space, so compare in 64 bits
+        let end = u64::from(block.address) + u64::from(size_kb) * 1024;
+        let in_place = size_kb <= block.size_kb || self.free_ranges().iter()
+            .any(|(address, free_kb)| *address <= block.address && end <= u64::from(*address) + u64::from(*free_kb) * 1024);
+        let address = if in_place {
+            Some(block.address)
+        } else {
+            self.find_free(size_kb)
+        };
+        match address {
+            Some(address) => {
+                if address != block.address {
+                    let len = block.size_kb.min(size_kb) * 1024;
+                    copy_memory(mmu, block.address, address, len);
+                }
+                self.blocks.insert(handle, ExtendedMemoryBlock { address, size_kb, lock_count: 0 });
+                Ok(())
+            }
+            None => {
+                self.blocks.insert(handle, block);
+                Err(ERR_OUT_OF_MEMORY)
+            }
+        }
+    }
+
+    /// returns the physical address of `len` bytes at `offset` in the block `handle`.
+    /// for handle 0, `offset` is a real mode segment:offset pair in conventional memory
+    fn block_address(&self, handle: u16, offset: u32, len: u32) -> Result<u32, bool> {
+        let (address, size) = if handle == 0 {
+            ((offset >> 16) * 16 + (offset & 0xFFFF), EMB_START)
+        } else {
+            match self.blocks.get(&handle) {
+                Some(block) => (block.address.wrapping_add(offset), block.size_kb * 1024),
+                None => return Err(false),
+            }
+        };
+        let start = if handle == 0 { address } else { offset };
+        if u64::from(start) + u64::from(len) > u64::from(size) {
+            return Err(true);
+        }
+        Ok(address)
+    }
+
+    /// copies memory as described by the EMM structure at DS:SI
+    fn move_block(&self, cpu: &CPU, mmu: &mut MMU) -> Result<(), u8> {
+        let ds = cpu.get_r16(R::DS);
+        let si = cpu.get_r16(R::SI);
+        let len = mmu.read_u32(ds, si);
+        let src_handle = mmu.read_u16(ds, si.wrapping_add(4));
+        let src_offset = mmu.read_u32(ds, si.wrapping_add(6));
+        let dst_handle = mmu.read_u16(ds, si.wrapping_add(10));
+        let dst_offset = mmu.read_u32(ds, si.wrapping_add(12));
+
+        if len & 1 != 0 {
+            return Err(ERR_INVALID_LENGTH);
+        }
+        let src = self.block_address(src_handle, src_offset, len)
+            .map_err(|bad_offset| if bad_offset { ERR_INVALID_SOURCE_OFFSET } else { ERR_INVALID_SOURCE_HANDLE })?;
+        let dst = self.block_address(dst_handle, dst_offset, len)
+            .map_err(|bad_offset| if bad_offset { ERR_INVALID_DEST_OFFSET } else { ERR_INVALID_DEST_HANDLE })?;
+        copy_memory(mmu, src, dst, len);
+        Ok(())
+    }
+}
+
+/// copies `len` bytes of physical memory from `src` to `dst`. like HIMEM.SYS does, the A20
+/// gate is enabled during the move
+fn copy_memory(mmu: &mut MMU, src: u32, dst: u32, len: u32) {
+    let a20 = mmu.a20_enabled();
+    mmu.set_a20(true);
+    if mmu.is_block_accessible(src, len) && mmu.is_block_accessible(dst, len) {
+        mmu.copy_block(src, dst, len);
+    } else if dst > src {
+        // copy backwards, so a overlapping destination doesn't overwrite the source first
+        for i in (0..len).rev() {
+            let val = mmu.read_physical(src.wrapping_add(i), 1);
+            mmu.write_physical(dst.wrapping_add(i), 1, val);
+        }
+    } else {
+        for i in 0..len {
+            let val = mmu.read_physical(src.wrapping_add(i), 1);
+            mmu.write_physical(dst.wrapping_add(i), 1, val);
+        }
+    }
+    mmu.set_a20(a20);
+}
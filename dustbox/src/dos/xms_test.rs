@@ -0,0 +1,153 @@
+use crate::cpu::{CPU, R};
+use crate::dos::XMS;
+use crate::memory::{MMU, FlatMemory};
+
+/// returns a driver and a MMU with 128 KiB of extended memory above the HMA
+fn xms_with_memory() -> (XMS, CPU, MMU) {
+    let mmu = MMU::with_memory(FlatMemory::with_size(0x13_0000));
+    (XMS::new(0x13_0000), CPU::deterministic(), mmu)
+}
+
+fn call(xms: &mut XMS, cpu: &mut CPU, mmu: &mut MMU, ah: u8) -> Option<bool> {
+    cpu.set_r8(R::AH, ah);
+    xms.call(cpu, mmu)
+}
+
+#[test]
+fn can_allocate_extended_memory() {
+    let (mut xms, mut cpu, mut mmu) = xms_with_memory();
+
+    call(&mut xms, &mut cpu, &mut mmu, 0x08);
+    assert_eq!(128, cpu.get_r16(R::AX));
+    assert_eq!(128, cpu.get_r16(R::DX));
+
+    cpu.set_r16(R::DX, 64);
+    call(&mut xms, &mut cpu, &mut mmu, 0x09);
+    assert_eq!(0x0001, cpu.get_r16(R::AX));
+    let first = cpu.get_r16(R::DX);
+
+    cpu.set_r16(R::DX, 32);
+    call(&mut xms, &mut cpu, &mut mmu, 0x09);
+    let second = cpu.get_r16(R::DX);
+    assert_ne!(first, second);
+
+    // lock returns the physical address in DX:BX
+    cpu.set_r16(R::DX, second);
+    call(&mut xms, &mut cpu, &mut mmu, 0x0C);
+    assert_eq!(0x0001, cpu.get_r16(R::AX));
+    assert_eq!(0x0012, cpu.get_r16(R::DX));
+    assert_eq!(0x0000, cpu.get_r16(R::BX));
+
+    // a locked block can't be freed
+    cpu.set_r16(R::DX, second);
+    call(&mut xms, &mut cpu, &mut mmu, 0x0A);
+    assert_eq!(0x0000, cpu.get_r16(R::AX));
+    assert_eq!(0xAB, cpu.get_r8(R::BL));
+
+    // too large request
+    cpu.set_r16(R::DX, 64);
+    call(&mut xms, &mut cpu, &mut mmu, 0x09);
+    assert_eq!(0x0000, cpu.get_r16(R::AX));
+    assert_eq!(0xA0, cpu.get_r8(R::BL));
+
+    // freeing the first block leaves a hole, reused by the next allocation
+    cpu.set_r16(R::DX, first);
+    call(&mut xms, &mut cpu, &mut mmu, 0x0A);
+    assert_eq!(0x0001, cpu.get_r16(R::AX));
+    call(&mut xms, &mut cpu, &mut mmu, 0x08);
+    assert_eq!(64, cpu.get_r16(R::AX));
+    assert_eq!(96, cpu.get_r16(R::DX));
+
+    cpu.set_r16(R::DX, 0x1234);
+    call(&mut xms, &mut cpu, &mut mmu, 0x0A);
+    assert_eq!(0xA2, cpu.get_r8(R::BL));
+}
+
+#[test]
+fn can_move_extended_memory() {
+    let (mut xms, mut cpu, mut mmu) = xms_with_memory();
+    cpu.set_r16(R::DX, 1);
+    call(&mut xms, &mut cpu, &mut mmu, 0x09);
+    let handle = cpu.get_r16(R::DX);
+
+    mmu.write(0x1000, 0x0000, &[1, 2, 3, 4]);
+
+    // move 4 bytes from conventional memory 1000:0000 to offset 2 of the block
+    mmu.write_u32(0x2000, 0x0000, 4);
+    mmu.write_u16(0x2000, 0x0004, 0);
+    mmu.write_u32(0x2000, 0x0006, 0x1000_0000);
+    mmu.write_u16(0x2000, 0x000A, handle);
+    mmu.write_u32(0x2000, 0x000C, 2);
+    cpu.set_r16(R::DS, 0x2000);
+    cpu.set_r16(R::SI, 0x0000);
+    call(&mut xms, &mut cpu, &mut mmu, 0x0B);
+    assert_eq!(0x0001, cpu.get_r16(R::AX));
+    assert_eq!(vec![0, 0, 1, 2, 3, 4], mmu.memory.read(0x11_0000, 6));
+
+    // the destination is past the end of the 1 KiB block
+    mmu.write_u32(0x2000, 0x000C, 0x03FE);
+    call(&mut xms, &mut cpu, &mut mmu, 0x0B);
+    assert_eq!(0x0000, cpu.get_r16(R::AX));
+    assert_eq!(0xA6, cpu.get_r8(R::BL));
+
+    // growing the block moves its contents past the block allocated after it
+    cpu.set_r16(R::DX, 1);
+    call(&mut xms, &mut cpu, &mut mmu, 0x09);
+    cpu.set_r16(R::DX, handle);
+    cpu.set_r16(R::BX, 2);
+    call(&mut xms, &mut cpu, &mut mmu, 0x0F);
+    assert_eq!(0x0001, cpu.get_r16(R::AX));
+    cpu.set_r16(R::DX, handle);
+    call(&mut xms, &mut cpu, &mut mmu, 0x0C);
+    assert_eq!(0x0011, cpu.get_r16(R::DX));
+    assert_eq!(0x0800, cpu.get_r16(R::BX));
+    assert_eq!(vec![0, 0, 1, 2, 3, 4], mmu.memory.read(0x11_0800, 6));
+
+    // a size past the end of the address space
+    cpu.set_r16(R::DX, handle);
+    call(&mut xms, &mut cpu, &mut mmu, 0x0D);
+    cpu.set_r16(R::DX, handle);
+    cpu.set_r32(R::EBX, 0xFFFF_FFFF);
+    call(&mut xms, &mut cpu, &mut mmu, 0x8F);
+    assert_eq!(0x0000, cpu.get_r16(R::AX));
+    assert_eq!(0xA0, cpu.get_r8(R::BL));
+
+    // a move into the BIOS ROM is dropped
+    mmu.map_rom(0xF_0000, 0x1_0000);
+    mmu.write_u16(0x2000, 0x000A, 0);
+    mmu.write_u32(0x2000, 0x000C, 0xF000_0000);
+    call(&mut xms, &mut cpu, &mut mmu, 0x0B);
+    assert_eq!(0x0001, cpu.get_r16(R::AX));
+    assert_eq!(vec![0, 0, 0, 0], mmu.memory.read(0xF_0000, 4));
+}
+
+#[test]
+fn can_control_hma_and_a20() {
+    let (mut xms, mut cpu, mut mmu) = xms_with_memory();
+    call(&mut xms, &mut cpu, &mut mmu, 0x00);
+    assert_eq!(0x0300, cpu.get_r16(R::AX));
+    assert_eq!(0x0001, cpu.get_r16(R::DX));
+
+    call(&mut xms, &mut cpu, &mut mmu, 0x01);
+    assert_eq!(0x0001, cpu.get_r16(R::AX));
+    call(&mut xms, &mut cpu, &mut mmu, 0x01);
+    assert_eq!(0x0000, cpu.get_r16(R::AX));
+    assert_eq!(0x91, cpu.get_r8(R::BL));
+    call(&mut xms, &mut cpu, &mut mmu, 0x02);
+    assert_eq!(0x0001, cpu.get_r16(R::AX));
+
+    assert_eq!(Some(true), call(&mut xms, &mut cpu, &mut mmu, 0x03));
+    assert_eq!(Some(true), call(&mut xms, &mut cpu, &mut mmu, 0x05));
+
+    // the local enable is still in effect
+    assert_eq!(None, call(&mut xms, &mut cpu, &mut mmu, 0x04));
+    assert_eq!(0x94, cpu.get_r8(R::BL));
+    assert_eq!(Some(false), call(&mut xms, &mut cpu, &mut mmu, 0x06));
+    assert_eq!(0x0001, cpu.get_r16(R::AX));
+
+    // no HMA without memory above 1 MiB
+    let mut xms = XMS::new(0xA_0000);
+    call(&mut xms, &mut cpu, &mut mmu, 0x01);
+    assert_eq!(0x0000, cpu.get_r16(R::AX));
+    assert_eq!(0x90, cpu.get_r8(R::BL));
+}
@@ -1,10 +1,15 @@
 /// http://www.delorie.com/djgpp/doc/exe/
 /// http://www.delorie.com/djgpp/doc/rbinter/id/51/29.html
 
+use std::error::Error;
 use std::fmt;
 
 use bincode::deserialize;
 
+#[cfg(test)]
+#[path = "./exe_test.rs"]
+mod exe_test;
+
 pub struct ExeFile {
     pub header: ExeHeader,
     pub relocs: Vec<ExeRelocation>,
@@ -14,10 +19,31 @@ pub struct ExeFile {
     exe_size: usize,
 }
 
+/// a reason for a file to be rejected by the parser
+#[derive(Debug, PartialEq)]
 pub enum ParseError {
+    /// the file ends inside the header
+    TruncatedHeader,
+
+    /// the file does not start with "MZ"
     WrongMagic,
+
+    /// the relocation table extends past the end of the file
+    RelocationsOutOfRange,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::TruncatedHeader => write!(f, "truncated exe header"),
+            ParseError::WrongMagic => write!(f, "not a exe file, wrong magic"),
+            ParseError::RelocationsOutOfRange => write!(f, "exe relocation table out of range"),
+        }
+    }
 }
 
+impl Error for ParseError {}
+
 const DEBUG_PARSER: bool = false;
 
 /// Header pages is 512 bytes
@@ -26,17 +52,20 @@ const PAGE_SIZE: u16 = 512;
 
 impl ExeFile {
     pub fn from_data(data: &[u8]) -> Result<Self, ParseError> {
-        let header = match ExeHeader::from_data(data) {
-            Ok(hdr) => hdr,
-            Err(e) => panic!(e),
-        };
+        let header = ExeHeader::from_data(data)?;
 
-        if header.exe_data_end_offset() > data.len() {
-            println!("WARNING: program end = {:04X} but data len = {:04X}", header.exe_data_end_offset(), data.len());
+        let mut program_end = header.exe_data_end_offset();
+        if program_end > data.len() {
+            println!("WARNING: program end = {:04X} but data len = {:04X}", program_end, data.len());
+            program_end = data.len();
         }
         let program_start = header.exe_data_start_offset();
-        let program_data = data[program_start..data.len()].to_vec();
-        let relocs = header.parse_relocations(data);
+        if program_start > program_end {
+            return Err(ParseError::TruncatedHeader);
+        }
+        // data following the load module (overlays, debug info) is not loaded
+        let program_data = data[program_start..program_end].to_vec();
+        let relocs = header.parse_relocations(data)?;
         println!("  program start in exe: {:04X}", program_start);
 
         Ok(ExeFile {
@@ -109,10 +138,17 @@ pub struct ExeHeader {
 
 impl ExeHeader {
     pub fn from_data(data: &[u8]) -> Result<Self, ParseError> {
-        let h: ExeHeader = deserialize(data).unwrap();
+        let h: ExeHeader = match deserialize(data) {
+            Ok(h) => h,
+            Err(_) => return Err(ParseError::TruncatedHeader),
+        };
         if h.signature[0] != 0x4D || h.signature[1] != 0x5A {
             return Err(ParseError::WrongMagic);
         }
+        if h.bytes_in_last_page > PAGE_SIZE || (h.pages == 0 && h.bytes_in_last_page > 0) {
+            // the partial last page is not within the pages of the file
+            return Err(ParseError::TruncatedHeader);
+        }
 
         Ok(h)
     }
@@ -131,15 +167,21 @@ impl ExeHeader {
 
     /// Returns the end offset of the program code inside the EXE file.
     fn exe_data_end_offset(&self) -> usize {
-        let mut code_end = self.pages as usize * 512;
+        let mut code_end = self.pages as usize * PAGE_SIZE as usize;
         if self.bytes_in_last_page > 0 {
-            code_end -= 512 - self.bytes_in_last_page as usize;
+            code_end = code_end.saturating_sub((PAGE_SIZE - self.bytes_in_last_page) as usize);
         }
         code_end
     }
 
+    /// Returns the size of the load module in 16-byte paragraphs.
+    pub fn load_module_paragraphs(&self) -> usize {
+        let size = self.exe_data_end_offset().saturating_sub(self.exe_data_start_offset());
+        size.div_ceil(16)
+    }
+
     /// parses the exe header relocation table
-    fn parse_relocations(&self, data: &[u8]) -> Vec<ExeRelocation> {
+    fn parse_relocations(&self, data: &[u8]) -> Result<Vec<ExeRelocation>, ParseError> {
         let mut relocs = Vec::new();
 
         if self.relocations > 0 {
@@ -147,8 +189,14 @@ impl ExeHeader {
                 println!("relocations ({}):", self.relocations);
             }
             let mut offset = self.reloc_table_offset as usize;
+            if offset + self.relocations as usize * 4 > data.len() {
+                return Err(ParseError::RelocationsOutOfRange);
+            }
             for i in 0..self.relocations {
-                let reloc: ExeRelocation = deserialize(&data[offset..offset+4]).unwrap();
+                let reloc: ExeRelocation = match deserialize(&data[offset..offset+4]) {
+                    Ok(reloc) => reloc,
+                    Err(_) => return Err(ParseError::RelocationsOutOfRange),
+                };
                 if DEBUG_PARSER {
                     println!("  {}: {:?}", i, reloc);
                 }
@@ -156,7 +204,7 @@ impl ExeHeader {
                 offset += 4;
             }
         }
-        relocs
+        Ok(relocs)
     }
 
     fn print_details(&self) {
@@ -185,7 +233,7 @@ impl ExeHeader {
 
         let code_start = self.exe_data_start_offset();
         let code_end   = self.exe_data_end_offset();
-        let code_size  = code_end - code_start;
+        let code_size  = code_end.saturating_sub(code_start);
         println!("- exe data from {:04X} to {:04X} ({} bytes)", code_start, code_end, code_size);
     }
 }
@@ -0,0 +1,63 @@
+use crate::format::{ExeFile, ParseError};
+
+/// returns a exe with a 2 paragraph header, a 4 byte load module and one relocation
+fn exe_data() -> Vec<u8> {
+    let mut data = vec![
+        b'M', b'Z',
+        0x24, 0x00, // bytes in last page
+        0x01, 0x00, // pages
+        0x01, 0x00, // relocations
+        0x02, 0x00, // header paragraphs
+        0x00, 0x00, // min extra paragraphs
+        0xFF, 0xFF, // max extra paragraphs
+        0x00, 0x00, // ss
+        0x00, 0x01, // sp
+        0x00, 0x00, // checksum
+        0x00, 0x00, // ip
+        0x00, 0x00, // cs
+        0x1C, 0x00, // relocation table offset
+        0x00, 0x00, // overlay number
+        0x01, 0x00, 0x00, 0x00, // relocation 0000:0001
+    ];
+    data.resize(0x20, 0);
+    data.extend_from_slice(&[0xB8, 0x00, 0x00, 0xC3]);
+    data
+}
+
+#[test]
+fn can_parse_exe() {
+    let exe = ExeFile::from_data(&exe_data()).ok().unwrap();
+    assert_eq!(vec![0xB8, 0x00, 0x00, 0xC3], exe.program_data);
+    assert_eq!(1, exe.relocs.len());
+    assert_eq!(0x0001, exe.relocs[0].offset);
+}
+
+#[test]
+fn rejects_malformed_exe() {
+    let data = exe_data();
+    assert_eq!(Some(ParseError::TruncatedHeader), ExeFile::from_data(&data[..0x10]).err());
+
+    let mut data = exe_data();
+    data[1] = b'X';
+    assert_eq!(Some(ParseError::WrongMagic), ExeFile::from_data(&data).err());
+
+    // the header claims more paragraphs than the file holds
+    let mut data = exe_data();
+    data[8] = 0x10;
+    assert_eq!(Some(ParseError::TruncatedHeader), ExeFile::from_data(&data).err());
+
+    let mut data = exe_data();
+    data[6] = 0x20;
+    assert_eq!(Some(ParseError::RelocationsOutOfRange), ExeFile::from_data(&data).err());
+
+    // a partial last page without any pages
+    let mut data = exe_data();
+    data[4] = 0x00;
+    assert_eq!(Some(ParseError::TruncatedHeader), ExeFile::from_data(&data).err());
+
+    // more bytes in the last page than a page holds
+    let mut data = exe_data();
+    data[2] = 0x01;
+    data[3] = 0x02;
+    assert_eq!(Some(ParseError::TruncatedHeader), ExeFile::from_data(&data).err());
+}
@@ -5,6 +5,18 @@ pub enum GraphicCard {
 }
 
 impl GraphicCard {
+    /// parses a card name such as "vga" or "tandy"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "cga" => Some(GraphicCard::CGA),
+            "ega" => Some(GraphicCard::EGA),
+            "vga" => Some(GraphicCard::VGA),
+            "tandy" => Some(GraphicCard::Tandy),
+            "pcjr" => Some(GraphicCard::PcJr),
+            _ => None,
+        }
+    }
+
      pub fn is_ega_vga(&self) -> bool {
         match *self {
             GraphicCard::EGA | GraphicCard::VGA => true,
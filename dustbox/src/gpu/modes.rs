@@ -76,7 +76,15 @@ impl VideoModeBlock {
             GraphicCard::VGA => {
                 vga_mode_block().to_vec()
             }
-            _ => panic!("unhandled {:?}", card)
+            GraphicCard::EGA => {
+                ega_mode_block().to_vec()
+            }
+            GraphicCard::CGA => {
+                other_mode_block().iter().filter(|block| block.mode < 0x008).cloned().collect()
+            }
+            GraphicCard::Tandy | GraphicCard::PcJr => {
+                other_mode_block().to_vec()
+            }
         }
     }
 
@@ -122,6 +130,20 @@ impl Default for SpecialMode {
     }
 }
 
+/// modes of the CGA, Tandy and PCjr. modes 08-0A are only available on Tandy and PCjr
+pub fn other_mode_block() -> [VideoModeBlock; 10] {[
+    VideoModeBlock{mode: 0x000, kind: GFXMode::TEXT, swidth: 320, sheight: 200, twidth: 40, theight: 25, cwidth: 8, cheight: 8,  ptotal: 8, pstart: 0xB_8000, plength: 0x0800, htotal: 56,  vtotal: 31,  hdispend: 40, vdispend: 25,  scale_x: 1., scale_y: 1.2, special: Default::default()},
+    VideoModeBlock{mode: 0x001, kind: GFXMode::TEXT, swidth: 320, sheight: 200, twidth: 40, theight: 25, cwidth: 8, cheight: 8,  ptotal: 8, pstart: 0xB_8000, plength: 0x0800, htotal: 56,  vtotal: 31,  hdispend: 40, vdispend: 25,  scale_x: 1., scale_y: 1.2, special: Default::default()},
+    VideoModeBlock{mode: 0x002, kind: GFXMode::TEXT, swidth: 640, sheight: 200, twidth: 80, theight: 25, cwidth: 8, cheight: 8,  ptotal: 4, pstart: 0xB_8000, plength: 0x1000, htotal: 113, vtotal: 31,  hdispend: 80, vdispend: 25,  scale_x: 1., scale_y: 2.4, special: Default::default()},
+    VideoModeBlock{mode: GFXMode::MODE_TEXT_80_25, kind: GFXMode::TEXT, swidth: 640, sheight: 200, twidth: 80, theight: 25, cwidth: 8, cheight: 8,  ptotal: 4, pstart: 0xB_8000, plength: 0x1000, htotal: 113, vtotal: 31,  hdispend: 80, vdispend: 25,  scale_x: 1., scale_y: 2.4, special: Default::default()},
+    VideoModeBlock{mode: 0x004, kind: GFXMode::CGA4, swidth: 320, sheight: 200, twidth: 40, theight: 25, cwidth: 8, cheight: 8,  ptotal: 1, pstart: 0xB_8000, plength: 0x4000, htotal: 56,  vtotal: 127, hdispend: 40, vdispend: 100, scale_x: 1., scale_y: 1.2, special: Default::default()},
+    VideoModeBlock{mode: 0x005, kind: GFXMode::CGA4, swidth: 320, sheight: 200, twidth: 40, theight: 25, cwidth: 8, cheight: 8,  ptotal: 1, pstart: 0xB_8000, plength: 0x4000, htotal: 56,  vtotal: 127, hdispend: 40, vdispend: 100, scale_x: 1., scale_y: 1.2, special: Default::default()},
+    VideoModeBlock{mode: 0x006, kind: GFXMode::CGA2, swidth: 640, sheight: 200, twidth: 80, theight: 25, cwidth: 8, cheight: 8,  ptotal: 1, pstart: 0xB_8000, plength: 0x4000, htotal: 56,  vtotal: 127, hdispend: 40, vdispend: 100, scale_x: 1., scale_y: 2.4, special: Default::default()},
+    VideoModeBlock{mode: 0x008, kind: GFXMode::TANDY16, swidth: 160, sheight: 200, twidth: 20, theight: 25, cwidth: 8, cheight: 8,  ptotal: 8, pstart: 0xB_8000, plength: 0x2000, htotal: 56,  vtotal: 127, hdispend: 40, vdispend: 100, scale_x: 2., scale_y: 1.2, special: Default::default()},
+    VideoModeBlock{mode: 0x009, kind: GFXMode::TANDY16, swidth: 320, sheight: 200, twidth: 40, theight: 25, cwidth: 8, cheight: 8,  ptotal: 8, pstart: 0xB_8000, plength: 0x2000, htotal: 113, vtotal: 63,  hdispend: 80, vdispend: 50,  scale_x: 1., scale_y: 1.2, special: Default::default()},
+    VideoModeBlock{mode: 0x00A, kind: GFXMode::CGA4, swidth: 640, sheight: 200, twidth: 80, theight: 25, cwidth: 8, cheight: 8,  ptotal: 8, pstart: 0xB_8000, plength: 0x2000, htotal: 113, vtotal: 63,  hdispend: 80, vdispend: 50,  scale_x: 1., scale_y: 2.4, special: Default::default()},
+]}
+
 pub fn ega_mode_block() -> [VideoModeBlock; 12] {[
     VideoModeBlock{mode: 0x000, kind: GFXMode::TEXT, swidth: 320, sheight: 350, twidth: 40, theight: 25, cwidth: 8, cheight: 14, ptotal: 8, pstart: 0xB_8000, plength: 0x0800, htotal: 50,  vtotal: 366, hdispend: 40, vdispend: 350, scale_x: 1., scale_y: 1., special: SpecialMode{ega_half_clock: true, ..Default::default()}},
     VideoModeBlock{mode: 0x001, kind: GFXMode::TEXT, swidth: 320, sheight: 350, twidth: 40, theight: 25, cwidth: 8, cheight: 14, ptotal: 8, pstart: 0xB_8000, plength: 0x0800, htotal: 50,  vtotal: 366, hdispend: 40, vdispend: 350, scale_x: 1., scale_y: 1., special: SpecialMode{ega_half_clock: true, ..Default::default()}},
@@ -1,3 +1,5 @@
+use std::ops::RangeInclusive;
+
 use image::{ImageBuffer, Rgb};
 
 use crate::cpu::{CPU, R};
@@ -47,6 +49,10 @@ pub static STATIC_FUNCTIONALITY: [u8; 0x10] = [
 ];
 
 impl Component for GPU {
+    fn io_ports(&self) -> Vec<RangeInclusive<u16>> {
+        vec![0x02C6..=0x02C9, 0x03B0..=0x03DF]
+    }
+
     fn in_u8(&mut self, port: u16) -> Option<u8> {
         match port {
             0x03C7 => Some(self.dac.get_state()),
@@ -554,7 +560,11 @@ impl VideoFrame {
 
 impl GPU {
     pub fn default() -> Self {
-        let generation = GraphicCard::VGA;
+        Self::new(GraphicCard::VGA)
+    }
+
+    /// returns a `generation` graphics card
+    pub fn new(generation: GraphicCard) -> Self {
         let modes = VideoModeBlock::get_mode_block(&generation);
         let mode = modes[3].clone();
         GPU {
@@ -768,7 +778,7 @@ impl GPU {
             GFXMode::TEXT => self.dac.pal = palette::text_palette().to_vec(),
             GFXMode::CGA2 => self.dac.pal = palette::cga_palette_2().to_vec(),
             GFXMode::CGA4 => self.dac.pal = palette::cga_palette().to_vec(), // XXX is this the right cga pal for this mode?
-            GFXMode::EGA => self.dac.pal = palette::ega_palette().to_vec(),
+            GFXMode::EGA | GFXMode::TANDY16 => self.dac.pal = palette::ega_palette().to_vec(),
             GFXMode::VGA => self.dac.pal = palette::vga_palette().to_vec(),
             _ => panic!("set_mode: unhandled palette for video mode {:?}", self.mode.kind),
         }
@@ -1386,8 +1396,8 @@ impl GPU {
         //let mut pos = 3;
         if self.card.is_ega_vga() {
             // ROM signature
-            mmu.write_u16_inc(&mut addr, 0xAA55);
-            mmu.write_u8_inc(&mut addr, (video_bios_size >> 9) as u8);
+            mmu.write_u16(addr.segment(), 0, 0xAA55);
+            mmu.write_u8(addr.segment(), 2, (video_bios_size >> 9) as u8);
 
             // entry point
             mmu.write_u8_inc(&mut addr, 0xFE); // Callback instruction
@@ -1452,7 +1462,12 @@ impl GPU {
             }
         }
 
-        mmu.write_vec(0x1F, self.font_8_second);
+        if self.card.is_ega_vga() {
+            mmu.write_vec(0x1F, self.font_8_second);
+        } else {
+            // the graphics characters table is provided by the user on CGA
+            mmu.write_vec(0x1F, MemoryAddress::RealSegmentOffset(0, 0));
+        }
         self.font_14_alternate = addr;
         self.font_16_alternate = addr;
 
@@ -1515,6 +1530,9 @@ impl GPU {
         if self.card.is_tandy() {
             mmu.write_vec(0x44, self.font_8_first);
         }
+
+        // the tables above are in the video ROM
+        mmu.map_rom(0xC_0000, u32::from(video_bios_size));
     }
 }
 
@@ -15,7 +15,7 @@ fn can_get_palette_entry() {
         0xB8, 0x15, 0x10,   // mov ax,0x1015
         0xCD, 0x10,         // int 0x10
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     machine.execute_instructions(3);
     machine.execute_instruction(); // trigger the interrupt
@@ -39,7 +39,7 @@ fn can_set_palette_entry() {
         0xB8, 0x15, 0x10,   // mov ax,0x1015
         0xCD, 0x10,         // int 0x10
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     machine.execute_instructions(6);
     machine.execute_instruction(); // trigger the interrupt
@@ -58,7 +58,7 @@ fn can_get_font_info() {
         0xB7, 0x06,         // mov bh,0x6     ; get ROM 8x16 font (MCGA, VGA)
         0xCD, 0x10,         // int 0x10       ; es:bp = c000:1700 i dosbox
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     machine.execute_instructions(3);
     machine.execute_instruction(); // trigger the interrupt
@@ -79,7 +79,7 @@ fn can_int10_put_pixel() {
         0xBA, 0x04, 0x00,   // mov dx,0x4       y
         0xCD, 0x10,         // int 0x10
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     machine.execute_instructions(2);
     machine.execute_instruction(); // trigger the interrupt
@@ -113,7 +113,7 @@ let mut machine = Machine::deterministic();
         0xB9, 0x01, 0x00,   // mov cx,0x1       ; count
         0xCD, 0x10,         // int 0x10
     ];
-    machine.load_executable(&code, 0x085F);
+    machine.load_executable(&code, 0x085F).unwrap();
 
     machine.execute_instructions(2);
     machine.execute_instruction(); // trigger the interrupt
@@ -1,6 +1,4 @@
-// TODO later: dont depend on sdl2 in the core crate (process events with something else?)
-
-use sdl2::keyboard::{Keycode, Mod};
+use std::ops::RangeInclusive;
 
 use crate::cpu::{CPU, R, FLAG_ZF};
 use crate::memory::MMU;
@@ -12,19 +10,38 @@ const DEBUG_KEYBOARD: bool = false;
 #[path = "./keyboard_test.rs"]
 mod keyboard_test;
 
+/// 8042 output port bit driving the A20 gate
+const OUTPUT_PORT_A20: u8 = 0b0000_0010;
+
 #[derive(Clone)]
 pub struct Keyboard {
     keypresses: Vec<Keypress>,
     status_register: StatusRegister,
+
+    /// the 8042 output port. bit 0 is the system reset line (active low), bit 1 the A20 gate
+    output_port: u8,
+
+    /// the controller command written to port 0x64 awaiting its data byte on port 0x60
+    command: Option<u8>,
+
+    /// the controller response to read from port 0x60, before any keyboard data
+    response: Option<u8>,
 }
 
 impl Component for Keyboard {
+    fn io_ports(&self) -> Vec<RangeInclusive<u16>> {
+        vec![0x0060..=0x0064, 0x0092..=0x0092]
+    }
+
     fn in_u8(&mut self, port: u16) -> Option<u8> {
         // PORT 0060-006F - KEYBOARD CONTROLLER 804x (8041, 8042) (or PPI (8255) on PC,XT)
         // Note: XT uses ports 60h-63h, AT uses ports 60h-64h
         match port {
             0x0060 => {
                 // keyboard controller data output buffer
+                if let Some(response) = self.response.take() {
+                    return Some(response);
+                }
                 let (scancode, _, keypress) = self.peek_dos_standard_scancode_and_ascii();
                 if let Some(keypress) = keypress {
                     self.consume(&keypress);
@@ -41,21 +58,54 @@ impl Component for Keyboard {
                 // keyboard controller read status
                 Some(self.get_status_register_byte())
             }
+            0x0092 => {
+                // PS/2 system control port A
+                // bit 1: A20 gate active
+                Some(self.output_port & OUTPUT_PORT_A20)
+            }
             _ => None
         }
     }
 
     fn out_u8(&mut self, port: u16, data: u8) -> bool {
         match port {
+            0x0060 => {
+                // keyboard controller data input buffer
+                match self.command.take() {
+                    Some(0xD1) => self.output_port = data | 0b0000_0001,
+                    _ => println!("XXX impl -- keyboard: write keyboard data {:02X}", data),
+                }
+            }
+            0x0064 => {
+                // keyboard controller command
+                match data {
+                    0xD0 => self.response = Some(self.output_port), // read output port
+                    0xD1 => self.command = Some(data),              // write output port
+                    0xDD => self.set_a20(false),
+                    0xDF => self.set_a20(true),
+                    0xFF => {}                                      // pulse output port: no-op
+                    _ => println!("XXX impl -- keyboard: controller command {:02X}", data),
+                }
+            }
             0x0061 => {
                 // keyboard controller port b OR ppi programmable periphial interface (XT only) - which mode are we in?
                 println!("XXX impl -- keyboard: write keyboard controller port b {:02X}", data);
             }
+            0x0092 => {
+                // PS/2 system control port A
+                // bit 1: enable A20 gate ("fast A20")
+                // XXX bit 0, fast reset, is ignored
+                self.set_a20(data & 0b0000_0010 != 0);
+            }
             _ => return false
         }
         true
     }
 
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
     fn int(&mut self, int: u8, cpu: &mut CPU, mmu: &mut MMU) -> bool {
         if int != 0x16 {
             return false;
@@ -85,7 +135,7 @@ impl Component for Keyboard {
 
                 // ZF set if no keystroke available
                 mmu.set_flag(FLAG_ZF, ah == 0);
-                //cpu.regs.flags.zero = ah == 0;
+                //cpu.regs.flags.set_zero(ah == 0);
 
                 if DEBUG_KEYBOARD {
                     println!("KEYBOARD - CHECK FOR KEYSTROKE, returns ah {:02x}, al {:02x}", ah, al);
@@ -112,7 +162,7 @@ impl Component for Keyboard {
                 // AL = ASCII character
                 println!("XXX impl KEYBOARD - CHECK FOR ENHANCED KEYSTROKE");
                 mmu.set_flag(FLAG_ZF, true);
-                //cpu.regs.flags.zero = true;
+                //cpu.regs.flags.set_zero(true);
             }
             0x92 => {
                 // KEYB.COM KEYBOARD CAPABILITIES CHECK (not an actual function!)
@@ -137,6 +187,22 @@ impl Keyboard {
         Self {
             keypresses: Vec::new(),
             status_register: StatusRegister::default(),
+            output_port: 0b0000_0001,
+            command: None,
+            response: None,
+        }
+    }
+
+    /// returns true if the controller output port enables the A20 gate
+    pub fn a20_enabled(&self) -> bool {
+        self.output_port & OUTPUT_PORT_A20 != 0
+    }
+
+    pub fn set_a20(&mut self, enabled: bool) {
+        if enabled {
+            self.output_port |= OUTPUT_PORT_A20;
+        } else {
+            self.output_port &= !OUTPUT_PORT_A20;
         }
     }
 
@@ -144,8 +210,8 @@ impl Keyboard {
         !self.keypresses.is_empty()
     }
 
-    pub fn add_keypress(&mut self, keycode: Keycode, modifier: Mod) {
-        let keypress = Keypress{keycode, modifier};
+    pub fn add_keypress(&mut self, key: Key, modifier: Modifier) {
+        let keypress = Keypress{key, modifier};
         if DEBUG_KEYBOARD {
             println!("keyboard: add_keypress {:?}", keypress);
         }
@@ -194,7 +260,7 @@ impl Keyboard {
     /// returns scancode, ascii, keypress
     pub fn peek_dos_standard_scancode_and_ascii(&self) -> (u8, u8, Option<Keypress>) {
         if let Some(keypress) = self.peek_keypress() {
-            let (ah, al) = map_sdl_to_dos_standard_codes(&keypress);
+            let (ah, al) = map_to_dos_standard_codes(&keypress);
             if DEBUG_KEYBOARD {
                 println!("keyboard: peek_dos_standard_scancode_and_ascii returns scancode {:02X}, ascii {:02X}, {:?}", ah, al, keypress);
             }
@@ -209,7 +275,6 @@ impl Keyboard {
 
     fn find_keypress_index(&self, keypress: &Keypress) -> Option<usize> {
         for (idx, x) in self.keypresses.iter().enumerate() {
-            println!("{}", x.keycode);
             if x == keypress {
                 return Some(idx);
             }
@@ -307,212 +372,234 @@ impl StatusRegister {
     }
 }
 
+/// a key on the PC keyboard. frontends translates their key events to these
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key {
+    Escape,
+    Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9, Num0,
+    Minus, Equals, Backspace, Tab,
+    Q, W, E, R, T, Y, U, I, O, P,
+    LeftBracket, RightBracket, Return,
+    A, S, D, F, G, H, J, K, L,
+    Semicolon, Quote, Backquote, Backslash,
+    Z, X, C, V, B, N, M,
+    Comma, Period, Slash,
+    KpAsterisk, Space,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10,
+    Home, Up, PageUp, KpMinus, Left, KpCenter, Right, KpPlus, End, Down, PageDown, Insert, Delete,
+}
+
+/// the modifier key held down during a keypress
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Modifier {
+    None,
+    Shift,
+    Ctrl,
+    Alt,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Keypress {
-    keycode: Keycode,
-    modifier: Mod,
+    key: Key,
+    modifier: Modifier,
 }
 
 /// returns keycodes as specified in https://sites.google.com/site/pcdosretro/scancodes
 impl Keypress {
     /// keycodes with no modifier key, returns scancode, ascii
     pub fn to_std_normal(&self) -> (u8, u8) {
-        match self.keycode {
-            Keycode::Escape => (0x01, 0x1B),
-            Keycode::Num1 => (0x02, 0x31),
-            Keycode::Num2 => (0x03, 0x32),
-            Keycode::Num3 => (0x04, 0x33),
-            Keycode::Num4 => (0x05, 0x34),
-            Keycode::Num5 => (0x06, 0x35),
-            Keycode::Num6 => (0x07, 0x36),
-            Keycode::Num7 => (0x08, 0x37),
-            Keycode::Num8 => (0x09, 0x38),
-            Keycode::Num9 => (0x0A, 0x39),
-            Keycode::Num0 => (0x0B, 0x30),
-            Keycode::Minus => (0x0C, 0x2D),
-            Keycode::Equals => (0x0D, 0x3D),
-            Keycode::Backspace => (0x0E, 0x08),
-            Keycode::Tab => (0x0F, 0x09),
-            Keycode::Q => (0x10, 0x71),
-            Keycode::W => (0x11, 0x77),
-            Keycode::E => (0x12, 0x65),
-            Keycode::R => (0x13, 0x72),
-            Keycode::T => (0x14, 0x74),
-            Keycode::Y => (0x15, 0x79),
-            Keycode::U => (0x16, 0x75),
-            Keycode::I => (0x17, 0x69),
-            Keycode::O => (0x18, 0x6F),
-            Keycode::P => (0x19, 0x70),
-            Keycode::LeftBracket => (0x1A, 0x5B),  // XXX [
-            Keycode::RightBracket => (0x1B, 0x5D), // XXX ]
-            Keycode::Return => (0x1C, 0x0D),
+        match self.key {
+            Key::Escape => (0x01, 0x1B),
+            Key::Num1 => (0x02, 0x31),
+            Key::Num2 => (0x03, 0x32),
+            Key::Num3 => (0x04, 0x33),
+            Key::Num4 => (0x05, 0x34),
+            Key::Num5 => (0x06, 0x35),
+            Key::Num6 => (0x07, 0x36),
+            Key::Num7 => (0x08, 0x37),
+            Key::Num8 => (0x09, 0x38),
+            Key::Num9 => (0x0A, 0x39),
+            Key::Num0 => (0x0B, 0x30),
+            Key::Minus => (0x0C, 0x2D),
+            Key::Equals => (0x0D, 0x3D),
+            Key::Backspace => (0x0E, 0x08),
+            Key::Tab => (0x0F, 0x09),
+            Key::Q => (0x10, 0x71),
+            Key::W => (0x11, 0x77),
+            Key::E => (0x12, 0x65),
+            Key::R => (0x13, 0x72),
+            Key::T => (0x14, 0x74),
+            Key::Y => (0x15, 0x79),
+            Key::U => (0x16, 0x75),
+            Key::I => (0x17, 0x69),
+            Key::O => (0x18, 0x6F),
+            Key::P => (0x19, 0x70),
+            Key::LeftBracket => (0x1A, 0x5B),  // XXX [
+            Key::RightBracket => (0x1B, 0x5D), // XXX ]
+            Key::Return => (0x1C, 0x0D),
             // 0x1D = CTRL but cant be read as its a modifier
-            Keycode::A => (0x1E, 0x61),
-            Keycode::S => (0x1F, 0x73),
-            Keycode::D => (0x20, 0x64),
-            Keycode::F => (0x21, 0x66),
-            Keycode::G => (0x22, 0x67),
-            Keycode::H => (0x23, 0x68),
-            Keycode::J => (0x24, 0x6A),
-            Keycode::K => (0x25, 0x6B),
-            Keycode::L => (0x26, 0x6C),
-            Keycode::Colon => (0x27, 0x3B), // XXX ; :
-            Keycode::Quote => (0x28, 0x27), // XXX ' "
-            Keycode::Caret => (0x29, 0x60), // XXX ` ~
+            Key::A => (0x1E, 0x61),
+            Key::S => (0x1F, 0x73),
+            Key::D => (0x20, 0x64),
+            Key::F => (0x21, 0x66),
+            Key::G => (0x22, 0x67),
+            Key::H => (0x23, 0x68),
+            Key::J => (0x24, 0x6A),
+            Key::K => (0x25, 0x6B),
+            Key::L => (0x26, 0x6C),
+            Key::Semicolon => (0x27, 0x3B), // XXX ; :
+            Key::Quote => (0x28, 0x27), // XXX ' "
+            Key::Backquote => (0x29, 0x60), // XXX ` ~
             // 0x2A = Left Shift
-            Keycode::Backslash => (0x2B, 0x5C), // XXX \ |
-            Keycode::Z => (0x2C, 0x7A),
-            Keycode::X => (0x2D, 0x78),
-            Keycode::C => (0x2E, 0x63),
-            Keycode::V => (0x2F, 0x76),
-            Keycode::B => (0x30, 0x62),
-            Keycode::N => (0x31, 0x6E),
-            Keycode::M => (0x32, 0x6D),
-            Keycode::Comma => (0x33, 0x2C), // XXX , <
-            Keycode::Period => (0x34, 0x2E), // XXX . >
-            Keycode::Slash => (0x35, 0x2F),  // XXX / ?
+            Key::Backslash => (0x2B, 0x5C), // XXX \ |
+            Key::Z => (0x2C, 0x7A),
+            Key::X => (0x2D, 0x78),
+            Key::C => (0x2E, 0x63),
+            Key::V => (0x2F, 0x76),
+            Key::B => (0x30, 0x62),
+            Key::N => (0x31, 0x6E),
+            Key::M => (0x32, 0x6D),
+            Key::Comma => (0x33, 0x2C), // XXX , <
+            Key::Period => (0x34, 0x2E), // XXX . >
+            Key::Slash => (0x35, 0x2F),  // XXX / ?
             // 0x36 = Right Shift
-            Keycode::Asterisk => (0x37, 0x2A),
+            Key::KpAsterisk => (0x37, 0x2A),
             // 0x38 = Alt
-            Keycode::Space => (0x39, 0x20),
+            Key::Space => (0x39, 0x20),
             // 0x3A = Caps Lock
-            Keycode::F1 => (0x3B, 0x00),
-            Keycode::F2 => (0x3C, 0x00),
-            Keycode::F3 => (0x3D, 0x00),
-            Keycode::F4 => (0x3E, 0x00),
-            Keycode::F5 => (0x3F, 0x00),
-            Keycode::F6 => (0x40, 0x00),
-            Keycode::F7 => (0x41, 0x00),
-            Keycode::F8 => (0x42, 0x00),
-            Keycode::F9 => (0x43, 0x00),
-            Keycode::F10 => (0x44, 0x00),
+            Key::F1 => (0x3B, 0x00),
+            Key::F2 => (0x3C, 0x00),
+            Key::F3 => (0x3D, 0x00),
+            Key::F4 => (0x3E, 0x00),
+            Key::F5 => (0x3F, 0x00),
+            Key::F6 => (0x40, 0x00),
+            Key::F7 => (0x41, 0x00),
+            Key::F8 => (0x42, 0x00),
+            Key::F9 => (0x43, 0x00),
+            Key::F10 => (0x44, 0x00),
             // 0x45 = Num Lock
             // 0x46 = Scroll Lock
-            Keycode::Home => (0x47, 0x00),
-            Keycode::Up => (0x48, 0x00),
-            Keycode::PageUp => (0x49, 0x00),
-            Keycode::KpMinus => (0x4A, 0x2D), // XXX numeric keypad minus
-            Keycode::Left => (0x4B, 0x00),
-            // XXX Keycode::KpClearEntry => (0x00, 0x00),
-            Keycode::Right => (0x4D, 0x00),
-            Keycode::KpPlus => (0x4E, 0x2B), // XXX numeric keypad plus
-            Keycode::End => (0x4F, 0x00),
-            Keycode::Down => (0x50, 0x00),
-            Keycode::PageDown => (0x51, 0x00),
-            Keycode::Insert => (0x52, 0x00),
-            Keycode::Delete => (0x53, 0x00),
+            Key::Home => (0x47, 0x00),
+            Key::Up => (0x48, 0x00),
+            Key::PageUp => (0x49, 0x00),
+            Key::KpMinus => (0x4A, 0x2D), // XXX numeric keypad minus
+            Key::Left => (0x4B, 0x00),
+            // XXX Key::KpCenter => (0x00, 0x00),
+            Key::Right => (0x4D, 0x00),
+            Key::KpPlus => (0x4E, 0x2B), // XXX numeric keypad plus
+            Key::End => (0x4F, 0x00),
+            Key::Down => (0x50, 0x00),
+            Key::PageDown => (0x51, 0x00),
+            Key::Insert => (0x52, 0x00),
+            Key::Delete => (0x53, 0x00),
             _ => {
-                println!("unhandled NORMAL keycode mapping for {:#?}", self.keycode);
+                println!("unhandled NORMAL keycode mapping for {:#?}", self.key);
                 (0, 0)
             }
         }
     }
 
     pub fn to_std_shift(&self) -> (u8, u8) {
-        match self.keycode {
-            Keycode::Escape => (0x01, 0x1B),
-            Keycode::Num1 => (0x02, 0x21),
-            Keycode::Num2 => (0x03, 0x40),
-            Keycode::Num3 => (0x04, 0x23),
-            Keycode::Num4 => (0x05, 0x24),
-            Keycode::Num5 => (0x06, 0x25),
-            Keycode::Num6 => (0x07, 0x5E),
-            Keycode::Num7 => (0x08, 0x26),
-            Keycode::Num8 => (0x09, 0x2A),
-            Keycode::Num9 => (0x0A, 0x28),
-            Keycode::Num0 => (0x0B, 0x29),
-            Keycode::Minus => (0x0C, 0x5F),
-            Keycode::Equals => (0x0D, 0x2B),
-            Keycode::Backspace => (0x0E, 0x08),
-            Keycode::Tab => (0x0F, 0x00),
-            Keycode::Q => (0x10, 0x51),
-            Keycode::W => (0x11, 0x57),
-            Keycode::E => (0x12, 0x45),
-            Keycode::R => (0x13, 0x52),
-            Keycode::T => (0x14, 0x54),
-            Keycode::Y => (0x15, 0x59),
-            Keycode::U => (0x16, 0x55),
-            Keycode::I => (0x17, 0x49),
-            Keycode::O => (0x18, 0x4F),
-            Keycode::P => (0x19, 0x50),
-            Keycode::LeftBracket => (0x1A, 0x7B),  // XXX [ {
-            Keycode::RightBracket => (0x1B, 0x7D), // XXX ] }
-            Keycode::Return => (0x1C, 0x0D),
+        match self.key {
+            Key::Escape => (0x01, 0x1B),
+            Key::Num1 => (0x02, 0x21),
+            Key::Num2 => (0x03, 0x40),
+            Key::Num3 => (0x04, 0x23),
+            Key::Num4 => (0x05, 0x24),
+            Key::Num5 => (0x06, 0x25),
+            Key::Num6 => (0x07, 0x5E),
+            Key::Num7 => (0x08, 0x26),
+            Key::Num8 => (0x09, 0x2A),
+            Key::Num9 => (0x0A, 0x28),
+            Key::Num0 => (0x0B, 0x29),
+            Key::Minus => (0x0C, 0x5F),
+            Key::Equals => (0x0D, 0x2B),
+            Key::Backspace => (0x0E, 0x08),
+            Key::Tab => (0x0F, 0x00),
+            Key::Q => (0x10, 0x51),
+            Key::W => (0x11, 0x57),
+            Key::E => (0x12, 0x45),
+            Key::R => (0x13, 0x52),
+            Key::T => (0x14, 0x54),
+            Key::Y => (0x15, 0x59),
+            Key::U => (0x16, 0x55),
+            Key::I => (0x17, 0x49),
+            Key::O => (0x18, 0x4F),
+            Key::P => (0x19, 0x50),
+            Key::LeftBracket => (0x1A, 0x7B),  // XXX [ {
+            Key::RightBracket => (0x1B, 0x7D), // XXX ] }
+            Key::Return => (0x1C, 0x0D),
             // 0x1D = CTRL but cant be read as its a modifier
-            Keycode::A => (0x1E, 0x41),
-            Keycode::S => (0x1F, 0x53),
-            Keycode::D => (0x20, 0x44),
-            Keycode::F => (0x21, 0x46),
-            Keycode::G => (0x22, 0x47),
-            Keycode::H => (0x23, 0x48),
-            Keycode::J => (0x24, 0x4A),
-            Keycode::K => (0x25, 0x4B),
-            Keycode::L => (0x26, 0x4C),
-            Keycode::Colon => (0x27, 0x3A), // XXX ; :
-            Keycode::Quote => (0x28, 0x22), // XXX ' "
-            Keycode::Caret => (0x29, 0x7E), // XXX ` ~
+            Key::A => (0x1E, 0x41),
+            Key::S => (0x1F, 0x53),
+            Key::D => (0x20, 0x44),
+            Key::F => (0x21, 0x46),
+            Key::G => (0x22, 0x47),
+            Key::H => (0x23, 0x48),
+            Key::J => (0x24, 0x4A),
+            Key::K => (0x25, 0x4B),
+            Key::L => (0x26, 0x4C),
+            Key::Semicolon => (0x27, 0x3A), // XXX ; :
+            Key::Quote => (0x28, 0x22), // XXX ' "
+            Key::Backquote => (0x29, 0x7E), // XXX ` ~
             // 0x2A = Left Shift
-            Keycode::Backslash => (0x2B, 0x7C), // XXX \ |
-            Keycode::Z => (0x2C, 0x5A),
-            Keycode::X => (0x2D, 0x58),
-            Keycode::C => (0x2E, 0x43),
-            Keycode::V => (0x2F, 0x56),
-            Keycode::B => (0x30, 0x42),
-            Keycode::N => (0x31, 0x4E),
-            Keycode::M => (0x32, 0x4D),
-            Keycode::Comma => (0x33, 0x3C), // XXX , <
-            Keycode::Period => (0x34, 0x3E), // XXX . >
-            Keycode::Slash => (0x35, 0x3F),  // XXX / ?
+            Key::Backslash => (0x2B, 0x7C), // XXX \ |
+            Key::Z => (0x2C, 0x5A),
+            Key::X => (0x2D, 0x58),
+            Key::C => (0x2E, 0x43),
+            Key::V => (0x2F, 0x56),
+            Key::B => (0x30, 0x42),
+            Key::N => (0x31, 0x4E),
+            Key::M => (0x32, 0x4D),
+            Key::Comma => (0x33, 0x3C), // XXX , <
+            Key::Period => (0x34, 0x3E), // XXX . >
+            Key::Slash => (0x35, 0x3F),  // XXX / ?
             // 0x36 = Right Shift
-            Keycode::Asterisk => (0x37, 0x2A),
+            Key::KpAsterisk => (0x37, 0x2A),
             // 0x38 = Alt
-            Keycode::Space => (0x39, 0x20),
+            Key::Space => (0x39, 0x20),
             // 0x3A = Caps Lock
-            Keycode::F1 => (0x54, 0x00),
-            Keycode::F2 => (0x55, 0x00),
-            Keycode::F3 => (0x56, 0x00),
-            Keycode::F4 => (0x57, 0x00),
-            Keycode::F5 => (0x58, 0x00),
-            Keycode::F6 => (0x59, 0x00),
-            Keycode::F7 => (0x5A, 0x00),
-            Keycode::F8 => (0x5B, 0x00),
-            Keycode::F9 => (0x5C, 0x00),
-            Keycode::F10 => (0x5D, 0x00),
+            Key::F1 => (0x54, 0x00),
+            Key::F2 => (0x55, 0x00),
+            Key::F3 => (0x56, 0x00),
+            Key::F4 => (0x57, 0x00),
+            Key::F5 => (0x58, 0x00),
+            Key::F6 => (0x59, 0x00),
+            Key::F7 => (0x5A, 0x00),
+            Key::F8 => (0x5B, 0x00),
+            Key::F9 => (0x5C, 0x00),
+            Key::F10 => (0x5D, 0x00),
             // 0x45 = Num Lock
             // 0x46 = Scroll Lock
-            Keycode::Home => (0x47, 0x37),
-            Keycode::Up => (0x48, 0x38),
-            Keycode::PageUp => (0x49, 0x39),
-            Keycode::KpMinus => (0x4A, 0x2D), // XXX numeric keypad minus
-            Keycode::Left => (0x4B, 0x34),
-            Keycode::KpClearEntry => (0x4C, 0x35), // XXX center numeric keyb
-            Keycode::Right => (0x4D, 0x36),
-            Keycode::KpPlus => (0x4E, 0x2B), // XXX numeric keypad plus
-            Keycode::End => (0x4F, 0x31),
-            Keycode::Down => (0x50, 0x32),
-            Keycode::PageDown => (0x51, 0x33),
-            Keycode::Insert => (0x52, 0x30),
-            Keycode::Delete => (0x53, 0x2E),
-            _ => {
-                println!("unhandled SHIFT keycode mapping for {:#?}", self.keycode);
-                (0, 0)
-            }
+            Key::Home => (0x47, 0x37),
+            Key::Up => (0x48, 0x38),
+            Key::PageUp => (0x49, 0x39),
+            Key::KpMinus => (0x4A, 0x2D), // XXX numeric keypad minus
+            Key::Left => (0x4B, 0x34),
+            Key::KpCenter => (0x4C, 0x35), // XXX center numeric keyb
+            Key::Right => (0x4D, 0x36),
+            Key::KpPlus => (0x4E, 0x2B), // XXX numeric keypad plus
+            Key::End => (0x4F, 0x31),
+            Key::Down => (0x50, 0x32),
+            Key::PageDown => (0x51, 0x33),
+            Key::Insert => (0x52, 0x30),
+            Key::Delete => (0x53, 0x2E),
         }
     }
 
     pub fn to_std_ctrl(&self) -> (u8, u8) {
-        match self.keycode {
+        match self.key {
             _ => {
-                println!("unhandled CTRL keycode mapping for {:#?}", self.keycode);
+                println!("unhandled CTRL keycode mapping for {:#?}", self.key);
                 (0, 0)
             }
         }
     }
 
     pub fn to_std_alt(&self) -> (u8, u8) {
-        match self.keycode {
+        match self.key {
             _ => {
-                println!("unhandled ALT keycode mapping for {:#?}", self.keycode);
+                println!("unhandled ALT keycode mapping for {:#?}", self.key);
                 (0, 0)
             }
         }
@@ -520,22 +607,11 @@ impl Keypress {
 }
 
 // returns scancode, ascii
-fn map_sdl_to_dos_standard_codes(keypress: &Keypress) -> (u8, u8) {
-    match keypress.keycode {
-        // misc mappings
-        Keycode::LGui => (0, 0),
-        Keycode::LShift => (0, 0),
-        Keycode::RShift => (0, 0),
-        _ => {
-            if keypress.modifier == Mod::LSHIFTMOD || keypress.modifier == Mod::RSHIFTMOD {
-                keypress.to_std_shift()
-            } else if keypress.modifier == Mod::LCTRLMOD || keypress.modifier == Mod::RCTRLMOD {
-                keypress.to_std_ctrl()
-            } else if keypress.modifier == Mod::LALTMOD || keypress.modifier == Mod::RALTMOD {
-                keypress.to_std_alt()
-            } else {
-                keypress.to_std_normal()
-            }
-        }
+fn map_to_dos_standard_codes(keypress: &Keypress) -> (u8, u8) {
+    match keypress.modifier {
+        Modifier::None => keypress.to_std_normal(),
+        Modifier::Shift => keypress.to_std_shift(),
+        Modifier::Ctrl => keypress.to_std_ctrl(),
+        Modifier::Alt => keypress.to_std_alt(),
     }
 }
@@ -1,6 +1,4 @@
-use sdl2::keyboard::{Keycode, Mod};
-
-use crate::keyboard::{Keyboard, StatusRegister};
+use crate::keyboard::{Keyboard, StatusRegister, Key, Modifier};
 use crate::machine::Component;
 
 #[test]
@@ -17,7 +15,7 @@ fn can_read_keys_from_io_ports() {
     assert_eq!(Some(0x14), keyboard.in_u8(0x64));
 
     // inject key press
-    keyboard.add_keypress(Keycode::Escape, Mod::NOMOD);
+    keyboard.add_keypress(Key::Escape, Modifier::None);
 
     // in al,0x64
     assert_eq!(Some(0x15), keyboard.in_u8(0x64));
@@ -35,8 +33,8 @@ fn consumes_keypress_queue() {
     assert_eq!(false, keyboard.has_queued_presses());
 
     // inject key press
-    keyboard.add_keypress(Keycode::Escape, Mod::NOMOD);
-    keyboard.add_keypress(Keycode::Escape, Mod::NOMOD);
+    keyboard.add_keypress(Key::Escape, Modifier::None);
+    keyboard.add_keypress(Key::Escape, Modifier::None);
     assert_eq!(true, keyboard.has_queued_presses());
 
     // read it
@@ -51,3 +49,13 @@ fn consumes_keypress_queue() {
     keyboard.consume(&keypress);
     assert_eq!(false, keyboard.has_queued_presses());
 }
+
+#[test]
+fn can_map_modifier_keys() {
+    let mut keyboard = Keyboard::default();
+    keyboard.add_keypress(Key::A, Modifier::Shift);
+    assert_eq!((0x1E, 0x41), keyboard.consume_dos_standard_scancode_and_ascii());
+
+    keyboard.add_keypress(Key::A, Modifier::None);
+    assert_eq!((0x1E, 0x61), keyboard.consume_dos_standard_scancode_and_ascii());
+}
@@ -1,25 +1,32 @@
 use std::{mem, u8};
+use std::any::Any;
+use std::ops::RangeInclusive;
+use std::f64::consts;
 use std::num::Wrapping;
 use std::fs::File;
 use std::path::Path;
 use std::io::{BufWriter, Write};
 use std::io;
+use std::error::Error;
+use std::fmt;
 
 use crate::bios::BIOS;
-use crate::cpu::{CPU, Op, Invalid, R, RegisterState};
-use crate::cpu::{Instruction, RepeatMode, Exception};
-use crate::cpu::{Parameter};
-use crate::format::ExeFile;
-use crate::gpu::GFXMode;
+use crate::cpu::{CPU, Op, Invalid, R, RegisterState, DR6_BS, FLAG_CF, r32};
+use crate::cpu::{Instruction, RepeatMode, Exception, CpuModel, instruction_cycles, rep_setup_cycles};
+use crate::cpu::{Parameter, OperandSize, AddressSize};
+use crate::cpu::{DescriptorTable, CR0_PE, CR0_TS, DESC_TSS16, DESC_LDT, DESC_TSS16_BUSY, DESC_CALL_GATE16, DESC_TASK_GATE, DESC_TSS32, DESC_TSS32_BUSY, DESC_CALL_GATE32};
+use crate::cpu::{FPU, FPR80, FPU_SW_IE, FPU_SW_ZE, bcd_to_fpr80};
+use crate::format::{ExeFile, ParseError};
+use crate::gpu::{GFXMode, GraphicCard};
 use crate::gpu::GPU as GPUComponent;
-use crate::dos::DOS;
+use crate::dos::{DOS, EMS, EMS_ENTRY, EMS_SEG, XMS, XMS_ENTRY};
 use crate::hex::hex_bytes;
 use crate::keyboard::Keyboard as KeyboardComponent;
-use crate::memory::{MMU, MemoryAddress};
+use crate::memory::{MMU, FlatMemory, MemoryAddress};
 use crate::mouse::Mouse as MouseComponent;
 use crate::ndisasm::ndisasm_first_instr;
 use crate::pic::PIC as PICComponent;
-use crate::pit::PIT as PITComponent;
+use crate::pit::{PIT as PITComponent, PIT_CLOCK_HZ};
 use crate::storage::Storage as StorageComponent;
 use crate::tools::read_binary;
 
@@ -41,16 +48,13 @@ pub const DEBUG_MARK_STACK: bool = false;
 /// value used to taint the stack, to notice on errors or small com apps just using "retn" to exit to DOS
 pub const STACK_MARKER: u16 = 0xDEAD;
 
-pub enum MachineComponent {
-    Storage(StorageComponent),
-    Keyboard(KeyboardComponent),
-    Mouse(MouseComponent),
-    PIC(PICComponent),
-    PIT(PITComponent),
-    GPU(GPUComponent),
-}
+/// a device attached to the machine, see Machine::add_device
+pub trait Component: Any {
+    /// returns the I/O ports handled by in_u8 and out_u8
+    fn io_ports(&self) -> Vec<RangeInclusive<u16>> {
+        Vec::new()
+    }
 
-pub trait Component {
     /// returns Some<u8> if read was handled
     fn in_u8(&mut self, _port: u16) -> Option<u8> {
         None
@@ -65,13 +69,131 @@ pub trait Component {
     fn int(&mut self, _int: u8, _cpu: &mut CPU, _mmu: &mut MMU) -> bool {
         false
     }
+
+    /// restores the power-on state, called by Machine::hard_reset
+    fn reset(&mut self) {}
+
+    /// called after each instruction with the cpu cycles it used.
+    /// returns the IRQ (0-15) to raise, if any
+    fn tick(&mut self, _cycles: usize, _mmu: &mut MMU) -> Option<u8> {
+        None
+    }
 }
 
+/// the hardware to emulate, see Machine::with_config
+#[derive(Clone, Debug)]
+pub struct MachineConfig {
+    /// installed memory in KiB, of which the first 640 KiB is conventional memory
+    pub memory_kb: u32,
+
+    pub cpu_model: CpuModel,
+
+    /// attaches a math coprocessor. the 80486 has one built in
+    pub fpu: bool,
+
+    pub graphic_card: GraphicCard,
+
+    /// attaches a mouse with a INT 33h driver
+    pub mouse: bool,
+
+    /// expanded memory in KiB provided by the INT 67h driver, 0 for none. at most 32 MiB is used
+    pub ems_kb: u32,
+
+    /// segment of the 64 KiB EMS page frame
+    pub ems_frame_segment: u16,
+
+    /// if set, the timer is not started and time services returns fixed values (used by tests)
+    pub deterministic: bool,
+}
+
+impl Default for MachineConfig {
+    fn default() -> Self {
+        MachineConfig {
+            memory_kb: 4096,
+            cpu_model: CpuModel::default(),
+            fpu: true,
+            graphic_card: GraphicCard::VGA,
+            mouse: true,
+            ems_kb: 1024,
+            ems_frame_segment: 0xE000,
+            deterministic: false,
+        }
+    }
+}
+
+impl MachineConfig {
+    /// returns the conventional memory size in KiB, as reported by INT 12h
+    pub fn conventional_memory_kb(&self) -> u16 {
+        self.memory_kb.min(640) as u16
+    }
+
+    /// returns true if a math coprocessor is present, as reported by INT 11h
+    pub fn has_fpu(&self) -> bool {
+        self.fpu || self.cpu_model == CpuModel::I80486
+    }
+}
+
+/// a reason for a program to fail to load, see Machine::load_executable_file
+#[derive(Debug)]
+pub enum LoadError {
+    /// the file could not be read
+    Io(io::Error),
+
+    /// the file is not a valid executable
+    Parse(ParseError),
+
+    /// the program needs more conventional memory than is available, in paragraphs
+    OutOfMemory { needed: usize, available: usize },
+
+    /// a relocation patches a word outside of the load module, at segment:offset
+    RelocationOutOfRange(u16, u16),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Parse(e) => write!(f, "{}", e),
+            LoadError::OutOfMemory { needed, available } =>
+                write!(f, "not enough memory to load exe, {:04X} paragraphs needed but {:04X} available", needed, available),
+            LoadError::RelocationOutOfRange(segment, offset) =>
+                write!(f, "exe relocation {:04X}:{:04X} is outside of the load module", segment, offset),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io(e) => Some(e),
+            LoadError::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+impl From<ParseError> for LoadError {
+    fn from(e: ParseError) -> Self {
+        LoadError::Parse(e)
+    }
+}
+
+/// marks a I/O port without a device in Machine::io_map
+const NO_DEVICE: u8 = 0xFF;
+
 pub struct Machine {
     pub mmu: MMU,
     pub bios: BIOS,
     pub cpu: CPU,
     dos: DOS,
+    xms: XMS,
+    ems: Option<EMS>,
 
     /// base offset where rom was loaded
     pub rom_base: MemoryAddress,
@@ -80,55 +202,95 @@ pub struct Machine {
     pub rom_length: usize,
 
     /// handlers for i/o ports and interrupts
-    components: Vec<MachineComponent>,
+    devices: Vec<Box<dyn Component>>,
+
+    /// index in `devices` of the handler of each I/O port, or NO_DEVICE
+    io_map: Vec<u8>,
 
     /// if set, writes opcode trace to `trace_file`
     trace_file: Option<File>,
 
     /// if set, limits the execution to `trace_count` instructions
     trace_count: Option<usize>,
+
+    /// cpu cycle count when the next video scanline is due
+    next_scanline_cycle: usize,
+
+    /// cpu cycle count when the next timer tick is due
+    next_timer_cycle: usize,
+
+    /// cpu cycle count when the devices were last ticked
+    tick_cycle: usize,
+
+    /// the emulated hardware, restored by hard_reset
+    config: MachineConfig,
 }
 
 impl Machine {
      // returns a non-deterministic Machine instance
     pub fn default() -> Self {
-        let mut m = Self::deterministic();
-        m.pit_mut().init();
-        m
+        Self::with_config(MachineConfig::default())
     }
 
     pub fn deterministic() -> Self {
-        let mut mmu = MMU::default();
+        Self::with_config(MachineConfig { deterministic: true, ..MachineConfig::default() })
+    }
+
+    /// returns a Machine emulating the hardware described by `config`
+    pub fn with_config(config: MachineConfig) -> Self {
+        // the address space below 1 MiB and the HMA are always backed by memory
+        let memory_size = (config.memory_kb as usize * 1024).max(0x11_0000);
+        let mut mmu = MMU::with_memory(FlatMemory::with_size(memory_size));
         let mut bios = BIOS::default();
-        bios.init(&mut mmu);
+        bios.init(&mut mmu, &config);
+        let xms = XMS::new(mmu.memory.data.len().min(config.memory_kb as usize * 1024) as u32);
+        xms.init(&mut mmu);
+        let ems = if config.ems_kb > 0 {
+            let ems = EMS::new(config.ems_kb, config.ems_frame_segment);
+            ems.init(&mut mmu);
+            Some(ems)
+        } else {
+            None
+        };
+
+        let mut cpu = CPU::deterministic();
+        cpu.set_model(config.cpu_model);
 
         let mut m = Machine {
-            cpu: CPU::deterministic(),
+            cpu,
             mmu,
             bios,
             dos: DOS::default(),
+            xms,
+            ems,
             rom_base: MemoryAddress::default_real(),
             rom_length: 0,
             trace_file: None,
             trace_count: None,
-            components: Vec::new(),
+            devices: Vec::new(),
+            io_map: vec![NO_DEVICE; 0x1_0000],
+            next_scanline_cycle: 0,
+            next_timer_cycle: 0,
+            tick_cycle: 0,
+            config: config.clone(),
         };
 
-        m.register_components();
+        m.register_components(&config);
+        m.update_a20();
+        m.next_scanline_cycle = m.scanline_cycles();
+        m.next_timer_cycle = m.timer_cycles();
+        if !config.deterministic {
+            m.pit_mut().init();
+        }
         m
     }
 
     /// Enables writing of opcode trace to file.
     /// The format tries to be similar to dosbox debugger "LOGS" format.
-    pub fn write_trace_to(&mut self, filename: &str) {
-        let trace_path = Path::new(filename);
-
-        let file = match File::create(&trace_path) {
-            Err(why) => panic!("couldn't create {:?}: {}", trace_path.display(), why),
-            Ok(file) => file,
-        };
-
+    pub fn write_trace_to(&mut self, filename: &str) -> io::Result<()> {
+        let file = File::create(Path::new(filename))?;
         self.trace_file = Some(file);
+        Ok(())
     }
 
     /// Limits the instruction trace to `count` instructions
@@ -136,96 +298,133 @@ impl Machine {
         self.trace_count = Some(count);
     }
 
-    fn register_components(&mut self) {
-        self.components.push(MachineComponent::PIC(PICComponent::new(0x0020)));
-        self.components.push(MachineComponent::PIC(PICComponent::new(0x00A0)));
-        self.components.push(MachineComponent::PIT(PITComponent::default()));
-        self.components.push(MachineComponent::Keyboard(KeyboardComponent::default()));
-        self.components.push(MachineComponent::Mouse(MouseComponent::default()));
-        self.components.push(MachineComponent::Storage(StorageComponent::default()));
+    /// Selects the emulated cpu model
+    pub fn set_cpu_model(&mut self, model: CpuModel) {
+        self.config.cpu_model = model;
+        self.cpu.set_model(model);
+    }
+
+    fn register_components(&mut self, config: &MachineConfig) {
+        self.add_device(Box::new(PICComponent::new(0x0020)));
+        self.add_device(Box::new(PICComponent::new(0x00A0)));
+        self.add_device(Box::new(PITComponent::default()));
+        self.add_device(Box::new(KeyboardComponent::default()));
+        if config.mouse {
+            self.add_device(Box::new(MouseComponent::default()));
+        }
+        self.add_device(Box::new(StorageComponent::default()));
 
-        let mut gpu = GPUComponent::default();
+        let mut gpu = GPUComponent::new(config.graphic_card.clone());
         gpu.init(&mut self.mmu);
         gpu.set_mode(&mut self.mmu, GFXMode::MODE_TEXT_80_25 as u8);
-        self.components.push(MachineComponent::GPU(gpu));
+        self.add_device(Box::new(gpu));
+    }
+
+    /// attaches a device. it takes precedence over the previously added devices for its
+    /// I/O ports and interrupts
+    pub fn add_device(&mut self, device: Box<dyn Component>) {
+        if self.devices.len() >= usize::from(NO_DEVICE) {
+            panic!("too many devices");
+        }
+        let index = self.devices.len() as u8;
+        for ports in device.io_ports() {
+            for port in ports {
+                self.io_map[usize::from(port)] = index;
+            }
+        }
+        self.devices.push(device);
+    }
+
+    /// returns a reference to the first device of type T
+    pub fn device<T: Component>(&self) -> Option<&T> {
+        self.devices.iter().find_map(|d| (d.as_ref() as &dyn Any).downcast_ref::<T>())
+    }
+
+    /// returns a mutable reference to the first device of type T
+    pub fn device_mut<T: Component>(&mut self) -> Option<&mut T> {
+        self.devices.iter_mut().find_map(|d| (d.as_mut() as &mut dyn Any).downcast_mut::<T>())
     }
 
     /// returns a mutable reference to the PIT component
     pub fn pit_mut(&mut self) -> &mut PITComponent {
-        for component in &mut self.components {
-            if let MachineComponent::PIT(c) = component {
-                return c;
-            }
+        self.device_mut().unwrap()
+    }
+
+    /// returns mutable references to the master and slave PIC
+    fn pics_mut(&mut self) -> impl Iterator<Item = &mut PICComponent> {
+        self.devices.iter_mut().filter_map(|d| (d.as_mut() as &mut dyn Any).downcast_mut::<PICComponent>())
+    }
+
+    /// raises hardware interrupt request `irq` (0-15) on the master or slave PIC
+    pub fn request_irq(&mut self, irq: u8) {
+        let io_base = if irq < 8 { 0x0020 } else { 0x00A0 };
+        if let Some(pic) = self.pics_mut().find(|pic| pic.io_base() == io_base) {
+            pic.request(irq & 7);
         }
-        unreachable!();
+    }
+
+    /// acknowledges the highest priority pending IRQ, returning its interrupt vector.
+    /// XXX the slave PIC is not cascaded through IRQ 2 of the master
+    fn acknowledge_irq(&mut self) -> Option<u8> {
+        self.pics_mut().find_map(|pic| pic.acknowledge())
     }
 
     /// returns a mutable reference to the Keyboard component
     pub fn keyboard_mut(&mut self) -> &mut KeyboardComponent {
-        for component in &mut self.components {
-            if let MachineComponent::Keyboard(c) = component {
-                return c;
-            }
-        }
-        unreachable!();
+        self.device_mut().unwrap()
     }
 
-    /// returns a mutable reference to the Mouse component
-    pub fn mouse_mut(&mut self) -> &mut MouseComponent {
-        for component in &mut self.components {
-            if let MachineComponent::Mouse(c) = component {
-                return c;
-            }
-        }
-        unreachable!();
+    /// applies the A20 gate state of the keyboard controller to the MMU
+    fn update_a20(&mut self) {
+        let enabled = self.device::<KeyboardComponent>().is_none_or(|kbc| kbc.a20_enabled());
+        self.mmu.set_a20(enabled);
+    }
+
+    /// returns a mutable reference to the Mouse component, if one is attached
+    pub fn mouse_mut(&mut self) -> Option<&mut MouseComponent> {
+        self.device_mut()
     }
 
     /// returns a mutable reference to the GPU component
     pub fn gpu_mut(&mut self) -> &mut GPUComponent {
-        for component in &mut self.components {
-            if let MachineComponent::GPU(c) = component {
-                return c;
-            }
-        }
-        unreachable!();
+        self.device_mut().unwrap()
     }
 
     /// returns a reference to the GPU component
     pub fn gpu(&self) -> &GPUComponent {
-        for component in &self.components {
-            if let MachineComponent::GPU(c) = component {
-                return c;
-            }
-        }
-        unreachable!();
+        self.device().unwrap()
     }
 
     /// reset the CPU and memory
     pub fn hard_reset(&mut self) {
-        self.cpu = CPU::default();
+        self.cpu = CPU::deterministic();
+        self.cpu.set_model(self.config.cpu_model);
+        self.mmu.set_paging(false, false);
+        for device in &mut self.devices {
+            device.reset();
+        }
+        self.update_a20();
     }
 
     /// Loads a program file
-    pub fn load_executable_file(&mut self, filename: &str) -> Option<io::Error> {
-
-        match read_binary(filename) {
-            Ok(data) => self.load_executable(&data, 0x0329),
-            Err(e) => return Some(e),
-        };
-
+    pub fn load_executable_file(&mut self, filename: &str) -> Result<(), LoadError> {
+        let data = read_binary(filename)?;
+        self.load_executable(&data, 0x0329)?;
         self.dos.program_path = String::from(filename);
-
-        None
+        Ok(())
     }
 
     /// loads a program file (.EXE or .COM) from data
-    pub fn load_executable(&mut self, data: &[u8], psp_segment: u16) {
-        self.init_psp(psp_segment);
-        if data[0] == b'M' && data[1] == b'Z' {
-            self.load_exe(data, psp_segment + 0x10);
+    pub fn load_executable(&mut self, data: &[u8], psp_segment: u16) -> Result<(), LoadError> {
+        if data.starts_with(b"MZ") {
+            let exe = ExeFile::from_data(data)?;
+            self.init_psp(psp_segment);
+            self.load_exe(&exe, data.len(), psp_segment + 0x10)?;
         } else {
+            self.init_psp(psp_segment);
             self.load_com(data, psp_segment);
         }
+        Ok(())
     }
 
     /// Writes the Program Segment Prefix (PSP) into given segment
@@ -289,12 +488,40 @@ impl Machine {
         self.dos.psp_segment = segment;
     }
 
+    /// returns the segment of the first byte beyond the conventional memory available to programs
+    fn memory_end_segment(&self) -> u16 {
+        let kb = self.mmu.read_u16(BIOS::DATA_SEG, BIOS::DATA_MEMORY_SIZE);
+        (kb * 64).saturating_sub(1)
+    }
+
     /// loads a .exe file
-    fn load_exe(&mut self, data: &[u8], segment: u16) {
-        let exe = match ExeFile::from_data(data) {
-            Ok(exe) => exe,
-            Err(e) => panic!(e),
-        };
+    fn load_exe(&mut self, exe: &ExeFile, file_size: usize, segment: u16) -> Result<(), LoadError> {
+        let psp_segment = self.dos.psp_segment;
+
+        // each relocation patches a word of the load module
+        for reloc in &exe.relocs {
+            let offset = u32::from(reloc.segment) * 16 + u32::from(reloc.offset);
+            if offset as usize + 2 > exe.program_data.len() {
+                return Err(LoadError::RelocationOutOfRange(reloc.segment, reloc.offset));
+            }
+        }
+
+        // size the program memory block as DOS does: the PSP and load module,
+        // plus as much of max_alloc as is available but at least min_alloc
+        let available = self.memory_end_segment().saturating_sub(psp_segment) as usize;
+        let required = 0x10 + exe.header.load_module_paragraphs();
+        let min_size = required + exe.header.min_extra_paragraphs as usize;
+        if min_size > available {
+            return Err(LoadError::OutOfMemory { needed: min_size, available });
+        }
+        let max_size = required + exe.header.max_extra_paragraphs as usize;
+        let block_size = if max_size > available { available } else { max_size };
+        // PSP:0002 holds the segment of the first byte beyond the memory allocated to the program
+        self.mmu.write_u16(psp_segment, 0x0002, psp_segment + block_size as u16);
+        if exe.header.min_extra_paragraphs == 0 && exe.header.max_extra_paragraphs == 0 {
+            // XXX the program should be loaded as high as possible
+            println!("XXX exe requests to be loaded high, loading low");
+        }
 
         // relative SS
         let ss = (segment as isize + (exe.header.ss as isize)) as u16;
@@ -304,24 +531,32 @@ impl Machine {
         // relative CS
         let cs = (segment as isize + (exe.header.cs as isize)) as u16;
         self.cpu.set_r16(R::CS, cs);
-        self.cpu.regs.ip = exe.header.ip;
+        self.cpu.regs.ip = u32::from(exe.header.ip);
 
         self.mmu.write(segment, 0, &exe.program_data);
 
-        let some_segment = 0x0329;
-        self.cpu.set_r16(R::DS, self.dos.psp_segment); // ds points to PSP
-        self.cpu.set_r16(R::ES, some_segment);
+        // patch the segment references of the load module with the load segment
+        for reloc in &exe.relocs {
+            let reloc_segment = segment.wrapping_add(reloc.segment);
+            let val = self.mmu.read_u16(reloc_segment, reloc.offset);
+            self.mmu.write_u16(reloc_segment, reloc.offset, val.wrapping_add(segment));
+        }
+
+        // ds and es points to PSP
+        self.cpu.set_r16(R::DS, psp_segment);
+        self.cpu.set_r16(R::ES, psp_segment);
         self.cpu.set_r16(R::BP, 0x091C);
         self.cpu.set_r16(R::CX, 0x00FF);
-        self.cpu.set_r16(R::DX, some_segment);
+        self.cpu.set_r16(R::DX, psp_segment);
         self.cpu.set_r16(R::SI, 0x0100);
         self.cpu.set_r16(R::DI, 0xFFFE);
         self.cpu.regs.flags.interrupt = true;
 
         self.rom_base = self.cpu.get_memory_address();
-        self.rom_length = data.len();
+        self.rom_length = file_size;
 
         self.mark_stack();
+        Ok(())
     }
 
     /// loads a .com program into CS:0100 and set IP to program start
@@ -349,7 +584,7 @@ impl Machine {
         self.rom_length = data.len();
 
         let cs = self.cpu.get_r16(R::CS);
-        self.mmu.write(cs, self.cpu.regs.ip, data);
+        self.mmu.write(cs, 0x0100, data);
 
         self.mark_stack();
     }
@@ -369,16 +604,15 @@ impl Machine {
     /// executes enough instructions that can run for 1 video frame
     pub fn execute_frame(&mut self) {
         let fps = 60;
-        let cycles = self.cpu.clock_hz / fps;
-        // println!("will execute {} cycles", cycles);
+        let end = self.cpu.cycle_count + self.cpu.clock_hz / fps;
+        // println!("will execute {} cycles", end - self.cpu.cycle_count);
 
         loop {
             self.execute_instruction();
             if self.cpu.fatal_error {
                 break;
             }
-            if self.cpu.cycle_count > cycles {
-                self.cpu.cycle_count = 0;
+            if self.cpu.cycle_count >= end {
                 break;
             }
         }
@@ -394,29 +628,24 @@ impl Machine {
         }
     }
 
-    /// returns first line of disassembly using nasm
-    fn external_disasm_of_bytes(&self, cs: u16, ip: u16) -> String {
-        let bytes = self.mmu.read(cs, ip, 16);
+    /// returns first line of disassembly using nasm of the instruction at linear address `addr`
+    fn external_disasm_of_bytes(&self, addr: u32) -> String {
+        let bytes = self.mmu.read_bytes(addr, 16);
         ndisasm_first_instr(&bytes).unwrap()
     }
 
     fn handle_interrupt(&mut self, int: u8) {
         // ask subsystems if they can handle the interrupt
-        for component in &mut self.components {
-            let handled = match component {
-                MachineComponent::PIC(c) => c.int(int, &mut self.cpu, &mut self.mmu),
-                MachineComponent::PIT(c) => c.int(int, &mut self.cpu, &mut self.mmu),
-                MachineComponent::Keyboard(c) => c.int(int, &mut self.cpu, &mut self.mmu),
-                MachineComponent::Mouse(c) => c.int(int, &mut self.cpu, &mut self.mmu),
-                MachineComponent::Storage(c) => c.int(int, &mut self.cpu, &mut self.mmu),
-                MachineComponent::GPU(c) => c.int(int, &mut self.cpu, &mut self.mmu),
-            };
-            if handled {
+        for device in self.devices.iter_mut().rev() {
+            if device.int(int, &mut self.cpu, &mut self.mmu) {
                 return;
             }
         }
 
         match int {
+            0x01 => {
+                // single step or debug register breakpoint, the default handler just returns
+            }
             0x03 => {
                 // debugger interrupt
                 // http://www.ctyme.com/intr/int-03.htm
@@ -425,6 +654,52 @@ impl Machine {
                     self.cpu.fatal_error = true; // stops execution
                 }
             }
+            0x11 => {
+                // BIOS - GET EQUIPMENT LIST
+                // Return: AX = BIOS equipment list word
+                let equipment = self.mmu.read_u16(BIOS::DATA_SEG, BIOS::DATA_EQUIPMENT);
+                self.cpu.set_r16(R::AX, equipment);
+            }
+            0x12 => {
+                // BIOS - GET MEMORY SIZE
+                // Return: AX = kilobytes of contiguous memory starting at absolute address 00000h
+                let kb = self.mmu.read_u16(BIOS::DATA_SEG, BIOS::DATA_MEMORY_SIZE);
+                self.cpu.set_r16(R::AX, kb);
+            }
+            0x15 => {
+                match self.cpu.get_r16(R::AX) {
+                    0x2400 | 0x2401 => {
+                        // SYSTEM - later PS/2s - DISABLE / ENABLE A20 GATE
+                        // Return: CF clear if successful, AH = 00h
+                        let enabled = self.cpu.get_r16(R::AX) == 0x2401;
+                        self.keyboard_mut().set_a20(enabled);
+                        self.update_a20();
+                        self.cpu.set_r8(R::AH, 0);
+                        self.mmu.set_flag(FLAG_CF, false);
+                    }
+                    0x2402 => {
+                        // SYSTEM - later PS/2s - GET A20 GATE STATUS
+                        // Return: CF clear if successful, AH = 00h, AL = current state (00h disabled, 01h enabled)
+                        let enabled = self.mmu.a20_enabled();
+                        self.cpu.set_r16(R::AX, u16::from(enabled));
+                        self.mmu.set_flag(FLAG_CF, false);
+                    }
+                    0x2403 => {
+                        // SYSTEM - later PS/2s - QUERY A20 GATE SUPPORT
+                        // Return: CF clear if successful, AH = 00h
+                        // BX = status bits: bit 0 supported on keyboard controller, bit 1 supported with port 92h
+                        self.cpu.set_r8(R::AH, 0);
+                        self.cpu.set_r16(R::BX, 0b11);
+                        self.mmu.set_flag(FLAG_CF, false);
+                    }
+                    _ => {
+                        println!("int error: unknown system interrupt, AX={:04X}", self.cpu.get_r16(R::AX));
+                        // CF set, AH = 86h function not supported
+                        self.cpu.set_r8(R::AH, 0x86);
+                        self.mmu.set_flag(FLAG_CF, true);
+                    }
+                }
+            }
             0x17 => {
                 // PRINTER
                 match self.cpu.get_r8(R::AH) {
@@ -455,6 +730,17 @@ impl Machine {
                 println!("XXX DOS - TERMINATE AND STAY RESIDENT");
                 self.cpu.fatal_error = true; // stops execution
             }
+            0x2F => {
+                if !self.xms.int(int, &mut self.cpu, &mut self.mmu) {
+                    println!("int error: unknown multiplex interrupt, AX={:04X}", self.cpu.get_r16(R::AX));
+                }
+            }
+            0x67 => match &mut self.ems {
+                Some(ems) => {
+                    ems.int(int, &mut self.cpu, &mut self.mmu);
+                }
+                None => println!("int error: no expanded memory manager, AX={:04X}", self.cpu.get_r16(R::AX)),
+            },
             _ => {
                 println!("int error: unknown interrupt {:02X}, AX={:04X}, BX={:04X}, CX={:04X}, DX={:04X}",
                         int,
@@ -466,17 +752,108 @@ impl Machine {
         }
     }
 
+    fn handle_xms_call(&mut self) {
+        if let Some(enabled) = self.xms.call(&mut self.cpu, &mut self.mmu) {
+            // the A20 gate is switched through the keyboard controller, like HIMEM.SYS does
+            self.keyboard_mut().set_a20(enabled);
+            self.update_a20();
+        }
+    }
+
+    /// returns the instruction at cs:eip, found at linear address `linear`, decoding it
+    /// unless it is in the instruction cache
+    fn fetch_instruction(&mut self, cs: u16, ip: u32, linear: u32) -> Instruction {
+        if self.mmu.take_code_flush() {
+            self.cpu.instruction_cache.clear();
+        }
+        for (addr, len) in self.mmu.take_code_writes() {
+            self.cpu.instruction_cache.invalidate(addr, len);
+        }
+        let code32 = self.cpu.is_code32();
+        let base = linear.wrapping_sub(ip);
+        let addr = match self.mmu.code_address(linear) {
+            Some(addr) => addr,
+            None => return self.cpu.decoder.get_instruction_at(&mut self.mmu, base, ip, code32),
+        };
+        if let Some(op) = self.cpu.instruction_cache.get(addr, cs, ip, code32) {
+            return op.clone();
+        }
+        let op = self.cpu.decoder.get_instruction_at(&mut self.mmu, base, ip, code32);
+        if self.mmu.paging.fault_pending() {
+            // the instruction crosses into a page that is not present
+            return op;
+        }
+        let len = u32::from(op.length);
+        let last = len.max(1) - 1;
+        if self.mmu.code_address(linear.wrapping_add(last)) != Some(addr.wrapping_add(last)) {
+            // the instruction is split over physical pages that are not contiguous, so
+            // writes to its tail would not be seen by the cache
+            return op;
+        }
+        self.mmu.mark_code(addr, len);
+        self.cpu.instruction_cache.insert(addr, cs, ip, code32, op.clone());
+        op
+    }
+
     /// executes the next CPU instruction
     pub fn execute_instruction(&mut self) {
+        // hardware interrupts are accepted between instructions while IF is set
+        if self.cpu.regs.flags.interrupt {
+            if let Some(vector) = self.acknowledge_irq() {
+                self.cpu.halted = false;
+                self.cpu.hardware_interrupt(&mut self.mmu, vector);
+                if self.cpu.exception_pending() {
+                    self.cpu.handle_exception(&mut self.mmu);
+                }
+            }
+        }
+        if self.cpu.halted {
+            // the timer is the only interrupt source, so skip ahead to the next tick
+            if self.next_timer_cycle > self.cpu.cycle_count {
+                self.cpu.cycle_count = self.next_timer_cycle;
+            }
+            self.progress_timers();
+            return;
+        }
+
         let cs = self.cpu.get_r16(R::CS);
         let ip = self.cpu.regs.ip;
-        if cs == 0xF000 {
-            // we are in interrupt vector code, execute high-level interrupt.
-            // the default interrupt vector table has a IRET
-            self.handle_interrupt(ip as u8);
+        if (cs == BIOS::ROM_SEG || cs == EMS_SEG) && (!self.cpu.is_protected_mode() || self.cpu.is_virtual_8086()) {
+            if cs == BIOS::ROM_SEG && ip < 0x100 {
+                // we are in interrupt vector code, execute high-level interrupt.
+                // the default interrupt vector table has a IRET
+                self.handle_interrupt(ip as u8);
+            } else if cs == BIOS::ROM_SEG && ip == u32::from(XMS_ENTRY) {
+                // far call to the XMS driver, the entry point has a RETF
+                self.handle_xms_call();
+            } else if cs == EMS_SEG && ip == u32::from(EMS_ENTRY) {
+                // INT 67h points to the EMS driver header, the entry point has a IRET
+                self.handle_interrupt(0x67);
+            }
         }
 
-        let op = self.cpu.decoder.get_instruction(&mut self.mmu, cs, ip);
+        // registers before the instruction, restored if it faults so it can be restarted.
+        // XXX memory writes made before the fault, and the fpu state, are not restored
+        let regs = if self.cpu.is_protected_mode() {
+            Some(self.cpu.regs.clone())
+        } else {
+            None
+        };
+
+        self.cpu.instruction_ip = ip;
+        let linear = match self.cpu.fetch_address(ip) {
+            Some(linear) => linear,
+            None => {
+                // outside of the code segment limit
+                self.cpu.handle_exception(&mut self.mmu);
+                return;
+            }
+        };
+        let op = self.fetch_instruction(cs, ip, linear);
+        if self.mmu.paging.fault_pending() {
+            self.deliver_page_fault(&regs);
+            return;
+        }
 
         if self.trace_file.is_some() {
             let ax = self.cpu.get_r16(R::AX);
@@ -521,6 +898,25 @@ impl Machine {
             }
         }
 
+        // instruction breakpoints are faults, raised before the instruction is executed
+        // unless RF was set by IRETD
+        let hits = if self.cpu.regs.flags.resume {
+            0
+        } else {
+            self.cpu.debug.execute_hits(linear)
+        };
+        self.cpu.regs.flags.resume = false;
+        if hits != 0 {
+            self.cpu.debug_exception(hits);
+            self.cpu.handle_exception(&mut self.mmu);
+            return;
+        }
+
+        // a single step trap follows instructions started with TF set, except software interrupts
+        let single_step = self.cpu.regs.flags.trap && op.command != Op::Int && op.command != Op::Into;
+        // data breakpoints are not triggered by the instruction fetch
+        self.mmu.take_breakpoint_hits();
+
         match op.command {
             Op::Uninitialized => {
                 self.cpu.fatal_error = true;
@@ -529,19 +925,19 @@ impl Machine {
             }
             Op::Invalid(bytes, reason) => {
                 let hex = hex_bytes(&bytes);
-                self.cpu.fatal_error = true;
+                self.cpu.exception(&Exception::UD, 0);
                 match reason {
                     Invalid::Op => {
                         println!("[{:04X}:{:04X}] {} ERROR: unhandled opcode", cs, ip, hex);
-                        println!("ndisasm: {}", self.external_disasm_of_bytes(cs, ip));
+                        println!("ndisasm: {}", self.external_disasm_of_bytes(linear));
                     }
                     Invalid::FPUOp => {
                         println!("[{:04X}:{:04X}] {} ERROR: unhandled FPU opcode", cs, ip, hex);
-                        println!("ndisasm: {}", self.external_disasm_of_bytes(cs, ip));
+                        println!("ndisasm: {}", self.external_disasm_of_bytes(linear));
                     }
                     Invalid::Reg(reg) => {
                         println!("[{:04X}:{:04X}] {} ERROR: unhandled reg value {:02X}", cs, ip, hex, reg);
-                        println!("ndisasm: {}", self.external_disasm_of_bytes(cs, ip));
+                        println!("ndisasm: {}", self.external_disasm_of_bytes(linear));
                     }
                 }
             }
@@ -553,20 +949,89 @@ impl Machine {
             },
         }
 
-        if self.cpu.cycle_count % 100 == 0 {
-            // XXX need instruction timing to do this properly
+        // data, I/O and single step breakpoints are traps, raised after the instruction completed
+        let mut status = u32::from(self.mmu.take_breakpoint_hits()) | self.cpu.debug.take_io_hits();
+        if single_step {
+            status |= DR6_BS;
+        }
+        if status != 0 && !self.cpu.exception_pending() && !self.mmu.paging.fault_pending() {
+            self.cpu.debug_exception(status);
+        }
+
+        if self.mmu.paging.enabled {
+            self.deliver_page_fault(&regs);
+        }
+
+        if let Some(regs) = regs {
+            // a faulting instruction leaves the registers unchanged, unless it already
+            // reloaded a segment register
+            if self.cpu.fault_pending() && regs.sreg16 == self.cpu.regs.sreg16 {
+                self.cpu.regs = regs;
+            }
+        }
+        if self.cpu.exception_pending() {
+            self.cpu.handle_exception(&mut self.mmu);
+        }
+
+        self.progress_timers();
+    }
+
+    /// returns false if I/O to `len` ports at `port` raised #GP. I/O breakpoints covering
+    /// the ports are reported after the instruction
+    fn check_io_access(&mut self, port: u16, len: u16) -> bool {
+        if !self.cpu.check_io_permission(&self.mmu, port, len) {
+            return false;
+        }
+        self.cpu.debug.check_io(port, len);
+        true
+    }
+
+    /// raises #PF if the current instruction page faulted, restoring the registers to `regs`
+    fn deliver_page_fault(&mut self, regs: &Option<RegisterState>) {
+        if let Some(fault) = self.mmu.take_page_fault() {
+            if let Some(regs) = regs {
+                self.cpu.regs = regs.clone();
+            }
+            self.cpu.page_fault(fault);
+            self.cpu.handle_exception(&mut self.mmu);
+        }
+    }
+
+    /// number of cpu cycles per video scanline, assuming a 60 Hz refresh rate
+    fn scanline_cycles(&self) -> usize {
+        let scanlines = self.gpu().mode.sheight as usize + 1;
+        self.cpu.clock_hz / (60 * scanlines)
+    }
+
+    /// number of cpu cycles per timer tick, with the default PIT divisor of 0x1_0000
+    fn timer_cycles(&self) -> usize {
+        self.cpu.clock_hz * 0x1_0000 / PIT_CLOCK_HZ
+    }
+
+    /// advances the video scanline, the PIT and the devices according to the elapsed cpu cycles
+    fn progress_timers(&mut self) {
+        let cycles = self.cpu.cycle_count - self.tick_cycle;
+        self.tick_cycle = self.cpu.cycle_count;
+        for i in 0..self.devices.len() {
+            if let Some(irq) = self.devices[i].tick(cycles, &mut self.mmu) {
+                self.request_irq(irq);
+            }
+        }
+
+        while self.cpu.cycle_count >= self.next_scanline_cycle {
             self.gpu_mut().progress_scanline();
+            self.next_scanline_cycle += self.scanline_cycles();
         }
 
-        // HACK: pit should be updated regularry, but in a deterministic way
-        if self.cpu.cycle_count % 100 == 0 {
-            for component in &mut self.components {
-                if let MachineComponent::PIT(pit) = component {
+        while self.cpu.cycle_count >= self.next_timer_cycle {
+            for device in &mut self.devices {
+                if let Some(pit) = (device.as_mut() as &mut dyn Any).downcast_mut::<PITComponent>() {
                     pit.update(&mut self.mmu);
                 }
             }
+            self.request_irq(0);
+            self.next_timer_cycle += self.timer_cycles();
         }
-
     }
 
     /// read byte from I/O port
@@ -575,16 +1040,8 @@ impl Machine {
             println!("in_u8: read from {:04X}", port);
         }
 
-        for component in &mut self.components {
-            let handled = match component {
-                MachineComponent::PIC(c) => c.in_u8(port),
-                MachineComponent::PIT(c) => c.in_u8(port),
-                MachineComponent::Keyboard(c) => c.in_u8(port),
-                MachineComponent::Mouse(c) => c.in_u8(port),
-                MachineComponent::Storage(c) => c.in_u8(port),
-                MachineComponent::GPU(c) => c.in_u8(port),
-            };
-            if let Some(v) = handled {
+        if let Some(device) = self.devices.get_mut(usize::from(self.io_map[usize::from(port)])) {
+            if let Some(v) = device.in_u8(port) {
                 return v;
             }
         }
@@ -629,16 +1086,12 @@ impl Machine {
             println!("out_u8: write to {:04X} = {:02X}", port, data);
         }
 
-        for component in &mut self.components {
-            let b = match component {
-                MachineComponent::PIC(c) => c.out_u8(port, data),
-                MachineComponent::PIT(c) => c.out_u8(port, data),
-                MachineComponent::Keyboard(c) => c.out_u8(port, data),
-                MachineComponent::Mouse(c) => c.out_u8(port, data),
-                MachineComponent::Storage(c) => c.out_u8(port, data),
-                MachineComponent::GPU(c) => c.out_u8(port, data),
-            };
-            if b {
+        if let Some(device) = self.devices.get_mut(usize::from(self.io_map[usize::from(port)])) {
+            if device.out_u8(port, data) {
+                if let 0x0060 | 0x0064 | 0x0092 = port {
+                    // the A20 gate is controlled by the 8042 output port and port 92h
+                    self.update_a20();
+                }
                 return;
             }
         }
@@ -670,10 +1123,12 @@ impl Machine {
 
     #[cfg_attr(feature = "cargo-clippy", allow(clippy::cyclomatic_complexity))]
     fn execute(&mut self, op: &Instruction) {
+        if op.repeat != RepeatMode::None && self.execute_string_block(op) {
+            return;
+        }
         let start_ip = self.cpu.regs.ip;
-        self.cpu.regs.ip = self.cpu.regs.ip.wrapping_add(op.length as u16);
+        self.cpu.regs.ip = self.next_ip(op);
         self.cpu.instruction_count += 1;
-        self.cpu.cycle_count += 1; // XXX temp hack; we pretend each instruction takes 8 cycles due to lack of timing
         match op.command {
             Op::Aaa => {
                 let v = if self.cpu.get_r8(R::AL) > 0xf9 {
@@ -691,9 +1146,9 @@ impl Machine {
                 ax += u16::from(self.cpu.get_r8(R::AL));
                 let al = ax as u8;
                 self.cpu.set_r16(R::AX, al as u16);
-                self.cpu.regs.flags.sign = al >= 0x80;
-                self.cpu.regs.flags.zero = al == 0;
-                self.cpu.regs.flags.set_parity(al as usize);
+                self.cpu.regs.flags.set_sign(al >= 0x80);
+                self.cpu.regs.flags.set_zero(al == 0);
+                self.cpu.regs.flags.set_parity_u8(al as usize);
             }
             Op::Aam => {
                 let imm8 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u8;
@@ -705,9 +1160,9 @@ impl Machine {
                 self.cpu.set_r8(R::AL, al % imm8);
                 // The SF, ZF, and PF flags are set according to the resulting binary value in the AL register
                 let al = self.cpu.get_r8(R::AL);
-                self.cpu.regs.flags.sign = al & 0x80 != 0;
-                self.cpu.regs.flags.zero = al == 0;
-                self.cpu.regs.flags.set_parity(al as usize);
+                self.cpu.regs.flags.set_sign(al & 0x80 != 0);
+                self.cpu.regs.flags.set_zero(al == 0);
+                self.cpu.regs.flags.set_parity_u8(al as usize);
             }
             Op::Aas => {
                 let v = if self.cpu.get_r8(R::AL) < 6 {
@@ -721,45 +1176,41 @@ impl Machine {
                 // two parameters (dst=reg)
                 let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
                 let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
-                let carry = if self.cpu.regs.flags.carry { 1 } else { 0 };
+                let carry = if self.cpu.regs.flags.carry() { 1 } else { 0 };
                 let res = (Wrapping(dst) + Wrapping(src) + Wrapping(carry)).0;
                 self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, (res & 0xFF) as u8);
 
                 // The OF, SF, ZF, AF, CF, and PF flags are set according to the result.
-                self.cpu.regs.flags.set_overflow_add_u8(res, src + carry, dst);
-                self.cpu.regs.flags.set_sign_u8(res);
-                self.cpu.regs.flags.set_zero_u8(res);
-                self.cpu.regs.flags.set_adjust(res, src + carry, dst);
-                self.cpu.regs.flags.set_carry_u8(res);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_add_u8(res, src + carry, dst);
             }
             Op::Adc16 => {
                 // two parameters (dst=reg)
                 let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
                 let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
-                let carry = if self.cpu.regs.flags.carry { 1 } else { 0 };
+                let carry = if self.cpu.regs.flags.carry() { 1 } else { 0 };
                 let res = (Wrapping(dst) + Wrapping(src) + Wrapping(carry)).0;
                 self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, (res & 0xFFFF) as u16);
 
                 // The OF, SF, ZF, AF, CF, and PF flags are set according to the result.
-                self.cpu.regs.flags.set_overflow_add_u16(res, src + carry, dst);
-                self.cpu.regs.flags.set_sign_u16(res);
-                self.cpu.regs.flags.set_zero_u16(res);
-                self.cpu.regs.flags.set_adjust(res, src + carry, dst);
-                self.cpu.regs.flags.set_carry_u16(res);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_add_u16(res, src + carry, dst);
+            }
+            Op::Adc32 => {
+                // two parameters (dst=reg)
+                let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
+                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
+                let carry = if self.cpu.regs.flags.carry() { 1 } else { 0 };
+                let res = (Wrapping(dst) + Wrapping(src) + Wrapping(carry)).0;
+                self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, (res & 0xFFFF_FFFF) as u32);
+
+                // The OF, SF, ZF, AF, CF, and PF flags are set according to the result.
+                self.cpu.regs.flags.set_add_u32(res, src + carry, dst);
             }
             Op::Add8 => {
                 // two parameters (dst=reg)
                 let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u8;
                 let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u8;
                 let res = src as usize + dst as usize;
-                self.cpu.regs.flags.set_carry_u8(res);
-                self.cpu.regs.flags.set_parity(res);
-                self.cpu.regs.flags.set_adjust(res, src as usize, dst as usize);
-                self.cpu.regs.flags.set_zero_u8(res);
-                self.cpu.regs.flags.set_sign_u8(res);
-                self.cpu.regs.flags.set_overflow_add_u8(res, src as usize, dst as usize);
+                self.cpu.regs.flags.set_add_u8(res, src as usize, dst as usize);
                 self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, res as u8);
             }
             Op::Add16 => {
@@ -767,12 +1218,7 @@ impl Machine {
                 let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u16;
                 let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
                 let res = src as usize + dst as usize;
-                self.cpu.regs.flags.set_carry_u16(res);
-                self.cpu.regs.flags.set_parity(res);
-                self.cpu.regs.flags.set_adjust(res, src as usize, dst as usize);
-                self.cpu.regs.flags.set_zero_u16(res);
-                self.cpu.regs.flags.set_sign_u16(res);
-                self.cpu.regs.flags.set_overflow_add_u16(res, src as usize, dst as usize);
+                self.cpu.regs.flags.set_add_u16(res, src as usize, dst as usize);
                 self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u16);
             }
             Op::Add32 => {
@@ -780,12 +1226,7 @@ impl Machine {
                 let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u32;
                 let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 let res = src as usize + dst as usize;
-                self.cpu.regs.flags.set_carry_u32(res);
-                self.cpu.regs.flags.set_parity(res);
-                self.cpu.regs.flags.set_adjust(res, src as usize, dst as usize);
-                self.cpu.regs.flags.set_zero_u32(res);
-                self.cpu.regs.flags.set_sign_u32(res);
-                self.cpu.regs.flags.set_overflow_add_u32(res, src as usize, dst as usize);
+                self.cpu.regs.flags.set_add_u32(res, src as usize, dst as usize);
                 self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u32);
             }
             Op::And8 => {
@@ -796,11 +1237,7 @@ impl Machine {
 
                 // The OF and CF flags are cleared; the SF, ZF, and PF flags
                 // are set according to the result.
-                self.cpu.regs.flags.overflow = false;
-                self.cpu.regs.flags.carry = false;
-                self.cpu.regs.flags.set_sign_u8(res);
-                self.cpu.regs.flags.set_zero_u8(res);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_logic_u8(res);
                 self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, res as u8);
             }
             Op::And16 => {
@@ -811,13 +1248,20 @@ impl Machine {
 
                 // The OF and CF flags are cleared; the SF, ZF, and PF flags
                 // are set according to the result.
-                self.cpu.regs.flags.overflow = false;
-                self.cpu.regs.flags.carry = false;
-                self.cpu.regs.flags.set_sign_u16(res);
-                self.cpu.regs.flags.set_zero_u16(res);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_logic_u16(res);
                 self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u16);
             }
+            Op::And32 => {
+                // two parameters (dst=reg)
+                let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
+                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
+                let res = dst & src;
+
+                // The OF and CF flags are cleared; the SF, ZF, and PF flags
+                // are set according to the result.
+                self.cpu.regs.flags.set_logic_u32(res);
+                self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u32);
+            }
             Op::Arpl => {
                 println!("XXX impl {}", op);
                 /*
@@ -825,61 +1269,65 @@ impl Machine {
                 let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
                 let mut dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
                 if dst & 3 < src & 3 {
-                    self.cpu.regs.flags.zero = true;
+                    self.cpu.regs.flags.set_zero(true);
                     dst = (dst & 0xFFFC) + (src & 3);
                     self.cpu.write_parameter_u16(&mut self.mmu, op.segment, &op.params.dst, (dst & 0xFFFF) as u16);
                 } else {
-                    self.cpu.regs.flags.zero = false;
+                    self.cpu.regs.flags.set_zero(false);
                 }
                 */
             }
-            Op::Bsf => {
-                let mut src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
+            Op::Bsf | Op::Bsr => {
+                // the destination is undefined if the source is 0
+                let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u32;
                 if src == 0 {
-                    self.cpu.regs.flags.zero = true;
+                    self.cpu.regs.flags.set_zero(true);
                 } else {
-                    let mut count = 0;
-                    while src & 1 == 0 {
-                        count += 1;
-                        src >>= 1;
+                    let index = if op.command == Op::Bsf {
+                        src.trailing_zeros()
+                    } else {
+                        31 - src.leading_zeros()
+                    };
+                    if op.params.dst.is_32bit() {
+                        self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, index);
+                    } else {
+                        self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, index as u16);
                     }
-                    self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, count);
-                    self.cpu.regs.flags.zero = false;
+                    self.cpu.regs.flags.set_zero(false);
                 }
             }
-            Op::Bt => {
-                let bit_base = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
-                let bit_offset = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
-                self.cpu.regs.flags.carry = bit_base & (1 << (bit_offset & 15)) != 0;
+            Op::Bswap => {
+                let val = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
+                self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, val.swap_bytes());
             }
+            Op::Bt | Op::Btc | Op::Btr | Op::Bts => self.bit_test(op),
             Op::Bound => {
-                // XXX throw BR exception if out of bounds
-                println!("XXX impl {}", op);
+                // raises BR if the signed index is outside of the bounds stored at src
+                let index = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16 as i16;
+                let (seg, off) = self.cpu.parameter_segment_offset(&op.params.src);
+                let lower = self.cpu.read_mem(&self.mmu, seg, off, 2) as u16 as i16;
+                let upper = self.cpu.read_mem(&self.mmu, seg, off.wrapping_add(2), 2) as u16 as i16;
+                if index < lower || index > upper {
+                    self.cpu.exception(&Exception::BR, 0);
+                }
             }
             Op::CallNear => {
                 let old_ip = self.cpu.regs.ip;
-                let temp_ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
-                self.cpu.push16(&mut self.mmu, old_ip);
-                self.cpu.regs.ip = temp_ip as u16;
+                let temp_ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
+                if op.op_size == OperandSize::_32bit {
+                    self.cpu.push32(&mut self.mmu, old_ip);
+                } else {
+                    self.cpu.push16(&mut self.mmu, old_ip as u16);
+                }
+                if !self.cpu.exception_pending() {
+                    self.cpu.regs.ip = temp_ip;
+                }
             }
             Op::CallFar => {
-                let old_seg = self.cpu.regs.get_r16(R::CS);
-                let old_ip = self.cpu.regs.ip;
-                self.cpu.push16(&mut self.mmu, old_seg);
-                self.cpu.push16(&mut self.mmu, old_ip);
-                let (seg, offs) = match op.params.dst {
-                    Parameter::Ptr16Imm(seg, offs) =>
-                        (seg, offs),
-                    Parameter::Ptr16(seg, offs) =>
-                        (self.cpu.segment(seg), offs),
-                    Parameter::Ptr16Amode(seg, ref amode) =>
-                        (self.cpu.segment(seg), self.cpu.amode(amode) as u16),
-                    Parameter::Ptr16AmodeS8(seg, ref amode, imm) =>
-                        (self.cpu.segment(seg), (self.cpu.amode(amode) as isize + imm as isize) as u16),
-                    _ => panic!("CallFar unhandled type {:?}", op.params.dst),
-                };
-                self.cpu.regs.set_r16(R::CS, seg);
-                self.cpu.regs.ip = offs;
+                let (seg, offs) = self.far_pointer(op);
+                if !self.cpu.exception_pending() {
+                    self.cpu.call_far(&mut self.mmu, seg, offs, op.op_size == OperandSize::_32bit);
+                }
             }
             Op::Cbw => {
                 let ah = if self.cpu.get_r8(R::AL) & 0x80 != 0 {
@@ -890,16 +1338,23 @@ impl Machine {
                 self.cpu.set_r8(R::AH, ah);
             }
             Op::Clc => {
-                self.cpu.regs.flags.carry = false;
+                self.cpu.regs.flags.set_carry(false);
             }
             Op::Cld => {
                 self.cpu.regs.flags.direction = false;
             }
             Op::Cli => {
-                self.cpu.regs.flags.interrupt = false;
+                if self.cpu.check_iopl() {
+                    self.cpu.regs.flags.interrupt = false;
+                }
+            }
+            Op::Clts => {
+                if self.cpu.check_privileged() {
+                    self.cpu.cr0 &= !CR0_TS;
+                }
             }
             Op::Cmc => {
-                self.cpu.regs.flags.carry = !self.cpu.regs.flags.carry;
+                self.cpu.regs.flags.set_carry(!self.cpu.regs.flags.carry());
             }
             Op::Cmp8 => {
                 // two parameters
@@ -922,47 +1377,74 @@ impl Machine {
                 let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
                 self.cpu.cmp32(dst, src);
             }
+            Op::Cmpxchg8 => {
+                let acc = usize::from(self.cpu.get_r8(R::AL));
+                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
+                self.cpu.cmp8(acc, dst);
+                if self.cpu.regs.flags.zero() {
+                    let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
+                    self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, src as u8);
+                } else {
+                    self.cpu.set_r8(R::AL, dst as u8);
+                }
+            }
+            Op::Cmpxchg16 => {
+                let acc = usize::from(self.cpu.get_r16(R::AX));
+                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
+                self.cpu.cmp16(acc, dst);
+                if self.cpu.regs.flags.zero() {
+                    let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
+                    self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, src as u16);
+                } else {
+                    self.cpu.set_r16(R::AX, dst as u16);
+                }
+            }
+            Op::Cmpxchg32 => {
+                let acc = self.cpu.get_r32(R::EAX) as usize;
+                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
+                self.cpu.cmp32(acc, dst);
+                if self.cpu.regs.flags.zero() {
+                    let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
+                    self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, src as u32);
+                } else {
+                    self.cpu.set_r32(R::EAX, dst as u32);
+                }
+            }
             Op::Cmpsb => {
                 // no parameters
                 // Compare byte at address DS:(E)SI with byte at address ES:(E)DI
                 // The DS segment may be overridden with a segment override prefix, but the ES segment cannot be overridden.
-                let src = self.mmu.read_u16(self.cpu.segment(op.segment_prefix), self.cpu.get_r16(R::SI)) as usize;
-                let dst = self.mmu.read_u16(self.cpu.get_r16(R::ES), self.cpu.get_r16(R::DI)) as usize;
+                let src = self.cpu.read_mem(&self.mmu, op.segment_prefix.as_register(), self.address_register(op, R::SI), 1) as u8 as usize;
+                let dst = self.cpu.read_mem(&self.mmu, R::ES, self.address_register(op, R::DI), 1) as u8 as usize;
                 self.cpu.cmp8(dst, src);
 
-                let si = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::SI).wrapping_add(1)
-                } else {
-                    self.cpu.get_r16(R::SI).wrapping_sub(1)
-                };
-                self.cpu.set_r16(R::SI, si);
-                let di = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::DI).wrapping_add(1)
-                } else {
-                    self.cpu.get_r16(R::DI).wrapping_sub(1)
-                };
-                self.cpu.set_r16(R::DI, di);
+                self.advance_string_index(op, R::SI, 1);
+                self.advance_string_index(op, R::DI, 1);
             }
             Op::Cmpsw => {
                 // no parameters
                 // Compare word at address DS:(E)SI with word at address ES:(E)DI
                 // The DS segment may be overridden with a segment override prefix, but the ES segment cannot be overridden.
-                let src = self.mmu.read_u16(self.cpu.segment(op.segment_prefix), self.cpu.get_r16(R::SI)) as usize;
-                let dst = self.mmu.read_u16(self.cpu.get_r16(R::ES), self.cpu.get_r16(R::DI)) as usize;
+                let src = self.cpu.read_mem(&self.mmu, op.segment_prefix.as_register(), self.address_register(op, R::SI), 2) as u16 as usize;
+                let dst = self.cpu.read_mem(&self.mmu, R::ES, self.address_register(op, R::DI), 2) as u16 as usize;
                 self.cpu.cmp16(dst, src);
 
-                let si = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::SI).wrapping_add(2)
-                } else {
-                    self.cpu.get_r16(R::SI).wrapping_sub(2)
-                };
-                self.cpu.set_r16(R::SI, si);
-                let di = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::DI).wrapping_add(2)
-                } else {
-                    self.cpu.get_r16(R::DI).wrapping_sub(2)
-                };
-                self.cpu.set_r16(R::DI, di);
+                self.advance_string_index(op, R::SI, 2);
+                self.advance_string_index(op, R::DI, 2);
+            }
+            Op::Cmpsd => {
+                // no parameters
+                // Compare dword at address DS:(E)SI with dword at address ES:(E)DI
+                // The DS segment may be overridden with a segment override prefix, but the ES segment cannot be overridden.
+                let src = self.cpu.read_mem(&self.mmu, op.segment_prefix.as_register(), self.address_register(op, R::SI), 4) as u32 as usize;
+                let dst = self.cpu.read_mem(&self.mmu, R::ES, self.address_register(op, R::DI), 4) as u32 as usize;
+                self.cpu.cmp32(dst, src);
+
+                self.advance_string_index(op, R::SI, 4);
+                self.advance_string_index(op, R::DI, 4);
+            }
+            Op::Cpuid => {
+                self.cpu.cpuid();
             }
             Op::Cwd16 => {
                 // DX:AX ← sign-extend of AX.
@@ -978,6 +1460,15 @@ impl Machine {
                 let ax = self.cpu.get_r16(R::AX) as i16;
                 self.cpu.set_r32(R::EAX, ax as u32);
             }
+            Op::Cdq32 => {
+                // EDX:EAX ← sign-extend of EAX.
+                let edx = if self.cpu.get_r32(R::EAX) & 0x8000_0000 != 0 {
+                    0xFFFF_FFFF
+                } else {
+                    0
+                };
+                self.cpu.set_r32(R::EDX, edx);
+            }
             Op::Daa => {
                 self.cpu.adj4(6, 0x60);
             }
@@ -992,11 +1483,7 @@ impl Machine {
 
                 // The CF flag is not affected. The OF, SF, ZF, AF,
                 // and PF flags are set according to the result.
-                self.cpu.regs.flags.set_overflow_sub_u8(res, src, dst);
-                self.cpu.regs.flags.set_sign_u8(res);
-                self.cpu.regs.flags.set_zero_u8(res);
-                self.cpu.regs.flags.set_adjust(res, src, dst);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_dec_u8(res, dst);
 
                 self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, res as u8);
             }
@@ -1008,11 +1495,7 @@ impl Machine {
 
                 // The CF flag is not affected. The OF, SF, ZF, AF,
                 // and PF flags are set according to the result.
-                self.cpu.regs.flags.set_overflow_sub_u16(res, src, dst);
-                self.cpu.regs.flags.set_sign_u16(res);
-                self.cpu.regs.flags.set_zero_u16(res);
-                self.cpu.regs.flags.set_adjust(res, src, dst);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_dec_u16(res, dst);
 
                 self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u16);
             }
@@ -1024,11 +1507,7 @@ impl Machine {
 
                 // The CF flag is not affected. The OF, SF, ZF, AF,
                 // and PF flags are set according to the result.
-                self.cpu.regs.flags.set_overflow_sub_u32(res, src, dst);
-                self.cpu.regs.flags.set_sign_u32(res);
-                self.cpu.regs.flags.set_zero_u32(res);
-                self.cpu.regs.flags.set_adjust(res, src, dst);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_dec_u32(res, dst);
 
                 self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u32);
             }
@@ -1073,7 +1552,7 @@ impl Machine {
                 }
                 let remainder = (num % op1) as u32;
                 let quotient = num / op1;
-                let quo32 = (quotient & 0xFFFF) as u32;
+                let quo32 = (quotient & 0xFFFF_FFFF) as u32;
                 if quotient != u64::from(quo32) {
                     return self.cpu.exception(&Exception::DIV0, 0);
                 }
@@ -1096,7 +1575,7 @@ impl Machine {
                     for i in 0..nesting_level {
                         let bp = self.cpu.get_r16(R::BP) - 2;
                         self.cpu.set_r16(R::BP, bp);
-                        let val = self.mmu.read_u16(self.cpu.get_r16(R::SS), self.cpu.get_r16(R::BP));
+                        let val = self.cpu.read_mem(&self.mmu, R::SS, u32::from(self.cpu.get_r16(R::BP)), 2) as u16;
                         println!("XXX ENTER: pushing {} = {:04X}", i, val);
                         self.cpu.push16(&mut self.mmu, val);
                     }
@@ -1107,18 +1586,255 @@ impl Machine {
                 let sp = self.cpu.get_r16(R::SP) - alloc_size;
                 self.cpu.set_r16(R::SP, sp);
             }
+            Op::F2xm1 => {
+                // st0 = 2^st0 - 1
+                // NOTE: the transcendental instructions are evaluated in double precision
+                let val = self.cpu.fpu.st(0).f64();
+                self.cpu.fpu.set_st(0, FPR80::from_f64((val * consts::LN_2).exp_m1()));
+            }
+            Op::Fabs => {
+                let val = self.cpu.fpu.st(0);
+                self.cpu.fpu.set_st(0, val.abs());
+                self.cpu.fpu.set_c1(false);
+            }
+            Op::Fadd | Op::Fiadd | Op::Fmul | Op::Fimul | Op::Fsub | Op::Fisub |
+            Op::Fsubr | Op::Fisubr | Op::Fdiv | Op::Fidiv | Op::Fdivr | Op::Fidivr => {
+                // one parameter: st0 = st0 op src
+                // two parameters: dst = dst op src (DC forms, "fadd st1, st0")
+                let (idx, src) = if op.params.src.is_none() {
+                    let src = match op.command {
+                        Op::Fiadd | Op::Fimul | Op::Fisub | Op::Fisubr | Op::Fidiv | Op::Fidivr => {
+                            FPR80::from_i64(self.cpu.read_parameter_int(&self.mmu, &op.params.dst))
+                        }
+                        _ => self.cpu.read_parameter_float(&self.mmu, &op.params.dst),
+                    };
+                    (0, src)
+                } else {
+                    (fpu_register_index(&op.params.dst), self.cpu.read_parameter_float(&self.mmu, &op.params.src))
+                };
+                let dst = self.cpu.fpu.st(idx);
+                let res = self.cpu.fpu.arithmetic(&op.command, dst, src);
+                self.cpu.fpu.set_st(idx, res);
+            }
+            Op::Faddp | Op::Fmulp | Op::Fsubp | Op::Fsubrp | Op::Fdivp | Op::Fdivrp => {
+                // st(i) = st(i) op st0, then pop
+                let idx = fpu_register_index(&op.params.dst);
+                let dst = self.cpu.fpu.st(idx);
+                let src = self.cpu.fpu.st(0);
+                let res = self.cpu.fpu.arithmetic(&op.command, dst, src);
+                self.cpu.fpu.set_st(idx, res);
+                self.cpu.fpu.pop();
+            }
+            Op::Fbld => {
+                let (seg, off) = self.cpu.parameter_segment_offset(&op.params.dst);
+                let val = bcd_to_fpr80(&self.cpu.read_mem_bytes(&self.mmu, seg, off, 10));
+                self.cpu.fpu.push(val);
+            }
+            Op::Fbstp => {
+                let val = self.cpu.fpu.st(0);
+                let bcd = self.cpu.fpu.bcd(val);
+                let (seg, off) = self.cpu.parameter_segment_offset(&op.params.dst);
+                self.cpu.write_mem_bytes(&mut self.mmu, seg, off, &bcd);
+                self.cpu.fpu.pop();
+            }
+            Op::Fchs => {
+                let val = self.cpu.fpu.st(0);
+                self.cpu.fpu.set_st(0, val.chs());
+                self.cpu.fpu.set_c1(false);
+            }
+            Op::Fclex => {
+                self.cpu.fpu.clear_exceptions();
+            }
+            Op::Fcom | Op::Fcomp | Op::Fucom | Op::Fucomp => {
+                // compare st0 with st1 or the given operand
+                let src = if op.params.dst.is_none() {
+                    self.cpu.fpu.st(1)
+                } else {
+                    self.cpu.read_parameter_float(&self.mmu, &op.params.dst)
+                };
+                let dst = self.cpu.fpu.st(0);
+                let ordered = match op.command {
+                    Op::Fcom | Op::Fcomp => true,
+                    _ => false,
+                };
+                self.cpu.fpu.compare(dst, src, ordered);
+                if op.command == Op::Fcomp || op.command == Op::Fucomp {
+                    self.cpu.fpu.pop();
+                }
+            }
+            Op::Fcompp | Op::Fucompp => {
+                // compare st0 with st1, then pop twice
+                let dst = self.cpu.fpu.st(0);
+                let src = self.cpu.fpu.st(1);
+                self.cpu.fpu.compare(dst, src, op.command == Op::Fcompp);
+                self.cpu.fpu.pop();
+                self.cpu.fpu.pop();
+            }
+            Op::Fdecstp => {
+                self.cpu.fpu.decrement_top();
+            }
+            Op::Ffree => {
+                let idx = fpu_register_index(&op.params.dst);
+                self.cpu.fpu.free(idx);
+            }
+            Op::Ficom | Op::Ficomp => {
+                let src = FPR80::from_i64(self.cpu.read_parameter_int(&self.mmu, &op.params.dst));
+                let dst = self.cpu.fpu.st(0);
+                self.cpu.fpu.compare(dst, src, true);
+                if op.command == Op::Ficomp {
+                    self.cpu.fpu.pop();
+                }
+            }
+            Op::Fild => {
+                let val = self.cpu.read_parameter_int(&self.mmu, &op.params.dst);
+                self.cpu.fpu.push(FPR80::from_i64(val));
+            }
+            Op::Fincstp => {
+                self.cpu.fpu.increment_top();
+            }
+            Op::Finit => {
+                self.cpu.fpu.init();
+            }
+            Op::Fist | Op::Fistp | Op::Fisttp => {
+                let val = self.cpu.fpu.st(0);
+                self.cpu.write_parameter_int(&mut self.mmu, &op.params.dst, val, op.command == Op::Fisttp);
+                if op.command != Op::Fist {
+                    self.cpu.fpu.pop();
+                }
+            }
+            Op::Fld => {
+                // NOTE: the value is read before the push, so "fld st0" duplicates st0
+                let val = self.cpu.read_parameter_float(&self.mmu, &op.params.dst);
+                self.cpu.fpu.push(val);
+            }
+            Op::Fld1 | Op::Fldl2t | Op::Fldl2e | Op::Fldpi | Op::Fldlg2 | Op::Fldln2 | Op::Fldz => {
+                self.cpu.fpu.push(FPU::constant(&op.command));
+            }
+            Op::Fldcw => {
+                self.cpu.fpu.control_word = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+            }
+            Op::Fldenv => {
+                let (seg, off) = self.cpu.parameter_segment_offset(&op.params.dst);
+                self.fpu_load_environment(seg, off);
+            }
+            Op::Fnop | Op::Fwait => {}
+            Op::Fnsave => {
+                // stores the environment followed by st0-st7, then initializes the fpu
+                let (seg, off) = self.cpu.parameter_segment_offset(&op.params.dst);
+                self.fpu_store_environment(seg, off);
+                for i in 0..8 {
+                    let reg = self.cpu.fpu.register(i);
+                    self.cpu.write_mem_bytes(&mut self.mmu, seg, off.wrapping_add(14 + (i as u32 * 10)), &reg.to_bytes());
+                }
+                self.cpu.fpu.init();
+            }
+            Op::Fnstcw => {
+                let cw = self.cpu.fpu.control_word;
+                self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, cw);
+            }
+            Op::Fnstenv => {
+                let (seg, off) = self.cpu.parameter_segment_offset(&op.params.dst);
+                self.fpu_store_environment(seg, off);
+                // all exceptions are masked after FNSTENV
+                self.cpu.fpu.control_word |= 0x3F;
+            }
+            Op::Fpatan => {
+                // st1 = arctan(st1 / st0), then pop
+                let x = self.cpu.fpu.st(0).f64();
+                let y = self.cpu.fpu.st(1).f64();
+                self.cpu.fpu.set_st(1, FPR80::from_f64(y.atan2(x)));
+                self.cpu.fpu.pop();
+            }
+            Op::Fprem => {
+                self.cpu.fpu.partial_remainder(false);
+            }
+            Op::Fprem1 => {
+                self.cpu.fpu.partial_remainder(true);
+            }
+            Op::Fptan | Op::Fsin | Op::Fcos | Op::Fsincos => {
+                self.cpu.fpu.trigonometric(&op.command);
+            }
+            Op::Frndint => {
+                let val = self.cpu.fpu.st(0);
+                let res = self.cpu.fpu.round(val);
+                self.cpu.fpu.set_st(0, res);
+            }
+            Op::Frstor => {
+                let (seg, off) = self.cpu.parameter_segment_offset(&op.params.dst);
+                self.fpu_load_environment(seg, off);
+                let tag_word = self.cpu.fpu.tag_word();
+                for i in 0..8 {
+                    let bytes = self.cpu.read_mem_bytes(&self.mmu, seg, off.wrapping_add(14 + (i as u32 * 10)), 10);
+                    self.cpu.fpu.set_register(i, FPR80::from_bytes(&bytes));
+                }
+                // recompute the tags from the restored register contents
+                self.cpu.fpu.set_tag_word(tag_word);
+            }
+            Op::Fscale => {
+                self.cpu.fpu.scale();
+            }
+            Op::Fsqrt => {
+                let val = self.cpu.fpu.st(0);
+                let res = self.cpu.fpu.square_root(val);
+                self.cpu.fpu.set_st(0, res);
+            }
+            Op::Fst | Op::Fstp => {
+                let val = self.cpu.fpu.st(0);
+                self.cpu.write_parameter_float(&mut self.mmu, &op.params.dst, val);
+                if op.command == Op::Fstp {
+                    self.cpu.fpu.pop();
+                }
+            }
+            Op::Fstsw => {
+                let sw = self.cpu.fpu.status_word();
+                self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, sw);
+            }
+            Op::Ftst => {
+                let val = self.cpu.fpu.st(0);
+                self.cpu.fpu.compare(val, FPR80::zero(false), true);
+            }
+            Op::Fxam => {
+                self.cpu.fpu.examine();
+            }
+            Op::Fxch => {
+                let idx = fpu_register_index(&op.params.dst);
+                self.cpu.fpu.exchange(idx);
+            }
+            Op::Fxtract => {
+                self.cpu.fpu.extract();
+            }
+            Op::Fyl2x => {
+                // st1 = st1 * log2(st0), then pop
+                let x = self.cpu.fpu.st(0).f64();
+                let y = self.cpu.fpu.st(1).f64();
+                if x < 0.0 {
+                    self.cpu.fpu.raise(FPU_SW_IE);
+                } else if x == 0.0 {
+                    self.cpu.fpu.raise(FPU_SW_ZE);
+                }
+                self.cpu.fpu.set_st(1, FPR80::from_f64(y * x.log2()));
+                self.cpu.fpu.pop();
+            }
+            Op::Fyl2xp1 => {
+                // st1 = st1 * log2(st0 + 1), then pop
+                let x = self.cpu.fpu.st(0).f64();
+                let y = self.cpu.fpu.st(1).f64();
+                self.cpu.fpu.set_st(1, FPR80::from_f64(y * x.ln_1p() / consts::LN_2));
+                self.cpu.fpu.pop();
+            }
             Op::Hlt => {
-                // println!("XXX impl {}", op);
-                // self.fatal_error = true;
+                if self.cpu.check_privileged() {
+                    self.cpu.halted = true;
+                }
             }
             Op::Idiv8 => {
                 let ax = self.cpu.get_r16(R::AX) as i16; // dividend
                 let op1 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as i8;
-                if op1 == 0 {
-                    return self.cpu.exception(&Exception::DIV0, 0);
-                }
-                let rem = (ax % i16::from(op1)) as i8;
-                let quo = ax / i16::from(op1);
+                // a zero divisor, or AX=8000h divided by -1, overflows the division itself
+                let (quo, rem) = match (ax.checked_div(i16::from(op1)), ax.checked_rem(i16::from(op1))) {
+                    (Some(quo), Some(rem)) => (quo, rem as i8),
+                    _ => return self.cpu.exception(&Exception::DIV0, 0),
+                };
                 let quo8s = (quo & 0xFF) as i8;
                 if quo != i16::from(quo8s) {
                     return self.cpu.exception(&Exception::DIV0, 0);
@@ -1130,13 +1846,12 @@ impl Machine {
             Op::Idiv16 => {
                 let dividend = ((u32::from(self.cpu.get_r16(R::DX)) << 16) | u32::from(self.cpu.get_r16(R::AX))) as i32; // DX:AX
                 let op1 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as i16;
-                if op1 == 0 {
-                    return self.cpu.exception(&Exception::DIV0, 0);
-                }
-                let quo = dividend / i32::from(op1);
-                let rem = (dividend % i32::from(op1)) as i16;
+                let (quo, rem) = match (dividend.checked_div(i32::from(op1)), dividend.checked_rem(i32::from(op1))) {
+                    (Some(quo), Some(rem)) => (quo, rem as i16),
+                    _ => return self.cpu.exception(&Exception::DIV0, 0),
+                };
                 let quo16s = quo as i16;
-	            if quo != i32::from(quo16s) {
+                if quo != i32::from(quo16s) {
                     return self.cpu.exception(&Exception::DIV0, 0);
                 }
                 self.cpu.set_r16(R::AX, quo16s as u16);
@@ -1146,13 +1861,12 @@ impl Machine {
             Op::Idiv32 => {
                 let dividend = ((u64::from(self.cpu.get_r32(R::EDX)) << 32) | u64::from(self.cpu.get_r32(R::EAX))) as i64; // EDX:EAX
                 let op1 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as i32;
-                if op1 == 0 {
-                    return self.cpu.exception(&Exception::DIV0, 0);
-                }
-                let quo = dividend / i64::from(op1);
-                let rem = (dividend % i64::from(op1)) as i32;
+                let (quo, rem) = match (dividend.checked_div(i64::from(op1)), dividend.checked_rem(i64::from(op1))) {
+                    (Some(quo), Some(rem)) => (quo, rem as i32),
+                    _ => return self.cpu.exception(&Exception::DIV0, 0),
+                };
                 let quo32s = quo as i32;
-	            if quo != i64::from(quo32s) {
+                if quo != i64::from(quo32s) {
                     return self.cpu.exception(&Exception::DIV0, 0);
                 }
                 self.cpu.set_r32(R::EAX, quo32s as u32);
@@ -1169,11 +1883,11 @@ impl Machine {
                 self.cpu.set_r16(R::AX, ax);
 
                 if (ax & 0xFF80) == 0xFF80 || (ax & 0xFF80) == 0x0000 {
-                    self.cpu.regs.flags.carry = false;
-                    self.cpu.regs.flags.overflow = false;
+                    self.cpu.regs.flags.set_carry(false);
+                    self.cpu.regs.flags.set_overflow(false);
                 } else {
-                    self.cpu.regs.flags.carry = true;
-                    self.cpu.regs.flags.overflow = true;
+                    self.cpu.regs.flags.set_carry(true);
+                    self.cpu.regs.flags.set_overflow(true);
                 }
             }
             Op::Imul16 => {
@@ -1188,11 +1902,11 @@ impl Machine {
 
                         let tempi = temps as u32;
                         if (tempi & 0xFFFF_8000) == 0xFFFF_8000 || (tempi & 0xFFFF_8000) == 0x0000_0000 {
-                            self.cpu.regs.flags.carry = false;
-                            self.cpu.regs.flags.overflow = false;
+                            self.cpu.regs.flags.set_carry(false);
+                            self.cpu.regs.flags.set_overflow(false);
                         } else {
-                            self.cpu.regs.flags.carry = true;
-                            self.cpu.regs.flags.overflow = true;
+                            self.cpu.regs.flags.set_carry(true);
+                            self.cpu.regs.flags.set_overflow(true);
                         }
                     }
                     2 => {
@@ -1204,11 +1918,11 @@ impl Machine {
 
                         let tempi = temps as u32;
                         if (tempi & 0xFFFF_8000) == 0xFFFF_8000 || (tempi & 0xFFFF_8000) == 0x0000_0000 {
-                            self.cpu.regs.flags.carry = false;
-                            self.cpu.regs.flags.overflow = false;
+                            self.cpu.regs.flags.set_carry(false);
+                            self.cpu.regs.flags.set_overflow(false);
                         } else {
-                            self.cpu.regs.flags.carry = true;
-                            self.cpu.regs.flags.overflow = true;
+                            self.cpu.regs.flags.set_carry(true);
+                            self.cpu.regs.flags.set_overflow(true);
                         }
                     }
                     3 => {
@@ -1221,11 +1935,11 @@ impl Machine {
 
                         let tempi = temps as u32;
                         if (tempi & 0xFFFF_8000) == 0xFFFF_8000 || (tempi & 0xFFFF_8000) == 0x0000_0000 {
-                            self.cpu.regs.flags.carry = false;
-                            self.cpu.regs.flags.overflow = false;
+                            self.cpu.regs.flags.set_carry(false);
+                            self.cpu.regs.flags.set_overflow(false);
                         } else {
-                            self.cpu.regs.flags.carry = true;
-                            self.cpu.regs.flags.overflow = true;
+                            self.cpu.regs.flags.set_carry(true);
+                            self.cpu.regs.flags.set_overflow(true);
                         }
                     }
                     _ => unreachable!(),
@@ -1259,24 +1973,28 @@ impl Machine {
                     _ => unreachable!(),
                 }
                 if tmp != (tmp as i32) as isize {
-                    self.cpu.regs.flags.carry = true;
-                    self.cpu.regs.flags.overflow = true;
+                    self.cpu.regs.flags.set_carry(true);
+                    self.cpu.regs.flags.set_overflow(true);
                 } else {
-                    self.cpu.regs.flags.carry = false;
-                    self.cpu.regs.flags.overflow = false;
+                    self.cpu.regs.flags.set_carry(false);
+                    self.cpu.regs.flags.set_overflow(false);
                 }
             }
             Op::In8 => {
                 // two parameters (dst=AL)
                 let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
-                let data = self.in_u8(src as u16);
-                self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, data);
+                if self.check_io_access(src as u16, 1) {
+                    let data = self.in_u8(src as u16);
+                    self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, data);
+                }
             }
             Op::In16 => {
                 // two parameters (dst=AX)
                 let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
-                let data = self.in_u16(src as u16);
-                self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, data);
+                if self.check_io_access(src as u16, 2) {
+                    let data = self.in_u16(src as u16);
+                    self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, data);
+                }
             }
             Op::Inc8 => {
                 let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
@@ -1284,11 +2002,7 @@ impl Machine {
                 let res = dst.wrapping_add(src);
 
                 // The OF, SF, ZF, AF, and PF flags are set according to the result.
-                self.cpu.regs.flags.set_overflow_add_u8(res, src, dst);
-                self.cpu.regs.flags.set_sign_u8(res);
-                self.cpu.regs.flags.set_zero_u8(res);
-                self.cpu.regs.flags.set_adjust(res, src, dst);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_inc_u8(res, dst);
 
                 self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, res as u8);
             }
@@ -1298,11 +2012,7 @@ impl Machine {
                 let res = dst.wrapping_add(src);
 
                 // The OF, SF, ZF, AF, and PF flags are set according to the result.
-                self.cpu.regs.flags.set_overflow_add_u16(res, src, dst);
-                self.cpu.regs.flags.set_sign_u16(res);
-                self.cpu.regs.flags.set_zero_u16(res);
-                self.cpu.regs.flags.set_adjust(res, src, dst);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_inc_u16(res, dst);
 
                 self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u16);
             }
@@ -1312,11 +2022,7 @@ impl Machine {
                 let res = dst.wrapping_add(src);
 
                 // The OF, SF, ZF, AF, and PF flags are set according to the result.
-                self.cpu.regs.flags.set_overflow_add_u32(res, src, dst);
-                self.cpu.regs.flags.set_sign_u32(res);
-                self.cpu.regs.flags.set_zero_u32(res);
-                self.cpu.regs.flags.set_adjust(res, src, dst);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_inc_u32(res, dst);
 
                 self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u32);
             }
@@ -1324,152 +2030,204 @@ impl Machine {
                 // Input byte from I/O port specified in DX into memory location specified in ES:DI.
                 // The ES segment cannot be overridden with a segment override prefix.
                 let dx = self.cpu.get_r16(R::DX);
-                let data = self.in_u8(dx);
-                self.mmu.write_u8(self.cpu.get_r16(R::ES), self.cpu.get_r16(R::DI), data);
-                let di = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::DI).wrapping_add(1)
-                } else {
-                    self.cpu.get_r16(R::DI).wrapping_sub(1)
-                };
-                self.cpu.set_r16(R::DI, di);
+                if self.check_io_access(dx, 1) {
+                    let data = self.in_u8(dx);
+                    let di = self.address_register(op, R::DI);
+                    self.cpu.write_mem(&mut self.mmu, R::ES, di, 1, u64::from(data));
+                    self.advance_string_index(op, R::DI, 1);
+                }
+            }
+            Op::Insw | Op::Insd => {
+                // Input word or dword from I/O port specified in DX into memory location specified in ES:DI.
+                // The ES segment cannot be overridden with a segment override prefix.
+                let dx = self.cpu.get_r16(R::DX);
+                let len = if op.command == Op::Insd { 4 } else { 2 };
+                if self.check_io_access(dx, len) {
+                    let di = self.address_register(op, R::DI);
+                    if op.command == Op::Insd {
+                        let data = u32::from(self.in_u16(dx)) | u32::from(self.in_u16(dx.wrapping_add(2))) << 16;
+                        self.cpu.write_mem(&mut self.mmu, R::ES, di, 4, u64::from(data));
+                    } else {
+                        let data = self.in_u16(dx);
+                        self.cpu.write_mem(&mut self.mmu, R::ES, di, 2, u64::from(data));
+                    }
+                    self.advance_string_index(op, R::DI, u32::from(len));
+                }
             }
             Op::Int => {
-                let int = self.cpu.read_parameter_imm(&op.params.dst);
-                self.cpu.execute_interrupt(&mut self.mmu, int as u8);
+                if self.cpu.check_virtual_8086_iopl() {
+                    let int = self.cpu.read_parameter_imm(&op.params.dst);
+                    self.cpu.execute_interrupt(&mut self.mmu, int as u8);
+                }
+            }
+            Op::Into => {
+                if self.cpu.regs.flags.overflow() && self.cpu.check_virtual_8086_iopl() {
+                    self.cpu.execute_interrupt(&mut self.mmu, Exception::OF as u8);
+                }
+            }
+            Op::Invd | Op::Wbinvd => {
+                // the internal cache is not emulated
+                self.cpu.check_privileged();
             }
             Op::Ja => {
-                if !self.cpu.regs.flags.carry & !self.cpu.regs.flags.zero {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if !self.cpu.regs.flags.carry() & !self.cpu.regs.flags.zero() {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::Jc => {
-                if self.cpu.regs.flags.carry {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if self.cpu.regs.flags.carry() {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::Jcxz => {
-                if self.cpu.get_r16(R::CX) == 0 {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if self.address_register(op, R::CX) == 0 {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::Jg => {
-                if !self.cpu.regs.flags.zero & self.cpu.regs.flags.sign == self.cpu.regs.flags.overflow {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if !self.cpu.regs.flags.zero() & self.cpu.regs.flags.sign() == self.cpu.regs.flags.overflow() {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::Jl => {
-                if self.cpu.regs.flags.sign != self.cpu.regs.flags.overflow {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if self.cpu.regs.flags.sign() != self.cpu.regs.flags.overflow() {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::JmpFar => {
-                let (seg, offs) = match op.params.dst {
-                    Parameter::Ptr16(seg, imm) =>
-                        (self.cpu.segment(seg), imm),
-                    Parameter::Ptr16Imm(seg, imm) =>
-                        (seg, imm),
-                    Parameter::Ptr16Amode(seg, ref amode) =>
-                        (self.cpu.segment(seg), self.cpu.amode(amode) as u16),
-                    Parameter::Ptr16AmodeS8(seg, ref amode, imm) =>
-                        (self.cpu.segment(seg), (self.cpu.amode(amode) as isize + imm as isize) as u16),
-                    _ => panic!("[{}] JmpFar unhandled type {:?}",  self.cpu.get_memory_address(), op.params.dst),
-                };
-                self.cpu.set_r16(R::CS, seg);
-                self.cpu.regs.ip = offs;
+                let (seg, offs) = self.far_pointer(op);
+                if !self.cpu.exception_pending() {
+                    self.cpu.jmp_far(&mut self.mmu, seg, offs);
+                }
             }
             Op::JmpNear | Op::JmpShort => {
-                self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
             }
             Op::Jna => {
-                if self.cpu.regs.flags.carry | self.cpu.regs.flags.zero {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if self.cpu.regs.flags.carry() | self.cpu.regs.flags.zero() {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::Jnc => {
-                if !self.cpu.regs.flags.carry {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if !self.cpu.regs.flags.carry() {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::Jng => {
-                if self.cpu.regs.flags.zero | self.cpu.regs.flags.sign != self.cpu.regs.flags.overflow {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if self.cpu.regs.flags.zero() | self.cpu.regs.flags.sign() != self.cpu.regs.flags.overflow() {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::Jnl => {
-                if self.cpu.regs.flags.sign == self.cpu.regs.flags.overflow {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if self.cpu.regs.flags.sign() == self.cpu.regs.flags.overflow() {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::Jno => {
-                if !self.cpu.regs.flags.overflow {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if !self.cpu.regs.flags.overflow() {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::Jns => {
-                if !self.cpu.regs.flags.sign {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if !self.cpu.regs.flags.sign() {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::Jnz => {
-                if !self.cpu.regs.flags.zero {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if !self.cpu.regs.flags.zero() {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::Jo => {
-                if self.cpu.regs.flags.overflow {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if self.cpu.regs.flags.overflow() {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::Jpe => {
-                if self.cpu.regs.flags.parity {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if self.cpu.regs.flags.parity() {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::Jpo => {
-                 if !self.cpu.regs.flags.parity {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                 if !self.cpu.regs.flags.parity() {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::Js => {
-                if self.cpu.regs.flags.sign {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if self.cpu.regs.flags.sign() {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::Jz => {
-                if self.cpu.regs.flags.zero {
-                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if self.cpu.regs.flags.zero() {
+                    self.cpu.regs.ip = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
                 }
             }
             Op::Lahf => {
                 // Load: AH ← EFLAGS(SF:ZF:0:AF:0:PF:1:CF).
                 let mut val = 0 as u8;
-                if self.cpu.regs.flags.carry {
+                if self.cpu.regs.flags.carry() {
                     val |= 1;
                 }
                 val |= 1 << 1;
-                if self.cpu.regs.flags.parity {
+                if self.cpu.regs.flags.parity() {
                     val |= 1 << 2;
                 }
-                if self.cpu.regs.flags.adjust {
+                if self.cpu.regs.flags.adjust() {
                     val |= 1 << 4;
                 }
-                if self.cpu.regs.flags.zero {
+                if self.cpu.regs.flags.zero() {
                     val |= 1 << 6;
                 }
-                if self.cpu.regs.flags.sign {
+                if self.cpu.regs.flags.sign() {
                     val |= 1 << 7;
                 }
                 self.cpu.set_r8(R::AH, val);
             }
+            Op::Lar16 => {
+                if self.cpu.check_protected_mode() {
+                    let selector = self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u16;
+                    let access = match self.cpu.visible_descriptor(&self.mmu, selector) {
+                        Some(desc) if desc.is_segment() => Some(desc),
+                        Some(desc) => match desc.system_type() {
+                            DESC_TSS16 | DESC_LDT | DESC_TSS16_BUSY | DESC_CALL_GATE16 | DESC_TASK_GATE |
+                            DESC_TSS32 | DESC_TSS32_BUSY | DESC_CALL_GATE32 => Some(desc),
+                            _ => None,
+                        },
+                        None => None,
+                    };
+                    self.cpu.regs.flags.set_zero(access.is_some());
+                    if let Some(desc) = access {
+                        let val = u16::from(desc.access()) << 8;
+                        self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, val);
+                    }
+                }
+            }
             Op::Lds => {
                 let (segment, offset) = self.cpu.read_segment_selector(&self.mmu, &op.params.src);
-                self.cpu.set_r16(R::DS, segment);
-                self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, offset);
+                if self.cpu.load_segment(&mut self.mmu, R::DS, segment) {
+                    self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, offset);
+                }
+            }
+            Op::Lfs | Op::Lgs | Op::Lss => {
+                let r = match op.command {
+                    Op::Lfs => R::FS,
+                    Op::Lgs => R::GS,
+                    _ => R::SS,
+                };
+                let (segment, offset) = self.cpu.read_segment_selector(&self.mmu, &op.params.src);
+                if self.cpu.load_segment(&mut self.mmu, r, segment) {
+                    self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, offset);
+                }
             }
             Op::Lea16 => {
                 let src = self.cpu.read_parameter_address(&op.params.src) as u16;
                 self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, src);
             }
+            Op::Lea32 => {
+                let src = self.cpu.read_parameter_address(&op.params.src) as u32;
+                self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, src);
+            }
             Op::Leave => {
                 // High Level Procedure Exit
                 // Set SP to BP, then pop BP.
@@ -1481,72 +2239,108 @@ impl Machine {
             }
             Op::Les => {
                 let (segment, offset) = self.cpu.read_segment_selector(&self.mmu, &op.params.src);
-                self.cpu.set_r16(R::ES, segment);
-                self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, offset);
+                if self.cpu.load_segment(&mut self.mmu, R::ES, segment) {
+                    self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, offset);
+                }
+            }
+            Op::Lgdt | Op::Lidt => {
+                if self.cpu.check_privileged() {
+                    let (seg, off) = self.cpu.parameter_segment_offset(&op.params.dst);
+                    let limit = self.cpu.read_mem(&self.mmu, seg, off, 2) as u16;
+                    let mut base = self.cpu.read_mem(&self.mmu, seg, off.wrapping_add(2), 4) as u32;
+                    if op.op_size == OperandSize::_16bit {
+                        base &= 0x00FF_FFFF;
+                    }
+                    let table = DescriptorTable { base, limit };
+                    if op.command == Op::Lgdt {
+                        self.cpu.gdtr = table;
+                    } else {
+                        self.cpu.idtr = table;
+                    }
+                }
+            }
+            Op::Lldt => {
+                if self.cpu.check_protected_mode() && self.cpu.check_privileged() {
+                    let selector = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                    self.cpu.load_ldt(&self.mmu, selector);
+                }
+            }
+            Op::Lmsw => {
+                if self.cpu.check_privileged() {
+                    // the PE bit can be set, but not cleared
+                    let msw = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
+                    let cr0 = (self.cpu.cr0 & !0xF) | (msw & 0xF) | (self.cpu.cr0 & CR0_PE);
+                    self.cpu.set_cr0(&mut self.mmu, cr0);
+                }
             }
             Op::Lodsb => {
                 // no arguments
                 // The DS segment may be over-ridden with a segment override prefix.
-                let val = self.mmu.read_u8(self.cpu.segment(op.segment_prefix), self.cpu.get_r16(R::SI));
+                let val = self.cpu.read_mem(&self.mmu, op.segment_prefix.as_register(), self.address_register(op, R::SI), 1) as u8;
 
                 self.cpu.set_r8(R::AL, val);
-                let si = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::SI).wrapping_add(1)
-                } else {
-                    self.cpu.get_r16(R::SI).wrapping_sub(1)
-                };
-                self.cpu.set_r16(R::SI, si);
+                self.advance_string_index(op, R::SI, 1);
             }
             Op::Lodsw => {
                 // no arguments
                 // The DS segment may be over-ridden with a segment override prefix.
-                let val = self.mmu.read_u16(self.cpu.segment(op.segment_prefix), self.cpu.get_r16(R::SI));
+                let val = self.cpu.read_mem(&self.mmu, op.segment_prefix.as_register(), self.address_register(op, R::SI), 2) as u16;
 
                 self.cpu.set_r16(R::AX, val);
-                let si = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::SI).wrapping_add(2)
-                } else {
-                    self.cpu.get_r16(R::SI).wrapping_sub(2)
-                };
-                self.cpu.set_r16(R::SI, si);
+                self.advance_string_index(op, R::SI, 2);
             }
             Op::Lodsd => {
                 // no arguments
                 // The DS segment may be over-ridden with a segment override prefix.
-                let val = self.mmu.read_u32(self.cpu.segment(op.segment_prefix), self.cpu.get_r16(R::SI));
+                let val = self.cpu.read_mem(&self.mmu, op.segment_prefix.as_register(), self.address_register(op, R::SI), 4) as u32;
 
                 self.cpu.set_r32(R::EAX, val);
-                let si = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::SI).wrapping_add(4)
-                } else {
-                    self.cpu.get_r16(R::SI).wrapping_sub(4)
-                };
-                self.cpu.set_r16(R::SI, si);
+                self.advance_string_index(op, R::SI, 4);
             }
             Op::Loop => {
-                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
-                let cx = self.cpu.get_r16(R::CX).wrapping_sub(1);
-                self.cpu.set_r16(R::CX, cx);
+                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
+                let cx = self.decrement_count_register(op);
                 if cx != 0 {
                     self.cpu.regs.ip = dst;
                 }
             }
             Op::Loope => {
-                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
-                let cx = self.cpu.get_r16(R::CX).wrapping_sub(1);
-                self.cpu.set_r16(R::CX, cx);
-                if cx != 0 && self.cpu.regs.flags.zero {
+                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
+                let cx = self.decrement_count_register(op);
+                if cx != 0 && self.cpu.regs.flags.zero() {
                     self.cpu.regs.ip = dst;
                 }
             }
             Op::Loopne => {
-                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
-                let cx = self.cpu.get_r16(R::CX).wrapping_sub(1);
-                self.cpu.set_r16(R::CX, cx);
-                if cx != 0 && !self.cpu.regs.flags.zero {
+                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
+                let cx = self.decrement_count_register(op);
+                if cx != 0 && !self.cpu.regs.flags.zero() {
                     self.cpu.regs.ip = dst;
                 }
             }
+            Op::Lsl16 => {
+                if self.cpu.check_protected_mode() {
+                    let selector = self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u16;
+                    let limit = match self.cpu.visible_descriptor(&self.mmu, selector) {
+                        Some(desc) if desc.is_segment() => Some(desc.limit()),
+                        Some(desc) => match desc.system_type() {
+                            DESC_TSS16 | DESC_LDT | DESC_TSS16_BUSY | DESC_TSS32 | DESC_TSS32_BUSY => Some(desc.limit()),
+                            _ => None,
+                        },
+                        None => None,
+                    };
+                    self.cpu.regs.flags.set_zero(limit.is_some());
+                    if let Some(limit) = limit {
+                        self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, limit as u16);
+                    }
+                }
+            }
+            Op::Ltr => {
+                if self.cpu.check_protected_mode() && self.cpu.check_privileged() {
+                    let selector = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                    self.cpu.load_task_register(&mut self.mmu, selector);
+                }
+            }
             Op::Mov8 => {
                 // two arguments (dst=reg)
                 let data = self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u8;
@@ -1559,68 +2353,41 @@ impl Machine {
             }
             Op::Mov32 => {
                 // two arguments (dst=reg)
+                if (op.params.dst.is_control_register() || op.params.src.is_control_register()) && !self.cpu.check_privileged() {
+                    return;
+                }
+                if (op.params.dst.is_debug_register() || op.params.src.is_debug_register()) && !self.cpu.check_debug_register_access() {
+                    return;
+                }
                 let data = self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u32;
                 self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, data);
             }
             Op::Movsb => {
                 // move byte from address DS:(E)SI to ES:(E)DI.
                 // The DS segment may be overridden with a segment override prefix, but the ES segment cannot be overridden.
-                let val = self.mmu.read_u8(self.cpu.segment(op.segment_prefix), self.cpu.get_r16(R::SI));
-                let si = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::SI).wrapping_add(1)
-                } else {
-                    self.cpu.get_r16(R::SI).wrapping_sub(1)
-                };
-                self.cpu.set_r16(R::SI, si);
-                let es = self.cpu.get_r16(R::ES);
-                let di = self.cpu.get_r16(R::DI);
-                self.mmu.write_u8(es, di, val);
-                let di = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::DI).wrapping_add(1)
-                } else {
-                    self.cpu.get_r16(R::DI).wrapping_sub(1)
-                };
-                self.cpu.set_r16(R::DI, di);
+                let val = self.cpu.read_mem(&self.mmu, op.segment_prefix.as_register(), self.address_register(op, R::SI), 1) as u8;
+                self.advance_string_index(op, R::SI, 1);
+                let di = self.address_register(op, R::DI);
+                self.cpu.write_mem(&mut self.mmu, R::ES, di, 1, u64::from(val));
+                self.advance_string_index(op, R::DI, 1);
             }
             Op::Movsw => {
                 // move word from address DS:(E)SI to ES:(E)DI.
                 // The DS segment may be overridden with a segment override prefix, but the ES segment cannot be overridden.
-                let val = self.mmu.read_u16(self.cpu.segment(op.segment_prefix), self.cpu.get_r16(R::SI));
-                let si = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::SI).wrapping_add(2)
-                } else {
-                    self.cpu.get_r16(R::SI).wrapping_sub(2)
-                };
-                self.cpu.set_r16(R::SI, si);
-                let es = self.cpu.get_r16(R::ES);
-                let di = self.cpu.get_r16(R::DI);
-                self.mmu.write_u16(es, di, val);
-                let di = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::DI).wrapping_add(2)
-                } else {
-                    self.cpu.get_r16(R::DI).wrapping_sub(2)
-                };
-                self.cpu.set_r16(R::DI, di);
+                let val = self.cpu.read_mem(&self.mmu, op.segment_prefix.as_register(), self.address_register(op, R::SI), 2) as u16;
+                self.advance_string_index(op, R::SI, 2);
+                let di = self.address_register(op, R::DI);
+                self.cpu.write_mem(&mut self.mmu, R::ES, di, 2, u64::from(val));
+                self.advance_string_index(op, R::DI, 2);
             }
             Op::Movsd => {
                 // move dword from address DS:(E)SI to ES:(E)DI
                 // The DS segment may be overridden with a segment override prefix, but the ES segment cannot be overridden.
-                let val = self.mmu.read_u32(self.cpu.segment(op.segment_prefix), self.cpu.get_r16(R::SI));
-                let si = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::SI).wrapping_add(4)
-                } else {
-                    self.cpu.get_r16(R::SI).wrapping_sub(4)
-                };
-                self.cpu.set_r16(R::SI, si);
-                let es = self.cpu.get_r16(R::ES);
-                let di = self.cpu.get_r16(R::DI);
-                self.mmu.write_u32(es, di, val);
-                let di = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::DI).wrapping_add(4)
-                } else {
-                    self.cpu.get_r16(R::DI).wrapping_sub(4)
-                };
-                self.cpu.set_r16(R::DI, di);
+                let val = self.cpu.read_mem(&self.mmu, op.segment_prefix.as_register(), self.address_register(op, R::SI), 4) as u32;
+                self.advance_string_index(op, R::SI, 4);
+                let di = self.address_register(op, R::DI);
+                self.cpu.write_mem(&mut self.mmu, R::ES, di, 4, u64::from(val));
+                self.advance_string_index(op, R::DI, 4);
             }
             Op::Movsx16 => {
                 // 80386+
@@ -1673,11 +2440,11 @@ impl Machine {
                 // result is 0; otherwise, they are set to 1.
                 // The SF, ZF, AF, and PF flags are undefined.
                 if ax & 0xFF00 != 0 {
-                    self.cpu.regs.flags.carry = true;
-                    self.cpu.regs.flags.overflow = true;
+                    self.cpu.regs.flags.set_carry(true);
+                    self.cpu.regs.flags.set_overflow(true);
                 } else {
-                    self.cpu.regs.flags.carry = false;
-                    self.cpu.regs.flags.overflow = false;
+                    self.cpu.regs.flags.set_carry(false);
+                    self.cpu.regs.flags.set_overflow(false);
                 }
             }
             Op::Mul16 => {
@@ -1690,8 +2457,8 @@ impl Machine {
                 let dx = (res >> 16) as u16;
                 self.cpu.set_r16(R::DX, dx);
 
-                self.cpu.regs.flags.carry = dx != 0;
-                self.cpu.regs.flags.overflow = dx != 0;
+                self.cpu.regs.flags.set_carry(dx != 0);
+                self.cpu.regs.flags.set_overflow(dx != 0);
             }
             Op::Mul32 => {
                 // Unsigned multiply (EDX:EAX ← EAX ∗ r/m32)
@@ -1703,8 +2470,8 @@ impl Machine {
                 let edx = (res >> 32) as u32;
                 self.cpu.set_r32(R::EDX, edx);
 
-                self.cpu.regs.flags.carry = edx != 0;
-                self.cpu.regs.flags.overflow = edx != 0;
+                self.cpu.regs.flags.set_carry(edx != 0);
+                self.cpu.regs.flags.set_overflow(edx != 0);
             }
             Op::Neg8 => {
                 // Two's Complement Negation
@@ -1714,13 +2481,13 @@ impl Machine {
                 let res = src.wrapping_sub(dst as u8) as usize;
                 self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, res as u8);
 
-                self.cpu.regs.flags.carry = dst != 0;
+                self.cpu.regs.flags.set_carry(dst != 0);
                 // The OF, SF, ZF, AF, and PF flags are set according to the result.
-                self.cpu.regs.flags.overflow = res == 0x80;
+                self.cpu.regs.flags.set_overflow(res == 0x80);
                 self.cpu.regs.flags.set_sign_u8(res);
                 self.cpu.regs.flags.set_zero_u8(res);
-                self.cpu.regs.flags.set_adjust(res, src as usize, dst);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_adjust_from(res, src as usize, dst);
+                self.cpu.regs.flags.set_parity_u8(res);
             }
             Op::Neg16 => {
                 // one argument
@@ -1729,13 +2496,13 @@ impl Machine {
                 let res = src.wrapping_sub(dst as u16) as usize;
                 self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u16);
 
-                self.cpu.regs.flags.carry = dst != 0;
+                self.cpu.regs.flags.set_carry(dst != 0);
                 // The OF, SF, ZF, AF, and PF flags are set according to the result.
-                self.cpu.regs.flags.overflow = res == 0x8000;
+                self.cpu.regs.flags.set_overflow(res == 0x8000);
                 self.cpu.regs.flags.set_sign_u16(res);
                 self.cpu.regs.flags.set_zero_u16(res);
-                self.cpu.regs.flags.set_adjust(res, src as usize, dst);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_adjust_from(res, src as usize, dst);
+                self.cpu.regs.flags.set_parity_u8(res);
             }
             Op::Neg32 => {
                 // one argument
@@ -1744,13 +2511,13 @@ impl Machine {
                 let res = src.wrapping_sub(dst as u32) as usize;
                 self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u32);
 
-                self.cpu.regs.flags.carry = dst != 0;
+                self.cpu.regs.flags.set_carry(dst != 0);
                 // The OF, SF, ZF, AF, and PF flags are set according to the result.
-                self.cpu.regs.flags.overflow = res == 0x8000_0000;
+                self.cpu.regs.flags.set_overflow(res == 0x8000_0000);
                 self.cpu.regs.flags.set_sign_u32(res);
                 self.cpu.regs.flags.set_zero_u32(res);
-                self.cpu.regs.flags.set_adjust(res, src as usize, dst);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_adjust_from(res, src as usize, dst);
+                self.cpu.regs.flags.set_parity_u8(res);
             }
             Op::Nop => {}
             Op::Not8 => {
@@ -1760,11 +2527,18 @@ impl Machine {
                 self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, (res & 0xFF) as u8);
                 // Flags Affected: None
             }
-            Op::Not16 => {
+            Op::Not16 => {
+                // one arguments (dst)
+                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
+                let res = !dst;
+                self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, (res & 0xFFFF) as u16);
+                // Flags Affected: None
+            }
+            Op::Not32 => {
                 // one arguments (dst)
                 let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
                 let res = !dst;
-                self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, (res & 0xFFFF) as u16);
+                self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, (res & 0xFFFF_FFFF) as u32);
                 // Flags Affected: None
             }
             Op::Or8 => {
@@ -1774,11 +2548,7 @@ impl Machine {
                 let res = dst | src;
                 // The OF and CF flags are cleared; the SF, ZF, and PF flags
                 // are set according to the result.
-                self.cpu.regs.flags.overflow = false;
-                self.cpu.regs.flags.carry = false;
-                self.cpu.regs.flags.set_sign_u8(res);
-                self.cpu.regs.flags.set_zero_u8(res);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_logic_u8(res);
                 self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, (res & 0xFF) as u8);
             }
             Op::Or16 => {
@@ -1788,50 +2558,65 @@ impl Machine {
                 let res = dst | src;
                 // The OF and CF flags are cleared; the SF, ZF, and PF flags
                 // are set according to the result.
-                self.cpu.regs.flags.overflow = false;
-                self.cpu.regs.flags.carry = false;
-                self.cpu.regs.flags.set_sign_u16(res);
-                self.cpu.regs.flags.set_zero_u16(res);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_logic_u16(res);
                 self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, (res & 0xFFFF) as u16);
             }
+            Op::Or32 => {
+                // two arguments (dst=EAX)
+                let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
+                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
+                let res = dst | src;
+                // The OF and CF flags are cleared; the SF, ZF, and PF flags
+                // are set according to the result.
+                self.cpu.regs.flags.set_logic_u32(res);
+                self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, (res & 0xFFFF_FFFF) as u32);
+            }
             Op::Out8 => {
                 // two arguments
                 let addr = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
-                let val = self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u8;
-                self.out_u8(addr, val);
+                if self.check_io_access(addr, 1) {
+                    let val = self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u8;
+                    self.out_u8(addr, val);
+                }
             }
             Op::Out16 => {
                 // two arguments
                 let addr = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
-                let val = self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u16;
-                self.out_u16(addr, val);
+                if self.check_io_access(addr, 2) {
+                    let val = self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u16;
+                    self.out_u16(addr, val);
+                }
             }
             Op::Outsb => {
                 // Output byte from memory location specified in DS:(E)SI or RSI to I/O port specified in DX.
                 // no arguments
-                let val = self.mmu.read_u8(self.cpu.segment(op.segment_prefix), self.cpu.get_r16(R::SI));
                 let port = self.cpu.get_r16(R::DX);
-                self.out_u8(port, val);
-                let si = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::SI).wrapping_add(1)
-                } else {
-                    self.cpu.get_r16(R::SI).wrapping_sub(1)
-                };
-                self.cpu.set_r16(R::SI, si);
+                if self.check_io_access(port, 1) {
+                    let val = self.cpu.read_mem(&self.mmu, op.segment_prefix.as_register(), self.address_register(op, R::SI), 1) as u8;
+                    self.out_u8(port, val);
+                    self.advance_string_index(op, R::SI, 1);
+                }
             }
             Op::Outsw => {
                 // Output word from memory location specified in DS:(E)SI or RSI to I/O port specified in DX**.
                 // no arguments
-                let val = self.mmu.read_u16(self.cpu.segment(op.segment_prefix), self.cpu.get_r16(R::SI));
                 let port = self.cpu.get_r16(R::DX);
-                self.out_u16(port, val);
-                let si = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::SI).wrapping_add(2)
-                } else {
-                    self.cpu.get_r16(R::SI).wrapping_sub(2)
-                };
-                self.cpu.set_r16(R::SI, si);
+                if self.check_io_access(port, 2) {
+                    let val = self.cpu.read_mem(&self.mmu, op.segment_prefix.as_register(), self.address_register(op, R::SI), 2) as u16;
+                    self.out_u16(port, val);
+                    self.advance_string_index(op, R::SI, 2);
+                }
+            }
+            Op::Outsd => {
+                // Output dword from memory location specified in DS:(E)SI or RSI to I/O port specified in DX.
+                // no arguments
+                let port = self.cpu.get_r16(R::DX);
+                if self.check_io_access(port, 4) {
+                    let val = self.cpu.read_mem(&self.mmu, op.segment_prefix.as_register(), self.address_register(op, R::SI), 4) as u32;
+                    self.out_u16(port, val as u16);
+                    self.out_u16(port.wrapping_add(2), (val >> 16) as u16);
+                    self.advance_string_index(op, R::SI, 4);
+                }
             }
             Op::Pop16 => {
                 // one arguments (dst)
@@ -1880,12 +2665,23 @@ impl Machine {
                 self.cpu.set_r32(R::EAX, eax);
             }
             Op::Popf => {
-                let data = self.cpu.pop16(&mut self.mmu);
-                self.cpu.regs.flags.set_u16(data);
+                if self.cpu.check_virtual_8086_iopl() {
+                    let data = self.cpu.pop16(&mut self.mmu);
+                    self.cpu.set_flags_u16(data);
+                }
+            }
+            Op::Popfd => {
+                if self.cpu.check_virtual_8086_iopl() {
+                    let data = self.cpu.pop32(&mut self.mmu);
+                    self.cpu.set_flags_u32(data);
+                }
             }
             Op::Push16 => {
                 // single parameter (dst)
-                let data = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                let mut data = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                if op.params.dst == Parameter::Reg16(R::SP) && self.cpu.model.pushes_decremented_sp() {
+                    data = data.wrapping_sub(2);
+                }
                 self.cpu.push16(&mut self.mmu, data);
             }
             Op::Push32 => {
@@ -1930,13 +2726,21 @@ impl Machine {
                 self.cpu.push32(&mut self.mmu, edi);
             }
             Op::Pushf => {
-                let data = self.cpu.regs.flags.u16();
-                self.cpu.push16(&mut self.mmu, data);
+                if self.cpu.check_virtual_8086_iopl() {
+                    let data = self.cpu.flags_u16();
+                    self.cpu.push16(&mut self.mmu, data);
+                }
+            }
+            Op::Pushfd => {
+                if self.cpu.check_virtual_8086_iopl() {
+                    let data = self.cpu.flags_u32();
+                    self.cpu.push32(&mut self.mmu, data);
+                }
             }
             Op::Rcl8 => {
                 // Rotate 9 bits (CF, r/m8) left imm8 times.
                 // two arguments
-                let count = (self.cpu.read_parameter_value(&self.mmu, &op.params.src) & 0x1F) % 9;
+                let count = self.cpu.read_shift_count(&self.mmu, &op.params.src) % 9;
                 if count > 0 {
                     let cf = self.cpu.regs.flags.carry_val() as u16;
                     let op1 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
@@ -1947,16 +2751,16 @@ impl Machine {
                     } as u8;
                     self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, res);
                     let cf = (op1 >> (8 - count)) & 1;
-                    self.cpu.regs.flags.carry = cf != 0;
+                    self.cpu.regs.flags.set_carry(cf != 0);
                     // For left rotates, the OF flag is set to the exclusive OR of the CF bit
                     // (after the rotate) and the most-significant bit of the result.
-                    self.cpu.regs.flags.overflow = cf ^ (u16::from(res) >> 7) != 0;
+                    self.cpu.regs.flags.set_overflow(cf ^ (u16::from(res) >> 7) != 0);
                 }
             }
             Op::Rcl16 => {
                 // Rotate 9 bits (CF, r/m8) left imm8 times.
                 // two arguments
-                let count = (self.cpu.read_parameter_value(&self.mmu, &op.params.src) & 0x1F) % 17;
+                let count = self.cpu.read_shift_count(&self.mmu, &op.params.src) % 17;
                 if count > 0 {
                     let cf = self.cpu.regs.flags.carry_val() as u16;
                     let op1 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
@@ -1968,13 +2772,27 @@ impl Machine {
                         (op1 << count) | (cf << (count - 1)) | (op1 >> (17 - count))
                     };
                     self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u16);
-                    self.cpu.regs.flags.carry = (op1 >> (16 - count)) & 1 != 0;
-                    self.cpu.regs.flags.overflow = self.cpu.regs.flags.carry_val() as u16 ^ (op1 >> 15) != 0;
+                    self.cpu.regs.flags.set_carry((op1 >> (16 - count)) & 1 != 0);
+                    self.cpu.regs.flags.set_overflow(self.cpu.regs.flags.carry_val() as u16 ^ (op1 >> 15) != 0);
+                }
+            }
+            Op::Rcl32 => {
+                // Rotate 33 bits (CF, r/m32) left imm8 times.
+                // two arguments
+                let count = self.cpu.read_shift_count(&self.mmu, &op.params.src) & 0x1F;
+                if count > 0 {
+                    let op1 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u64 & 0xFFFF_FFFF;
+                    let val = (self.cpu.regs.flags.carry_val() as u64) << 32 | op1;
+                    let rotated = ((val << count) | (val >> (33 - count))) & 0x1_FFFF_FFFF;
+                    let res = rotated as u32;
+                    self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res);
+                    self.cpu.regs.flags.set_carry(rotated >> 32 != 0);
+                    self.cpu.regs.flags.set_overflow(self.cpu.regs.flags.carry() ^ (res >> 31 != 0));
                 }
             }
             Op::Rcr8 => {
                 // two arguments
-                let count = ((self.cpu.read_parameter_value(&self.mmu, &op.params.src) & 0x1F) % 9) as u16;
+                let count = (self.cpu.read_shift_count(&self.mmu, &op.params.src) % 9) as u16;
                 if count != 0 {
                     let op1 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
                     let cf = self.cpu.regs.flags.carry_val() as u16;
@@ -1984,22 +2802,22 @@ impl Machine {
 
                     // NOTE: overflow is identical to bochs and dosbox, but differs in WinXP vm.
                     let of = ((res ^ (res << 1)) & 0x80) >> 7;
-                    self.cpu.regs.flags.carry = (op1 >> (count - 1)) & 0x1 != 0;
-                    self.cpu.regs.flags.overflow = of != 0;
+                    self.cpu.regs.flags.set_carry((op1 >> (count - 1)) & 0x1 != 0);
+                    self.cpu.regs.flags.set_overflow(of != 0);
                 }
             }
             Op::Rcr16 => {
                 // two arguments
                 let op1 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
-                let count = (self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u32 & 0x1F) % 17;
+                let count = self.cpu.read_shift_count(&self.mmu, &op.params.src) as u32 % 17;
                 if count > 0 {
                     let cf = self.cpu.regs.flags.carry_val();
                     let res = (op1 >> count) | (cf << (16 - count)) | (op1 << (17 - count));
                     self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u16);
-                    self.cpu.regs.flags.carry = (op1 >> (count - 1)) & 1 != 0;
+                    self.cpu.regs.flags.set_carry((op1 >> (count - 1)) & 1 != 0);
                     let bit15 = (res >> 15) & 1;
                     let bit14 = (res >> 14) & 1;
-                    self.cpu.regs.flags.overflow = bit15 ^ bit14 != 0;
+                    self.cpu.regs.flags.set_overflow(bit15 ^ bit14 != 0);
                 }
             }
             Op::Rcr32 => {
@@ -2014,32 +2832,32 @@ impl Machine {
                          (op1 >> count) | (cf << (32-count)) | (op1 << (33-count))
                     };
                     self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u32);
-                    self.cpu.regs.flags.carry = (op1 >> (count - 1)) & 1 != 0;
-                    self.cpu.regs.flags.overflow = (res ^ (res << 1)) & 0x8000_0000 != 0;
+                    self.cpu.regs.flags.set_carry((op1 >> (count - 1)) & 1 != 0);
+                    self.cpu.regs.flags.set_overflow((res ^ (res << 1)) & 0x8000_0000 != 0);
                 }
             }
-            Op::Iret => {
-                self.cpu.regs.ip = self.cpu.pop16(&mut self.mmu);
-                let cs = self.cpu.pop16(&mut self.mmu);
-                self.cpu.set_r16(R::CS, cs);
-                let flags = self.cpu.pop16(&mut self.mmu);
-                self.cpu.regs.flags.set_u16(flags);
-                self.mmu.flags_address = MemoryAddress::Unset;
+            Op::Iret | Op::Iretd => {
+                if self.cpu.check_virtual_8086_iopl() {
+                    self.cpu.iret(&mut self.mmu, op.command == Op::Iretd);
+                    self.mmu.flags_address = MemoryAddress::Unset;
+                }
             }
             Op::Retf => {
-                if op.params.count() == 1 {
+                let imm16 = if op.params.count() == 1 {
                     // 1 argument: pop imm16 bytes from stack
-                    let imm16 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
-                    let sp = self.cpu.get_r16(R::SP) + imm16;
-                    self.cpu.set_r16(R::SP, sp);
-                }
-                self.cpu.regs.ip = self.cpu.pop16(&mut self.mmu);
-                let cs = self.cpu.pop16(&mut self.mmu);
-                self.cpu.set_r16(R::CS, cs);
+                    self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16
+                } else {
+                    0
+                };
+                self.cpu.ret_far(&mut self.mmu, imm16, op.op_size == OperandSize::_32bit);
             }
             Op::Retn => {
-                let val = self.cpu.pop16(&mut self.mmu);
-                if DEBUG_MARK_STACK && val == STACK_MARKER {
+                let val = if op.op_size == OperandSize::_32bit {
+                    self.cpu.pop32(&mut self.mmu)
+                } else {
+                    u32::from(self.cpu.pop16(&mut self.mmu))
+                };
+                if DEBUG_MARK_STACK && val == u32::from(STACK_MARKER) {
                     println!("[{}] WARNING: stack marker was popped after {} instr. execution ended. (can be valid where small app just return to DOS with a 'ret', but can also indicate memory corruption)",
                         self.cpu.get_memory_address(), self.cpu.instruction_count);
                     self.cpu.fatal_error = true;
@@ -2048,22 +2866,22 @@ impl Machine {
                 self.cpu.regs.ip = val;
                 if op.params.count() == 1 {
                     // 1 argument: pop imm16 bytes from stack
-                    let imm16 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
-                    let sp = self.cpu.get_r16(R::SP).wrapping_add(imm16);
-                    self.cpu.set_r16(R::SP, sp);
+                    let imm16 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
+                    let sp = self.cpu.stack_pointer().wrapping_add(imm16);
+                    self.cpu.set_stack_pointer(sp);
                 }
             }
             Op::Rol8 => {
                 // Rotate 8 bits of 'dst' left for 'src' times.
                 // two arguments: op1, count
                 let op1 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u8;
-                let mut count = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
+                let mut count = self.cpu.read_shift_count(&self.mmu, &op.params.src);
                 if count & 0b0_0111 == 0 {
                     if count & 0b1_1000 != 0 {
                         let bit0 = op1 & 1;
                         let bit7 = op1 >> 7;
-                        self.cpu.regs.flags.overflow = bit0 ^ bit7 != 0;
-                        self.cpu.regs.flags.carry = bit0 != 0;
+                        self.cpu.regs.flags.set_overflow(bit0 ^ bit7 != 0);
+                        self.cpu.regs.flags.set_carry(bit0 != 0);
                     }
                     // no-op if count is 0
                     return;
@@ -2073,35 +2891,50 @@ impl Machine {
                 self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, res);
                 let bit0 = res & 1;
                 let bit7 = res >> 7;
-                self.cpu.regs.flags.overflow = bit0 ^ bit7 != 0;
-                self.cpu.regs.flags.carry = bit0 != 0;
+                self.cpu.regs.flags.set_overflow(bit0 ^ bit7 != 0);
+                self.cpu.regs.flags.set_carry(bit0 != 0);
             }
             Op::Rol16 => {
                 // Rotate 16 bits of 'dst' left for 'src' times.
                 // two arguments
                 let mut res = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
-                let count = self.cpu.read_parameter_value(&self.mmu, &op.params.src) & 0x1F;
+                let count = self.cpu.read_shift_count(&self.mmu, &op.params.src);
                 res = res.rotate_left(count as u32);
                 self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res);
                 let bit0 = res & 1;
                 let bit15 = (res >> 15) & 1;
                 if count == 1 {
-                    self.cpu.regs.flags.overflow = bit0 ^ bit15 != 0;
+                    self.cpu.regs.flags.set_overflow(bit0 ^ bit15 != 0);
+                }
+                self.cpu.regs.flags.set_carry(bit0 != 0);
+            }
+            Op::Rol32 => {
+                // Rotate 32 bits of 'dst' left for 'src' times.
+                // two arguments
+                let count = self.cpu.read_shift_count(&self.mmu, &op.params.src) & 0x1F;
+                if count > 0 {
+                    let res = (self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32).rotate_left(count as u32);
+                    self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res);
+                    let bit0 = res & 1;
+                    let bit31 = res >> 31;
+                    if count == 1 {
+                        self.cpu.regs.flags.set_overflow(bit0 ^ bit31 != 0);
+                    }
+                    self.cpu.regs.flags.set_carry(bit0 != 0);
                 }
-                self.cpu.regs.flags.carry = bit0 != 0;
             }
             Op::Ror8 => {
                 // Rotate 8 bits of 'dst' right for 'src' times.
                 // two arguments
                 let op1 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u8;
-                let count = self.cpu.read_parameter_value(&self.mmu, &op.params.src) & 0x1F;
+                let count = self.cpu.read_shift_count(&self.mmu, &op.params.src);
 
                 if count & 0b0_0111 == 0 {
                     if count & 0b1_1000 != 0 {
                         let bit6 = (op1 >> 6) & 1;
                         let bit7 = op1 >> 7;
-                        self.cpu.regs.flags.overflow = bit6 ^ bit7 != 0;
-                        self.cpu.regs.flags.carry = bit7 != 0;
+                        self.cpu.regs.flags.set_overflow(bit6 ^ bit7 != 0);
+                        self.cpu.regs.flags.set_carry(bit7 != 0);
                     }
                     return;
                 }
@@ -2110,22 +2943,22 @@ impl Machine {
                 self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, res);
                 let bit6 = (res >> 6) & 1;
                 let bit7 = res >> 7;
-                self.cpu.regs.flags.overflow = bit6 ^ bit7 != 0;
-                self.cpu.regs.flags.carry = bit7 != 0;
+                self.cpu.regs.flags.set_overflow(bit6 ^ bit7 != 0);
+                self.cpu.regs.flags.set_carry(bit7 != 0);
             }
             Op::Ror16 => {
                 // Rotate 16 bits of 'dst' right for 'src' times.
                 // two arguments
                 let mut res = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
-                let count = self.cpu.read_parameter_value(&self.mmu, &op.params.src) & 0x1F;
+                let count = self.cpu.read_shift_count(&self.mmu, &op.params.src);
                 res = res.rotate_right(count as u32);
                 self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res);
                 let bit14 = (res >> 14) & 1;
                 let bit15 = (res >> 15) & 1;
                 if count == 1 {
-                    self.cpu.regs.flags.overflow = bit14 ^ bit15 != 0;
+                    self.cpu.regs.flags.set_overflow(bit14 ^ bit15 != 0);
                 }
-                self.cpu.regs.flags.carry = bit15 != 0;
+                self.cpu.regs.flags.set_carry(bit15 != 0);
             }
             Op::Ror32 => {
                 // Rotate 32 bits of 'dst' right for 'src' times.
@@ -2140,14 +2973,14 @@ impl Machine {
                 // Loads the SF, ZF, AF, PF, and CF flags of the EFLAGS register with values
                 // from the corresponding bits in the AH register (bits 7, 6, 4, 2, and 0, respectively).
                 let ah = self.cpu.get_r8(R::AH);
-                self.cpu.regs.flags.carry = ah & 0x1 != 0; // bit 0
-                self.cpu.regs.flags.parity = ah & 0x4 != 0; // bit 2
-                self.cpu.regs.flags.adjust = ah & 0x10 != 0; // bit 4
-                self.cpu.regs.flags.zero = ah & 0x40 != 0; // bit 6
-                self.cpu.regs.flags.sign = ah & 0x80 != 0; // bit 7
+                self.cpu.regs.flags.set_carry(ah & 0x1 != 0); // bit 0
+                self.cpu.regs.flags.set_parity(ah & 0x4 != 0); // bit 2
+                self.cpu.regs.flags.set_adjust(ah & 0x10 != 0); // bit 4
+                self.cpu.regs.flags.set_zero(ah & 0x40 != 0); // bit 6
+                self.cpu.regs.flags.set_sign(ah & 0x80 != 0); // bit 7
             }
             Op::Salc => {
-                let al = if self.cpu.regs.flags.carry {
+                let al = if self.cpu.regs.flags.carry() {
                     0xFF
                 } else {
                     0
@@ -2158,7 +2991,7 @@ impl Machine {
                 // Signed divide r/m8 by 2, imm8 times.
                 // two arguments
                 let op1 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u8;
-                let mut count = self.cpu.read_parameter_value(&self.mmu, &op.params.src) & 0x1F;
+                let mut count = self.cpu.read_shift_count(&self.mmu, &op.params.src);
                 if count > 0 {
                     if count > 8 {
                         count = 8;
@@ -2171,18 +3004,18 @@ impl Machine {
                     };
 
                     self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, res as u8);
-                    self.cpu.regs.flags.carry = (op1 as isize >> (count - 1)) & 0x1 != 0;
-                    self.cpu.regs.flags.overflow = false;
+                    self.cpu.regs.flags.set_carry((op1 as isize >> (count - 1)) & 0x1 != 0);
+                    self.cpu.regs.flags.set_overflow(false);
                     self.cpu.regs.flags.set_sign_u8(res as usize);
                     self.cpu.regs.flags.set_zero_u8(res as usize);
-                    self.cpu.regs.flags.set_parity(res as usize);
+                    self.cpu.regs.flags.set_parity_u8(res as usize);
                 }
             }
             Op::Sar16 => {
                 // Signed divide r/m8 by 2, imm8 times.
                 // two arguments
                 let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
-                let mut count = self.cpu.read_parameter_value(&self.mmu, &op.params.src) & 0x1F; // use 5 lsb
+                let mut count = self.cpu.read_shift_count(&self.mmu, &op.params.src);
                 if count > 0 {
                     if count > 16 {
                         count = 16;
@@ -2194,11 +3027,11 @@ impl Machine {
                     };
                     self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u16);
 
-                    self.cpu.regs.flags.carry = (dst as u16 >> (count - 1)) & 0x1 != 0;
-                    self.cpu.regs.flags.overflow = false;
+                    self.cpu.regs.flags.set_carry((dst as u16 >> (count - 1)) & 0x1 != 0);
+                    self.cpu.regs.flags.set_overflow(false);
                     self.cpu.regs.flags.set_sign_u16(res);
                     self.cpu.regs.flags.set_zero_u16(res);
-                    self.cpu.regs.flags.set_parity(res);
+                    self.cpu.regs.flags.set_parity_u8(res);
                 }
             }
             Op::Sar32 => {
@@ -2214,90 +3047,96 @@ impl Machine {
                     };
 
                     self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u32);
-                    self.cpu.regs.flags.carry = (dst as u32 >> (count - 1)) & 0x1 != 0;
-                    self.cpu.regs.flags.overflow = false;
+                    self.cpu.regs.flags.set_carry((dst as u32 >> (count - 1)) & 0x1 != 0);
+                    self.cpu.regs.flags.set_overflow(false);
                     self.cpu.regs.flags.set_sign_u32(res);
                     self.cpu.regs.flags.set_zero_u32(res);
-                    self.cpu.regs.flags.set_parity(res);
+                    self.cpu.regs.flags.set_parity_u8(res);
                 }
             }
             Op::Sbb8 => {
                 let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
                 let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
-                let cf = if self.cpu.regs.flags.carry { 1 } else { 0 };
+                let cf = if self.cpu.regs.flags.carry() { 1 } else { 0 };
                 let res = (Wrapping(dst) - (Wrapping(src) + Wrapping(cf))).0;
 
                 // The OF, SF, ZF, AF, PF, and CF flags are set according to the result.
-                self.cpu.regs.flags.set_overflow_sub_u8(res, src, dst);
-                self.cpu.regs.flags.set_sign_u8(res);
-                self.cpu.regs.flags.set_zero_u8(res);
-                self.cpu.regs.flags.set_adjust(res, src, dst);
-                self.cpu.regs.flags.set_parity(res);
-                self.cpu.regs.flags.set_carry_u8(res);
+                self.cpu.regs.flags.set_sub_u8(res, src, dst);
 
                 self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, res as u8);
             }
             Op::Sbb16 => {
                 let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
                 let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
-                let cf = if self.cpu.regs.flags.carry { 1 } else { 0 };
+                let cf = if self.cpu.regs.flags.carry() { 1 } else { 0 };
                 let res = (Wrapping(dst) - (Wrapping(src) + Wrapping(cf))).0;
 
                 // The OF, SF, ZF, AF, PF, and CF flags are set according to the result.
-                self.cpu.regs.flags.set_overflow_sub_u16(res, src, dst);
-                self.cpu.regs.flags.set_sign_u16(res);
-                self.cpu.regs.flags.set_zero_u16(res);
-                self.cpu.regs.flags.set_adjust(res, src, dst);
-                self.cpu.regs.flags.set_parity(res);
-                self.cpu.regs.flags.set_carry_u16(res);
+                self.cpu.regs.flags.set_sub_u16(res, src, dst);
 
                 self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u16);
             }
+            Op::Sbb32 => {
+                let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
+                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
+                let cf = if self.cpu.regs.flags.carry() { 1 } else { 0 };
+                let res = (Wrapping(dst) - (Wrapping(src) + Wrapping(cf))).0;
+
+                // The OF, SF, ZF, AF, PF, and CF flags are set according to the result.
+                self.cpu.regs.flags.set_sub_u32(res, src, dst);
+
+                self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u32);
+            }
             Op::Scasb => {
                 // Compare AL with byte at ES:(E)DI then set status flags.
                 // ES cannot be overridden with a segment override prefix.
                 let src = self.cpu.get_r8(R::AL);
-                let dst = self.mmu.read_u8(self.cpu.get_r16(R::ES), self.cpu.get_r16(R::DI));
+                let dst = self.cpu.read_mem(&self.mmu, R::ES, self.address_register(op, R::DI), 1) as u8;
                 self.cpu.cmp8(dst as usize, src as usize);
-                let di = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::DI).wrapping_add(1)
-                } else {
-                    self.cpu.get_r16(R::DI).wrapping_sub(1)
-                };
-                self.cpu.set_r16(R::DI, di);
+                self.advance_string_index(op, R::DI, 1);
             }
             Op::Scasw => {
                 // Compare AX with word at ES:(E)DI or RDI then set status flags.
                 // ES cannot be overridden with a segment override prefix.
                 let src = self.cpu.get_r16(R::AX);
-                let dst = self.mmu.read_u16(self.cpu.get_r16(R::ES), self.cpu.get_r16(R::DI));
+                let dst = self.cpu.read_mem(&self.mmu, R::ES, self.address_register(op, R::DI), 2) as u16;
                 self.cpu.cmp16(dst as usize, src as usize);
-                let di = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::DI).wrapping_add(2)
-                } else {
-                    self.cpu.get_r16(R::DI).wrapping_sub(2)
-                };
-                self.cpu.set_r16(R::DI, di);
-            }
-            Op::Setc => {
-                let val = if self.cpu.regs.flags.carry {
-                    1
-                } else {
-                    0
-                };
-                self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, val);
+                self.advance_string_index(op, R::DI, 2);
             }
-            Op::Setnz => {
-                let val = if !self.cpu.regs.flags.zero {
-                    1
-                } else {
-                    0
+            Op::Scasd => {
+                // Compare EAX with dword at ES:(E)DI or RDI then set status flags.
+                // ES cannot be overridden with a segment override prefix.
+                let src = self.cpu.get_r32(R::EAX);
+                let dst = self.cpu.read_mem(&self.mmu, R::ES, self.address_register(op, R::DI), 4) as u32;
+                self.cpu.cmp32(dst as usize, src as usize);
+                self.advance_string_index(op, R::DI, 4);
+            }
+            Op::Seta | Op::Setc | Op::Setg | Op::Setl | Op::Setna | Op::Setnc | Op::Setng | Op::Setnl |
+            Op::Setno | Op::Setns | Op::Setnz | Op::Seto | Op::Setpe | Op::Setpo | Op::Sets | Op::Setz => {
+                let flags = &self.cpu.regs.flags;
+                let condition = match op.command {
+                    Op::Seto => flags.overflow(),
+                    Op::Setno => !flags.overflow(),
+                    Op::Setc => flags.carry(),
+                    Op::Setnc => !flags.carry(),
+                    Op::Setz => flags.zero(),
+                    Op::Setnz => !flags.zero(),
+                    Op::Setna => flags.carry() || flags.zero(),
+                    Op::Seta => !flags.carry() && !flags.zero(),
+                    Op::Sets => flags.sign(),
+                    Op::Setns => !flags.sign(),
+                    Op::Setpe => flags.parity(),
+                    Op::Setpo => !flags.parity(),
+                    Op::Setl => flags.sign() != flags.overflow(),
+                    Op::Setnl => flags.sign() == flags.overflow(),
+                    Op::Setng => flags.zero() || flags.sign() != flags.overflow(),
+                    _ => !flags.zero() && flags.sign() == flags.overflow(), // setg
                 };
-                self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, val);
+                self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, condition as u8);
             }
             Op::Shl8 => {
                 // two arguments
-                let count = self.cpu.read_parameter_value(&self.mmu, &op.params.src) & 0x1F;
+                let count = self.cpu.read_shift_count(&self.mmu, &op.params.src);
                 if count > 0 {
                     let op1 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
 
@@ -2314,16 +3153,16 @@ impl Machine {
                     };
                     self.cpu.regs.flags.set_sign_u8(res as usize);
                     self.cpu.regs.flags.set_zero_u8(res as usize);
-                    self.cpu.regs.flags.set_parity(res as usize);
-                    self.cpu.regs.flags.carry = cf != 0;
-                    self.cpu.regs.flags.overflow = of != 0;
+                    self.cpu.regs.flags.set_parity_u8(res as usize);
+                    self.cpu.regs.flags.set_carry(cf != 0);
+                    self.cpu.regs.flags.set_overflow(of != 0);
 
                     self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, res as u8);
                 }
             }
             Op::Shl16 => {
                 // two arguments
-                let count = self.cpu.read_parameter_value(&self.mmu, &op.params.src) & 0x1F; // use only 5 LSB
+                let count = self.cpu.read_shift_count(&self.mmu, &op.params.src);
                 if count > 0 {
                     let op1 = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
 
@@ -2342,9 +3181,9 @@ impl Machine {
 
                     self.cpu.regs.flags.set_sign_u16(res as usize);
                     self.cpu.regs.flags.set_zero_u16(res as usize);
-                    self.cpu.regs.flags.set_parity(res as usize);
-                    self.cpu.regs.flags.carry = cf != 0;
-                    self.cpu.regs.flags.overflow = (of & 1) != 0;
+                    self.cpu.regs.flags.set_parity_u8(res as usize);
+                    self.cpu.regs.flags.set_carry(cf != 0);
+                    self.cpu.regs.flags.set_overflow((of & 1) != 0);
                 }
             }
             Op::Shl32 => {
@@ -2355,15 +3194,16 @@ impl Machine {
                 if count > 0 {
                     let res = dst.wrapping_shl(count as u32);
                     self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u32);
-                    self.cpu.regs.flags.carry = (res & 0x8000_0000) != 0;
+                    self.cpu.regs.flags.set_carry((res & 0x8000_0000) != 0);
                     if count == 1 {
-                        self.cpu.regs.flags.overflow = self.cpu.regs.flags.carry_val() ^ ((res & 0x8000) >> 15) != 0; // XXX
+                        self.cpu.regs.flags.set_overflow(self.cpu.regs.flags.carry_val() ^ ((res & 0x8000) >> 15) != 0); // XXX
                     }
                     self.cpu.regs.flags.set_sign_u32(res);
                     self.cpu.regs.flags.set_zero_u32(res);
-                    self.cpu.regs.flags.set_parity(res);
+                    self.cpu.regs.flags.set_parity_u8(res);
                 }
             }
+            Op::Shld | Op::Shrd if op.params.dst.is_32bit() => self.shift_double32(op),
             Op::Shld => {
                 // 3 arguments
                 let count = self.cpu.read_parameter_value(&self.mmu, &op.params.src2) & 0x1F; // use 5 lsb
@@ -2389,29 +3229,29 @@ impl Machine {
                     self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res16);
 
                     let cf = (temp_32 >> (32 - count)) & 0x1;
-                    self.cpu.regs.flags.carry = cf != 0;
+                    self.cpu.regs.flags.set_carry(cf != 0);
 
                     let of = cf ^ (u32::from(res16 >> 15));
-                    self.cpu.regs.flags.overflow = of != 0;
+                    self.cpu.regs.flags.set_overflow(of != 0);
 
                     self.cpu.regs.flags.set_zero_u16(res16 as usize);
                     self.cpu.regs.flags.set_sign_u16(res16 as usize);
-                    self.cpu.regs.flags.set_parity(res16 as usize);
+                    self.cpu.regs.flags.set_parity_u8(res16 as usize);
                 }
             }
             Op::Shr8 => {
                 // Unsigned divide r/m8 by 2, `src` times.
                 // two arguments
                 let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
-                let count = self.cpu.read_parameter_value(&self.mmu, &op.params.src) & 0x1F;
+                let count = self.cpu.read_shift_count(&self.mmu, &op.params.src);
                 if count > 0 {
-                    let res = dst.wrapping_shr(count as u32);
+                    let res = dst.checked_shr(count as u32).unwrap_or(0);
                     self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, res as u8);
-                    self.cpu.regs.flags.carry = (dst.wrapping_shr((count - 1) as u32) & 0x1) != 0;
-                    self.cpu.regs.flags.overflow = dst & 0x80 != 0;
+                    self.cpu.regs.flags.set_carry((dst.checked_shr((count - 1) as u32).unwrap_or(0) & 0x1) != 0);
+                    self.cpu.regs.flags.set_overflow(dst & 0x80 != 0);
                     self.cpu.regs.flags.set_sign_u8(res);
                     self.cpu.regs.flags.set_zero_u8(res);
-                    self.cpu.regs.flags.set_parity(res);
+                    self.cpu.regs.flags.set_parity_u8(res);
                     /*
                     The CF flag contains the value of the last bit shifted out of the destination operand;
                     it is undefined for SHL and SHR instructions where the count is greater than or equal to the size (in bits) of the destination operand.
@@ -2425,15 +3265,15 @@ impl Machine {
             Op::Shr16 => {
                 // two arguments
                 let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
-                let count = self.cpu.read_parameter_value(&self.mmu, &op.params.src) & 0x1F;
+                let count = self.cpu.read_shift_count(&self.mmu, &op.params.src);
                 if count > 0 {
-                    let res = dst.wrapping_shr(count as u32);
+                    let res = dst.checked_shr(count as u32).unwrap_or(0);
                     self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u16);
-                    self.cpu.regs.flags.carry = (dst.wrapping_shr((count - 1) as u32) & 0x1) != 0;
-                    self.cpu.regs.flags.overflow = dst & 0x8000 != 0;
+                    self.cpu.regs.flags.set_carry((dst.checked_shr((count - 1) as u32).unwrap_or(0) & 0x1) != 0);
+                    self.cpu.regs.flags.set_overflow(dst & 0x8000 != 0);
                     self.cpu.regs.flags.set_sign_u16(res);
                     self.cpu.regs.flags.set_zero_u16(res);
-                    self.cpu.regs.flags.set_parity(res);
+                    self.cpu.regs.flags.set_parity_u8(res);
                 }
             }
             Op::Shr32 => {
@@ -2443,11 +3283,11 @@ impl Machine {
                 if count > 0 {
                     let res = dst.wrapping_shr(count as u32);
                     self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u32);
-                    self.cpu.regs.flags.carry = (dst.wrapping_shr((count - 1) as u32) & 0x1) != 0; // XXX
-                    self.cpu.regs.flags.overflow = dst & 0x8000_0000 != 0;
+                    self.cpu.regs.flags.set_carry((dst.wrapping_shr((count - 1) as u32) & 0x1) != 0); // XXX
+                    self.cpu.regs.flags.set_overflow(dst & 0x8000_0000 != 0);
                     self.cpu.regs.flags.set_sign_u32(res);
                     self.cpu.regs.flags.set_zero_u32(res);
-                    self.cpu.regs.flags.set_parity(res);
+                    self.cpu.regs.flags.set_parity_u8(res);
                 }
             }
             Op::Shrd => {
@@ -2481,7 +3321,7 @@ impl Machine {
                 // SF, ZF, and PF flags are set according to the value of the result.
                 self.cpu.regs.flags.set_sign_u16(result_16 as usize);
                 self.cpu.regs.flags.set_zero_u16(result_16 as usize);
-                self.cpu.regs.flags.set_parity(result_16 as usize);
+                self.cpu.regs.flags.set_parity_u8(result_16 as usize);
 
                 let mut cf = (dst >> (count - 1)) & 0x1;
                 let of = (((result_16 << 1) ^ result_16) >> 15) & 0x1; // of = result14 ^ result15
@@ -2489,66 +3329,77 @@ impl Machine {
                     // undefined flags behavior matching real HW
                     cf = (src >> (count - 17)) & 0x1;
                 }
-                self.cpu.regs.flags.carry = cf != 0;
-                self.cpu.regs.flags.overflow = of != 0;
+                self.cpu.regs.flags.set_carry(cf != 0);
+                self.cpu.regs.flags.set_overflow(of != 0);
+            }
+            Op::Sgdt | Op::Sidt => {
+                let table = if op.command == Op::Sgdt {
+                    self.cpu.gdtr
+                } else {
+                    self.cpu.idtr
+                };
+                let base = if op.op_size == OperandSize::_16bit {
+                    table.base & 0x00FF_FFFF
+                } else {
+                    table.base
+                };
+                let (seg, off) = self.cpu.parameter_segment_offset(&op.params.dst);
+                self.cpu.write_mem(&mut self.mmu, seg, off, 2, u64::from(table.limit));
+                self.cpu.write_mem(&mut self.mmu, seg, off.wrapping_add(2), 4, u64::from(base));
             }
             Op::Sldt => {
-                println!("XXX impl {}", op);
+                if self.cpu.check_protected_mode() {
+                    let ldtr = self.cpu.ldtr;
+                    self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, ldtr);
+                }
+            }
+            Op::Smsw => {
+                let msw = self.cpu.cr0 as u16;
+                self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, msw);
             }
             Op::Stc => {
-                self.cpu.regs.flags.carry = true;
+                self.cpu.regs.flags.set_carry(true);
             }
             Op::Std => {
                 self.cpu.regs.flags.direction = true;
             }
             Op::Sti => {
-                self.cpu.regs.flags.interrupt = true;
+                if self.cpu.check_iopl() {
+                    self.cpu.regs.flags.interrupt = true;
+                }
             }
             Op::Stosb => {
                 // no parameters
                 // store AL at ES:(E)DI
                 // The ES segment cannot be overridden with a segment override prefix.
                 let al = self.cpu.get_r8(R::AL);
-                let es = self.cpu.get_r16(R::ES);
-                let di = self.cpu.get_r16(R::DI);
-                self.mmu.write_u8(es, di, al);
-                let di = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::DI).wrapping_add(1)
-                } else {
-                    self.cpu.get_r16(R::DI).wrapping_sub(1)
-                };
-                self.cpu.set_r16(R::DI, di);
+                let di = self.address_register(op, R::DI);
+                self.cpu.write_mem(&mut self.mmu, R::ES, di, 1, u64::from(al));
+                self.advance_string_index(op, R::DI, 1);
             }
             Op::Stosw => {
                 // no parameters
                 // store AX at address ES:(E)DI
                 // The ES segment cannot be overridden with a segment override prefix.
                 let ax = self.cpu.get_r16(R::AX);
-                let es = self.cpu.get_r16(R::ES);
-                let di = self.cpu.get_r16(R::DI);
-                self.mmu.write_u16(es, di, ax);
-                let di = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::DI).wrapping_add(2)
-                } else {
-                    self.cpu.get_r16(R::DI).wrapping_sub(2)
-                };
-                self.cpu.set_r16(R::DI, di);
+                let di = self.address_register(op, R::DI);
+                self.cpu.write_mem(&mut self.mmu, R::ES, di, 2, u64::from(ax));
+                self.advance_string_index(op, R::DI, 2);
             }
             Op::Stosd => {
                 // no parameters
                 // store EAX at address ES:(E)DI
                 // The ES segment cannot be overridden with a segment override prefix.
                 let eax = self.cpu.get_r32(R::EAX);
-                let es = self.cpu.get_r16(R::ES);
-                let di = self.cpu.get_r16(R::DI);
-                self.mmu.write_u32(es, di, eax);
-                // XXX adjust DI or EDI ?
-                let di = if !self.cpu.regs.flags.direction {
-                    self.cpu.get_r16(R::DI).wrapping_add(4)
-                } else {
-                    self.cpu.get_r16(R::DI).wrapping_sub(4)
-                };
-                self.cpu.set_r16(R::DI, di);
+                let di = self.address_register(op, R::DI);
+                self.cpu.write_mem(&mut self.mmu, R::ES, di, 4, u64::from(eax));
+                self.advance_string_index(op, R::DI, 4);
+            }
+            Op::Str => {
+                if self.cpu.check_protected_mode() {
+                    let tr = self.cpu.tr;
+                    self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, tr);
+                }
             }
             Op::Sub8 => {
                 // two parameters (dst=reg)
@@ -2557,12 +3408,7 @@ impl Machine {
                 let res = dst.wrapping_sub(src);
 
                 // The OF, SF, ZF, AF, PF, and CF flags are set according to the result.
-                self.cpu.regs.flags.set_overflow_sub_u8(res, src, dst);
-                self.cpu.regs.flags.set_sign_u8(res);
-                self.cpu.regs.flags.set_zero_u8(res);
-                self.cpu.regs.flags.set_adjust(res, src, dst);
-                self.cpu.regs.flags.set_parity(res);
-                self.cpu.regs.flags.set_carry_u8(res);
+                self.cpu.regs.flags.set_sub_u8(res, src, dst);
 
                 self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, res as u8);
             }
@@ -2573,12 +3419,7 @@ impl Machine {
                 let res = dst.wrapping_sub(src);
 
                 // The OF, SF, ZF, AF, PF, and CF flags are set according to the result.
-                self.cpu.regs.flags.set_overflow_sub_u16(res, src, dst);
-                self.cpu.regs.flags.set_sign_u16(res);
-                self.cpu.regs.flags.set_zero_u16(res);
-                self.cpu.regs.flags.set_adjust(res, src, dst);
-                self.cpu.regs.flags.set_parity(res);
-                self.cpu.regs.flags.set_carry_u16(res);
+                self.cpu.regs.flags.set_sub_u16(res, src, dst);
 
                 self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u16);
             }
@@ -2589,12 +3430,7 @@ impl Machine {
                 let res = dst.wrapping_sub(src);
 
                 // The OF, SF, ZF, AF, PF, and CF flags are set according to the result.
-                self.cpu.regs.flags.set_overflow_sub_u32(res, src, dst);
-                self.cpu.regs.flags.set_sign_u32(res);
-                self.cpu.regs.flags.set_zero_u32(res);
-                self.cpu.regs.flags.set_adjust(res, src, dst);
-                self.cpu.regs.flags.set_parity(res);
-                self.cpu.regs.flags.set_carry_u32(res);
+                self.cpu.regs.flags.set_sub_u32(res, src, dst);
 
                 self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u32);
             }
@@ -2603,24 +3439,35 @@ impl Machine {
                 let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
                 let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
                 let res = dst & src;
-                self.cpu.regs.flags.overflow = false;
-                self.cpu.regs.flags.carry = false;
-                // set SF, ZF, PF according to result.
-                self.cpu.regs.flags.set_sign_u8(res);
-                self.cpu.regs.flags.set_zero_u8(res);
-                self.cpu.regs.flags.set_parity(res);
+                // The OF and CF flags are cleared; set SF, ZF, PF according to result.
+                self.cpu.regs.flags.set_logic_u8(res);
             }
             Op::Test16 => {
                 // two parameters
                 let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
                 let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
                 let res = dst & src;
-                self.cpu.regs.flags.overflow = false;
-                self.cpu.regs.flags.carry = false;
-                // set SF, ZF, PF according to result.
-                self.cpu.regs.flags.set_sign_u16(res);
-                self.cpu.regs.flags.set_zero_u16(res);
-                self.cpu.regs.flags.set_parity(res);
+                // The OF and CF flags are cleared; set SF, ZF, PF according to result.
+                self.cpu.regs.flags.set_logic_u16(res);
+            }
+            Op::Test32 => {
+                // two parameters
+                let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
+                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst);
+                let res = dst & src;
+                // The OF and CF flags are cleared; set SF, ZF, PF according to result.
+                self.cpu.regs.flags.set_logic_u32(res);
+            }
+            Op::Verr | Op::Verw => {
+                if self.cpu.check_protected_mode() {
+                    let selector = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                    let accessible = match self.cpu.visible_descriptor(&self.mmu, selector) {
+                        Some(desc) if op.command == Op::Verr => desc.is_readable(),
+                        Some(desc) => desc.is_writable(),
+                        None => false,
+                    };
+                    self.cpu.regs.flags.set_zero(accessible);
+                }
             }
             Op::Xchg8 => {
                 // two parameters (registers)
@@ -2630,6 +3477,30 @@ impl Machine {
                 self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, dst as u8);
                 self.cpu.write_parameter_u8(&mut self.mmu, &op.params.src, src as u8);
             }
+            Op::Xadd8 => {
+                let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u8;
+                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u8;
+                let res = src as usize + dst as usize;
+                self.cpu.regs.flags.set_add_u8(res, src as usize, dst as usize);
+                self.cpu.write_parameter_u8(&mut self.mmu, &op.params.src, dst);
+                self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, res as u8);
+            }
+            Op::Xadd16 => {
+                let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u16;
+                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u16;
+                let res = src as usize + dst as usize;
+                self.cpu.regs.flags.set_add_u16(res, src as usize, dst as usize);
+                self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.src, dst);
+                self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u16);
+            }
+            Op::Xadd32 => {
+                let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u32;
+                let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u32;
+                let res = src as usize + dst as usize;
+                self.cpu.regs.flags.set_add_u32(res, src as usize, dst as usize);
+                self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.src, dst);
+                self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u32);
+            }
             Op::Xchg16 => {
                 // two parameters (registers)
                 let mut src = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
@@ -2650,7 +3521,11 @@ impl Machine {
                 // no parameters
                 // Set AL to memory byte DS:[(E)BX + unsigned AL].
                 // The DS segment may be overridden with a segment override prefix.
-                let al = self.mmu.read_u8(self.cpu.segment(op.segment_prefix), self.cpu.get_r16(R::BX) + u16::from(self.cpu.get_r8(R::AL)));
+                let mut offset = self.address_register(op, R::BX).wrapping_add(u32::from(self.cpu.get_r8(R::AL)));
+                if op.address_size == AddressSize::_16bit {
+                    offset &= 0xFFFF;
+                }
+                let al = self.cpu.read_mem(&self.mmu, op.segment_prefix.as_register(), offset, 1) as u8;
                 self.cpu.set_r8(R::AL, al);
             }
             Op::Xor8 => {
@@ -2661,11 +3536,7 @@ impl Machine {
 
                 // The OF and CF flags are cleared; the SF, ZF,
                 // and PF flags are set according to the result.
-                self.cpu.regs.flags.overflow = false;
-                self.cpu.regs.flags.carry = false;
-                self.cpu.regs.flags.set_sign_u8(res);
-                self.cpu.regs.flags.set_zero_u8(res);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_logic_u8(res);
 
                 self.cpu.write_parameter_u8(&mut self.mmu, &op.params.dst, res as u8);
             }
@@ -2677,11 +3548,7 @@ impl Machine {
 
                 // The OF and CF flags are cleared; the SF, ZF,
                 // and PF flags are set according to the result.
-                self.cpu.regs.flags.overflow = false;
-                self.cpu.regs.flags.carry = false;
-                self.cpu.regs.flags.set_sign_u16(res);
-                self.cpu.regs.flags.set_zero_u16(res);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_logic_u16(res);
 
                 self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u16);
             }
@@ -2693,11 +3560,7 @@ impl Machine {
 
                 // The OF and CF flags are cleared; the SF, ZF,
                 // and PF flags are set according to the result.
-                self.cpu.regs.flags.overflow = false;
-                self.cpu.regs.flags.carry = false;
-                self.cpu.regs.flags.set_sign_u32(res);
-                self.cpu.regs.flags.set_zero_u32(res);
-                self.cpu.regs.flags.set_parity(res);
+                self.cpu.regs.flags.set_logic_u32(res);
 
                 self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u32);
             }
@@ -2711,34 +3574,383 @@ impl Machine {
             }
         }
 
+        let taken = self.cpu.regs.ip != self.next_ip_from(start_ip, op);
+        self.cpu.cycle_count += instruction_cycles(self.cpu.model, op, taken);
+
+        if self.cpu.exception_pending() {
+            // the instruction was aborted
+            return;
+        }
+
         match op.repeat {
             RepeatMode::Rep => {
-                let cx = self.cpu.get_r16(R::CX).wrapping_sub(1);
-                self.cpu.set_r16(R::CX, cx);
+                let cx = self.decrement_count_register(op);
                 if cx != 0 {
                     self.cpu.regs.ip = start_ip;
                 }
             }
             RepeatMode::Repe => {
-                let cx = self.cpu.get_r16(R::CX).wrapping_sub(1);
-                self.cpu.set_r16(R::CX, cx);
-                if cx != 0 && self.cpu.regs.flags.zero {
+                let cx = self.decrement_count_register(op);
+                if cx != 0 && self.cpu.regs.flags.zero() {
                     self.cpu.regs.ip = start_ip;
                 }
             }
             RepeatMode::Repne => {
-                let cx = self.cpu.get_r16(R::CX).wrapping_sub(1);
-                self.cpu.set_r16(R::CX, cx);
-                if cx != 0 && !self.cpu.regs.flags.zero {
+                let cx = self.decrement_count_register(op);
+                if cx != 0 && !self.cpu.regs.flags.zero() {
                     self.cpu.regs.ip = start_ip;
                 }
             }
             RepeatMode::None => {}
         }
 
+        if op.repeat != RepeatMode::None && self.cpu.regs.ip != start_ip {
+            // the last iteration of a REP prefixed instruction
+            self.cpu.cycle_count += rep_setup_cycles(self.cpu.model, op);
+        }
+
         if op.lock {
             // TODO implement lock
             // println!("XXX FIXME: instruction has LOCK prefix: {}", op);
         }
     }
+
+    /// returns the offset of the instruction following `op` at `ip`, wrapping at 64k in a 16-bit code segment
+    fn next_ip_from(&self, ip: u32, op: &Instruction) -> u32 {
+        let next = ip.wrapping_add(u32::from(op.length));
+        if self.cpu.is_code32() {
+            next
+        } else {
+            next & 0xFFFF
+        }
+    }
+
+    /// returns the offset of the instruction following `op`
+    fn next_ip(&self, op: &Instruction) -> u32 {
+        self.next_ip_from(self.cpu.regs.ip, op)
+    }
+
+    /// returns the selector:offset of a far jump or call, given as a immediate or read from a m16:16 or m16:32 operand
+    fn far_pointer(&mut self, op: &Instruction) -> (u16, u32) {
+        match op.params.dst {
+            Parameter::Ptr16Imm(seg, imm) => (seg, u32::from(imm)),
+            Parameter::Ptr16Imm32(seg, imm) => (seg, imm),
+            _ => {
+                let (r, offset) = self.cpu.parameter_segment_offset(&op.params.dst);
+                if op.op_size == OperandSize::_32bit {
+                    let imm = self.cpu.read_mem(&self.mmu, r, offset, 4) as u32;
+                    let seg = self.cpu.read_mem(&self.mmu, r, offset.wrapping_add(4), 2) as u16;
+                    (seg, imm)
+                } else {
+                    let imm = self.cpu.read_mem(&self.mmu, r, offset, 2) as u32;
+                    let seg = self.cpu.read_mem(&self.mmu, r, offset.wrapping_add(2), 2) as u16;
+                    (seg, imm)
+                }
+            }
+        }
+    }
+
+    /// decrements the count register used by REP and LOOP, which is ECX with a 32-bit address size
+    fn decrement_count_register(&mut self, op: &Instruction) -> u32 {
+        if op.address_size == AddressSize::_32bit {
+            let ecx = self.cpu.get_r32(R::ECX).wrapping_sub(1);
+            self.cpu.set_r32(R::ECX, ecx);
+            ecx
+        } else {
+            let cx = self.cpu.get_r16(R::CX).wrapping_sub(1);
+            self.cpu.set_r16(R::CX, cx);
+            u32::from(cx)
+        }
+    }
+
+    /// returns the 16-bit register `r`, or its 32-bit form with a 32-bit address size, as used
+    /// for the (E)SI and (E)DI string indexes, JCXZ and XLAT
+    fn address_register(&self, op: &Instruction, r: R) -> u32 {
+        if op.address_size == AddressSize::_32bit {
+            self.cpu.get_r32(r32(r.u8()))
+        } else {
+            u32::from(self.cpu.get_r16(r))
+        }
+    }
+
+    /// steps the string index `r` (SI or DI) by `size` bytes in the direction of the direction flag
+    fn advance_string_index(&mut self, op: &Instruction, r: R, size: u32) {
+        let val = self.address_register(op, r);
+        let val = if !self.cpu.regs.flags.direction {
+            val.wrapping_add(size)
+        } else {
+            val.wrapping_sub(size)
+        };
+        if op.address_size == AddressSize::_32bit {
+            self.cpu.set_r32(r32(r.u8()), val);
+        } else {
+            self.cpu.set_r16(r, val as u16);
+        }
+    }
+
+    /// executes the iterations of a REP prefixed string instruction as a block operation on
+    /// memory. returns false if the instruction must be executed one iteration at a time
+    fn execute_string_block(&mut self, op: &Instruction) -> bool {
+        let size: u32 = match op.command {
+            Op::Movsb | Op::Stosb | Op::Lodsb | Op::Scasb | Op::Cmpsb => 1,
+            Op::Movsw | Op::Stosw | Op::Lodsw | Op::Scasw | Op::Cmpsw => 2,
+            Op::Movsd | Op::Stosd | Op::Lodsd | Op::Scasd | Op::Cmpsd => 4,
+            _ => return false,
+        };
+        // single stepping traps after each iteration
+        if self.cpu.regs.flags.trap || op.address_size != AddressSize::_16bit {
+            return false;
+        }
+
+        // stop before the next timer tick, so the interrupt is taken between iterations
+        let cycles = instruction_cycles(self.cpu.model, op, false);
+        let budget = self.next_timer_cycle.saturating_sub(self.cpu.cycle_count) / cycles.max(1);
+        let count = self.cpu.get_r16(R::CX).min(budget.min(0xFFFF) as u16);
+        if count == 0 {
+            return false;
+        }
+
+        let backward = self.cpu.regs.flags.direction;
+        let uses_src = match op.command {
+            Op::Movsb | Op::Movsw | Op::Movsd | Op::Lodsb | Op::Lodsw | Op::Lodsd | Op::Cmpsb | Op::Cmpsw | Op::Cmpsd => true,
+            _ => false,
+        };
+        let uses_dst = match op.command {
+            Op::Lodsb | Op::Lodsw | Op::Lodsd => false,
+            _ => true,
+        };
+        let writes_dst = match op.command {
+            Op::Movsb | Op::Movsw | Op::Movsd | Op::Stosb | Op::Stosw | Op::Stosd => true,
+            _ => false,
+        };
+        let si = self.cpu.get_r16(R::SI);
+        let di = self.cpu.get_r16(R::DI);
+        let len = u32::from(count) * size;
+        let src = match string_block_start(si, count, size, backward) {
+            Some(start) => match self.cpu.checked_segment_address(op.segment_prefix.as_register(), u32::from(start), len, false) {
+                Some(addr) => addr,
+                None if uses_src => return false,
+                None => 0,
+            },
+            None if uses_src => return false,
+            None => 0,
+        };
+        let dst = match string_block_start(di, count, size, backward) {
+            Some(start) => match self.cpu.checked_segment_address(R::ES, u32::from(start), len, writes_dst) {
+                Some(addr) => addr,
+                None if uses_dst => return false,
+                None => 0,
+            },
+            None if uses_dst => return false,
+            None => 0,
+        };
+        if (uses_src && !self.mmu.is_block_accessible(src, len)) || (uses_dst && !self.mmu.is_block_accessible(dst, len)) {
+            return false;
+        }
+
+        // linear address of element `i`, counted in the direction of the iterations
+        let element = |base: u32, i: u32| if backward {
+            base + (u32::from(count) - 1 - i) * size
+        } else {
+            base + i * size
+        };
+        let read = |mmu: &MMU, addr: u32| match size {
+            1 => u32::from(mmu.memory.read_u8(addr)),
+            2 => u32::from(mmu.memory.read_u16(addr)),
+            _ => mmu.memory.read_u32(addr),
+        };
+        let acc = self.cpu.get_r32(R::EAX) & (u32::MAX >> (32 - 8 * size));
+
+        let mut done = count;
+        let mut stopped = false;
+        match op.command {
+            Op::Movsb | Op::Movsw | Op::Movsd => {
+                if src < dst + len && dst < src + len {
+                    // overlapping moves repeats the data, as done one iteration at a time
+                    return false;
+                }
+                self.mmu.copy_block(src, dst, len);
+            }
+            Op::Stosb | Op::Stosw | Op::Stosd => {
+                self.mmu.fill_block(dst, &acc.to_le_bytes()[..size as usize], u32::from(count));
+            }
+            Op::Lodsb | Op::Lodsw | Op::Lodsd => {
+                let val = read(&self.mmu, element(src, u32::from(count) - 1));
+                match size {
+                    1 => self.cpu.set_r8(R::AL, val as u8),
+                    2 => self.cpu.set_r16(R::AX, val as u16),
+                    _ => self.cpu.set_r32(R::EAX, val),
+                }
+            }
+            _ => {
+                // SCAS compares the accumulator and CMPS the DS:SI element with the ES:DI element,
+                // until the REPE or REPNE condition ends the repetition
+                let mut last = (0, 0);
+                for i in 0..u32::from(count) {
+                    let left = read(&self.mmu, element(dst, i));
+                    let right = if uses_src {
+                        read(&self.mmu, element(src, i))
+                    } else {
+                        acc
+                    };
+                    last = (left, right);
+                    let stop = match op.repeat {
+                        RepeatMode::Repe => left != right,
+                        RepeatMode::Repne => left == right,
+                        _ => false,
+                    };
+                    if stop {
+                        done = i as u16 + 1;
+                        stopped = true;
+                        break;
+                    }
+                }
+                let (left, right) = (last.0 as usize, last.1 as usize);
+                match size {
+                    1 => self.cpu.cmp8(left, right),
+                    2 => self.cpu.cmp16(left, right),
+                    _ => self.cpu.cmp32(left, right),
+                }
+            }
+        }
+
+        let advance = done.wrapping_mul(size as u16);
+        let (si, di) = if backward {
+            (si.wrapping_sub(advance), di.wrapping_sub(advance))
+        } else {
+            (si.wrapping_add(advance), di.wrapping_add(advance))
+        };
+        if uses_src {
+            self.cpu.set_r16(R::SI, si);
+        }
+        if uses_dst {
+            self.cpu.set_r16(R::DI, di);
+        }
+        let cx = self.cpu.get_r16(R::CX) - done;
+        self.cpu.set_r16(R::CX, cx);
+        self.cpu.instruction_count += usize::from(done);
+        self.cpu.cycle_count += usize::from(done) * cycles;
+
+        if cx == 0 || stopped {
+            // the last iteration
+            self.cpu.regs.ip = self.next_ip(op);
+            self.cpu.cycle_count += rep_setup_cycles(self.cpu.model, op);
+        }
+        true
+    }
+
+    /// executes BT, BTS, BTR and BTC
+    fn bit_test(&mut self, op: &Instruction) {
+        let bits: isize = if op.params.dst.is_32bit() {
+            32
+        } else {
+            16
+        };
+        // a register bit offset is signed, an immediate bit offset is taken modulo the operand size
+        let offset = self.cpu.read_parameter_value(&self.mmu, &op.params.src);
+        let offset = match op.params.src {
+            Parameter::Reg16(_) => offset as u16 as i16 as isize,
+            Parameter::Reg32(_) => offset as u32 as i32 as isize,
+            _ => offset as isize & (bits - 1),
+        };
+        let mask = 1usize << (offset & (bits - 1));
+
+        let (val, address) = if op.params.dst.is_reg() {
+            (self.cpu.read_parameter_value(&self.mmu, &op.params.dst), None)
+        } else {
+            // with a memory operand, the bit offset may address bits outside of the operand
+            let (seg, off) = self.cpu.parameter_segment_offset(&op.params.dst);
+            let mut off = off.wrapping_add((offset.div_euclid(bits) * (bits / 8)) as u32);
+            if op.address_size == AddressSize::_16bit {
+                off &= 0xFFFF;
+            }
+            let val = if bits == 32 {
+                self.cpu.read_mem(&self.mmu, seg, off, 4) as u32 as usize
+            } else {
+                self.cpu.read_mem(&self.mmu, seg, off, 2) as u16 as usize
+            };
+            (val, Some((seg, off)))
+        };
+
+        self.cpu.regs.flags.set_carry(val & mask != 0);
+        let res = match op.command {
+            Op::Bts => val | mask,
+            Op::Btr => val & !mask,
+            Op::Btc => val ^ mask,
+            _ => return,
+        };
+        match address {
+            Some((seg, off)) if bits == 32 => self.cpu.write_mem(&mut self.mmu, seg, off, 4, u64::from(res as u32)),
+            Some((seg, off)) => self.cpu.write_mem(&mut self.mmu, seg, off, 2, u64::from(res as u16)),
+            None if bits == 32 => self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u32),
+            None => self.cpu.write_parameter_u16(&mut self.mmu, op.segment_prefix, &op.params.dst, res as u16),
+        }
+    }
+
+    /// executes SHLD and SHRD with 32-bit operands
+    fn shift_double32(&mut self, op: &Instruction) {
+        let count = self.cpu.read_parameter_value(&self.mmu, &op.params.src2) & 0x1F; // use 5 lsb
+        if count == 0 {
+            return;
+        }
+        let dst = self.cpu.read_parameter_value(&self.mmu, &op.params.dst) as u64 & 0xFFFF_FFFF;
+        let src = self.cpu.read_parameter_value(&self.mmu, &op.params.src) as u64 & 0xFFFF_FFFF;
+        let (res, cf) = if op.command == Op::Shld {
+            // shift dst:src left, bits from src fills in from the right
+            ((((dst << 32 | src) << count) >> 32) as u32, (dst >> (32 - count)) & 1)
+        } else {
+            // shift src:dst right, bits from src fills in from the left
+            (((src << 32 | dst) >> count) as u32, (dst >> (count - 1)) & 1)
+        };
+        self.cpu.write_parameter_u32(&mut self.mmu, op.segment_prefix, &op.params.dst, res);
+
+        // OF is only defined for 1-bit shifts, and is set if the sign bit changed
+        self.cpu.regs.flags.set_carry(cf != 0);
+        self.cpu.regs.flags.set_overflow((u64::from(res) ^ dst) & 0x8000_0000 != 0);
+        self.cpu.regs.flags.set_sign_u32(res as usize);
+        self.cpu.regs.flags.set_zero_u32(res as usize);
+        self.cpu.regs.flags.set_parity_u8(res as usize);
+    }
+
+    /// stores the 14 byte real mode fpu environment (FNSTENV, FNSAVE)
+    /// XXX the instruction and operand pointers are not tracked and are stored as 0
+    fn fpu_store_environment(&mut self, seg: R, off: u32) {
+        let words = [self.cpu.fpu.control_word, self.cpu.fpu.status_word(), self.cpu.fpu.tag_word(), 0, 0, 0, 0];
+        for (i, w) in words.iter().enumerate() {
+            self.cpu.write_mem(&mut self.mmu, seg, off.wrapping_add(i as u32 * 2), 2, u64::from(*w));
+        }
+    }
+
+    /// loads the 14 byte real mode fpu environment (FLDENV, FRSTOR)
+    fn fpu_load_environment(&mut self, seg: R, off: u32) {
+        self.cpu.fpu.control_word = self.cpu.read_mem(&self.mmu, seg, off, 2) as u16;
+        let status_word = self.cpu.read_mem(&self.mmu, seg, off.wrapping_add(2), 2) as u16;
+        self.cpu.fpu.set_status_word(status_word);
+        let tag_word = self.cpu.read_mem(&self.mmu, seg, off.wrapping_add(4), 2) as u16;
+        self.cpu.fpu.set_tag_word(tag_word);
+    }
+}
+
+/// returns the stack index of a fpu register parameter
+fn fpu_register_index(p: &Parameter) -> usize {
+    match *p {
+        Parameter::FPR80(r) => r.index(),
+        _ => panic!("expected fpu register, got {:?}", p),
+    }
+}
+
+/// returns the offset of the lowest element of a string instruction block of `count` elements
+/// of `size` bytes, starting at `offset`. returns None if the block wraps around the segment
+fn string_block_start(offset: u16, count: u16, size: u32, backward: bool) -> Option<u16> {
+    let offset = u32::from(offset);
+    let span = (u32::from(count) - 1) * size;
+    let start = if backward {
+        offset.checked_sub(span)?
+    } else {
+        offset
+    };
+    if start + span + size > 0x1_0000 {
+        return None;
+    }
+    Some(start as u16)
 }
@@ -9,11 +9,17 @@ const DEBUG_MEMORY: bool = false;
 
 impl FlatMemory {
     pub fn new() -> Self {
-        FlatMemory { data: vec![0u8; 0x1_0000 * 64] }
+        Self::with_size(0x1_0000 * 64)
     }
 
+    /// returns a memory of `size` bytes
+    pub fn with_size(size: usize) -> Self {
+        FlatMemory { data: vec![0u8; size] }
+    }
+
+    /// reads the byte at `addr`. addresses past the end of the memory reads as a open bus
     pub fn read_u8(&self, addr: u32) -> u8 {
-        let val = self.data[addr as usize];
+        let val = self.data.get(addr as usize).copied().unwrap_or(0xFF);
         if DEBUG_MEMORY {
             println!("read_u8 from {:06x} = {:02x}", addr, val);
         }
@@ -24,11 +30,14 @@ impl FlatMemory {
         u16::from(self.read_u8(addr + 1)) << 8 | u16::from(self.read_u8(addr))
     }
 
+    /// writes the byte at `addr`. writes past the end of the memory are dropped
     pub fn write_u8(&mut self, addr: u32, data: u8) {
         if DEBUG_MEMORY {
             println!("write_u8 to {:06x} = {:02x}", addr, data);
         }
-        self.data[addr as usize] = data;
+        if let Some(val) = self.data.get_mut(addr as usize) {
+            *val = data;
+        }
     }
 
     pub fn write_u16(&mut self, addr: u32, data: u16) {
@@ -45,11 +54,22 @@ impl FlatMemory {
         self.write_u16(addr + 2, (data >> 16) as u16);
     }
 
+    pub fn read_u64(&self, addr: u32) -> u64 {
+        u64::from(self.read_u32(addr + 4)) << 32 | u64::from(self.read_u32(addr))
+    }
+
+    pub fn write_u64(&mut self, addr: u32, data: u64) {
+        self.write_u32(addr, data as u32);
+        self.write_u32(addr + 4, (data >> 32) as u32);
+    }
+
+    /// returns the `length` bytes at `addr`, which must be within the memory
     pub fn read(&self, addr: u32, length: usize) -> &[u8] {
         let addr = addr as usize;
         &self.data[addr..addr+length]
     }
 
+    /// writes `data` at `addr`, which must be within the memory
     pub fn write(&mut self, addr: u32, data: &[u8]) {
         let addr = addr as usize;
         if DEBUG_MEMORY {
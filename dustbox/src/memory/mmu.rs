@@ -1,4 +1,7 @@
-use crate::memory::{FlatMemory, MemoryAddress};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use crate::memory::{FlatMemory, MemoryAddress, Paging, PageFault};
 use crate::codepage::cp437;
 
 #[cfg(test)]
@@ -8,19 +11,423 @@ mod mmu_test;
 const DEBUG_MMU: bool = false;
 const DEBUG_VEC: bool = false;
 
+/// granularity of the tracking of writes to memory holding cached instructions
+const CODE_PAGE_SHIFT: u32 = 12;
+
+/// address line 20, forced low while the A20 gate is disabled
+const A20_BIT: u32 = 0x10_0000;
+
+/// granularity of the physical memory regions
+const REGION_PAGE_SHIFT: u32 = 12;
+const REGION_PAGE_SIZE: u32 = 1 << REGION_PAGE_SHIFT;
+
+/// a device handling the accesses to a memory-mapped I/O region, see MMU::map_mmio
+pub trait MemoryHandler {
+    /// reads the byte at physical address `addr`
+    fn read_u8(&mut self, addr: u32) -> u8;
+
+    /// writes the byte at physical address `addr`
+    fn write_u8(&mut self, addr: u32, data: u8);
+}
+
+/// the kind of a page of physical memory
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Region {
+    /// plain memory, accessed directly
+    Ram,
+
+    /// read-only memory, writes are ignored
+    Rom,
+
+    /// memory-mapped I/O, accesses are forwarded to the handler with this index
+    Mmio(usize),
+
+    /// no memory, reads as a open bus and writes are ignored
+    Unmapped,
+}
+
+/// a data breakpoint from the debug registers, watching `len` bytes at linear address `address`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DataBreakpoint {
+    pub address: u32,
+    pub len: u32,
+
+    /// if set, reads does not trigger the breakpoint
+    pub write_only: bool,
+}
+
 #[derive(Clone)]
 pub struct MMU {
     pub memory: FlatMemory,
 
     /// the FLAGS register offset on stack while in interrupt
     pub flags_address: MemoryAddress,
+
+    /// pages holding instructions in the instruction cache
+    code_pages: Vec<bool>,
+
+    /// writes to code pages not yet seen by the instruction cache, as (physical address, length)
+    code_writes: Vec<(u32, u32)>,
+
+    /// set when all cached instructions must be dropped, see take_code_flush
+    code_flush: bool,
+
+    /// translates linear addresses when CR0.PG is set
+    pub paging: Paging,
+
+    /// data breakpoints of DR0-DR3, indexed by debug register
+    data_breakpoints: [Option<DataBreakpoint>; 4],
+
+    /// bit n is set when data breakpoint n was hit since the last take_breakpoint_hits
+    breakpoint_hits: Cell<u8>,
+
+    /// the region of each page of physical memory
+    regions: Vec<Region>,
+
+    /// handlers of the memory-mapped I/O regions, indexed by Region::Mmio
+    handlers: Vec<Rc<RefCell<dyn MemoryHandler>>>,
+
+    /// set if any page is not plain memory
+    has_regions: bool,
+
+    /// if clear, address line 20 is forced low, wrapping accesses above 1 MiB as on the 8086
+    a20_enabled: bool,
 }
 
 impl MMU {
     pub fn default() -> Self{
+        Self::with_memory(FlatMemory::new())
+    }
+
+    /// returns a MMU addressing `memory`
+    pub fn with_memory(memory: FlatMemory) -> Self {
+        let pages = memory.data.len() >> CODE_PAGE_SHIFT;
+        let region_pages = (memory.data.len() + REGION_PAGE_SIZE as usize - 1) >> REGION_PAGE_SHIFT;
         MMU {
-            memory: FlatMemory::new(),
+            memory,
             flags_address: MemoryAddress::Unset,
+            code_pages: vec![false; pages],
+            code_writes: Vec::new(),
+            code_flush: false,
+            paging: Paging::default(),
+            data_breakpoints: [None; 4],
+            breakpoint_hits: Cell::new(0),
+            regions: vec![Region::Ram; region_pages],
+            handlers: Vec::new(),
+            has_regions: false,
+            a20_enabled: false,
+        }
+    }
+
+    /// enables or disables the A20 gate. the cached instructions are dropped when the
+    /// gate changes, as the code above 1 MiB is then fetched from other memory
+    pub fn set_a20(&mut self, enabled: bool) {
+        if self.a20_enabled != enabled {
+            self.code_flush = true;
+        }
+        self.a20_enabled = enabled;
+    }
+
+    pub fn a20_enabled(&self) -> bool {
+        self.a20_enabled
+    }
+
+    /// returns true if a access of `len` bytes at physical address `addr` crosses the
+    /// 1 MiB boundary while the A20 gate is disabled, so it must be wrapped byte by byte
+    fn crosses_a20(&self, addr: u32, len: u32) -> bool {
+        !self.a20_enabled && (addr ^ addr.wrapping_add(len - 1)) & A20_BIT != 0
+    }
+
+    /// applies the A20 gate to physical address `addr`
+    fn a20_address(&self, addr: u32) -> u32 {
+        if self.a20_enabled {
+            addr
+        } else {
+            addr & !A20_BIT
+        }
+    }
+
+    /// maps `len` bytes at physical address `start` as plain memory
+    pub fn map_ram(&mut self, start: u32, len: u32) {
+        self.map_region(start, len, Region::Ram);
+    }
+
+    /// maps `len` bytes at physical address `start` as read-only memory. the current
+    /// contents are kept, and can still be initialized with the _inc writers and `write`
+    pub fn map_rom(&mut self, start: u32, len: u32) {
+        self.map_region(start, len, Region::Rom);
+    }
+
+    /// forwards the accesses to `len` bytes at physical address `start` to `handler`
+    pub fn map_mmio(&mut self, start: u32, len: u32, handler: Rc<RefCell<dyn MemoryHandler>>) {
+        self.handlers.push(handler);
+        self.map_region(start, len, Region::Mmio(self.handlers.len() - 1));
+    }
+
+    fn map_region(&mut self, start: u32, len: u32, region: Region) {
+        if (start | len) & (REGION_PAGE_SIZE - 1) != 0 {
+            panic!("memory region {:06X}+{:X} is not page aligned", start, len);
+        }
+        let first = (start >> REGION_PAGE_SHIFT) as usize;
+        let last = ((start + len) >> REGION_PAGE_SHIFT) as usize;
+        for page in &mut self.regions[first..last] {
+            *page = region;
+        }
+        self.has_regions = self.regions.iter().any(|r| *r != Region::Ram);
+    }
+
+    /// returns the region holding physical address `addr`
+    fn region(&self, addr: u32) -> Region {
+        if addr as usize >= self.memory.data.len() {
+            return Region::Unmapped;
+        }
+        self.regions[(addr >> REGION_PAGE_SHIFT) as usize]
+    }
+
+    /// returns true if a access to the `len` bytes at physical address `addr` can use the
+    /// backing memory directly. reads from ROM are direct, writes to it are not
+    fn is_direct(&self, addr: u32, len: u32, write: bool) -> bool {
+        if !self.has_regions {
+            return addr as usize + len as usize <= self.memory.data.len();
+        }
+        let direct = |region| match region {
+            Region::Ram => true,
+            Region::Rom => !write,
+            Region::Mmio(_) | Region::Unmapped => false,
+        };
+        direct(self.region(addr)) && direct(self.region(addr.wrapping_add(len - 1)))
+    }
+
+    /// sets the data breakpoints watched by memory accesses
+    pub fn set_data_breakpoints(&mut self, breakpoints: [Option<DataBreakpoint>; 4]) {
+        self.data_breakpoints = breakpoints;
+    }
+
+    /// returns a mask of the data breakpoints hit since the last call
+    pub fn take_breakpoint_hits(&self) -> u8 {
+        self.breakpoint_hits.replace(0)
+    }
+
+    /// records the data breakpoints covering an access of `len` bytes at linear address `addr`
+    fn check_breakpoints(&self, addr: u32, len: u32, write: bool) {
+        for (n, bp) in self.data_breakpoints.iter().enumerate() {
+            if let Some(bp) = bp {
+                if (write || !bp.write_only) && addr <= bp.address.wrapping_add(bp.len - 1) && bp.address <= addr.wrapping_add(len - 1) {
+                    self.breakpoint_hits.set(self.breakpoint_hits.get() | 1 << n);
+                }
+            }
+        }
+    }
+
+    /// returns true if `len` bytes at linear address `addr` can be accessed directly as a
+    /// block of physical memory: paging is disabled, the range is plain memory and no data
+    /// breakpoint watches it
+    pub fn is_block_accessible(&self, addr: u32, len: u32) -> bool {
+        if self.paging.enabled || addr as usize + len as usize > self.memory.data.len() {
+            return false;
+        }
+        if !self.a20_enabled && addr + len > A20_BIT {
+            return false;
+        }
+        if self.has_regions && len > 0 {
+            let first = (addr >> REGION_PAGE_SHIFT) as usize;
+            let last = (addr.wrapping_add(len - 1) >> REGION_PAGE_SHIFT) as usize;
+            if self.regions[first..=last].iter().any(|r| *r != Region::Ram) {
+                return false;
+            }
+        }
+        !self.data_breakpoints.iter().flatten().any(|bp| addr <= bp.address.wrapping_add(bp.len - 1) && bp.address < addr + len)
+    }
+
+    /// copies `len` bytes from linear address `src` to `dst`, see is_block_accessible
+    pub fn copy_block(&mut self, src: u32, dst: u32, len: u32) {
+        let src = src as usize;
+        self.memory.data.copy_within(src..src + len as usize, dst as usize);
+        self.track_write(dst, len);
+    }
+
+    /// fills `count` copies of `pattern` at linear address `dst`, see is_block_accessible
+    pub fn fill_block(&mut self, dst: u32, pattern: &[u8], count: u32) {
+        let start = dst as usize;
+        let len = pattern.len() * count as usize;
+        for chunk in self.memory.data[start..start + len].chunks_exact_mut(pattern.len()) {
+            chunk.copy_from_slice(pattern);
+        }
+        self.track_write(dst, len as u32);
+    }
+
+    /// marks `len` bytes at physical address `addr` as holding a cached instruction,
+    /// so that later writes to them are reported by take_code_writes
+    pub fn mark_code(&mut self, addr: u32, len: u32) {
+        let first = (addr >> CODE_PAGE_SHIFT) as usize;
+        let last = (addr.wrapping_add(len.max(1) - 1) >> CODE_PAGE_SHIFT) as usize;
+        for page in first..=last {
+            if let Some(p) = self.code_pages.get_mut(page) {
+                *p = true;
+            }
+        }
+    }
+
+    /// returns the writes to cached code since the last call, as (physical address, length)
+    pub fn take_code_writes(&mut self) -> Vec<(u32, u32)> {
+        std::mem::take(&mut self.code_writes)
+    }
+
+    /// returns true if all cached instructions were invalidated since the last call
+    pub fn take_code_flush(&mut self) -> bool {
+        std::mem::replace(&mut self.code_flush, false)
+    }
+
+    /// records a change of the `len` bytes at physical address `addr` made without writing
+    /// through the MMU, such as a bank switch, so that cached instructions are dropped
+    pub fn invalidate_code(&mut self, addr: u32, len: u32) {
+        self.track_write(addr, len);
+    }
+
+    /// records a write of `len` bytes at physical address `addr` if it touches cached code
+    fn track_write(&mut self, addr: u32, len: u32) {
+        if len == 0 {
+            return;
+        }
+        let first = (addr >> CODE_PAGE_SHIFT) as usize;
+        let last = (addr.wrapping_add(len - 1) >> CODE_PAGE_SHIFT) as usize;
+        if (first..=last).any(|page| self.code_pages.get(page) == Some(&true)) {
+            self.code_writes.push((addr, len));
+        }
+    }
+
+    /// translates a real mode segment:offset pair to a linear address. protected mode
+    /// segments are translated by the cpu, through the descriptor cache of the segment register
+    pub fn linear_address(&self, seg: u16, offset: u16) -> u32 {
+        MemoryAddress::RealSegmentOffset(seg, offset).value()
+    }
+
+    /// translates linear address `addr` of a instruction fetch to the physical address the
+    /// byte is read from. returns None and raises a page fault if the page is not present
+    pub fn code_address(&self, addr: u32) -> Option<u32> {
+        let phys = if self.paging.enabled {
+            self.paging.translate_read(&self.memory, addr, self.paging.user_mode)?
+        } else {
+            addr
+        };
+        Some(self.a20_address(phys))
+    }
+
+    /// enables or disables paging (CR0.PG), flushing the TLB
+    pub fn set_paging(&mut self, enabled: bool, write_protect: bool) {
+        self.paging.enabled = enabled;
+        self.paging.write_protect = write_protect;
+        self.paging.flush_tlb();
+    }
+
+    /// returns the page fault raised by the accesses since the last call, if any.
+    /// the accessed bits of the page table entries walked since the last call are updated
+    pub fn take_page_fault(&mut self) -> Option<PageFault> {
+        if !self.paging.enabled {
+            return None;
+        }
+        self.paging.update_accessed_bits(&mut self.memory);
+        self.paging.take_fault()
+    }
+
+    /// reads `len` (1, 2, 4 or 8) bytes at linear address `addr`. reads from unmapped
+    /// pages returns 0 and raises a page fault, returned by take_page_fault
+    fn read_linear(&self, addr: u32, len: u32, user: bool) -> u64 {
+        self.check_breakpoints(addr, len, false);
+        if !self.paging.enabled {
+            return self.read_physical(addr, len);
+        }
+        if (addr & 0xFFF) + len > 0x1000 {
+            // the access crosses a page boundary
+            return (0..len).fold(0, |val, i| val | self.read_linear(addr.wrapping_add(i), 1, user) << (8 * i));
+        }
+        match self.paging.translate_read(&self.memory, addr, user) {
+            Some(phys) => self.read_physical(phys, len),
+            None => 0,
+        }
+    }
+
+    /// reads `len` (1, 2, 4 or 8) bytes at physical address `addr`, through the A20 gate
+    /// and the memory regions
+    pub fn read_physical(&self, addr: u32, len: u32) -> u64 {
+        if self.crosses_a20(addr, len) {
+            return (0..len).fold(0, |val, i| val | self.read_physical(addr.wrapping_add(i), 1) << (8 * i));
+        }
+        let addr = self.a20_address(addr);
+        if !self.is_direct(addr, len, false) {
+            return (0..len).fold(0, |val, i| val | u64::from(self.read_region_u8(addr.wrapping_add(i))) << (8 * i));
+        }
+        match len {
+            1 => u64::from(self.memory.read_u8(addr)),
+            2 => u64::from(self.memory.read_u16(addr)),
+            4 => u64::from(self.memory.read_u32(addr)),
+            _ => self.memory.read_u64(addr),
+        }
+    }
+
+    /// writes the `len` (1, 2, 4 or 8) low bytes of `data` at linear address `addr`.
+    /// writes to unmapped or read-only pages are dropped and raises a page fault
+    fn write_linear(&mut self, addr: u32, len: u32, data: u64, user: bool) {
+        self.check_breakpoints(addr, len, true);
+        if !self.paging.enabled {
+            self.write_physical(addr, len, data);
+        } else if (addr & 0xFFF) + len > 0x1000 {
+            // the access crosses a page boundary, so check that both pages are writable first
+            let last = addr.wrapping_add(len - 1);
+            if self.paging.translate_write(&mut self.memory, addr, user).is_none()
+                || self.paging.translate_write(&mut self.memory, last, user).is_none() {
+                return;
+            }
+            for i in 0..len {
+                if let Some(phys) = self.paging.translate_write(&mut self.memory, addr.wrapping_add(i), user) {
+                    self.write_physical(phys, 1, data >> (8 * i));
+                }
+            }
+        } else {
+            if let Some(phys) = self.paging.translate_write(&mut self.memory, addr, user) {
+                self.write_physical(phys, len, data);
+            }
+        }
+    }
+
+    /// writes the `len` (1, 2, 4 or 8) low bytes of `data` at physical address `addr`,
+    /// through the A20 gate and the memory regions
+    pub fn write_physical(&mut self, addr: u32, len: u32, data: u64) {
+        if self.crosses_a20(addr, len) {
+            for i in 0..len {
+                self.write_physical(addr.wrapping_add(i), 1, data >> (8 * i));
+            }
+            return;
+        }
+        let addr = self.a20_address(addr);
+        self.track_write(addr, len);
+        if !self.is_direct(addr, len, true) {
+            for i in 0..len {
+                self.write_region_u8(addr.wrapping_add(i), (data >> (8 * i)) as u8);
+            }
+            return;
+        }
+        match len {
+            1 => self.memory.write_u8(addr, data as u8),
+            2 => self.memory.write_u16(addr, data as u16),
+            4 => self.memory.write_u32(addr, data as u32),
+            _ => self.memory.write_u64(addr, data),
+        }
+    }
+
+    fn read_region_u8(&self, addr: u32) -> u8 {
+        match self.region(addr) {
+            Region::Ram | Region::Rom => self.memory.read_u8(addr),
+            Region::Mmio(n) => self.handlers[n].borrow_mut().read_u8(addr),
+            Region::Unmapped => 0xFF,
+        }
+    }
+
+    fn write_region_u8(&mut self, addr: u32, data: u8) {
+        match self.region(addr) {
+            Region::Ram => self.memory.write_u8(addr, data),
+            Region::Rom | Region::Unmapped => {}
+            Region::Mmio(n) => self.handlers[n].borrow_mut().write_u8(addr, data),
         }
     }
 
@@ -36,11 +443,19 @@ impl MMU {
             flags &= !flag_mask;
         }
         self.memory.write_u16(self.flags_address.value(), flags);
+        self.track_write(self.flags_address.value(), 2);
     }
 
     /// reads a sequence of data from memory
     pub fn read(&self, seg: u16, offset: u16, length: usize) -> Vec<u8> {
-        let addr = MemoryAddress::RealSegmentOffset(seg, offset).value();
+        self.read_bytes(self.linear_address(seg, offset), length)
+    }
+
+    /// reads `length` bytes at linear address `addr` with the privilege of the running code
+    pub fn read_bytes(&self, addr: u32, length: usize) -> Vec<u8> {
+        if !self.is_block_accessible(addr, length as u32) {
+            return (0..length as u32).map(|i| self.read_linear(addr.wrapping_add(i), 1, self.paging.user_mode) as u8).collect();
+        }
         Vec::from(self.memory.read(addr, length))
     }
 
@@ -98,8 +513,8 @@ impl MMU {
     }
 
     pub fn read_u8(&self, seg: u16, offset: u16) -> u8 {
-        let addr = MemoryAddress::RealSegmentOffset(seg, offset).value();
-        let v = self.memory.read_u8(addr);
+        let addr = self.linear_address(seg, offset);
+        let v = self.read_linear(addr, 1, self.paging.user_mode) as u8;
         if DEBUG_MMU {
             println!("mmu.read_u8 from ({:04X}:{:04X} == {:06X}) = {:02X}", seg, offset, addr, v);
         }
@@ -107,8 +522,8 @@ impl MMU {
     }
 
     pub fn read_u16(&self, seg: u16, offset: u16) -> u16 {
-        let addr = MemoryAddress::RealSegmentOffset(seg, offset).value();
-        let v = self.memory.read_u16(addr);
+        let addr = self.linear_address(seg, offset);
+        let v = self.read_linear(addr, 2, self.paging.user_mode) as u16;
         if DEBUG_MMU {
             println!("mmu.read_u16 from ({:04X}:{:04X} == {:06X}) = {:04X}", seg, offset, addr, v);
         }
@@ -116,16 +531,17 @@ impl MMU {
     }
 
     pub fn write_u8(&mut self, seg: u16, offset: u16, data: u8) {
-        let addr = MemoryAddress::RealSegmentOffset(seg, offset).value();
+        let addr = self.linear_address(seg, offset);
         if DEBUG_MMU {
             println!("mmu.write_u8 to ({:04X}:{:04X} == {:06X}) = {:02X}", seg, offset, addr, data);
         }
-        self.memory.write_u8(addr, data);
+        self.write_linear(addr, 1, u64::from(data), self.paging.user_mode);
     }
 
     /// write data and increase addr
     pub fn write_u8_inc(&mut self, addr: &mut MemoryAddress, data: u8) {
         self.memory.write_u8(addr.value(), data);
+        self.track_write(addr.value(), 1);
         if DEBUG_MMU {
             println!("mmu.write_u8_inc to {:06X} = {:02X}", addr.value(), data);
         }
@@ -134,21 +550,33 @@ impl MMU {
 
     /// writes a sequence of data to memory
     pub fn write(&mut self, seg: u16, offset: u16, data: &[u8]) {
-        let addr = MemoryAddress::RealSegmentOffset(seg, offset).value();
+        self.write_bytes(self.linear_address(seg, offset), data);
+    }
+
+    /// writes `data` at linear address `addr` with the privilege of the running code
+    pub fn write_bytes(&mut self, addr: u32, data: &[u8]) {
+        if !self.is_block_accessible(addr, data.len() as u32) {
+            for (i, b) in data.iter().enumerate() {
+                self.write_linear(addr.wrapping_add(i as u32), 1, u64::from(*b), self.paging.user_mode);
+            }
+            return;
+        }
         self.memory.write(addr, data);
+        self.track_write(addr, data.len() as u32);
     }
 
     pub fn write_u16(&mut self, seg: u16, offset: u16, data: u16) {
-        let addr = MemoryAddress::RealSegmentOffset(seg, offset).value();
+        let addr = self.linear_address(seg, offset);
         if DEBUG_MMU {
             println!("mmu.write_u16 to ({:04X}:{:04X} == {:06X}) = {:02X}", seg, offset, addr, data);
         }
-        self.memory.write_u16(addr, data);
+        self.write_linear(addr, 2, u64::from(data), self.paging.user_mode);
     }
 
     /// write data and increase addr
     pub fn write_u16_inc(&mut self, addr: &mut MemoryAddress, data: u16) {
         self.memory.write_u16(addr.value(), data);
+        self.track_write(addr.value(), 2);
         if DEBUG_MMU {
             println!("mmu.write_u16_inc to {:06X} = {:08X}", addr.value(), data);
         }
@@ -156,8 +584,8 @@ impl MMU {
     }
 
     pub fn read_u32(&self, seg: u16, offset: u16) -> u32 {
-        let addr = MemoryAddress::RealSegmentOffset(seg, offset).value();
-        let v = self.memory.read_u32(addr);
+        let addr = self.linear_address(seg, offset);
+        let v = self.read_linear(addr, 4, self.paging.user_mode) as u32;
         if DEBUG_MMU {
             println!("mmu.read_u32 from {:06X} = {:04X}", addr, v);
         }
@@ -166,22 +594,95 @@ impl MMU {
 
     pub fn write_u32(&mut self, seg: u16, offset: u16, data: u32) {
         // TODO take MemoryAddress parameter directly
-        let addr = MemoryAddress::RealSegmentOffset(seg, offset).value();
+        let addr = self.linear_address(seg, offset);
         if DEBUG_MMU {
             println!("mmu.write_u32 to {:06X} = {:08X}", addr, data);
         }
-        self.memory.write_u32(addr, data);
+        self.write_linear(addr, 4, u64::from(data), self.paging.user_mode);
     }
 
     /// write data and increase addr
     pub fn write_u32_inc(&mut self, addr: &mut MemoryAddress, data: u32) {
         self.memory.write_u32(addr.value(), data);
+        self.track_write(addr.value(), 4);
         if DEBUG_MMU {
             println!("mmu.write_u32_inc to {:06X} = {:08X}", addr.value(), data);
         }
         addr.inc_u32();
     }
 
+    pub fn read_u64(&self, seg: u16, offset: u16) -> u64 {
+        let addr = self.linear_address(seg, offset);
+        let v = self.read_linear(addr, 8, self.paging.user_mode);
+        if DEBUG_MMU {
+            println!("mmu.read_u64 from {:06X} = {:016X}", addr, v);
+        }
+        v
+    }
+
+    pub fn write_u64(&mut self, seg: u16, offset: u16, data: u64) {
+        let addr = self.linear_address(seg, offset);
+        if DEBUG_MMU {
+            println!("mmu.write_u64 to {:06X} = {:016X}", addr, data);
+        }
+        self.write_linear(addr, 8, data, self.paging.user_mode);
+    }
+
+    /// reads `len` (1, 2, 4 or 8) bytes at linear address `addr` with the privilege of the running code
+    pub fn read_at(&self, addr: u32, len: u32) -> u64 {
+        let v = self.read_linear(addr, len, self.paging.user_mode);
+        if DEBUG_MMU {
+            println!("mmu.read_at {:08X} ({} bytes) = {:X}", addr, len, v);
+        }
+        v
+    }
+
+    /// writes the `len` (1, 2, 4 or 8) low bytes of `data` at linear address `addr` with
+    /// the privilege of the running code
+    pub fn write_at(&mut self, addr: u32, len: u32, data: u64) {
+        if DEBUG_MMU {
+            println!("mmu.write_at {:08X} ({} bytes) = {:X}", addr, len, data);
+        }
+        self.write_linear(addr, len, data, self.paging.user_mode);
+    }
+
+    pub fn read_u8_linear(&self, addr: u32) -> u8 {
+        self.read_linear(addr, 1, false) as u8
+    }
+
+    pub fn read_u16_linear(&self, addr: u32) -> u16 {
+        self.read_linear(addr, 2, false) as u16
+    }
+
+    pub fn read_u32_linear(&self, addr: u32) -> u32 {
+        self.read_linear(addr, 4, false) as u32
+    }
+
+    pub fn read_u64_linear(&self, addr: u32) -> u64 {
+        self.read_linear(addr, 8, false)
+    }
+
+    pub fn write_u8_linear(&mut self, addr: u32, data: u8) {
+        if DEBUG_MMU {
+            println!("mmu.write_u8_linear to {:08X} = {:02X}", addr, data);
+        }
+        self.write_linear(addr, 1, u64::from(data), false);
+    }
+
+    pub fn write_u16_linear(&mut self, addr: u32, data: u16) {
+        if DEBUG_MMU {
+            println!("mmu.write_u16_linear to {:08X} = {:04X}", addr, data);
+        }
+        self.write_linear(addr, 2, u64::from(data), false);
+    }
+
+    pub fn write_u32_linear(&mut self, addr: u32, data: u32) {
+        if DEBUG_MMU {
+            println!("mmu.write_u32_linear to {:08X} = {:08X}", addr, data);
+        }
+        self.write_linear(addr, 4, u64::from(data), false);
+    }
+
     /// read interrupt vector, returns segment, offset
     pub fn read_vec(&self, v: u16) -> (u16, u16) {
         // XXX better naming
@@ -199,6 +700,7 @@ impl MMU {
         let v_abs = u32::from(v) << 2;
         self.memory.write_u16(v_abs, data.segment());
         self.memory.write_u16(v_abs + 2, data.offset());
+        self.track_write(v_abs, 4);
         if DEBUG_VEC {
             println!("mmu.write_vec: {:04X} = {:04X}:{:04X}", v, data.segment(), data.offset());
         }
@@ -1,4 +1,24 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use crate::memory::mmu::MemoryAddress;
+use crate::memory::{FlatMemory, MMU, MemoryHandler};
+
+/// records the writes to a memory-mapped I/O region, and reads back the low byte of the address
+#[derive(Default)]
+struct RecordingHandler {
+    writes: Vec<(u32, u8)>,
+}
+
+impl MemoryHandler for RecordingHandler {
+    fn read_u8(&mut self, addr: u32) -> u8 {
+        addr as u8
+    }
+
+    fn write_u8(&mut self, addr: u32, data: u8) {
+        self.writes.push((addr, data));
+    }
+}
 
 #[test]
 fn can_handle_real_mode_addressing() {
@@ -25,3 +45,95 @@ fn resolve_real_addressing() {
     let ma2 = MemoryAddress::RealSegmentOffset(0x0040, 0x006C);
     assert_eq!(ma1.value(), ma2.value());
 }
+
+#[test]
+fn can_forward_accesses_to_memory_regions() {
+    let mut mmu = MMU::default();
+    let handler = Rc::new(RefCell::new(RecordingHandler::default()));
+    mmu.map_mmio(0xA_0000, 0x1_0000, handler.clone());
+
+    mmu.write_u16(0xA000, 0x0010, 0x1234);
+    assert_eq!(vec![(0xA_0010, 0x34), (0xA_0011, 0x12)], handler.borrow().writes);
+    assert_eq!(0x1110, mmu.read_u16(0xA000, 0x0010));
+    assert_eq!(0, mmu.memory.read_u16(0xA_0010));
+    assert_eq!(false, mmu.is_block_accessible(0x9_FFF0, 0x20));
+
+    // writes to rom are ignored
+    mmu.write_u8_inc(&mut MemoryAddress::RealSegmentOffset(0xF000, 0x0000), 0x11);
+    mmu.map_rom(0xF_0000, 0x1_0000);
+    mmu.write_u8(0xF000, 0x0000, 0x22);
+    assert_eq!(0x11, mmu.read_u8(0xF000, 0x0000));
+
+    // plain memory is restored
+    mmu.map_ram(0xA_0000, 0x1_0000);
+    mmu.write_u16(0xA000, 0x0010, 0x5678);
+    assert_eq!(0x5678, mmu.read_u16(0xA000, 0x0010));
+    assert_eq!(2, handler.borrow().writes.len());
+    assert_eq!(true, mmu.is_block_accessible(0x9_FFF0, 0x20));
+}
+
+#[test]
+fn can_access_memory_above_installed_memory() {
+    let mut mmu = MMU::with_memory(FlatMemory::with_size(0x10_0000));
+    mmu.set_a20(true);
+
+    // a flat segment probing memory at 16 MiB
+    mmu.write_u32_linear(0x0100_0000, 0x1234_5678);
+    assert_eq!(0xFFFF_FFFF, mmu.read_u32_linear(0x0100_0000));
+
+    // a access straddling the end of memory
+    mmu.write_u16_linear(0x0F_FFFF, 0x1234);
+    assert_eq!(0xFF34, mmu.read_u16_linear(0x0F_FFFF));
+}
+
+#[test]
+fn can_read_unmapped_region_as_open_bus() {
+    let mut mmu = MMU::with_memory(FlatMemory::with_size(0x10_0000));
+    mmu.set_a20(true);
+    mmu.map_rom(0xF_0000, 0x1_0000);
+
+    assert_eq!(0xFFFF_FFFF, mmu.read_u32_linear(0x0010_0000));
+    mmu.write_u32_linear(0x0010_0000, 0x1234_5678);
+    assert_eq!(0xFFFF_FFFF, mmu.read_u32_linear(0x0010_0000));
+    assert_eq!(false, mmu.is_block_accessible(0x0010_0000, 4));
+
+    // the rom part of the access is still read
+    mmu.memory.write_u8(0x0F_FFFF, 0x12);
+    assert_eq!(0xFF12, mmu.read_u16_linear(0x0F_FFFF));
+
+    // a access at the top of the address space wraps around to address 0
+    mmu.write_u32_linear(0xFFFF_FFFE, 0x1234_5678);
+    assert_eq!(0x1234, mmu.memory.read_u16(0x0000));
+    assert_eq!(0x1234_FFFF, mmu.read_u32_linear(0xFFFF_FFFE));
+}
+
+#[test]
+fn can_track_code_writes_by_physical_address() {
+    let mut mmu = MMU::default();
+    mmu.set_a20(true);
+    mmu.mark_code(0x0003_0100, 3);
+
+    // linear page 0x0000_5000 and 0x0003_0000 both map to physical page 0x0003_0000
+    mmu.write_u32_linear(0x1000, 0x2000 | 0x3);
+    mmu.write_u32_linear(0x2000 + 5 * 4, 0x0003_0000 | 0x3);
+    mmu.write_u32_linear(0x2000 + 0x30 * 4, 0x0003_0000 | 0x3);
+    mmu.paging.set_page_directory(0x1000);
+    mmu.set_paging(true, false);
+    assert_eq!(Some(0x0003_0100), mmu.code_address(0x0000_5100));
+
+    mmu.write_u8_linear(0x0000_5101, 0x90);
+    assert_eq!(vec![(0x0003_0101, 1)], mmu.take_code_writes());
+}
+
+#[test]
+fn can_flush_code_on_a20_change() {
+    let mut mmu = MMU::default();
+    assert_eq!(Some(0x0000_0010), mmu.code_address(0x0010_0010));
+    mmu.set_a20(false);
+    assert_eq!(false, mmu.take_code_flush());
+
+    mmu.set_a20(true);
+    assert_eq!(Some(0x0010_0010), mmu.code_address(0x0010_0010));
+    assert_eq!(true, mmu.take_code_flush());
+    assert_eq!(false, mmu.take_code_flush());
+}
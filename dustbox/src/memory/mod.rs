@@ -8,3 +8,6 @@ mod memory_address;
 
 pub use self::mmu::*;
 mod mmu;
+
+pub use self::paging::*;
+mod paging;
@@ -0,0 +1,214 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use crate::memory::FlatMemory;
+
+#[cfg(test)]
+#[path = "./paging_test.rs"]
+mod paging_test;
+
+/// page directory and page table entry bits
+pub const PTE_PRESENT: u32 = 0x01;
+pub const PTE_WRITABLE: u32 = 0x02;
+pub const PTE_USER: u32 = 0x04;
+pub const PTE_ACCESSED: u32 = 0x20;
+pub const PTE_DIRTY: u32 = 0x40;
+
+/// page fault error code bits
+/// set for protection violations, clear for not present pages
+pub const PF_PROTECTION: u16 = 0x1;
+/// set if the faulting access was a write
+pub const PF_WRITE: u16 = 0x2;
+/// set if the faulting access was made at CPL 3
+pub const PF_USER: u16 = 0x4;
+
+const PAGE_SHIFT: u32 = 12;
+const FRAME_MASK: u32 = 0xFFFF_F000;
+
+/// a page fault raised by a memory access, delivered by the CPU as #PF
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PageFault {
+    /// the faulting linear address, reported in CR2
+    pub address: u32,
+
+    /// the error code pushed by the #PF handler
+    pub error: u16,
+}
+
+/// a cached translation of a linear page
+#[derive(Clone, Copy, Debug)]
+struct TlbEntry {
+    /// physical address of the page
+    frame: u32,
+
+    /// physical address of the page table entry, for dirty bit updates
+    pte_address: u32,
+
+    /// combined R/W and U/S bits of the page directory and page table entries
+    writable: bool,
+    user: bool,
+
+    dirty: bool,
+}
+
+/// the 386 paging unit, translating linear addresses to physical addresses through the
+/// two-level page tables at CR3
+#[derive(Clone, Default)]
+pub struct Paging {
+    /// CR0.PG
+    pub enabled: bool,
+
+    /// CR0.WP (486+), if set supervisor writes to read-only pages faults
+    pub write_protect: bool,
+
+    /// set while executing at CPL 3, making accesses to supervisor pages fault
+    pub user_mode: bool,
+
+    /// physical address of the page directory (CR3)
+    page_directory: u32,
+
+    /// translation lookaside buffer, keyed by linear page number
+    tlb: RefCell<HashMap<u32, TlbEntry>>,
+
+    /// the first page fault raised since the last take_fault
+    fault: Cell<Option<PageFault>>,
+
+    /// page directory and table entries walked by reads, whose accessed bit is
+    /// set by update_accessed_bits
+    accessed: RefCell<Vec<u32>>,
+}
+
+impl Paging {
+    /// loads CR3, flushing the TLB
+    pub fn set_page_directory(&mut self, cr3: u32) {
+        self.page_directory = cr3 & FRAME_MASK;
+        self.flush_tlb();
+    }
+
+    pub fn flush_tlb(&mut self) {
+        self.tlb.get_mut().clear();
+    }
+
+    /// returns the first page fault raised since the last call
+    pub fn take_fault(&self) -> Option<PageFault> {
+        self.fault.take()
+    }
+
+    pub fn fault_pending(&self) -> bool {
+        self.fault.get().is_some()
+    }
+
+    /// records a page fault, unless one is already pending
+    fn raise(&self, fault: PageFault) {
+        if self.fault.get().is_none() {
+            self.fault.set(Some(fault));
+        }
+    }
+
+    /// translates linear address `addr` for a read. on failure the page fault is recorded
+    pub fn translate_read(&self, memory: &FlatMemory, addr: u32, user: bool) -> Option<u32> {
+        match self.lookup(memory, addr, false, user) {
+            Ok(entry) => Some(entry.frame | (addr & !FRAME_MASK)),
+            Err(fault) => {
+                self.raise(fault);
+                None
+            }
+        }
+    }
+
+    /// translates linear address `addr` for a write, setting the dirty bit of the page.
+    /// on failure the page fault is recorded
+    pub fn translate_write(&self, memory: &mut FlatMemory, addr: u32, user: bool) -> Option<u32> {
+        let entry = match self.lookup(memory, addr, true, user) {
+            Ok(entry) => entry,
+            Err(fault) => {
+                self.raise(fault);
+                return None;
+            }
+        };
+        self.update_accessed_bits(memory);
+        if !entry.dirty {
+            let pte = memory.read_u32(entry.pte_address);
+            memory.write_u32(entry.pte_address, pte | PTE_ACCESSED | PTE_DIRTY);
+            if let Some(e) = self.tlb.borrow_mut().get_mut(&(addr >> PAGE_SHIFT)) {
+                e.dirty = true;
+            }
+        }
+        Some(entry.frame | (addr & !FRAME_MASK))
+    }
+
+    /// sets the accessed bit of the entries walked by reads since the last call
+    pub fn update_accessed_bits(&self, memory: &mut FlatMemory) {
+        for entry_address in self.accessed.borrow_mut().drain(..) {
+            let entry = memory.read_u32(entry_address);
+            memory.write_u32(entry_address, entry | PTE_ACCESSED);
+        }
+    }
+
+    /// returns the translation of the page holding `addr` if the access is permitted
+    fn lookup(&self, memory: &FlatMemory, addr: u32, write: bool, user: bool) -> Result<TlbEntry, PageFault> {
+        let page = addr >> PAGE_SHIFT;
+        let cached = self.tlb.borrow().get(&page).copied();
+        let entry = match cached {
+            Some(entry) => entry,
+            None => {
+                let entry = self.walk(memory, addr, write, user)?;
+                self.tlb.borrow_mut().insert(page, entry);
+                entry
+            }
+        };
+
+        let denied = (user && !entry.user) || (write && !entry.writable && (user || self.write_protect));
+        if denied {
+            return Err(PageFault { address: addr, error: fault_error(true, write, user) });
+        }
+        Ok(entry)
+    }
+
+    /// walks the page directory and page table for `addr`
+    fn walk(&self, memory: &FlatMemory, addr: u32, write: bool, user: bool) -> Result<TlbEntry, PageFault> {
+        let not_present = PageFault { address: addr, error: fault_error(false, write, user) };
+
+        let pde_address = self.page_directory | ((addr >> 22) << 2);
+        let pde = memory.read_u32(pde_address);
+        if pde & PTE_PRESENT == 0 {
+            return Err(not_present);
+        }
+
+        let pte_address = (pde & FRAME_MASK) | (((addr >> PAGE_SHIFT) & 0x3FF) << 2);
+        let pte = memory.read_u32(pte_address);
+        if pte & PTE_PRESENT == 0 {
+            return Err(not_present);
+        }
+
+        let mut accessed = self.accessed.borrow_mut();
+        if pde & PTE_ACCESSED == 0 {
+            accessed.push(pde_address);
+        }
+        if pte & PTE_ACCESSED == 0 {
+            accessed.push(pte_address);
+        }
+
+        Ok(TlbEntry {
+            frame: pte & FRAME_MASK,
+            pte_address,
+            writable: pde & pte & PTE_WRITABLE != 0,
+            user: pde & pte & PTE_USER != 0,
+            dirty: pte & PTE_DIRTY != 0,
+        })
+    }
+}
+
+fn fault_error(protection: bool, write: bool, user: bool) -> u16 {
+    let mut error = 0;
+    if protection {
+        error |= PF_PROTECTION;
+    }
+    if write {
+        error |= PF_WRITE;
+    }
+    if user {
+        error |= PF_USER;
+    }
+    error
+}
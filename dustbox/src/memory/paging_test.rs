@@ -0,0 +1,85 @@
+use crate::memory::{FlatMemory, Paging, PageFault, PTE_PRESENT, PTE_WRITABLE, PTE_USER, PTE_ACCESSED, PTE_DIRTY, PF_PROTECTION, PF_WRITE, PF_USER};
+
+/// maps linear page 0x0040_1000 to physical page 0x0003_0000, with the page directory at 0x1000
+fn mapped_memory(pte_bits: u32) -> (FlatMemory, Paging) {
+    let mut memory = FlatMemory::new();
+    memory.write_u32(0x1000 + 4, 0x2000 | PTE_PRESENT | PTE_WRITABLE | PTE_USER);
+    memory.write_u32(0x2000 + 4, 0x0003_0000 | pte_bits);
+    let mut paging = Paging::default();
+    paging.enabled = true;
+    paging.set_page_directory(0x1000);
+    (memory, paging)
+}
+
+#[test]
+fn can_translate_through_page_tables() {
+    let (mut memory, paging) = mapped_memory(PTE_PRESENT | PTE_WRITABLE);
+    assert_eq!(Some(0x0003_0123), paging.translate_read(&memory, 0x0040_1123, false));
+    assert_eq!(false, paging.fault_pending());
+
+    // the accessed bits are set once the walked entries are flushed
+    assert_eq!(0, memory.read_u32(0x2004) & PTE_ACCESSED);
+    paging.update_accessed_bits(&mut memory);
+    assert_eq!(PTE_ACCESSED, memory.read_u32(0x1004) & PTE_ACCESSED);
+    assert_eq!(PTE_ACCESSED, memory.read_u32(0x2004) & PTE_ACCESSED);
+
+    // writes sets the dirty bit
+    assert_eq!(Some(0x0003_0FFF), paging.translate_write(&mut memory, 0x0040_1FFF, false));
+    assert_eq!(PTE_DIRTY, memory.read_u32(0x2004) & PTE_DIRTY);
+}
+
+#[test]
+fn can_fault_on_not_present_page() {
+    let (memory, paging) = mapped_memory(PTE_PRESENT);
+    assert_eq!(None, paging.translate_read(&memory, 0x0040_2000, true));
+    assert_eq!(Some(PageFault { address: 0x0040_2000, error: PF_USER }), paging.take_fault());
+    assert_eq!(None, paging.take_fault());
+}
+
+#[test]
+fn can_fault_on_protection_violation() {
+    let (mut memory, mut paging) = mapped_memory(PTE_PRESENT);
+
+    // supervisor pages are not accessible from user mode
+    assert_eq!(None, paging.translate_read(&memory, 0x0040_1000, true));
+    assert_eq!(Some(PageFault { address: 0x0040_1000, error: PF_PROTECTION | PF_USER }), paging.take_fault());
+
+    // supervisor writes to read-only pages only faults with CR0.WP set
+    assert_eq!(Some(0x0003_0010), paging.translate_write(&mut memory, 0x0040_1010, false));
+    paging.write_protect = true;
+    assert_eq!(None, paging.translate_write(&mut memory, 0x0040_1010, false));
+    assert_eq!(Some(PageFault { address: 0x0040_1010, error: PF_PROTECTION | PF_WRITE }), paging.take_fault());
+}
+
+#[test]
+fn can_flush_tlb_on_page_directory_load() {
+    let (mut memory, mut paging) = mapped_memory(PTE_PRESENT);
+    assert_eq!(Some(0x0003_0000), paging.translate_read(&memory, 0x0040_1000, false));
+
+    // stale translations are used until CR3 is reloaded
+    memory.write_u32(0x2004, 0x0005_0000 | PTE_PRESENT);
+    assert_eq!(Some(0x0003_0000), paging.translate_read(&memory, 0x0040_1000, false));
+    paging.set_page_directory(0x1000);
+    assert_eq!(Some(0x0005_0000), paging.translate_read(&memory, 0x0040_1000, false));
+}
+
+#[test]
+fn can_walk_page_tables_above_installed_memory() {
+    let mut memory = FlatMemory::with_size(0x10_0000);
+    let mut paging = Paging::default();
+    paging.enabled = true;
+
+    // a page directory past the end of memory reads as a open bus
+    paging.set_page_directory(0x0100_0000);
+    assert_eq!(Some(0xFFFF_F123), paging.translate_read(&memory, 0x0040_1123, false));
+    paging.update_accessed_bits(&mut memory);
+
+    // a page table entry pointing past the end of memory
+    memory.write_u32(0x1000 + 4, 0x2000 | PTE_PRESENT | PTE_WRITABLE);
+    memory.write_u32(0x2000 + 4, 0x0100_0000 | PTE_PRESENT | PTE_WRITABLE);
+    paging.set_page_directory(0x1000);
+    assert_eq!(Some(0x0100_0123), paging.translate_write(&mut memory, 0x0040_1123, false));
+    assert_eq!(0xFF, memory.read_u8(0x0100_0123));
+    memory.write_u8(0x0100_0123, 0x12);
+    assert_eq!(0xFF, memory.read_u8(0x0100_0123));
+}
@@ -4,7 +4,11 @@
 // The 8259 PIC controls the CPU's interrupt mechanism, by accepting several
 // interrupt requests and feeding them to the processor in order.
 
+use std::ops::RangeInclusive;
+
+use crate::cpu::CPU;
 use crate::machine::Component;
+use crate::memory::MMU;
 
 #[cfg(test)]
 #[path = "./pic_test.rs"]
@@ -24,6 +28,15 @@ enum OperationMode {
     RotateOnSpecificEOICommand,         // 7 (WORD_D) rotate on specific EOI command
 }
 
+/// the initialization command word expected next on the data port
+#[derive(Clone, Debug, PartialEq)]
+enum InitStep {
+    Done,
+    ICW2,
+    ICW3,
+    ICW4,
+}
+
 #[derive(Clone)]
 pub struct PIC {
     command: u8,
@@ -33,9 +46,37 @@ pub struct PIC {
     io_base: u16,
 
     operation: OperationMode,
+
+    /// interrupt request register, one bit per IRQ line
+    irr: u8,
+
+    /// in-service register, IRQs acknowledged by the CPU and waiting for EOI
+    isr: u8,
+
+    /// interrupt mask register (OCW1), masked lines are not delivered
+    imr: u8,
+
+    /// interrupt vector of IRQ 0 of this controller, set by ICW2
+    vector_base: u8,
+
+    /// progress of the ICW1-ICW4 initialization sequence
+    init: InitStep,
+
+    /// ICW3 is expected (cascade mode)
+    needs_icw3: bool,
+
+    /// ICW4 is expected
+    needs_icw4: bool,
+
+    /// OCW3 selected reading the in-service register instead of the request register
+    read_isr: bool,
 }
 
 impl Component for PIC {
+    fn io_ports(&self) -> Vec<RangeInclusive<u16>> {
+        vec![self.io_base..=self.io_base + 1]
+    }
+
     fn in_u8(&mut self, port: u16) -> Option<u8> {
         match port {
             _ if port < self.io_base => None,
@@ -54,6 +95,21 @@ impl Component for PIC {
         }
         true
     }
+
+    fn int(&mut self, int: u8, _cpu: &mut CPU, _mmu: &mut MMU) -> bool {
+        if self.io_base != 0x0020 || int != 0x08 {
+            return false;
+        }
+        // IRQ 0 - SYSTEM TIMER. the ticks since midnight are counted by PIT::update,
+        // the BIOS handler only signals the end of interrupt
+        // XXX INT 1C is not called
+        self.end_of_interrupt();
+        true
+    }
+
+    fn reset(&mut self) {
+        *self = PIC::new(self.io_base);
+    }
 }
 
 impl PIC {
@@ -63,16 +119,61 @@ impl PIC {
             data: 0,
             io_base,
             operation: OperationMode::NoOperation, // XXX default?
+            irr: 0,
+            isr: 0,
+            imr: 0,
+            // vectors as programmed by the BIOS
+            vector_base: if io_base == 0x0020 { 0x08 } else { 0x70 },
+            init: InitStep::Done,
+            needs_icw3: false,
+            needs_icw4: false,
+            read_isr: false,
         }
     }
 
+    pub fn io_base(&self) -> u16 {
+        self.io_base
+    }
+
+    /// raises interrupt request line `irq` (0-7)
+    pub fn request(&mut self, irq: u8) {
+        self.irr |= 1 << irq;
+    }
+
+    /// returns the highest priority unmasked request, unless an IRQ of equal or
+    /// higher priority is in service
+    pub fn pending_irq(&self) -> Option<u8> {
+        let requests = self.irr & !self.imr;
+        if requests == 0 {
+            return None;
+        }
+        let irq = requests.trailing_zeros() as u8;
+        if self.isr != 0 && self.isr.trailing_zeros() as u8 <= irq {
+            return None;
+        }
+        Some(irq)
+    }
+
+    /// acknowledges the pending IRQ, marking it in service. returns its interrupt vector
+    pub fn acknowledge(&mut self) -> Option<u8> {
+        let irq = self.pending_irq()?;
+        self.irr &= !(1 << irq);
+        self.isr |= 1 << irq;
+        Some(self.vector_base.wrapping_add(irq))
+    }
+
+    /// nonspecific EOI, clearing the highest priority IRQ in service
+    fn end_of_interrupt(&mut self) {
+        self.isr &= self.isr.wrapping_sub(1);
+    }
+
     /// io read of port 0021 (pic1) or 00A1 (pic2)
     fn get_ocw1(&self) -> u8 {
         // read: PIC master interrupt mask register OCW1
         if DEBUG_PIC {
             println!("PIC {:04x} get_ocw1", self.io_base);
         }
-        0 // XXX
+        self.imr
     }
 
     /// io read of port 0020 (pic1) or 00A0 (pic2)
@@ -89,7 +190,11 @@ impl PIC {
             bit 7-0 = 0  corresponding line not currently being serviced
                 = 1  corresponding int. line currently being serviced
         */
-        0 // XXX
+        if self.read_isr {
+            self.isr
+        } else {
+            self.irr
+        }
     }
 
     /// PIC - Command register, port 0x0020
@@ -117,6 +222,17 @@ impl PIC {
         0	ICW4 needed
         SeeAlso: #P0011,#P0012,#P0013
         */
+        if val & 0b1_0000 != 0 {
+            // ICW1 resets the controller and starts the initialization sequence
+            self.irr = 0;
+            self.isr = 0;
+            self.imr = 0;
+            self.read_isr = false;
+            self.needs_icw3 = val & 0b10 == 0;
+            self.needs_icw4 = val & 0b1 != 0;
+            self.init = InitStep::ICW2;
+            return;
+        }
         let kind = (val >> 3) & 0b11; // bits 4-3: reserved (00 - signals OCW2)
         match kind {
             0 => { // 0020  -W  PIC output control word OCW2
@@ -134,9 +250,14 @@ impl PIC {
                     _ => unreachable!(),
                 };
 
-                let data = val & 0b11; // bits 0-2: interrupt request to which the command applies
+                let data = val & 0b111; // bits 0-2: interrupt request to which the command applies
                 //     (only used by WORD_B, WORD_D, and WORD_E)
-                println!("XXX: pic ocw2 operation {:?}, data {}", self.operation, data);
+                match self.operation {
+                    OperationMode::NonspecificEOI => self.end_of_interrupt(),
+                    OperationMode::SpecificEOI => self.isr &= !(1 << data),
+                    OperationMode::NoOperation => {}
+                    _ => println!("XXX: pic ocw2 operation {:?}, data {}", self.operation, data),
+                }
             }
             1 => { // 0020  -W  PIC output control word OCW3 (see #P0016)
                 // Bit(s)	Description	(Table P0016)
@@ -154,8 +275,13 @@ impl PIC {
                 //     lower priority) to be processed while an interrupt is already in
                 //     service, but will not re-issue an interrupt for a particular IRQ
                 //     while it remains in service
+                match val & 0b11 {
+                    0b10 => self.read_isr = false,
+                    0b11 => self.read_isr = true,
+                    _ => {}
+                }
             }
-            _ => panic!("unhandled kind {}", kind),
+            _ => unreachable!(),
         }
     }
 
@@ -165,8 +291,26 @@ impl PIC {
             println!("PIC {:04x} set_data = {:02x}", self.io_base, val);
         }
 
-        // XXX: one value if written immediately after value to 0020, another otherwise....
         self.data = val;
+        match self.init {
+            InitStep::Done => {
+                // OCW1: interrupt mask
+                self.imr = val;
+            }
+            InitStep::ICW2 => {
+                // ICW2: bits 7-3 of the interrupt vectors
+                self.vector_base = val & 0b1111_1000;
+                self.init = self.next_init_step(InitStep::ICW3);
+            }
+            InitStep::ICW3 => {
+                // ICW3: cascade configuration, not emulated
+                self.init = self.next_init_step(InitStep::ICW4);
+            }
+            InitStep::ICW4 => {
+                // ICW4: XXX auto EOI and buffered modes are not supported
+                self.init = InitStep::Done;
+            }
+        }
 
         // XXX impl, from https://wiki.osdev.org/8259_PIC#Disabling
         //If you are going to use the processor local APIC and the IOAPIC, you must first disable the PIC. This is done via:
@@ -174,4 +318,13 @@ impl PIC {
         //out 0xa1, al
         //out 0x21, al
     }
+
+    /// returns the initialization step following ICW2 or ICW3, skipping unneeded words
+    fn next_init_step(&self, step: InitStep) -> InitStep {
+        match step {
+            InitStep::ICW3 if self.needs_icw3 => InitStep::ICW3,
+            InitStep::ICW3 | InitStep::ICW4 if self.needs_icw4 => InitStep::ICW4,
+            _ => InitStep::Done,
+        }
+    }
 }
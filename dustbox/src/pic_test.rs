@@ -0,0 +1,50 @@
+use crate::machine::Component;
+use crate::pic::PIC;
+
+#[test]
+fn can_prioritize_unmasked_requests() {
+    let mut pic = PIC::new(0x0020);
+    pic.request(3);
+    pic.request(1);
+
+    // mask IRQ 1
+    pic.out_u8(0x21, 0b0000_0010);
+    assert_eq!(Some(0b0000_0010), pic.in_u8(0x21));
+    assert_eq!(Some(3), pic.pending_irq());
+
+    // IRQ 0 preempts IRQ 3 in service, but IRQ 3 blocks itself until EOI
+    assert_eq!(Some(0x0B), pic.acknowledge());
+    assert_eq!(None, pic.acknowledge());
+    pic.request(0);
+    assert_eq!(Some(0x08), pic.acknowledge());
+
+    // read the in-service register with OCW3
+    pic.out_u8(0x20, 0b0000_1011);
+    assert_eq!(Some(0b0000_1001), pic.in_u8(0x20));
+
+    // nonspecific EOI ends IRQ 0, then IRQ 3
+    pic.out_u8(0x20, 0x20);
+    assert_eq!(Some(0b0000_1000), pic.in_u8(0x20));
+    pic.out_u8(0x20, 0x20);
+    assert_eq!(Some(0), pic.in_u8(0x20));
+
+    // unmasking releases IRQ 1
+    pic.out_u8(0x21, 0);
+    assert_eq!(Some(1), pic.pending_irq());
+}
+
+#[test]
+fn can_remap_vectors_with_initialization_words() {
+    let mut pic = PIC::new(0x0020);
+    pic.out_u8(0x20, 0x11); // ICW1: cascade mode, ICW4 needed
+    pic.out_u8(0x21, 0x20); // ICW2: vector base
+    pic.out_u8(0x21, 0x04); // ICW3: slave at IRQ 2
+    pic.out_u8(0x21, 0x01); // ICW4: 8086 mode
+    assert_eq!(Some(0), pic.in_u8(0x21));
+
+    pic.out_u8(0x21, 0xFE); // OCW1: only IRQ 0 unmasked
+    pic.request(4);
+    assert_eq!(None, pic.acknowledge());
+    pic.request(0);
+    assert_eq!(Some(0x20), pic.acknowledge());
+}
@@ -5,6 +5,8 @@
 // A 8253/8254 chip that runs at 18.2065 Hz (or an IRQ every 54.9254 ms)
 // with the default divisor of 0x1_0000
 
+use std::ops::RangeInclusive;
+
 use crate::cpu::{CPU, R};
 use crate::machine::Component;
 use crate::memory::MMU;
@@ -15,6 +17,9 @@ mod pit_test;
 
 const DEBUG_PIT: bool = false;
 
+/// input clock of the PIT, in Hz
+pub const PIT_CLOCK_HZ: usize = 1_193_182;
+
 #[derive(Clone)]
 pub struct PIT {
     pub timer0: Timer,
@@ -24,6 +29,10 @@ pub struct PIT {
 }
 
 impl Component for PIT {
+    fn io_ports(&self) -> Vec<RangeInclusive<u16>> {
+        vec![0x0040..=0x0043]
+    }
+
     fn in_u8(&mut self, port: u16) -> Option<u8> {
         // PORT 0040-005F - PIT - PROGRAMMABLE INTERVAL TIMER (8253, 8254)
         match port {
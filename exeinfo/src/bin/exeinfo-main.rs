@@ -24,6 +24,6 @@ fn main() {
 
     match ExeFile::from_data(&data) {
         Ok(exe) => exe.print_details(),
-        Err(e) => panic!(e),
+        Err(e) => println!("error: {}", e),
     }
 }
@@ -2,6 +2,7 @@ use std::time::{Duration, SystemTime};
 use std::thread::sleep;
 
 use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
 use sdl2::pixels;
 use sdl2::pixels::PixelFormatEnum;
 
@@ -9,7 +10,10 @@ use sdl2::pixels::PixelFormatEnum;
 extern crate clap;
 use clap::{Arg, App};
 
-use dustbox::machine::Machine;
+use dustbox::cpu::CpuModel;
+use dustbox::gpu::GraphicCard;
+use dustbox::keyboard::{Key, Modifier};
+use dustbox::machine::{Machine, MachineConfig};
 use dustbox::mouse::MouseButton;
 
 const DEBUG_PERFORMANCE: bool = true;
@@ -39,26 +43,68 @@ fn main() {
             .help("Limits the trace to a number of instructions (debugging)")
             .takes_value(true)
             .long("tracecount"))
+        .arg(Arg::with_name("CPU")
+            .help("Sets the emulated CPU: 8086, 186, 286, 386 (default) or 486")
+            .takes_value(true)
+            .long("cpu"))
+        .arg(Arg::with_name("VIDEO")
+            .help("Sets the emulated graphics card: cga, ega, vga (default), tandy or pcjr")
+            .takes_value(true)
+            .long("video"))
+        .arg(Arg::with_name("MEMORY")
+            .help("Sets the installed memory in KiB (default 4096)")
+            .takes_value(true)
+            .long("memory"))
+        .arg(Arg::with_name("EMS")
+            .help("Sets the expanded memory in KiB, 0 for none, at most 32768 (default 1024)")
+            .takes_value(true)
+            .long("ems"))
+        .arg(Arg::with_name("NOFPU")
+            .help("Don't attach a math coprocessor")
+            .long("no-fpu"))
+        .arg(Arg::with_name("NOMOUSE")
+            .help("Don't attach a mouse")
+            .long("no-mouse"))
         .get_matches();
 
     let filename = matches.value_of("INPUT").unwrap();
 
-    let mut machine = if matches.is_present("DETERMINISTIC") {
-        Machine::deterministic()
-    } else {
-        Machine::default()
-    };
+    let mut config = MachineConfig::default();
+    config.deterministic = matches.is_present("DETERMINISTIC");
+    config.fpu = !matches.is_present("NOFPU");
+    config.mouse = !matches.is_present("NOMOUSE");
+    if let Some(name) = matches.value_of("CPU") {
+        match CpuModel::from_name(name) {
+            Some(model) => config.cpu_model = model,
+            None => panic!("unknown cpu model {}", name),
+        }
+    }
+    if let Some(name) = matches.value_of("VIDEO") {
+        match GraphicCard::from_name(name) {
+            Some(card) => config.graphic_card = card,
+            None => panic!("unknown graphics card {}", name),
+        }
+    }
+    if matches.is_present("MEMORY") {
+        config.memory_kb = value_t!(matches, "MEMORY", u32).unwrap();
+    }
+    if matches.is_present("EMS") {
+        config.ems_kb = value_t!(matches, "EMS", u32).unwrap();
+    }
+
+    let mut machine = Machine::with_config(config);
 
     if matches.is_present("TRACEFILE") {
         let tracename = matches.value_of("TRACEFILE").unwrap();
         println!("Instruction trace will be written to {}", tracename);
-        machine.write_trace_to(tracename);
+        if let Err(e) = machine.write_trace_to(tracename) {
+            panic!("couldn't create {}: {}", tracename, e);
+        }
     }
     if matches.is_present("TRACECOUNT") {
         machine.set_trace_count(value_t!(matches, "TRACECOUNT", usize).unwrap());
     }
-
-    if let Some(e) = machine.load_executable_file(filename) {
+    if let Err(e) = machine.load_executable_file(filename) {
         panic!("error {}", e);
     };
 
@@ -107,23 +153,23 @@ fn main() {
                         // break 'main
                     }
 
-                    machine.keyboard_mut().add_keypress(keycode, modifier);
+                    if let Some(key) = map_key(keycode) {
+                        machine.keyboard_mut().add_keypress(key, map_modifier(modifier));
+                    }
+                }
+                Event::MouseMotion {x, y, ..} => {
+                    if let Some(mouse) = machine.mouse_mut() {
+                        mouse.set_position(x, y);
+                    }
                 }
-                Event::MouseMotion {x, y, ..} => machine.mouse_mut().set_position(x, y),
                 Event::MouseButtonDown {mouse_btn, ..} => {
-                    match mouse_btn {
-                        sdl2::mouse::MouseButton::Left => machine.mouse_mut().set_button(MouseButton::Left, true),
-                        sdl2::mouse::MouseButton::Right => machine.mouse_mut().set_button(MouseButton::Right, true),
-                        sdl2::mouse::MouseButton::Middle => machine.mouse_mut().set_button(MouseButton::Middle, true),
-                        _ => {},
+                    if let (Some(mouse), Some(button)) = (machine.mouse_mut(), mouse_button(mouse_btn)) {
+                        mouse.set_button(button, true);
                     }
                 }
                 Event::MouseButtonUp {mouse_btn, ..} => {
-                    match mouse_btn {
-                        sdl2::mouse::MouseButton::Left => machine.mouse_mut().set_button(MouseButton::Left, false),
-                        sdl2::mouse::MouseButton::Right => machine.mouse_mut().set_button(MouseButton::Right, false),
-                        sdl2::mouse::MouseButton::Middle => machine.mouse_mut().set_button(MouseButton::Middle, false),
-                        _ => {},
+                    if let (Some(mouse), Some(button)) = (machine.mouse_mut(), mouse_button(mouse_btn)) {
+                        mouse.set_button(button, false);
                     }
                 }
                 _ => {}
@@ -260,3 +306,110 @@ fn main() {
         canvas.present();
     }
 }
+
+/// maps a SDL mouse button to the emulated mouse
+fn mouse_button(button: sdl2::mouse::MouseButton) -> Option<MouseButton> {
+    match button {
+        sdl2::mouse::MouseButton::Left => Some(MouseButton::Left),
+        sdl2::mouse::MouseButton::Right => Some(MouseButton::Right),
+        sdl2::mouse::MouseButton::Middle => Some(MouseButton::Middle),
+        _ => None,
+    }
+}
+
+/// maps a SDL key to the emulated keyboard
+fn map_key(keycode: Keycode) -> Option<Key> {
+    let key = match keycode {
+        Keycode::Escape => Key::Escape,
+        Keycode::Num1 => Key::Num1,
+        Keycode::Num2 => Key::Num2,
+        Keycode::Num3 => Key::Num3,
+        Keycode::Num4 => Key::Num4,
+        Keycode::Num5 => Key::Num5,
+        Keycode::Num6 => Key::Num6,
+        Keycode::Num7 => Key::Num7,
+        Keycode::Num8 => Key::Num8,
+        Keycode::Num9 => Key::Num9,
+        Keycode::Num0 => Key::Num0,
+        Keycode::Minus => Key::Minus,
+        Keycode::Equals => Key::Equals,
+        Keycode::Backspace => Key::Backspace,
+        Keycode::Tab => Key::Tab,
+        Keycode::Q => Key::Q,
+        Keycode::W => Key::W,
+        Keycode::E => Key::E,
+        Keycode::R => Key::R,
+        Keycode::T => Key::T,
+        Keycode::Y => Key::Y,
+        Keycode::U => Key::U,
+        Keycode::I => Key::I,
+        Keycode::O => Key::O,
+        Keycode::P => Key::P,
+        Keycode::LeftBracket => Key::LeftBracket,
+        Keycode::RightBracket => Key::RightBracket,
+        Keycode::Return => Key::Return,
+        Keycode::A => Key::A,
+        Keycode::S => Key::S,
+        Keycode::D => Key::D,
+        Keycode::F => Key::F,
+        Keycode::G => Key::G,
+        Keycode::H => Key::H,
+        Keycode::J => Key::J,
+        Keycode::K => Key::K,
+        Keycode::L => Key::L,
+        Keycode::Semicolon | Keycode::Colon => Key::Semicolon,
+        Keycode::Quote => Key::Quote,
+        Keycode::Backquote | Keycode::Caret => Key::Backquote,
+        Keycode::Backslash => Key::Backslash,
+        Keycode::Z => Key::Z,
+        Keycode::X => Key::X,
+        Keycode::C => Key::C,
+        Keycode::V => Key::V,
+        Keycode::B => Key::B,
+        Keycode::N => Key::N,
+        Keycode::M => Key::M,
+        Keycode::Comma => Key::Comma,
+        Keycode::Period => Key::Period,
+        Keycode::Slash => Key::Slash,
+        Keycode::KpMultiply | Keycode::Asterisk => Key::KpAsterisk,
+        Keycode::Space => Key::Space,
+        Keycode::F1 => Key::F1,
+        Keycode::F2 => Key::F2,
+        Keycode::F3 => Key::F3,
+        Keycode::F4 => Key::F4,
+        Keycode::F5 => Key::F5,
+        Keycode::F6 => Key::F6,
+        Keycode::F7 => Key::F7,
+        Keycode::F8 => Key::F8,
+        Keycode::F9 => Key::F9,
+        Keycode::F10 => Key::F10,
+        Keycode::Home => Key::Home,
+        Keycode::Up => Key::Up,
+        Keycode::PageUp => Key::PageUp,
+        Keycode::KpMinus => Key::KpMinus,
+        Keycode::Left => Key::Left,
+        Keycode::KpClearEntry => Key::KpCenter,
+        Keycode::Right => Key::Right,
+        Keycode::KpPlus => Key::KpPlus,
+        Keycode::End => Key::End,
+        Keycode::Down => Key::Down,
+        Keycode::PageDown => Key::PageDown,
+        Keycode::Insert => Key::Insert,
+        Keycode::Delete => Key::Delete,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// maps the SDL modifier keys to the emulated keyboard
+fn map_modifier(modifier: Mod) -> Modifier {
+    if modifier.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+        Modifier::Shift
+    } else if modifier.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+        Modifier::Ctrl
+    } else if modifier.intersects(Mod::LALTMOD | Mod::RALTMOD) {
+        Modifier::Alt
+    } else {
+        Modifier::None
+    }
+}
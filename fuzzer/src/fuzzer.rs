@@ -115,7 +115,7 @@ fn fuzz(runner: &CodeRunner, data: &[u8], op_count: usize, affected_flag_mask: u
     let affected_registers = vec!("eax", "ebx", "ecx", "edx");
     let mut machine = Machine::deterministic();
 
-    machine.load_executable(data, 0x085F);
+    machine.load_executable(data, 0x085F).unwrap();
     machine.execute_instructions(op_count);
     // println!("regs: {}", machine.cpu.regs);
 
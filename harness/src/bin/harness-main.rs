@@ -48,9 +48,10 @@ fn run_and_save_video_frames(set: &SetDocument) {
         let mut machine = Machine::deterministic();
         let bin_path = format!("{}{}", set.root, bin);
 
-        if let Some(e) = machine.load_executable_file(&bin_path) {
-            panic!("error {}", e);
-        };
+        if let Err(e) = machine.load_executable_file(&bin_path) {
+            println!("{}: {}", "failed to load".red(), e);
+            continue;
+        }
 
         // XXX allow per-rom override + more properties on a rom basis
         machine.execute_instructions(set.default_instructions);